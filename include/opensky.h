@@ -0,0 +1,92 @@
+/*
+ * C interface to the opensky crate (built with the `capi` feature).
+ *
+ *   cargo rustc --release --lib --features capi --crate-type cdylib
+ *
+ * Functions returning a pointer return NULL on failure; functions returning
+ * int return non-zero on failure. opensky_last_error() describes the most
+ * recent failure on the calling thread.
+ */
+#ifndef OPENSKY_H
+#define OPENSKY_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Arrow C Data Interface, see
+ * https://arrow.apache.org/docs/format/CDataInterface.html */
+#ifndef ARROW_C_DATA_INTERFACE
+#define ARROW_C_DATA_INTERFACE
+
+#define ARROW_FLAG_DICTIONARY_ORDERED 1
+#define ARROW_FLAG_NULLABLE 2
+#define ARROW_FLAG_MAP_KEYS_SORTED 4
+
+struct ArrowSchema {
+  const char* format;
+  const char* name;
+  const char* metadata;
+  int64_t flags;
+  int64_t n_children;
+  struct ArrowSchema** children;
+  struct ArrowSchema* dictionary;
+  void (*release)(struct ArrowSchema*);
+  void* private_data;
+};
+
+struct ArrowArray {
+  int64_t length;
+  int64_t null_count;
+  int64_t offset;
+  int64_t n_buffers;
+  int64_t n_children;
+  const void** buffers;
+  struct ArrowArray** children;
+  struct ArrowArray* dictionary;
+  void (*release)(struct ArrowArray*);
+  void* private_data;
+};
+
+#endif /* ARROW_C_DATA_INTERFACE */
+
+typedef struct OpenSkyClient OpenSkyClient;
+typedef struct OpenSkyResult OpenSkyResult;
+
+/* Message of the last error on this thread, or NULL. Owned by the library. */
+const char* opensky_last_error(void);
+
+/* Create a client using credentials from the OpenSky config file. */
+OpenSkyClient* opensky_client_new(void);
+void opensky_client_free(OpenSkyClient* client);
+
+/* Run a history query. Any string may be NULL; limit 0 means no limit. */
+OpenSkyResult* opensky_history(OpenSkyClient* client,
+                               const char* start,
+                               const char* stop,
+                               const char* icao24,
+                               const char* callsign,
+                               const char* departure,
+                               const char* arrival,
+                               uint32_t limit);
+
+size_t opensky_result_num_rows(const OpenSkyResult* result);
+size_t opensky_result_num_columns(const OpenSkyResult* result);
+
+/* Export one column. The caller owns out_array/out_schema on success and
+ * must call their release callbacks. */
+int opensky_result_export_column(const OpenSkyResult* result,
+                                 size_t index,
+                                 struct ArrowArray* out_array,
+                                 struct ArrowSchema* out_schema);
+
+void opensky_result_free(OpenSkyResult* result);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* OPENSKY_H */
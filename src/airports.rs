@@ -0,0 +1,147 @@
+//! Airport groups and country lookups for airport filters.
+//!
+//! There is no airport database in this crate: countries are expanded into
+//! their ICAO location prefixes (e.g. `EH` for the Netherlands), which the
+//! query builder turns into `LIKE 'EH%'` conditions, and named groups are
+//! short curated lists of ICAO codes. Runway thresholds for
+//! [`crate::FlightData::infer_runway`] are supplied by the caller.
+
+use crate::types::{IntoAirports, OpenSkyError, Result};
+
+/// Named groups of airports serving the same area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AirportGroup {
+    /// Heathrow, Gatwick, Stansted, Luton, City, Southend
+    LondonArea,
+    /// Charles de Gaulle, Orly, Le Bourget, Beauvais
+    ParisArea,
+    /// JFK, LaGuardia, Newark
+    NewYorkArea,
+    /// Malpensa, Linate, Bergamo
+    MilanArea,
+    /// Arlanda, Bromma, Skavsta
+    StockholmArea,
+    /// Sheremetyevo, Domodedovo, Vnukovo
+    MoscowArea,
+    /// Haneda, Narita
+    TokyoArea,
+}
+
+impl AirportGroup {
+    /// ICAO codes of the airports in the group.
+    pub fn airports(&self) -> &'static [&'static str] {
+        match self {
+            AirportGroup::LondonArea => &["EGLL", "EGKK", "EGSS", "EGGW", "EGLC", "EGMC"],
+            AirportGroup::ParisArea => &["LFPG", "LFPO", "LFPB", "LFOB"],
+            AirportGroup::NewYorkArea => &["KJFK", "KLGA", "KEWR"],
+            AirportGroup::MilanArea => &["LIMC", "LIML", "LIME"],
+            AirportGroup::StockholmArea => &["ESSA", "ESSB", "ESKN"],
+            AirportGroup::MoscowArea => &["UUEE", "UUDD", "UUWW"],
+            AirportGroup::TokyoArea => &["RJTT", "RJAA"],
+        }
+    }
+}
+
+impl IntoAirports for AirportGroup {
+    fn into_airports(self) -> Vec<String> {
+        self.airports().iter().map(|a| a.to_string()).collect()
+    }
+}
+
+/// A runway threshold, as used by [`crate::FlightData::infer_runway`].
+///
+/// Each direction of a runway is a separate entry, e.g. `09` and `27`, with
+/// the position of its threshold and the true bearing of operations on it.
+/// OurAirports' `runways.csv` provides both as `le_*` and `he_*` columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Runway {
+    /// Designator, e.g. "27" or "18R"
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    /// True bearing (degrees)
+    pub bearing: f64,
+}
+
+impl Runway {
+    pub fn new(name: impl Into<String>, lat: f64, lon: f64, bearing: f64) -> Self {
+        Runway {
+            name: name.into(),
+            lat,
+            lon,
+            bearing,
+        }
+    }
+
+    /// East and north offsets (m) of a position from the threshold.
+    #[cfg(feature = "client")]
+    pub(crate) fn offset(&self, lat: f64, lon: f64) -> (f64, f64) {
+        use crate::types::EARTH_RADIUS;
+        (
+            (lon - self.lon).to_radians() * self.lat.to_radians().cos() * EARTH_RADIUS,
+            (lat - self.lat).to_radians() * EARTH_RADIUS,
+        )
+    }
+}
+
+/// ICAO location prefixes of a country, by ISO 3166 alpha-2 code.
+///
+/// Only countries whose airports share unambiguous prefixes are listed.
+pub fn country_prefixes(country: &str) -> Option<&'static [&'static str]> {
+    let prefixes: &[&str] = match country.to_uppercase().as_str() {
+        "AT" => &["LO"],
+        "AU" => &["Y"],
+        "BE" => &["EB"],
+        "CA" => &["C"],
+        "CH" => &["LS"],
+        "CZ" => &["LK"],
+        "DE" => &["ED", "ET"],
+        "DK" => &["EK"],
+        "ES" => &["LE", "GC"],
+        "FI" => &["EF"],
+        "FR" => &["LF"],
+        "GB" | "UK" => &["EG"],
+        "GR" => &["LG"],
+        "HU" => &["LH"],
+        "IE" => &["EI"],
+        "IS" => &["BI"],
+        "IT" => &["LI"],
+        "JP" => &["RJ", "RO"],
+        "LU" => &["EL"],
+        "NL" => &["EH"],
+        "NO" => &["EN"],
+        "NZ" => &["NZ"],
+        "PL" => &["EP"],
+        "PT" => &["LP"],
+        "RO" => &["LR"],
+        "SE" => &["ES"],
+        "TR" => &["LT"],
+        "US" => &["K", "PA", "PH"],
+        _ => return None,
+    };
+    Some(prefixes)
+}
+
+/// `LIKE` patterns matching all airports of a country.
+pub(crate) fn country_patterns(country: &str) -> Result<Vec<String>> {
+    country_prefixes(country)
+        .map(|prefixes| prefixes.iter().map(|p| format!("{p}%")).collect())
+        .ok_or_else(|| {
+            OpenSkyError::InvalidParam(format!(
+                "Unknown or unsupported country code '{}'",
+                country
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_country_patterns() {
+        assert_eq!(country_patterns("nl").unwrap(), ["EH%"]);
+        assert_eq!(country_patterns("DE").unwrap(), ["ED%", "ET%"]);
+        assert!(country_patterns("XX").is_err());
+    }
+}
@@ -0,0 +1,1294 @@
+//! OpenSky CLI - Command-line interface for querying OpenSky Network flight data.
+
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone};
+use opensky::sink::{CsvSink, ParquetSink, ResultSink};
+use opensky::{CsvOptions, CsvQuoting, FlightData, OpenSkyError, QueryCanceller, QueryParams, StatsInterval, Trino};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+mod table;
+#[cfg(feature = "tui")]
+mod tui;
+
+use table::ColorChoice;
+
+/// Parse a duration string like "30m", "2h", "1d", "1w" into chrono::Duration.
+/// Maximum allowed is 1 week.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim().to_lowercase();
+    if s.is_empty() {
+        return Err("Empty duration".to_string());
+    }
+
+    let (num_str, unit) = s.split_at(s.len() - 1);
+    let num: i64 = num_str.parse().map_err(|_| format!("Invalid number: {}", num_str))?;
+
+    if num <= 0 {
+        return Err("Duration must be positive".to_string());
+    }
+
+    let duration = match unit {
+        "m" => Duration::minutes(num),
+        "h" => Duration::hours(num),
+        "d" => Duration::days(num),
+        "w" => Duration::weeks(num),
+        _ => return Err(format!("Unknown unit '{}'. Use m, h, d, or w", unit)),
+    };
+
+    // Max 1 week
+    if duration > Duration::weeks(1) {
+        return Err("Duration cannot exceed 1 week".to_string());
+    }
+
+    Ok(duration)
+}
+
+/// One-line progress summary, e.g. "RUNNING 45.0% | 312 aircraft | 1.2M rows/s | 30.5 MB/s | ETA 12s".
+fn progress_line(status: &opensky::QueryStatus) -> String {
+    let mut parts = vec![format!("{} {:.1}%", status.state, status.progress)];
+    if let Some(aircraft) = status.aircraft.filter(|&n| n > 0) {
+        parts.push(format!("{} aircraft", aircraft));
+    }
+    if let Some(rate) = status.rows_per_sec {
+        parts.push(if rate >= 1e6 {
+            format!("{:.1}M rows/s", rate / 1e6)
+        } else {
+            format!("{:.0} rows/s", rate)
+        });
+    }
+    if let Some(rate) = status.bytes_per_sec {
+        parts.push(format!("{:.1} MB/s", rate / 1e6));
+    }
+    if let Some(eta) = status.eta {
+        parts.push(format!("ETA {:.0}s", eta));
+    }
+    parts.join(" | ")
+}
+
+/// Final report of a history query: logged, or printed as one JSON line on
+/// stdout with `--report-json`.
+fn report_query(trino: &Trino, started: Instant, rows: usize, output: Option<&Path>, json: bool) {
+    let report = trino.last_report().cloned().unwrap_or_default();
+    let wall_time = started.elapsed().as_secs_f64();
+    if json {
+        let line = serde_json::json!({
+            "query_id": report.query_id,
+            "wall_time": wall_time,
+            "rows": rows,
+            "bytes": report.bytes,
+            "cache": report.cache,
+            "cache_files": report.cache_files,
+            "output": output.map(|path| path.display().to_string()),
+            "sql_hash": report.sql_hash,
+            "sql": report.sql,
+            "truncated": report.truncated,
+            "warnings": report.warnings.iter().map(|w| &w.message).collect::<Vec<_>>(),
+        });
+        println!("{}", line);
+    } else {
+        tracing::info!(
+            "Query {}: {} rows{} in {:.1}s, cache {}, SQL hash {}{}",
+            report.query_id.as_deref().unwrap_or("-"),
+            rows,
+            report.bytes.map(|b| format!(", {:.1} MB scanned", b as f64 / 1e6)).unwrap_or_default(),
+            wall_time,
+            report.cache.as_str(),
+            report.sql_hash,
+            match report.warnings.len() {
+                0 => String::new(),
+                1 => ", 1 warning".to_string(),
+                n => format!(", {} warnings", n),
+            }
+        );
+    }
+}
+
+/// Parse a CLI time to UTC.
+///
+/// Times with an offset ("2025-01-01T10:00:00+01:00", "...Z") are converted
+/// directly; other times are read in `tz` if given, UTC otherwise.
+fn parse_cli_time(value: &str, tz: Option<chrono_tz::Tz>) -> Result<NaiveDateTime, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value.trim()) {
+        return Ok(time.naive_utc());
+    }
+    let naive = opensky::types::parse_time(value).ok_or_else(|| format!("Invalid time: {}", value))?;
+    match tz {
+        Some(tz) => tz
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|time| time.naive_utc())
+            .ok_or_else(|| format!("Time '{}' does not exist in {}", value, tz)),
+        None => Ok(naive),
+    }
+}
+
+/// Resolve --start with --stop or --duration into UTC "YYYY-MM-DD HH:MM:SS" bounds.
+///
+/// A date-only stop is the end of that day; without a stop or duration the
+/// range ends at the end of the start day.
+fn resolve_time_range(
+    start: &str,
+    stop: Option<&str>,
+    duration: Option<&str>,
+    tz: Option<chrono_tz::Tz>,
+) -> Result<(String, String), String> {
+    let end_of_day = |date: NaiveDate| parse_cli_time(&format!("{} 23:59:59", date), tz);
+
+    let start_dt = parse_cli_time(start, tz)?;
+    let stop_dt = match (stop, duration) {
+        (_, Some(dur_str)) => start_dt + parse_duration(dur_str)?,
+        (Some(s), None) => match NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d") {
+            Ok(date) => end_of_day(date)?,
+            Err(_) => parse_cli_time(s, tz)?,
+        },
+        // The start day as written, which differs from start_dt's in another tz
+        (None, None) => end_of_day(opensky::types::parse_time(start).map_or(start_dt.date(), |t| t.date()))?,
+    };
+
+    let format = |time: NaiveDateTime| time.format("%Y-%m-%d %H:%M:%S").to_string();
+    Ok((format(start_dt), format(stop_dt)))
+}
+
+/// Time range options shared by `history` and `flightlist`.
+#[derive(Args)]
+struct TimeArgs {
+    /// Start time (YYYY-MM-DD or YYYY-MM-DD HH:MM:SS)
+    #[arg(short, long, required_unless_present_any = ["date", "today", "yesterday", "last"])]
+    start: Option<String>,
+
+    /// Stop time (YYYY-MM-DD or YYYY-MM-DD HH:MM:SS)
+    #[arg(short = 'e', long, requires = "start", conflicts_with = "duration")]
+    stop: Option<String>,
+
+    /// Duration from start (e.g., 30m, 2h, 1d, 1w). Max 1 week.
+    #[arg(short = 'D', long, requires = "start", conflicts_with = "stop")]
+    duration: Option<String>,
+
+    /// A whole day (YYYY-MM-DD)
+    #[arg(long, conflicts_with_all = ["start", "today", "yesterday", "last"])]
+    date: Option<String>,
+
+    /// From midnight until now
+    #[arg(long, conflicts_with_all = ["start", "yesterday", "last"])]
+    today: bool,
+
+    /// The whole previous day
+    #[arg(long, conflicts_with_all = ["start", "last"])]
+    yesterday: bool,
+
+    /// The given duration up to now (e.g., 6h, 7d)
+    #[arg(long, conflicts_with = "start")]
+    last: Option<String>,
+
+    /// Time zone of the times and days above (e.g., Europe/Amsterdam); default UTC
+    #[arg(long)]
+    tz: Option<String>,
+}
+
+impl TimeArgs {
+    /// Time zone given with --tz.
+    fn zone(&self) -> Result<Option<chrono_tz::Tz>, String> {
+        self.tz
+            .as_deref()
+            .map(|tz| tz.parse::<chrono_tz::Tz>().map_err(|_| format!("Unknown time zone '{}'", tz)))
+            .transpose()
+    }
+
+    /// UTC "YYYY-MM-DD HH:MM:SS" bounds of the requested range.
+    ///
+    /// Ranges are cut off at the current time; history queries then check
+    /// them against the data ingested so far.
+    fn resolve(&self) -> Result<(String, String), String> {
+        let tz = self.zone()?;
+        let now = chrono::Utc::now();
+        let today = match tz {
+            Some(tz) => now.with_timezone(&tz).date_naive(),
+            None => now.date_naive(),
+        };
+        let day = |date: NaiveDate| resolve_time_range(&date.to_string(), None, None, tz);
+
+        let (start, stop) = if let Some(date) = &self.date {
+            let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                .map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD", date))?;
+            day(date)?
+        } else if self.today {
+            day(today)?
+        } else if self.yesterday {
+            day(today.pred_opt().ok_or("Invalid date")?)?
+        } else if let Some(last) = &self.last {
+            let start = now.naive_utc() - parse_duration(last)?;
+            resolve_time_range(&start.format("%Y-%m-%d %H:%M:%S").to_string(), None, Some(last), None)?
+        } else {
+            let start = self.start.as_deref().ok_or("--start is required")?;
+            resolve_time_range(start, self.stop.as_deref(), self.duration.as_deref(), tz)?
+        };
+
+        let format = |time: NaiveDateTime| time.format("%Y-%m-%d %H:%M:%S").to_string();
+        let now = now.naive_utc();
+        let stop = match NaiveDateTime::parse_from_str(&stop, "%Y-%m-%d %H:%M:%S") {
+            Ok(time) if time > now => format(now),
+            _ => stop,
+        };
+        Ok((start, stop))
+    }
+}
+
+/// Write results to `path` by extension: Parquet, NDJSON (`.ndjson`, `.jsonl`)
+/// or CSV. When appending, a directory or a path without extension is a
+/// Parquet dataset that gets a new part file.
+fn save_output(data: &FlightData, path: &Path, csv: &CsvOptions, append: bool) -> opensky::Result<()> {
+    let extension = path.extension().and_then(|e| e.to_str());
+    match extension {
+        _ if append && (path.is_dir() || extension.is_none()) => opensky::append_parquet(data.dataframe(), path),
+        Some("parquet") if append => opensky::append_parquet(data.dataframe(), path),
+        Some("parquet") => data.to_parquet(path),
+        Some("ndjson" | "jsonl") => opensky::write_ndjson(data.dataframe(), path, append),
+        _ => data.to_csv_with(path, &csv.clone().append(append)),
+    }
+}
+
+/// Passes batches on to a sink, counting their rows.
+struct Counted<S> {
+    sink: S,
+    rows: usize,
+}
+
+impl<S> Counted<S> {
+    fn new(sink: S) -> Self {
+        Counted { sink, rows: 0 }
+    }
+}
+
+impl<S: ResultSink> ResultSink for Counted<S> {
+    type Output = usize;
+
+    fn on_schema(&mut self, schema: &polars::prelude::Schema) -> opensky::Result<()> {
+        self.sink.on_schema(schema)
+    }
+
+    fn on_batch(&mut self, batch: &opensky::DataFrame) -> opensky::Result<()> {
+        self.rows += batch.height();
+        self.sink.on_batch(batch)
+    }
+
+    fn ready(&mut self) -> std::pin::Pin<Box<dyn std::future::Future<Output = opensky::Result<()>> + Send + '_>> {
+        self.sink.ready()
+    }
+
+    fn finish(self) -> opensky::Result<usize> {
+        self.sink.finish()?;
+        Ok(self.rows)
+    }
+}
+
+/// Write history rows to `path` page by page for `--stream`, then check the
+/// row count the file reports against the rows received.
+async fn stream_output(
+    trino: &mut Trino,
+    params: QueryParams,
+    path: &Path,
+    csv: &CsvOptions,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let (received, written) = match path.extension().and_then(|e| e.to_str()) {
+        Some("parquet") => {
+            let received = trino.history_into(params, Counted::new(ParquetSink::new(path))).await?;
+            // From the footer, without reading the row groups
+            use polars::prelude::SerReader;
+            let written = polars::prelude::ParquetReader::new(std::fs::File::open(path)?).num_rows()?;
+            (received, written)
+        }
+        Some("ndjson" | "jsonl") => return Err(OpenSkyError::InvalidParam("--stream writes Parquet or CSV output".to_string()).into()),
+        _ => {
+            let received = trino.history_into(params, Counted::new(CsvSink::new(path, csv.clone()))).await?;
+            (received, csv_rows(path, csv)?)
+        }
+    };
+    if received != written {
+        return Err(format!("{} holds {} rows, but {} were received", path.display(), written, received).into());
+    }
+    Ok(received)
+}
+
+/// Number of records in a CSV file written with `options`.
+fn csv_rows(path: &Path, options: &CsvOptions) -> std::io::Result<usize> {
+    use std::io::BufRead;
+    let file = std::fs::File::open(path)?;
+    let reader: Box<dyn std::io::Read> = if options.gzip {
+        Box::new(flate2::read::MultiGzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let lines: usize = std::io::BufReader::new(reader).lines().try_fold(0, |count, line| line.map(|_| count + 1))?;
+    Ok(lines.saturating_sub(usize::from(options.include_header)))
+}
+
+/// Drop rows within the time span their aircraft already has in `path`.
+fn skip_existing(data: FlightData, path: &Path) -> Result<FlightData, Box<dyn std::error::Error>> {
+    let spans = opensky::output_time_spans(path)?;
+    if spans.is_empty() {
+        return Ok(data);
+    }
+    let fresh = data.exclude_time_spans(&spans)?;
+    if fresh.len() < data.len() {
+        tracing::warn!(
+            "Skipping {} rows within the time span of their aircraft already in {}",
+            data.len() - fresh.len(),
+            path.display()
+        );
+    }
+    Ok(fresh)
+}
+
+/// Send log output to stderr, or to `log_file` with timestamps.
+///
+/// `-q` keeps only errors; `-v` adds debug and `-vv` trace output.
+fn init_logging(quiet: bool, verbose: u8, log_file: Option<&Path>, color: ColorChoice) -> std::io::Result<()> {
+    use tracing_subscriber::filter::LevelFilter;
+
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::ERROR,
+        (false, 0) => LevelFilter::INFO,
+        (false, 1) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    };
+    let builder = tracing_subscriber::fmt().with_max_level(level).with_target(false);
+    match log_file {
+        Some(path) => {
+            let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+            builder.with_ansi(false).with_writer(std::sync::Mutex::new(file)).init();
+        }
+        None => builder
+            .without_time()
+            .with_ansi(color.enabled(std::io::stderr().is_terminal()))
+            .with_writer(std::io::stderr)
+            .init(),
+    }
+    Ok(())
+}
+
+/// Exit with the query failure code if chunks of a split history query
+/// failed, after their ranges were logged.
+///
+/// The rows of the other chunks are written and cached by then, so running
+/// the command again only queries the failed chunks.
+fn exit_on_failed_chunks(failed: usize) {
+    if failed > 0 {
+        tracing::error!("{} chunks failed and are missing from the output; run the command again to retry them", failed);
+        std::process::exit(4);
+    }
+}
+
+/// Cancellers of the clients created so far, to stop their queries on Ctrl-C.
+static CANCELLERS: Mutex<Vec<QueryCanceller>> = Mutex::new(Vec::new());
+
+/// Create a client whose running query is cancelled on Ctrl-C.
+async fn new_client() -> opensky::Result<Trino> {
+    let trino = Trino::new().await?;
+    CANCELLERS.lock().unwrap_or_else(|e| e.into_inner()).push(trino.canceller());
+    Ok(trino)
+}
+
+/// Cancel the queries still running on the server, so an interrupted
+/// command does not leave them using the cluster until they expire.
+async fn cancel_queries() {
+    let cancellers = std::mem::take(&mut *CANCELLERS.lock().unwrap_or_else(|e| e.into_inner()));
+    for canceller in cancellers {
+        match tokio::time::timeout(std::time::Duration::from_secs(5), canceller.cancel()).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => tracing::debug!("Could not cancel query: {}", e),
+            Err(_) => tracing::debug!("Timed out cancelling query"),
+        }
+    }
+}
+
+/// Process exit code for an error, so scripts can tell failures apart.
+///
+/// 2: configuration or invalid input, 3: authentication, 4: query failure,
+/// 5: cancelled, 6: I/O error, 7: result missing from a read-only cache,
+/// 1: anything else.
+fn exit_code(error: &(dyn std::error::Error + 'static)) -> i32 {
+    if let Some(error) = error.downcast_ref::<OpenSkyError>() {
+        return match error {
+            OpenSkyError::Config(_) | OpenSkyError::InvalidParam(_) => 2,
+            OpenSkyError::Auth(_) | OpenSkyError::HttpStatus { status: 401 | 403, .. } => 3,
+            OpenSkyError::Cancelled => 5,
+            OpenSkyError::Io(_) => 6,
+            OpenSkyError::CacheMiss { .. } => 7,
+            OpenSkyError::InQuery { source, .. } | OpenSkyError::Verify { source, .. } => exit_code(source.as_ref()),
+            _ => 4,
+        };
+    }
+    if error.is::<std::io::Error>() {
+        6
+    } else if error.is::<serde_json::Error>() || error.is::<serde_yaml::Error>() {
+        // Query files of `prefetch`
+        2
+    } else {
+        1
+    }
+}
+
+#[derive(Parser)]
+#[command(name = "opensky")]
+#[command(author, version, about = "Query OpenSky Network flight data", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+
+    /// Only log errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Log more detail (-v for debug, -vv for trace output)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Append log output to a file instead of stderr
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+
+    /// Color tables and log output: auto (terminals, unless NO_COLOR is set), always or never
+    #[arg(long, global = true, value_enum, default_value = "auto")]
+    color: ColorChoice,
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+enum Commands {
+    /// Query historical flight data
+    History {
+        #[command(flatten)]
+        time: TimeArgs,
+
+        /// Aircraft ICAO24 address (hex, e.g., 485a32)
+        #[arg(short, long)]
+        icao24: Option<String>,
+
+        /// Flight callsign (e.g., KLM1234)
+        #[arg(short, long)]
+        callsign: Option<String>,
+
+        /// Departure airport(s) (ICAO codes, e.g., EHAM or EGLL,EGKK)
+        #[arg(short, long, value_delimiter = ',')]
+        departure: Vec<String>,
+
+        /// Arrival airport(s) (ICAO codes, e.g., EGLL)
+        #[arg(short, long, value_delimiter = ',')]
+        arrival: Vec<String>,
+
+        /// Airport(s) (departure or arrival)
+        #[arg(long, value_delimiter = ',')]
+        airport: Vec<String>,
+
+        /// Columns to return (e.g., time,icao24,lat,lon,baroaltitude);
+        /// time and icao24 are always included
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+
+        /// Maximum number of rows
+        #[arg(short, long)]
+        limit: Option<u32>,
+
+        /// Exclude rows without a position
+        #[arg(long)]
+        require_position: bool,
+
+        /// Exclude rows with a null, (0, 0) or out-of-range position
+        #[arg(long)]
+        valid_positions: bool,
+
+        /// Output file (CSV, NDJSON or Parquet based on extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Append to --output instead of overwriting it, skipping rows in a
+        /// time span it already covers for their aircraft
+        #[arg(long, requires = "output")]
+        append: bool,
+
+        /// Print a JSON summary of the result (columns, rows, time span) to stdout
+        #[arg(long)]
+        summary_json: bool,
+
+        /// Print a JSON report of the query (id, wall time, rows, bytes, cache
+        /// hit, output, SQL hash) to stdout
+        #[arg(long)]
+        report_json: bool,
+
+        /// Show generated SQL query
+        #[arg(long)]
+        show_query: bool,
+
+        /// Estimate the partitions and data scanned instead of running the query
+        #[arg(long)]
+        estimate: bool,
+
+        /// Emit progress as JSON lines on stderr
+        #[arg(long)]
+        progress_json: bool,
+
+        /// CSV field delimiter
+        #[arg(long, default_value_t = ',')]
+        delimiter: char,
+
+        /// CSV quoting: necessary, always, non-numeric or never
+        #[arg(long, default_value = "necessary")]
+        quote: CsvQuoting,
+
+        /// Omit the CSV header row
+        #[arg(long)]
+        no_header: bool,
+
+        /// Number of decimals for floats in CSV output
+        #[arg(long)]
+        float_precision: Option<usize>,
+
+        /// chrono format for datetime columns in CSV output
+        #[arg(long)]
+        datetime_format: Option<String>,
+
+        /// Gzip the CSV output (implied by a .gz extension)
+        #[arg(long)]
+        gzip: bool,
+
+        /// Add a time_local column in the --tz time zone to the results
+        #[arg(long, requires = "tz")]
+        local_time: bool,
+
+        /// Re-run the query every interval (e.g., 5m), appending rows newer
+        /// than the last one seen to --output
+        #[arg(long, requires = "output")]
+        watch: Option<String>,
+
+        /// Rows from the start shown without --output (default 10)
+        #[arg(long, conflicts_with = "output")]
+        head: Option<usize>,
+
+        /// Rows from the end shown without --output
+        #[arg(long, conflicts_with = "output")]
+        tail: Option<usize>,
+
+        /// Show all columns and full values without --output
+        #[arg(long, conflicts_with = "output")]
+        no_truncate: bool,
+
+        /// Write rows to the Parquet or CSV --output as they arrive instead
+        /// of collecting them first (single query, no cache)
+        #[arg(long, requires = "output", conflicts_with_all = ["append", "watch", "local_time", "summary_json"])]
+        stream: bool,
+    },
+
+    /// List flights with their departure and arrival airports
+    Flightlist {
+        #[command(flatten)]
+        time: TimeArgs,
+
+        /// Aircraft ICAO24 address (hex, e.g., 485a32)
+        #[arg(short, long)]
+        icao24: Option<String>,
+
+        /// Flight callsign (e.g., KLM1234)
+        #[arg(short, long)]
+        callsign: Option<String>,
+
+        /// Departure airport(s) (ICAO codes, e.g., EHAM or EGLL,EGKK)
+        #[arg(short, long, value_delimiter = ',')]
+        departure: Vec<String>,
+
+        /// Arrival airport(s) (ICAO codes, e.g., EGLL)
+        #[arg(short, long, value_delimiter = ',')]
+        arrival: Vec<String>,
+
+        /// Airport(s) (departure or arrival)
+        #[arg(long, value_delimiter = ',')]
+        airport: Vec<String>,
+
+        /// Maximum number of flights
+        #[arg(short, long)]
+        limit: Option<u32>,
+
+        /// Output file (CSV, NDJSON or Parquet based on extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Show all columns and full values without --output
+        #[arg(long, conflicts_with = "output")]
+        no_truncate: bool,
+    },
+
+    /// Look up a flight by callsign and date and fetch its state vectors
+    Track {
+        /// Flight callsign (e.g., KLM1234)
+        callsign: String,
+
+        /// Date of the flight (YYYY-MM-DD, UTC)
+        date: String,
+
+        /// Margin around the detected flight window (e.g., 10m, 1h)
+        #[arg(long, default_value = "10m")]
+        buffer: String,
+
+        /// Flight to fetch when the callsign flew several legs that day (1-based)
+        #[arg(long, default_value_t = 1)]
+        leg: usize,
+
+        /// Output file (CSV or Parquet based on extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Show all columns and full values without --output
+        #[arg(long, conflicts_with = "output")]
+        no_truncate: bool,
+    },
+
+    /// Recent flights of an aircraft
+    Aircraft {
+        /// Aircraft ICAO24 address (hex, e.g., 485a32)
+        icao24: String,
+
+        /// Number of days to look back
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+    },
+
+    /// Daily or hourly departure and arrival counts at an airport
+    AirportStats {
+        /// Airport ICAO code (e.g., EHAM)
+        airport: String,
+
+        /// Start date (YYYY-MM-DD or YYYY-MM-DD HH:MM:SS)
+        #[arg(short, long)]
+        start: String,
+
+        /// Stop date (defaults to the end of the start day)
+        #[arg(short = 'e', long)]
+        stop: Option<String>,
+
+        /// Bucket size: daily or hourly
+        #[arg(long, default_value = "daily")]
+        interval: StatsInterval,
+
+        /// Output file (CSV or Parquet based on extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Show all columns and full values without --output
+        #[arg(long, conflicts_with = "output")]
+        no_truncate: bool,
+    },
+
+    /// Run a list of history queries to populate the cache
+    Prefetch {
+        /// YAML or JSON file with a list of query parameters
+        #[arg(long)]
+        params_file: PathBuf,
+
+        /// Write a JSON report with the outcome and result summary of each query
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+
+    /// Interactive query form with live progress and a result preview
+    #[cfg(feature = "tui")]
+    Tui,
+
+    /// Print a shell completion script (bash, zsh, fish, powershell, elvish)
+    Completions {
+        shell: clap_complete::Shell,
+    },
+
+    /// Write manpages for opensky and its subcommands
+    #[command(hide = true)]
+    Man {
+        /// Directory for the generated .1 files
+        #[arg(short, long, default_value = ".")]
+        out_dir: PathBuf,
+    },
+
+    /// Configure OpenSky credentials
+    Config {
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
+
+        /// OpenSky username
+        #[arg(short, long)]
+        username: Option<String>,
+
+        /// OpenSky password (visible in shell history; prefer `config set`)
+        #[arg(short, long)]
+        password: Option<String>,
+
+        /// Show current configuration
+        #[arg(long)]
+        show: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Prompt for credentials, reading the password without echo
+    Set,
+    /// Check the credentials with a token request and a trivial query
+    Test,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    // The terminal UI owns the screen, so it only logs to a file
+    #[cfg(feature = "tui")]
+    let log_to_stderr = !matches!(cli.command, Commands::Tui);
+    #[cfg(not(feature = "tui"))]
+    let log_to_stderr = true;
+
+    let log_file = cli.log_file.clone();
+    if log_file.is_some() || log_to_stderr {
+        if let Err(e) = init_logging(cli.quiet, cli.verbose, log_file.as_deref(), cli.color) {
+            eprintln!("Error: cannot open log file: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let result = tokio::select! {
+        result = run(cli) => result,
+        _ = tokio::signal::ctrl_c() => {
+            cancel_queries().await;
+            Err(OpenSkyError::Cancelled.into())
+        }
+    };
+    if let Err(e) = result {
+        tracing::error!("{}", e);
+        if log_file.is_some() {
+            eprintln!("Error: {}", e);
+        }
+        std::process::exit(exit_code(e.as_ref()));
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let quiet = cli.quiet;
+    let color = cli.color.enabled(std::io::stdout().is_terminal());
+
+    match cli.command {
+        Commands::History {
+            time,
+            icao24,
+            callsign,
+            departure,
+            arrival,
+            airport,
+            columns,
+            limit,
+            require_position,
+            valid_positions,
+            output,
+            append,
+            summary_json,
+            report_json,
+            show_query,
+            estimate,
+            progress_json,
+            delimiter,
+            quote,
+            no_header,
+            float_precision,
+            datetime_format,
+            gzip,
+            local_time,
+            watch,
+            head,
+            tail,
+            no_truncate,
+            stream,
+        } => {
+            if !delimiter.is_ascii() {
+                return Err(OpenSkyError::InvalidParam("Delimiter must be a single ASCII character".to_string()).into());
+            }
+            let watch = watch.as_deref().map(parse_duration).transpose().map_err(OpenSkyError::InvalidParam)?;
+
+            // Build query parameters
+            let mut params = QueryParams::new();
+
+            // Parse start/stop (from --stop, --duration, --date, --today, --yesterday or --last)
+            let (start_str, stop_str) = time.resolve().map_err(OpenSkyError::InvalidParam)?;
+            params = params.time_range(start_str, stop_str);
+            let tz = time.tz;
+
+            params.icao24 = icao24;
+            params.callsign = callsign;
+            params.departure_airport = departure;
+            params.arrival_airport = arrival;
+            params.airport = airport;
+            params.columns = columns;
+            params.limit = limit;
+            params.require_position = require_position;
+            params.valid_positions = valid_positions;
+            params.validate_columns(opensky::FLIGHT_COLUMNS)?;
+
+            for warning in params.warnings() {
+                tracing::warn!("{}", warning);
+            }
+
+            // Show query if requested
+            if show_query {
+                let preview = opensky::build_query_preview(&params);
+                println!("Query:\n{}\n", preview);
+            }
+
+            let base = params.clone();
+
+            let mut csv_options = CsvOptions::new()
+                .separator(delimiter as u8)
+                .quoting(quote)
+                .include_header(!no_header)
+                .gzip(gzip || output.as_ref().is_some_and(|path| path.extension().is_some_and(|e| e == "gz")));
+            csv_options.float_precision = float_precision;
+            csv_options.datetime_format = datetime_format;
+
+            // Execute query
+            tracing::info!("Connecting to OpenSky Trino...");
+            let mut trino = new_client().await?;
+
+            if estimate {
+                let estimate = trino.estimate(params).await?;
+                if report_json {
+                    println!("{}", serde_json::to_string(&estimate)?);
+                } else {
+                    println!("{}", estimate);
+                }
+                return Ok(());
+            }
+
+            tracing::info!("Executing query...");
+            let started = Instant::now();
+            if let (true, Some(path)) = (stream, &output) {
+                let rows = stream_output(&mut trino, params, path, &csv_options).await?;
+                tracing::info!("Saved {} rows to {}", rows, path.display());
+                report_query(&trino, started, rows, Some(path), report_json);
+                return Ok(());
+            }
+            let result = if progress_json {
+                trino
+                    .history_chunked_with_progress(params, |status| {
+                        let line = serde_json::json!({
+                            "query_id": status.query_id,
+                            "state": status.state,
+                            "progress": status.progress,
+                            "rows": status.row_count,
+                            "elapsed": status.elapsed,
+                            "rows_per_sec": status.rows_per_sec,
+                            "bytes_per_sec": status.bytes_per_sec,
+                            "eta": status.eta,
+                            "aircraft": status.aircraft,
+                            "first_time": status.first_time,
+                            "last_time": status.last_time,
+                        });
+                        eprintln!("{}", line);
+                    })
+                    .await?
+            } else if !quiet && std::io::stderr().is_terminal() {
+                let result = trino
+                    .history_chunked_with_progress(params, |status| {
+                        eprint!("\r\x1b[K{}", progress_line(&status));
+                    })
+                    .await;
+                eprintln!();
+                result?
+            } else {
+                trino.history_chunked(params).await?
+            };
+            let failed = result.failed.len();
+            let data = result.data;
+
+            let data = match &tz {
+                Some(tz) if local_time => data.with_local_time(tz)?,
+                _ => data,
+            };
+
+            let row_count = data.len();
+            tracing::info!("Retrieved {} rows", row_count);
+            if summary_json {
+                println!("{}", serde_json::to_string(&data.metadata())?);
+            }
+
+            if row_count == 0 && watch.is_none() {
+                tracing::info!("No data found for the specified criteria.");
+                report_query(&trino, started, 0, None, report_json);
+                exit_on_failed_chunks(failed);
+                return Ok(());
+            }
+
+            // Output results
+            match &output {
+                Some(path) => {
+                    if append {
+                        save_output(&skip_existing(data.clone(), path)?, path, &csv_options, true)?;
+                    } else {
+                        save_output(&data, path, &csv_options, false)?;
+                    }
+                    tracing::info!("Saved to {}", path.display());
+                }
+                None => println!("\n{}", table::preview(data.dataframe(), head, tail, color, !no_truncate)),
+            }
+            report_query(&trino, started, row_count, output.as_deref(), report_json);
+            if watch.is_none() {
+                exit_on_failed_chunks(failed);
+            } else if failed > 0 {
+                tracing::warn!("{} chunks failed and are missing from the output", failed);
+            }
+
+            if let (Some(interval), Some(path)) = (watch, &output) {
+                let format = |time: NaiveDateTime| time.format("%Y-%m-%d %H:%M:%S").to_string();
+                // Unix time of the newest row written so far
+                let mut since = data.metadata().stop;
+                // Every round ends at a new time, so its cache entry would
+                // never be read again
+                trino.set_cache_policy(opensky::CachePolicy::Off);
+
+                loop {
+                    tokio::time::sleep(interval.to_std()?).await;
+                    let now = chrono::Utc::now().naive_utc();
+                    let started = Instant::now();
+
+                    let mut round = base.clone();
+                    if let Some(start) = since.and_then(|t| DateTime::from_timestamp(t + 1, 0)) {
+                        round.start = Some(format(start.naive_utc()));
+                    }
+                    round.stop = Some(format(now));
+
+                    let data = match trino.history(round).await {
+                        Ok(data) => match &tz {
+                            Some(tz) if local_time => data.with_local_time(tz)?,
+                            _ => data,
+                        },
+                        Err(e) => {
+                            tracing::warn!("[{}] query failed: {}", now.format("%H:%M:%S"), e);
+                            continue;
+                        }
+                    };
+                    if !data.is_empty() {
+                        since = data.metadata().stop.or(since);
+                        save_output(&data, path, &csv_options, true)?;
+                    }
+                    tracing::info!("[{}] appended {} rows", now.format("%H:%M:%S"), data.len());
+                    report_query(&trino, started, data.len(), Some(path), report_json);
+                }
+            }
+        }
+
+        Commands::Flightlist {
+            time,
+            icao24,
+            callsign,
+            departure,
+            arrival,
+            airport,
+            limit,
+            output,
+            no_truncate,
+        } => {
+            let (start, stop) = time.resolve().map_err(OpenSkyError::InvalidParam)?;
+            let mut params = QueryParams::new().time_range(start, stop);
+            params.icao24 = icao24;
+            params.callsign = callsign;
+            params.departure_airport = departure;
+            params.arrival_airport = arrival;
+            params.airport = airport;
+            params.limit = limit;
+
+            let mut trino = new_client().await?;
+            let flights = trino.flightlist(params).await?;
+            tracing::info!("Retrieved {} flights", flights.len());
+
+            match output {
+                Some(path) => {
+                    save_output(&flights.into(), &path, &CsvOptions::new(), false)?;
+                    tracing::info!("Saved to {}", path.display());
+                }
+                None => println!("{}", table::render(flights.dataframe(), color, !no_truncate)),
+            }
+        }
+
+        Commands::Track {
+            callsign,
+            date,
+            buffer,
+            leg,
+            output,
+            no_truncate,
+        } => {
+            let day = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                .map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD", date))?;
+            let buffer = parse_duration(&buffer).map_err(OpenSkyError::InvalidParam)?;
+
+            let mut params = QueryParams::new()
+                .time_range(format!("{} 00:00:00", day), format!("{} 23:59:59", day));
+            params.callsign = Some(callsign.clone());
+
+            let mut trino = new_client().await?;
+            let flights = trino.flightlist(params).await?;
+            if flights.is_empty() {
+                return Err(format!("No flight {} found on {}", callsign, day).into());
+            }
+
+            for (i, flight) in flights.iter().enumerate() {
+                println!(
+                    "[{}] {} {} -> {}, {} - {}",
+                    i + 1,
+                    flight.icao24,
+                    flight.estdepartureairport.as_deref().unwrap_or("?"),
+                    flight.estarrivalairport.as_deref().unwrap_or("?"),
+                    flight.firstseen.format("%H:%M"),
+                    flight.lastseen.format("%H:%M"),
+                );
+            }
+            let flight = leg.checked_sub(1).and_then(|i| flights.get(i)).ok_or_else(|| {
+                format!("--leg must be between 1 and {}", flights.len())
+            })?;
+            if flights.len() > 1 {
+                tracing::info!("Fetching flight {} (choose another with --leg)", leg);
+            }
+
+            let data = trino.history(flight.history_params(buffer)).await?;
+            let data = data.attach_flight_info(&flights)?;
+            tracing::info!("Retrieved {} rows", data.len());
+
+            match output {
+                Some(path) if path.extension().and_then(|e| e.to_str()) == Some("parquet") => {
+                    data.to_parquet(&path)?;
+                    tracing::info!("Saved to {}", path.display());
+                }
+                Some(path) => {
+                    data.to_csv_with(&path, &CsvOptions::new())?;
+                    tracing::info!("Saved to {}", path.display());
+                }
+                None => println!("\n{}", table::preview(data.dataframe(), None, None, color, !no_truncate)),
+            }
+        }
+
+        Commands::Aircraft { icao24, days } => {
+            let stop = chrono::Utc::now().naive_utc();
+            let start = stop - Duration::days(days.into());
+            let format = |time: NaiveDateTime| time.format("%Y-%m-%d %H:%M:%S").to_string();
+            let params = QueryParams::new().icao24(icao24).time_range(format(start), format(stop));
+            params.validate()?;
+            let icao24 = params.icao24.clone().unwrap_or_default();
+
+            let mut trino = new_client().await?;
+            let flights = trino.flightlist(params).await?;
+
+            println!("Aircraft {}", icao24);
+            if flights.is_empty() {
+                println!("No flights in the last {} days.", days);
+                return Ok(());
+            }
+            println!("{} flights in the last {} days:", flights.len(), days);
+            for flight in flights.iter() {
+                println!(
+                    "  {}  {:<8} {} -> {}  {} - {}",
+                    flight.firstseen.format("%Y-%m-%d"),
+                    flight.callsign.as_deref().unwrap_or("-"),
+                    flight.estdepartureairport.as_deref().unwrap_or("????"),
+                    flight.estarrivalairport.as_deref().unwrap_or("????"),
+                    flight.firstseen.format("%H:%M"),
+                    flight.lastseen.format("%H:%M"),
+                );
+            }
+        }
+
+        Commands::AirportStats {
+            airport,
+            start,
+            stop,
+            interval,
+            output,
+            no_truncate,
+        } => {
+            let (start_str, stop_str) = resolve_time_range(&start, stop.as_deref(), None, None).map_err(OpenSkyError::InvalidParam)?;
+
+            let mut trino = new_client().await?;
+            let stats = trino
+                .airport_stats(&airport, &start_str, &stop_str, interval)
+                .await?;
+
+            match output {
+                Some(path) if path.extension().and_then(|e| e.to_str()) == Some("parquet") => {
+                    opensky::write_parquet(&stats, &path)?;
+                    tracing::info!("Saved to {}", path.display());
+                }
+                Some(path) => {
+                    opensky::write_csv(&stats, &path)?;
+                    tracing::info!("Saved to {}", path.display());
+                }
+                None => println!("{}", table::render(&stats, color, !no_truncate)),
+            }
+        }
+
+        Commands::Prefetch { params_file, report } => {
+            let content = std::fs::read_to_string(&params_file)?;
+            let queries: Vec<QueryParams> = match params_file.extension().and_then(|e| e.to_str()) {
+                Some("json") => serde_json::from_str(&content)?,
+                _ => serde_yaml::from_str(&content)?,
+            };
+
+            let mut trino = new_client().await?;
+            let (mut fetched, mut cached, mut failed) = (0, 0, 0);
+            let mut entries = Vec::new();
+
+            for (i, params) in queries.into_iter().enumerate() {
+                let started = Instant::now();
+                let result = trino.history(params.clone()).await;
+                let was_cached = trino.last_report().is_some_and(|r| r.cache == opensky::CacheStatus::Hit);
+                let (status, metadata, error) = match result {
+                    Ok(data) if was_cached => {
+                        cached += 1;
+                        tracing::info!("[{}] cached", i + 1);
+                        ("cached", Some(data.metadata()), None)
+                    }
+                    Ok(data) => {
+                        fetched += 1;
+                        tracing::info!(
+                            "[{}] fetched {} rows in {:.1}s",
+                            i + 1,
+                            data.len(),
+                            started.elapsed().as_secs_f64()
+                        );
+                        ("fetched", Some(data.metadata()), None)
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        tracing::warn!("[{}] failed: {}", i + 1, e);
+                        ("failed", None, Some(e.to_string()))
+                    }
+                };
+                entries.push(serde_json::json!({
+                    "params": params,
+                    "status": status,
+                    "seconds": started.elapsed().as_secs_f64(),
+                    "metadata": metadata,
+                    "error": error,
+                }));
+            }
+
+            if let Some(report) = report {
+                std::fs::write(&report, serde_json::to_string_pretty(&entries)?)?;
+                tracing::info!("Report written to {}", report.display());
+            }
+
+            tracing::info!("Prefetch done: {} fetched, {} already cached, {} failed", fetched, cached, failed);
+            if failed > 0 {
+                std::process::exit(4);
+            }
+        }
+
+        #[cfg(feature = "tui")]
+        Commands::Tui => tui::run().await?,
+
+        Commands::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "opensky", &mut std::io::stdout());
+        }
+
+        Commands::Man { out_dir } => {
+            std::fs::create_dir_all(&out_dir)?;
+            clap_mangen::generate_to(Cli::command(), &out_dir)?;
+            tracing::info!("Manpages written to {}", out_dir.display());
+        }
+
+        Commands::Config {
+            action: Some(ConfigAction::Set),
+            ..
+        } => {
+            use std::io::Write;
+
+            let mut config = opensky::Config::load().unwrap_or_default();
+
+            let current = config.username.clone().unwrap_or_default();
+            if current.is_empty() {
+                print!("Username: ");
+            } else {
+                print!("Username [{}]: ", current);
+            }
+            std::io::stdout().flush()?;
+            let mut username = String::new();
+            std::io::stdin().read_line(&mut username)?;
+            let username = username.trim();
+            if !username.is_empty() {
+                config.username = Some(username.to_string());
+            }
+
+            let password = rpassword::prompt_password("Password: ")?;
+            if !password.is_empty() {
+                config.password = Some(password.into());
+            }
+
+            config.save()?;
+            tracing::info!("Configuration saved to {}", opensky::Config::config_path()?.display());
+        }
+
+        Commands::Config {
+            action: Some(ConfigAction::Test),
+            ..
+        } => {
+            let mut trino = new_client().await?;
+            match trino.verify_credentials().await {
+                Ok(()) => tracing::info!("Credentials OK: token issued and test query succeeded."),
+                Err(e) => {
+                    tracing::error!("{}", e);
+                    std::process::exit(exit_code(&e));
+                }
+            }
+        }
+
+        Commands::Config {
+            action: None,
+            username,
+            password,
+            show,
+        } => {
+            if show {
+                match opensky::Config::load() {
+                    Ok(config) => {
+                        println!("OpenSky Configuration:");
+                        println!("  Username: {}", config.username.unwrap_or_default());
+                        println!(
+                            "  Password: {}",
+                            if config.password.is_some() {
+                                "********"
+                            } else {
+                                "(not set)"
+                            }
+                        );
+                    }
+                    Err(_) => {
+                        println!("No configuration found. Use `opensky config set` to create one.");
+                    }
+                }
+                return Ok(());
+            }
+
+            if username.is_none() && password.is_none() {
+                println!("Use `opensky config set` to set credentials, or --show to view.");
+                return Ok(());
+            }
+
+            // Load existing or create new config
+            let mut config = opensky::Config::load().unwrap_or_default();
+
+            if let Some(u) = username {
+                config.username = Some(u);
+            }
+            if let Some(p) = password {
+                config.password = Some(p.into());
+            }
+
+            config.save()?;
+            tracing::info!("Configuration saved.");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,181 @@
+//! Aligned tables for result previews on stdout.
+//!
+//! Headers are bold and missing values dimmed when colors are enabled; with
+//! colors off the output is plain text that survives pipes and log files.
+//! Wide results are truncated to the first and last columns, and long values
+//! cut, unless `--no-truncate` is given.
+
+use opensky::DataFrame;
+use polars::prelude::AnyValue;
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// Columns shown when truncating, half of them from each end.
+const MAX_COLUMNS: usize = 8;
+/// Characters shown of a value when truncating.
+const MAX_CELL: usize = 24;
+/// Rows of a preview without `--head` or `--tail`.
+const DEFAULT_HEAD: usize = 10;
+
+/// When to color output, set by `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color terminals, unless NO_COLOR is set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color a stream that is a terminal or not.
+    pub fn enabled(self, terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => terminal && std::env::var_os("NO_COLOR").map_or(true, |value| value.is_empty()),
+        }
+    }
+}
+
+/// Format `df` as a table, one line per row; numbers are right-aligned.
+pub fn render(df: &DataFrame, color: bool, truncate: bool) -> String {
+    render_rows(df, None, color, truncate)
+}
+
+/// Format the first `head` and last `tail` rows of `df`, with a line
+/// counting the rows left out. Without either, the first rows are shown.
+pub fn preview(df: &DataFrame, head: Option<usize>, tail: Option<usize>, color: bool, truncate: bool) -> String {
+    let (head, tail) = match (head, tail) {
+        (None, None) => (DEFAULT_HEAD, 0),
+        (head, tail) => (head.unwrap_or(0), tail.unwrap_or(0)),
+    };
+    if head + tail >= df.height() {
+        return render(df, color, truncate);
+    }
+    let mut rows = df.head(Some(head));
+    if let Err(e) = rows.vstack_mut(&df.tail(Some(tail))) {
+        tracing::debug!("Cannot join head and tail of the preview: {}", e);
+        rows = df.head(Some(head));
+    }
+    let gap = (head, df.height() - rows.height());
+    render_rows(&rows, Some(gap), color, truncate)
+}
+
+/// Format `df`, with a line after `gap.0` rows saying `gap.1` rows are
+/// not shown.
+fn render_rows(df: &DataFrame, gap: Option<(usize, usize)>, color: bool, truncate: bool) -> String {
+    // Keep the first and last columns of wide results, with a `…` column
+    // standing in for the others
+    let all = df.get_columns();
+    let shown: Vec<Option<&polars::prelude::Column>> = if truncate && all.len() > MAX_COLUMNS {
+        let half = MAX_COLUMNS / 2;
+        all[..half]
+            .iter()
+            .map(Some)
+            .chain([None])
+            .chain(all[all.len() - half..].iter().map(Some))
+            .collect()
+    } else {
+        all.iter().map(Some).collect()
+    };
+    let names: Vec<String> = shown
+        .iter()
+        .map(|column| column.map_or("…".to_string(), |column| column.name().to_string()))
+        .collect();
+    let columns: Vec<Vec<Option<String>>> = shown
+        .iter()
+        .map(|column| {
+            (0..df.height())
+                .map(|i| match column {
+                    Some(column) => column.get(i).ok().and_then(cell).map(|text| cut(text, truncate)),
+                    None => Some("…".to_string()),
+                })
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = names
+        .iter()
+        .zip(&columns)
+        .map(|(name, cells)| {
+            cells
+                .iter()
+                .map(|cell| cell.as_deref().map_or(4, |text| text.chars().count()))
+                .chain([name.chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let numeric: Vec<bool> = shown
+        .iter()
+        .map(|column| column.is_some_and(|column| column.dtype().is_primitive_numeric()))
+        .collect();
+
+    let style = |text: &str, code: &str| if color { format!("{}{}{}", code, text, RESET) } else { text.to_string() };
+    let pad = |text: &str, width: usize, right: bool| {
+        let fill = " ".repeat(width.saturating_sub(text.chars().count()));
+        if right {
+            format!("{}{}", fill, text)
+        } else {
+            format!("{}{}", text, fill)
+        }
+    };
+
+    let mut lines = Vec::with_capacity(df.height() + 3);
+    let header: Vec<String> = names
+        .iter()
+        .zip(&widths)
+        .zip(&numeric)
+        .map(|((name, &width), &right)| style(&pad(name, width, right), BOLD))
+        .collect();
+    lines.push(header.join("  ").trim_end().to_string());
+    lines.push(widths.iter().map(|&width| "-".repeat(width)).collect::<Vec<_>>().join("  "));
+    let gap_line = |row: usize| {
+        gap.filter(|&(at, _)| at == row)
+            .map(|(_, hidden)| style(&format!("... ({} more rows)", hidden), DIM))
+    };
+    for row in 0..df.height() {
+        lines.extend(gap_line(row));
+        let cells: Vec<String> = columns
+            .iter()
+            .zip(&widths)
+            .zip(&numeric)
+            .map(|((cells, &width), &right)| match &cells[row] {
+                Some(text) => pad(text, width, right),
+                None => style(&pad("null", width, right), DIM),
+            })
+            .collect();
+        lines.push(cells.join("  ").trim_end().to_string());
+    }
+    lines.extend(gap_line(df.height()));
+    lines.join("\n")
+}
+
+/// Cut a long value to [`MAX_CELL`] characters when truncating.
+fn cut(text: String, truncate: bool) -> String {
+    if !truncate || text.chars().count() <= MAX_CELL {
+        return text;
+    }
+    let mut cut: String = text.chars().take(MAX_CELL - 1).collect();
+    cut.push('…');
+    cut
+}
+
+/// Text of a value, `None` for a missing one.
+fn cell(value: AnyValue) -> Option<String> {
+    match value {
+        AnyValue::Null => None,
+        AnyValue::String(text) => Some(text.to_string()),
+        AnyValue::StringOwned(text) => Some(text.to_string()),
+        AnyValue::Float32(value) => Some(float(value.into())),
+        AnyValue::Float64(value) => Some(float(value)),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Up to five decimals, without trailing zeros.
+fn float(value: f64) -> String {
+    let text = format!("{:.5}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
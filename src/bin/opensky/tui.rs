@@ -0,0 +1,369 @@
+//! Interactive terminal UI: a query form, live progress, a result preview and export.
+
+use opensky::{CsvOptions, FlightData, QueryParams, QueryStatus, Trino};
+use polars::prelude::AnyValue;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Cell, Gauge, Paragraph, Row, Table, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::path::Path;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+const FIELDS: &[&str] = &[
+    "Start",
+    "Stop / duration",
+    "ICAO24",
+    "Callsign",
+    "Departure",
+    "Arrival",
+    "Limit",
+];
+
+/// Rows kept in memory for the preview; larger selections are cut short.
+const MAX_ROWS: usize = 1_000_000;
+
+#[allow(clippy::large_enum_variant)]
+enum Screen {
+    Form,
+    Running {
+        params: QueryParams,
+        status: Option<QueryStatus>,
+        updates: mpsc::UnboundedReceiver<QueryStatus>,
+        task: JoinHandle<opensky::Result<FlightData>>,
+    },
+    Results {
+        data: FlightData,
+        offset: usize,
+        /// File name being typed after pressing `e`
+        export: Option<String>,
+        message: Option<String>,
+    },
+    Error(String),
+}
+
+struct App {
+    values: Vec<String>,
+    focus: usize,
+    screen: Screen,
+    quit: bool,
+}
+
+impl App {
+    fn new() -> Self {
+        App {
+            values: vec![String::new(); FIELDS.len()],
+            focus: 0,
+            screen: Screen::Form,
+            quit: false,
+        }
+    }
+
+    /// Query parameters from the form fields.
+    ///
+    /// The stop field takes a time or a duration such as `2h`; when empty the
+    /// query runs to the end of the start day, as on the command line.
+    fn params(&self) -> Result<QueryParams, String> {
+        let field = |i: usize| Some(self.values[i].trim()).filter(|v| !v.is_empty());
+        let list = |i: usize| {
+            field(i).map_or_else(Vec::new, |v| v.split(',').map(|a| a.trim().to_uppercase()).collect())
+        };
+
+        let start = field(0).ok_or("Start time is required")?;
+        let (stop, duration) = match field(1) {
+            Some(v) if super::parse_duration(v).is_ok() => (None, Some(v)),
+            other => (other, None),
+        };
+        let (start, stop) = super::resolve_time_range(start, stop, duration, None)?;
+
+        let mut params = QueryParams::new().time_range(start, stop);
+        params.icao24 = field(2).map(opensky::types::normalize_icao24);
+        params.callsign = field(3).map(str::to_string);
+        params.departure_airport = list(4);
+        params.arrival_airport = list(5);
+        params.limit = field(6)
+            .map(|v| v.parse().map_err(|_| format!("Invalid limit: {}", v)))
+            .transpose()?;
+        params.validate().map_err(|e| e.to_string())?;
+        Ok(params)
+    }
+
+    fn submit(&mut self) {
+        let params = match self.params() {
+            Ok(params) => params,
+            Err(e) => {
+                self.screen = Screen::Error(e);
+                return;
+            }
+        };
+        let (tx, updates) = mpsc::unbounded_channel();
+        let query = params.clone();
+        let task = tokio::spawn(async move {
+            let mut trino = Trino::new().await?;
+            trino.set_max_rows(Some(MAX_ROWS));
+            trino
+                .history_with_progress(query, |status| {
+                    let _ = tx.send(status);
+                })
+                .await
+        });
+        self.screen = Screen::Running {
+            params,
+            status: None,
+            updates,
+            task,
+        };
+    }
+
+    /// Pick up progress updates and the result of a running query.
+    async fn poll_query(&mut self) {
+        let Screen::Running { status, updates, task, .. } = &mut self.screen else {
+            return;
+        };
+        while let Ok(update) = updates.try_recv() {
+            *status = Some(update);
+        }
+        if task.is_finished() {
+            self.screen = match task.await {
+                Ok(Ok(data)) => Screen::Results {
+                    data,
+                    offset: 0,
+                    export: None,
+                    message: None,
+                },
+                Ok(Err(e)) => Screen::Error(e.to_string()),
+                Err(e) => Screen::Error(e.to_string()),
+            };
+        }
+    }
+
+    fn on_key(&mut self, key: KeyEvent) {
+        match &mut self.screen {
+            Screen::Form => match key.code {
+                KeyCode::Esc => self.quit = true,
+                KeyCode::Enter => self.submit(),
+                KeyCode::Tab | KeyCode::Down => self.focus = (self.focus + 1) % FIELDS.len(),
+                KeyCode::BackTab | KeyCode::Up => self.focus = (self.focus + FIELDS.len() - 1) % FIELDS.len(),
+                KeyCode::Backspace => {
+                    self.values[self.focus].pop();
+                }
+                KeyCode::Char(c) => self.values[self.focus].push(c),
+                _ => {}
+            },
+            Screen::Running { task, .. } => {
+                if key.code == KeyCode::Esc {
+                    // Stops waiting; the query itself expires on the server
+                    task.abort();
+                    self.screen = Screen::Form;
+                }
+            }
+            Screen::Results {
+                data,
+                offset,
+                export,
+                message,
+            } => {
+                if let Some(path) = export {
+                    match key.code {
+                        KeyCode::Esc => *export = None,
+                        KeyCode::Backspace => {
+                            path.pop();
+                        }
+                        KeyCode::Char(c) => path.push(c),
+                        KeyCode::Enter => {
+                            *message = Some(match save(data, Path::new(path.trim())) {
+                                Ok(()) => format!("Saved to {}", path.trim()),
+                                Err(e) => format!("Export failed: {}", e),
+                            });
+                            *export = None;
+                        }
+                        _ => {}
+                    }
+                    return;
+                }
+                let last = data.len().saturating_sub(1);
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+                    KeyCode::Char('n') => self.screen = Screen::Form,
+                    KeyCode::Char('e') => {
+                        *export = Some(String::from("flights.parquet"));
+                        *message = None;
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => *offset = (*offset + 1).min(last),
+                    KeyCode::Up | KeyCode::Char('k') => *offset = offset.saturating_sub(1),
+                    KeyCode::PageDown => *offset = (*offset + 20).min(last),
+                    KeyCode::PageUp => *offset = offset.saturating_sub(20),
+                    KeyCode::Home => *offset = 0,
+                    KeyCode::End => *offset = last,
+                    _ => {}
+                }
+            }
+            Screen::Error(_) => self.screen = Screen::Form,
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, help] = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let keys = match &self.screen {
+            Screen::Form => "Tab/↑↓ move  Enter run  Esc quit",
+            Screen::Running { .. } => "Esc stop waiting",
+            Screen::Results { export: Some(_), .. } => "Enter save (.parquet, .csv or .csv.gz)  Esc cancel",
+            Screen::Results { .. } => "↑↓/PgUp/PgDn scroll  e export  n new query  q quit",
+            Screen::Error(_) => "Any key: back to the form",
+        };
+        frame.render_widget(Paragraph::new(keys).style(Style::new().fg(Color::DarkGray)), help);
+
+        match &self.screen {
+            Screen::Form => self.draw_form(frame, main),
+            Screen::Running { params, status, .. } => draw_progress(frame, main, params, status.as_ref()),
+            Screen::Results {
+                data,
+                offset,
+                export,
+                message,
+            } => draw_results(frame, main, data, *offset, export.as_deref(), message.as_deref()),
+            Screen::Error(message) => frame.render_widget(
+                Paragraph::new(message.as_str())
+                    .wrap(Wrap { trim: false })
+                    .style(Style::new().fg(Color::Red))
+                    .block(Block::default().borders(Borders::ALL).title(" Error ")),
+                main,
+            ),
+        }
+    }
+
+    fn draw_form(&self, frame: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = FIELDS
+            .iter()
+            .zip(&self.values)
+            .enumerate()
+            .map(|(i, (label, value))| {
+                let style = if i == self.focus {
+                    Style::new().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::new()
+                };
+                Line::styled(format!("{:>16}: {}", label, value), style)
+            })
+            .collect();
+        let hint = Line::styled(
+            "Times as YYYY-MM-DD [HH:MM:SS] UTC; stop may be a duration (30m, 2h, 1d); airports comma-separated",
+            Style::new().fg(Color::DarkGray),
+        );
+        let text: Vec<Line> = lines.into_iter().chain([Line::default(), hint]).collect();
+        frame.render_widget(
+            Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(" History query ")),
+            area,
+        );
+    }
+}
+
+fn draw_progress(frame: &mut Frame, area: Rect, params: &QueryParams, status: Option<&QueryStatus>) {
+    let [gauge, query] = Layout::vertical([Constraint::Length(3), Constraint::Min(1)]).areas(area);
+    let (ratio, label) = match status {
+        Some(status) => (
+            (status.progress / 100.0).clamp(0.0, 1.0),
+            format!("{} | {} rows", super::progress_line(status), status.row_count),
+        ),
+        None => (0.0, String::from("Connecting...")),
+    };
+    frame.render_widget(
+        Gauge::default()
+            .block(Block::default().borders(Borders::ALL).title(" Progress "))
+            .gauge_style(Style::new().fg(Color::Cyan))
+            .ratio(ratio)
+            .label(label),
+        gauge,
+    );
+    frame.render_widget(
+        Paragraph::new(opensky::build_query_preview(params))
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title(" Query ")),
+        query,
+    );
+}
+
+fn draw_results(
+    frame: &mut Frame,
+    area: Rect,
+    data: &FlightData,
+    offset: usize,
+    export: Option<&str>,
+    message: Option<&str>,
+) {
+    let [table_area, status_area] = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(area);
+    let df = data.dataframe();
+    let columns = df.get_columns();
+    // Borders and the header row
+    let visible = table_area.height.saturating_sub(3) as usize;
+
+    let header = Row::new(columns.iter().map(|c| Cell::from(c.name().to_string())))
+        .style(Style::new().add_modifier(Modifier::BOLD));
+    let rows = (offset..data.len().min(offset + visible)).map(|i| {
+        Row::new(columns.iter().map(|c| {
+            Cell::from(match c.get(i) {
+                Ok(AnyValue::Null) | Err(_) => String::new(),
+                Ok(AnyValue::String(s)) => s.to_string(),
+                Ok(value) => value.to_string(),
+            })
+        }))
+    });
+    let widths = columns.iter().map(|c| match c.name().as_str() {
+        "icao24" | "squawk" | "callsign" | "onground" => Constraint::Length(8),
+        _ => Constraint::Length(12),
+    });
+    let truncated = if data.is_truncated() { ", truncated" } else { "" };
+    let title = format!(
+        " {} rows{} ({}-{}) ",
+        data.len(),
+        truncated,
+        offset + 1,
+        data.len().min(offset + visible)
+    );
+    frame.render_widget(
+        Table::new(rows, widths)
+            .header(header)
+            .block(Block::default().borders(Borders::ALL).title(title)),
+        table_area,
+    );
+
+    let status = match (export, message) {
+        (Some(path), _) => format!("Export to: {}_", path),
+        (None, Some(message)) => message.to_string(),
+        (None, None) => String::new(),
+    };
+    frame.render_widget(Paragraph::new(status), status_area);
+}
+
+/// Write the results to Parquet or (optionally gzipped) CSV by extension.
+fn save(data: &FlightData, path: &Path) -> opensky::Result<()> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("parquet") => data.to_parquet(path),
+        ext => data.to_csv_with(path, &CsvOptions::new().gzip(ext == Some("gz"))),
+    }
+}
+
+async fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> std::io::Result<()> {
+    while !app.quit {
+        app.poll_query().await;
+        terminal.draw(|frame| app.draw(frame))?;
+        if event::poll(std::time::Duration::from_millis(100))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    app.on_key(key);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run the terminal UI until the user quits.
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, &mut App::new()).await;
+    ratatui::restore();
+    Ok(result?)
+}
@@ -1,13 +1,18 @@
 //! Query result caching for OpenSky data.
 //!
 //! Caches query results as Parquet files in `~/.cache/opensky/`.
-//! Cache keys are derived from query parameters using a hash.
-
-use crate::types::{FlightData, QueryParams, OpenSkyError};
+//! Cache keys are derived from the generated SQL and a cache version using a
+//! hash. Each entry has a JSON manifest next to it (same name, `.json`)
+//! describing the query and the cached result.
+
+use crate::query::build_history_query;
+use crate::types::{FlightData, QueryParams, OpenSkyError, ResultMetadata};
+use serde::{Deserialize, Serialize};
+use polars::prelude::{GzipLevel, ParquetCompression, ZstdLevel};
 use std::collections::hash_map::DefaultHasher;
 use std::fs;
 use std::hash::{Hash, Hasher};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 
 /// Default cache directory name.
@@ -33,32 +38,150 @@ pub fn ensure_cache_dir() -> Result<PathBuf, OpenSkyError> {
     Ok(dir)
 }
 
+/// Parquet compression of cache files.
+///
+/// Configured with `compression` (`zstd`, `snappy`, `lz4`, `gzip` or
+/// `uncompressed`) and `compression_level` in the `[cache]` section.
+/// Low-cardinality columns such as `icao24` and `callsign` are
+/// dictionary-encoded by the Parquet writer regardless of the codec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheCompression {
+    Uncompressed,
+    Snappy,
+    Lz4,
+    /// Gzip with an optional level (0-9)
+    Gzip(Option<u8>),
+    /// Zstd with an optional level (1-22)
+    Zstd(Option<i32>),
+    /// Polars' default codec
+    #[default]
+    Default,
+}
+
+impl CacheCompression {
+    /// Parse a codec name and optional level, validating the level.
+    pub fn parse(codec: &str, level: Option<i32>) -> Result<Self, OpenSkyError> {
+        let compression = match (codec.to_lowercase().as_str(), level) {
+            ("zstd", level) => CacheCompression::Zstd(level),
+            ("gzip", level) => CacheCompression::Gzip(level.map(|l| u8::try_from(l).unwrap_or(u8::MAX))),
+            ("snappy", None) => CacheCompression::Snappy,
+            ("lz4", None) => CacheCompression::Lz4,
+            ("uncompressed" | "none", None) => CacheCompression::Uncompressed,
+            ("snappy" | "lz4" | "uncompressed" | "none", Some(_)) => {
+                return Err(OpenSkyError::Config(format!(
+                    "Cache compression '{}' does not take a level",
+                    codec
+                )))
+            }
+            _ => {
+                return Err(OpenSkyError::Config(format!(
+                    "Unknown cache compression '{}'",
+                    codec
+                )))
+            }
+        };
+        compression.to_parquet()?;
+        Ok(compression)
+    }
+
+    /// The Polars Parquet compression setting.
+    pub fn to_parquet(self) -> Result<ParquetCompression, OpenSkyError> {
+        let to_err = |e: polars::prelude::PolarsError| {
+            OpenSkyError::Config(format!("Invalid cache compression level: {}", e))
+        };
+        Ok(match self {
+            CacheCompression::Uncompressed => ParquetCompression::Uncompressed,
+            CacheCompression::Snappy => ParquetCompression::Snappy,
+            CacheCompression::Lz4 => ParquetCompression::Lz4Raw,
+            CacheCompression::Gzip(level) => {
+                ParquetCompression::Gzip(level.map(GzipLevel::try_new).transpose().map_err(to_err)?)
+            }
+            CacheCompression::Zstd(level) => {
+                ParquetCompression::Zstd(level.map(ZstdLevel::try_new).transpose().map_err(to_err)?)
+            }
+            CacheCompression::Default => ParquetCompression::default(),
+        })
+    }
+}
+
+/// Whether [`crate::Trino`] reads and writes cached results.
+///
+/// Set with [`crate::Trino::set_cache_policy`]; `[cache] enabled = false`
+/// starts a client with [`CachePolicy::Off`] and `[cache] read_only = true`
+/// with [`CachePolicy::ReadOnly`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CachePolicy {
+    /// Never read or write cache files
+    Off,
+    /// Serve results from the cache only: a result that is not cached fails
+    /// with [`OpenSkyError::CacheMiss`] instead of querying Trino, so a
+    /// re-run uses exactly the data of the original one
+    ReadOnly,
+    /// Serve cached results and store new ones
+    #[default]
+    ReadWrite,
+}
+
+impl CachePolicy {
+    /// Whether cached results may be served.
+    pub fn reads(self) -> bool {
+        self != CachePolicy::Off
+    }
+
+    /// Whether results may be written to (or removed from) the cache.
+    pub fn writes(self) -> bool {
+        self == CachePolicy::ReadWrite
+    }
+}
+
+/// Version of the cache layout, part of every cache key.
+///
+/// Bump it when cached files change shape in a way the SQL does not capture,
+/// e.g. a different Parquet layout or result conversion.
+pub const CACHE_VERSION: u32 = 2;
+
 /// Generate a cache key (filename) from query parameters.
+///
+/// The key hashes [`CACHE_VERSION`] and the SQL generated from the
+/// [canonical](QueryParams::canonical) parameters, which captures every
+/// parameter as well as the selected columns, so changing `FLIGHT_COLUMNS`
+/// or fixing the query builder invalidates stale entries, while spelling
+/// the same query differently does not miss the cache.
 pub fn cache_key(params: &QueryParams) -> String {
     let mut hasher = DefaultHasher::new();
-
-    // Hash all relevant parameters
-    params.icao24.hash(&mut hasher);
-    params.start.hash(&mut hasher);
-    params.stop.hash(&mut hasher);
-    params.callsign.hash(&mut hasher);
-    params.departure_airport.hash(&mut hasher);
-    params.arrival_airport.hash(&mut hasher);
-    params.airport.hash(&mut hasher);
-    params.limit.hash(&mut hasher);
-
-    if let Some(bounds) = &params.bounds {
-        // Hash bounds using their bit representation (f64 doesn't impl Hash)
-        bounds.west.to_bits().hash(&mut hasher);
-        bounds.south.to_bits().hash(&mut hasher);
-        bounds.east.to_bits().hash(&mut hasher);
-        bounds.north.to_bits().hash(&mut hasher);
-    }
+    CACHE_VERSION.hash(&mut hasher);
+    // Invalid parameters have no SQL, and nothing is ever saved under them
+    build_history_query(&params.canonical()).ok().hash(&mut hasher);
 
     let hash = hasher.finish();
     format!("{:016x}.parquet", hash)
 }
 
+/// Manifest stored next to a cache entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheManifest {
+    /// SQL that produced the cached result, exactly as it ran; statements
+    /// of a query split after a resource error are separated by `;`
+    pub sql: String,
+    /// The canonical parameters of the query, see [`QueryParams::canonical`]
+    #[serde(default)]
+    pub params: serde_json::Value,
+    /// When the entry was written (Unix seconds)
+    pub created: i64,
+    pub metadata: ResultMetadata,
+}
+
+/// Path of the manifest of the cache entry at `path`.
+fn manifest_path(path: &Path) -> PathBuf {
+    path.with_extension("json")
+}
+
+/// Remove a cache entry and its manifest.
+fn remove_entry(path: &Path) -> std::io::Result<()> {
+    let _ = fs::remove_file(manifest_path(path));
+    fs::remove_file(path)
+}
+
 /// Get the full cache file path for a query.
 pub fn cache_path(params: &QueryParams) -> Option<PathBuf> {
     cache_dir().map(|d| d.join(cache_key(params)))
@@ -66,7 +189,12 @@ pub fn cache_path(params: &QueryParams) -> Option<PathBuf> {
 
 /// Check if a cached result exists and is not expired.
 pub fn get_cached(params: &QueryParams, max_age: Option<Duration>) -> Option<FlightData> {
-    let path = cache_path(params)?;
+    get_cached_in(&cache_dir()?, params, max_age)
+}
+
+/// Like [`get_cached`], looking in `dir` instead of the default cache directory.
+pub fn get_cached_in(dir: &Path, params: &QueryParams, max_age: Option<Duration>) -> Option<FlightData> {
+    let path = dir.join(cache_key(params));
 
     if !path.exists() {
         return None;
@@ -79,7 +207,7 @@ pub fn get_cached(params: &QueryParams, max_age: Option<Duration>) -> Option<Fli
                 if let Ok(age) = SystemTime::now().duration_since(modified) {
                     if age > max_age {
                         // Cache expired, remove it
-                        let _ = fs::remove_file(&path);
+                        let _ = remove_entry(&path);
                         return None;
                     }
                 }
@@ -93,22 +221,71 @@ pub fn get_cached(params: &QueryParams, max_age: Option<Duration>) -> Option<Fli
 
 /// Save query results to cache.
 pub fn save_to_cache(params: &QueryParams, data: &FlightData) -> Result<PathBuf, OpenSkyError> {
-    let dir = ensure_cache_dir()?;
+    save_to_cache_in(&ensure_cache_dir()?, params, data, CacheCompression::default())
+}
+
+/// Save query results to `dir` with the given compression, creating `dir`
+/// if needed.
+pub fn save_to_cache_in(
+    dir: &Path,
+    params: &QueryParams,
+    data: &FlightData,
+    compression: CacheCompression,
+) -> Result<PathBuf, OpenSkyError> {
+    let sql = build_history_query(params)?;
+    save_to_cache_with_sql(dir, params, data, compression, &sql)
+}
+
+/// Like [`save_to_cache_in`], recording `sql` as the statement that ran.
+pub(crate) fn save_to_cache_with_sql(
+    dir: &Path,
+    params: &QueryParams,
+    data: &FlightData,
+    compression: CacheCompression,
+    sql: &str,
+) -> Result<PathBuf, OpenSkyError> {
+    if !dir.exists() {
+        fs::create_dir_all(dir).map_err(|e| {
+            OpenSkyError::Config(format!("Failed to create cache directory: {}", e))
+        })?;
+    }
     let path = dir.join(cache_key(params));
 
-    data.to_parquet(&path)?;
+    crate::write_parquet_with(data.dataframe(), &path, compression.to_parquet()?)?;
+
+    let params = params.canonical();
+    let manifest = CacheManifest {
+        sql: sql.to_string(),
+        params: serde_json::to_value(&params)?,
+        created: chrono::Utc::now().timestamp(),
+        metadata: data.metadata(),
+    };
+    fs::write(manifest_path(&path), serde_json::to_string_pretty(&manifest)?)?;
 
     Ok(path)
 }
 
+/// Manifest of the cached result of a query in `dir`, if any.
+pub fn cached_manifest_in(dir: &Path, params: &QueryParams) -> Option<CacheManifest> {
+    let content = fs::read_to_string(manifest_path(&dir.join(cache_key(params)))).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
 /// Remove a specific cache entry.
 pub fn remove_cached(params: &QueryParams) -> Result<(), OpenSkyError> {
-    if let Some(path) = cache_path(params) {
-        if path.exists() {
-            fs::remove_file(&path).map_err(|e| {
-                OpenSkyError::Config(format!("Failed to remove cache file: {}", e))
-            })?;
-        }
+    match cache_dir() {
+        Some(dir) => remove_cached_in(&dir, params),
+        None => Ok(()),
+    }
+}
+
+/// Like [`remove_cached`], in `dir` instead of the default cache directory.
+pub fn remove_cached_in(dir: &Path, params: &QueryParams) -> Result<(), OpenSkyError> {
+    let path = dir.join(cache_key(params));
+    if path.exists() {
+        remove_entry(&path).map_err(|e| {
+            OpenSkyError::Config(format!("Failed to remove cache file: {}", e))
+        })?;
     }
     Ok(())
 }
@@ -123,14 +300,10 @@ pub fn clear_cache() -> Result<usize, OpenSkyError> {
     let mut count = 0;
     for entry in fs::read_dir(&dir).map_err(|e| {
         OpenSkyError::Config(format!("Failed to read cache directory: {}", e))
-    })? {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            if path.extension().map_or(false, |e| e == "parquet") {
-                if fs::remove_file(&path).is_ok() {
-                    count += 1;
-                }
-            }
+    })?.flatten() {
+        let path = entry.path();
+        if path.extension().is_some_and(|e| e == "parquet") && remove_entry(&path).is_ok() {
+            count += 1;
         }
     }
 
@@ -149,18 +322,14 @@ pub fn purge_old_cache(max_age: Duration) -> Result<usize, OpenSkyError> {
 
     for entry in fs::read_dir(&dir).map_err(|e| {
         OpenSkyError::Config(format!("Failed to read cache directory: {}", e))
-    })? {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            if path.extension().map_or(false, |e| e == "parquet") {
-                if let Ok(metadata) = fs::metadata(&path) {
-                    if let Ok(modified) = metadata.modified() {
-                        if let Ok(age) = now.duration_since(modified) {
-                            if age > max_age {
-                                if fs::remove_file(&path).is_ok() {
-                                    count += 1;
-                                }
-                            }
+    })?.flatten() {
+        let path = entry.path();
+        if path.extension().is_some_and(|e| e == "parquet") {
+            if let Ok(metadata) = fs::metadata(&path) {
+                if let Ok(modified) = metadata.modified() {
+                    if let Ok(age) = now.duration_since(modified) {
+                        if age > max_age && remove_entry(&path).is_ok() {
+                            count += 1;
                         }
                     }
                 }
@@ -189,14 +358,12 @@ pub fn cache_stats() -> Result<CacheStats, OpenSkyError> {
 
     for entry in fs::read_dir(&dir).map_err(|e| {
         OpenSkyError::Config(format!("Failed to read cache directory: {}", e))
-    })? {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            if path.extension().map_or(false, |e| e == "parquet") {
-                stats.file_count += 1;
-                if let Ok(metadata) = fs::metadata(&path) {
-                    stats.total_size += metadata.len();
-                }
+    })?.flatten() {
+        let path = entry.path();
+        if path.extension().is_some_and(|e| e == "parquet") {
+            stats.file_count += 1;
+            if let Ok(metadata) = fs::metadata(&path) {
+                stats.total_size += metadata.len();
             }
         }
     }
@@ -215,19 +382,24 @@ pub struct CacheStats {
 impl CacheStats {
     /// Get total size as a human-readable string.
     pub fn size_human(&self) -> String {
-        const KB: u64 = 1024;
-        const MB: u64 = KB * 1024;
-        const GB: u64 = MB * 1024;
-
-        if self.total_size >= GB {
-            format!("{:.2} GB", self.total_size as f64 / GB as f64)
-        } else if self.total_size >= MB {
-            format!("{:.2} MB", self.total_size as f64 / MB as f64)
-        } else if self.total_size >= KB {
-            format!("{:.2} KB", self.total_size as f64 / KB as f64)
-        } else {
-            format!("{} B", self.total_size)
-        }
+        format_size(self.total_size)
+    }
+}
+
+/// A byte count as a human-readable string, e.g. "1.50 GB".
+pub(crate) fn format_size(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = KB * 1024;
+    const GB: u64 = MB * 1024;
+
+    if bytes >= GB {
+        format!("{:.2} GB", bytes as f64 / GB as f64)
+    } else if bytes >= MB {
+        format!("{:.2} MB", bytes as f64 / MB as f64)
+    } else if bytes >= KB {
+        format!("{:.2} KB", bytes as f64 / KB as f64)
+    } else {
+        format!("{} B", bytes)
     }
 }
 
@@ -263,4 +435,88 @@ mod tests {
 
         assert_ne!(key1, key2);
     }
+
+    #[test]
+    fn test_cache_compression() {
+        assert_eq!(CacheCompression::parse("ZSTD", Some(19)).unwrap(), CacheCompression::Zstd(Some(19)));
+        assert_eq!(CacheCompression::parse("snappy", None).unwrap(), CacheCompression::Snappy);
+        assert!(CacheCompression::parse("zstd", Some(40)).is_err());
+        assert!(CacheCompression::parse("snappy", Some(3)).is_err());
+        assert!(CacheCompression::parse("brotli", None).is_err());
+    }
+
+    #[test]
+    fn test_cache_key_follows_sql() {
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00");
+
+        assert_ne!(cache_key(&params), cache_key(&params.clone().order(crate::Order::None)));
+        // Not part of the history SQL, so it does not split the cache
+        assert_eq!(
+            cache_key(&params),
+            cache_key(&params.clone().flight_time(crate::FlightTime::Overlap))
+        );
+    }
+
+    #[test]
+    fn test_cache_key_canonical() {
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01", "2025-01-01")
+            .departure(["EHAM", "EGLL"]);
+        let mut same = QueryParams::new()
+            .time_range("2025-01-01 00:00:00", "2025-01-01 23:59:59")
+            .departure(["EGLL", "EHAM", "EHAM"])
+            .resolution(1);
+        same.icao24 = Some("485A32".to_string());
+        assert_eq!(cache_key(&params), cache_key(&same));
+        assert_eq!(same.canonical().departure_airport, ["EGLL", "EHAM"]);
+        assert_ne!(cache_key(&params), cache_key(&params.clone().time_range("2025-01-01", "2025-01-02")));
+
+        // Callsigns and airport codes select different rows in other cases
+        let callsign = |callsign: &str| QueryParams { callsign: Some(callsign.to_string()), ..params.clone() };
+        assert_eq!(callsign("klm1234 ").canonical().callsign.as_deref(), Some("klm1234 "));
+        assert_ne!(cache_key(&callsign("klm1234 ")), cache_key(&callsign("KLM1234")));
+        assert_ne!(cache_key(&params), cache_key(&params.clone().departure(["eham", "EGLL"])));
+    }
+
+    #[test]
+    fn test_cache_key_runs_keyed_sql() {
+        use polars::prelude::*;
+
+        // Spellings sharing a key must run the same statement
+        let dir = tempfile::tempdir().unwrap();
+        let date_only = QueryParams::new().icao24("485a32").time_range("2025-01-01", "2025-01-01");
+        let full_day = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01 00:00:00", "2025-01-01 23:59:59");
+        assert_eq!(cache_key(&date_only), cache_key(&full_day));
+        let data = FlightData::new(df!("time" => [1735725600i64], "icao24" => ["485a32"]).unwrap());
+        save_to_cache_in(dir.path(), &date_only, &data, CacheCompression::default()).unwrap();
+        let manifest = cached_manifest_in(dir.path(), &full_day).unwrap();
+        assert_eq!(manifest.sql, build_history_query(&full_day).unwrap());
+        assert_eq!(manifest.sql, build_history_query(&full_day.canonical()).unwrap());
+        assert!(manifest.sql.contains("time <= 1735775999"));
+    }
+
+    #[test]
+    fn test_cache_manifest() {
+        use polars::prelude::*;
+
+        let dir = tempfile::tempdir().unwrap();
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00");
+        let data = FlightData::new(df!("time" => [1735725600i64], "icao24" => ["485a32"]).unwrap());
+
+        let path = save_to_cache_in(dir.path(), &params, &data, CacheCompression::default()).unwrap();
+        let manifest = cached_manifest_in(dir.path(), &params).unwrap();
+        assert_eq!(manifest.sql, build_history_query(&params).unwrap());
+        assert_eq!(manifest.params["icao24"], "485a32");
+        assert_eq!(manifest.metadata, data.metadata());
+
+        remove_cached_in(dir.path(), &params).unwrap();
+        assert!(!path.exists() && !path.with_extension("json").exists());
+    }
 }
@@ -0,0 +1,251 @@
+//! C ABI for embedding the client (enabled with the `capi` feature).
+//!
+//! Build a shared library with:
+//!
+//! ```bash
+//! cargo rustc --release --lib --features capi --crate-type cdylib
+//! ```
+//!
+//! and include `include/opensky.h`. Results are handed out column by column
+//! through the [Arrow C Data Interface], so C++, Java (via Arrow's JNI
+//! bridge) or any Arrow implementation can import them without parsing CSV.
+//!
+//! All functions returning a pointer return NULL on failure, and all
+//! functions returning `int` return non-zero on failure; call
+//! [`opensky_last_error`] for the message.
+//!
+//! [Arrow C Data Interface]: https://arrow.apache.org/docs/format/CDataInterface.html
+
+use crate::trino::Trino;
+use crate::types::{FlightData, OpenSkyError, QueryParams};
+use polars::prelude::CompatLevel;
+use polars_arrow::ffi::{export_array_to_c, export_field_to_c, ArrowArray, ArrowSchema};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl Into<String>) {
+    let message = CString::new(message.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Opaque client handle.
+pub struct OpenSkyClient {
+    trino: Trino,
+    runtime: tokio::runtime::Runtime,
+}
+
+/// Opaque query result handle.
+pub struct OpenSkyResult {
+    data: FlightData,
+}
+
+/// Read an optional UTF-8 C string argument.
+///
+/// # Safety
+///
+/// `s` must be NULL or a valid NUL-terminated string.
+unsafe fn opt_string(s: *const c_char, name: &str) -> Result<Option<String>, OpenSkyError> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map(|s| Some(s.to_string()))
+        .map_err(|_| OpenSkyError::InvalidParam(format!("{} is not valid UTF-8", name)))
+}
+
+/// Message of the last error on the calling thread, or NULL.
+///
+/// The string stays valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn opensky_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Create a client using credentials from the OpenSky config file.
+#[no_mangle]
+pub extern "C" fn opensky_client_new() -> *mut OpenSkyClient {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(rt) => rt,
+        Err(e) => {
+            set_last_error(e.to_string());
+            return ptr::null_mut();
+        }
+    };
+
+    match runtime.block_on(Trino::new()) {
+        Ok(trino) => Box::into_raw(Box::new(OpenSkyClient { trino, runtime })),
+        Err(e) => {
+            set_last_error(e.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a client. Passing NULL is a no-op.
+///
+/// # Safety
+///
+/// `client` must be NULL or a pointer returned by [`opensky_client_new`]
+/// that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn opensky_client_free(client: *mut OpenSkyClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
+
+/// Run a history query. Any string argument may be NULL; `limit` 0 means no limit.
+///
+/// # Safety
+///
+/// `client` must be a valid client handle and all non-NULL strings must be
+/// NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn opensky_history(
+    client: *mut OpenSkyClient,
+    start: *const c_char,
+    stop: *const c_char,
+    icao24: *const c_char,
+    callsign: *const c_char,
+    departure: *const c_char,
+    arrival: *const c_char,
+    limit: u32,
+) -> *mut OpenSkyResult {
+    let Some(client) = client.as_mut() else {
+        set_last_error("client is NULL");
+        return ptr::null_mut();
+    };
+
+    let result = (|| {
+        let params = QueryParams {
+            start: opt_string(start, "start")?,
+            stop: opt_string(stop, "stop")?,
+            icao24: opt_string(icao24, "icao24")?,
+            callsign: opt_string(callsign, "callsign")?,
+            departure_airport: opt_string(departure, "departure")?.into_iter().collect(),
+            arrival_airport: opt_string(arrival, "arrival")?.into_iter().collect(),
+            limit: (limit > 0).then_some(limit),
+            ..Default::default()
+        };
+        client.runtime.block_on(client.trino.history(params))
+    })();
+
+    match result {
+        Ok(data) => Box::into_raw(Box::new(OpenSkyResult { data })),
+        Err(e) => {
+            set_last_error(e.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Number of rows in a result.
+///
+/// # Safety
+///
+/// `result` must be a valid result handle.
+#[no_mangle]
+pub unsafe extern "C" fn opensky_result_num_rows(result: *const OpenSkyResult) -> usize {
+    result.as_ref().map_or(0, |r| r.data.len())
+}
+
+/// Number of columns in a result.
+///
+/// # Safety
+///
+/// `result` must be a valid result handle.
+#[no_mangle]
+pub unsafe extern "C" fn opensky_result_num_columns(result: *const OpenSkyResult) -> usize {
+    result.as_ref().map_or(0, |r| r.data.dataframe().width())
+}
+
+/// Export column `index` through the Arrow C Data Interface.
+///
+/// On success the caller owns `out_array` and `out_schema` and must call
+/// their `release` callbacks. The schema carries the column name.
+///
+/// # Safety
+///
+/// `result` must be a valid result handle; `out_array` and `out_schema`
+/// must point to writable, uninitialized Arrow structs.
+#[no_mangle]
+pub unsafe extern "C" fn opensky_result_export_column(
+    result: *const OpenSkyResult,
+    index: usize,
+    out_array: *mut ArrowArray,
+    out_schema: *mut ArrowSchema,
+) -> c_int {
+    let Some(result) = result.as_ref() else {
+        set_last_error("result is NULL");
+        return 1;
+    };
+    if out_array.is_null() || out_schema.is_null() {
+        set_last_error("output pointers must not be NULL");
+        return 1;
+    }
+
+    let Some(column) = result.data.dataframe().get_columns().get(index) else {
+        set_last_error(format!("column index {} out of range", index));
+        return 1;
+    };
+
+    let series = column.as_materialized_series().rechunk();
+    let field = series.field().to_arrow(CompatLevel::oldest());
+    let array = series.to_arrow(0, CompatLevel::oldest());
+
+    ptr::write(out_array, export_array_to_c(array));
+    ptr::write(out_schema, export_field_to_c(&field));
+    0
+}
+
+/// Free a result. Passing NULL is a no-op.
+///
+/// # Safety
+///
+/// `result` must be NULL or a pointer returned by [`opensky_history`] that
+/// has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn opensky_result_free(result: *mut OpenSkyResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::MaybeUninit;
+
+    #[test]
+    fn test_export_column() {
+        let df = polars::df!("icao24" => ["485a32", "485a33"], "time" => [1i64, 2]).unwrap();
+        let result = OpenSkyResult { data: FlightData::new(df) };
+
+        let mut array = MaybeUninit::<ArrowArray>::uninit();
+        let mut schema = MaybeUninit::<ArrowSchema>::uninit();
+
+        unsafe {
+            assert_eq!(opensky_result_num_rows(&result), 2);
+            assert_eq!(opensky_result_num_columns(&result), 2);
+
+            let rc = opensky_result_export_column(&result, 5, array.as_mut_ptr(), schema.as_mut_ptr());
+            assert_eq!(rc, 1);
+            assert!(!opensky_last_error().is_null());
+
+            let rc = opensky_result_export_column(&result, 1, array.as_mut_ptr(), schema.as_mut_ptr());
+            assert_eq!(rc, 0);
+
+            let schema = schema.assume_init();
+            let field = polars_arrow::ffi::import_field_from_c(&schema).unwrap();
+            assert_eq!(field.name.as_str(), "time");
+            let array = polars_arrow::ffi::import_array_from_c(array.assume_init(), field.dtype).unwrap();
+            assert_eq!(array.len(), 2);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//! Record-and-replay of HTTP exchanges with OpenSky.
+//!
+//! A cassette stores every auth and Trino request made by a [`Trino`] client
+//! together with the response it received. Replaying the cassette later
+//! serves the same responses without touching the network, which makes
+//! analysis code runnable offline and bug reports reproducible.
+//!
+//! Enable it programmatically with [`Trino::set_cassette`], or for any
+//! program using this crate through the environment:
+//!
+//! ```bash
+//! OPENSKY_CASSETTE=session.jsonl OPENSKY_CASSETTE_MODE=record ./analysis
+//! OPENSKY_CASSETTE=session.jsonl ./analysis   # replays, since the file exists
+//! ```
+//!
+//! The file holds one JSON exchange per line, appended as the requests are
+//! made, so recording a query of many response pages writes each page once.
+//!
+//! Credentials are never written: auth request bodies are not recorded, and
+//! tokens in auth responses are replaced with a placeholder, which replays
+//! just as well since the recorded server does not check them.
+//!
+//! [`Trino`]: crate::Trino
+//! [`Trino::set_cassette`]: crate::Trino::set_cassette
+
+use crate::types::{OpenSkyError, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Environment variable holding the cassette path.
+pub const CASSETTE_ENV: &str = "OPENSKY_CASSETTE";
+
+/// Environment variable selecting the mode (`record`, `replay` or `auto`).
+pub const CASSETTE_MODE_ENV: &str = "OPENSKY_CASSETTE_MODE";
+
+/// Response fields holding credentials, redacted before recording.
+const REDACTED_FIELDS: &[&str] = &["access_token", "refresh_token", "id_token"];
+
+/// Placeholder written instead of a redacted value.
+const REDACTED: &str = "redacted";
+
+/// `response` with the values of [`REDACTED_FIELDS`] replaced, if it is a
+/// JSON object holding any of them.
+fn redact(response: &str) -> String {
+    let Ok(serde_json::Value::Object(mut object)) = serde_json::from_str(response) else {
+        return response.to_string();
+    };
+    let mut redacted = false;
+    for field in REDACTED_FIELDS {
+        if let Some(value) = object.get_mut(*field) {
+            *value = serde_json::Value::String(REDACTED.to_string());
+            redacted = true;
+        }
+    }
+    if redacted {
+        serde_json::Value::Object(object).to_string()
+    } else {
+        response.to_string()
+    }
+}
+
+/// How a cassette is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CassetteMode {
+    /// Perform real requests and append them to the cassette.
+    Record,
+    /// Serve responses from the cassette, never touching the network.
+    Replay,
+    /// Replay if the cassette file exists, otherwise record.
+    #[default]
+    Auto,
+}
+
+impl std::str::FromStr for CassetteMode {
+    type Err = OpenSkyError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "record" => Ok(CassetteMode::Record),
+            "replay" => Ok(CassetteMode::Replay),
+            "auto" | "" => Ok(CassetteMode::Auto),
+            other => Err(OpenSkyError::Config(format!(
+                "Unknown cassette mode '{}'. Use record, replay or auto",
+                other
+            ))),
+        }
+    }
+}
+
+/// A single recorded request and its response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    pub method: String,
+    pub url: String,
+    /// Request body, omitted for requests carrying credentials.
+    pub body: Option<String>,
+    pub status: u16,
+    pub response: String,
+    /// Set once the exchange has been served during replay.
+    #[serde(skip)]
+    used: bool,
+}
+
+/// A file of recorded HTTP exchanges.
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    replay: bool,
+    exchanges: Vec<Exchange>,
+    /// The file being recorded, opened on the first exchange
+    writer: Option<BufWriter<File>>,
+}
+
+impl Cassette {
+    /// Open a cassette at `path` in the given mode.
+    ///
+    /// Recording starts from an empty cassette and overwrites the file.
+    /// Replaying also reads cassettes written as a single JSON array.
+    pub fn open(path: impl AsRef<Path>, mode: CassetteMode) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let replay = match mode {
+            CassetteMode::Record => false,
+            CassetteMode::Replay => true,
+            CassetteMode::Auto => path.exists(),
+        };
+
+        let exchanges = if replay {
+            let content = std::fs::read_to_string(&path).map_err(|e| {
+                OpenSkyError::Config(format!("Failed to read cassette {}: {}", path.display(), e))
+            })?;
+            if content.trim_start().starts_with('[') {
+                serde_json::from_str(&content)?
+            } else {
+                content
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(serde_json::from_str)
+                    .collect::<std::result::Result<_, _>>()?
+            }
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            path,
+            replay,
+            exchanges,
+            writer: None,
+        })
+    }
+
+    /// Open the cassette named by `OPENSKY_CASSETTE`, if set.
+    pub fn from_env() -> Result<Option<Self>> {
+        let path = match std::env::var_os(CASSETTE_ENV) {
+            Some(p) if !p.is_empty() => p,
+            _ => return Ok(None),
+        };
+        let mode = std::env::var(CASSETTE_MODE_ENV)
+            .unwrap_or_default()
+            .parse()?;
+        Self::open(path, mode).map(Some)
+    }
+
+    /// Whether responses are served from the cassette.
+    pub fn is_replay(&self) -> bool {
+        self.replay
+    }
+
+    /// Path of the cassette file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Recorded exchanges, in request order.
+    pub fn exchanges(&self) -> &[Exchange] {
+        &self.exchanges
+    }
+
+    /// Find the first unused exchange matching the request.
+    ///
+    /// A recorded body of `None` matches any request body.
+    pub(crate) fn replay(&mut self, method: &str, url: &str, body: Option<&str>) -> Result<(u16, String)> {
+        let exchange = self
+            .exchanges
+            .iter_mut()
+            .find(|e| {
+                !e.used
+                    && e.method == method
+                    && e.url == url
+                    && (e.body.is_none() || e.body.as_deref() == body)
+            })
+            .ok_or_else(|| {
+                OpenSkyError::Config(format!(
+                    "No recorded response for {} {} in cassette {}",
+                    method,
+                    url,
+                    self.path.display()
+                ))
+            })?;
+
+        exchange.used = true;
+        Ok((exchange.status, exchange.response.clone()))
+    }
+
+    /// Append an exchange to the cassette file, as one line.
+    ///
+    /// Tokens in the response are redacted first. The line is flushed right
+    /// away, so the file is complete should the program stop.
+    pub(crate) fn record(
+        &mut self,
+        method: &str,
+        url: &str,
+        body: Option<&str>,
+        status: u16,
+        response: &str,
+    ) -> Result<()> {
+        let exchange = Exchange {
+            method: method.to_string(),
+            url: url.to_string(),
+            body: body.map(str::to_string),
+            status,
+            response: redact(response),
+            used: false,
+        };
+
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => {
+                if let Some(parent) = self.path.parent() {
+                    if !parent.as_os_str().is_empty() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                }
+                self.writer.insert(BufWriter::new(File::create(&self.path)?))
+            }
+        };
+        serde_json::to_writer(&mut *writer, &exchange)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        self.exchanges.push(exchange);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+
+        let mut cassette = Cassette::open(&path, CassetteMode::Auto).unwrap();
+        assert!(!cassette.is_replay());
+        cassette.record("POST", "http://x/v1/statement", Some("SELECT 1"), 200, "{}").unwrap();
+        cassette.record("GET", "http://x/next", None, 200, "{\"a\":1}").unwrap();
+
+        let mut cassette = Cassette::open(&path, CassetteMode::Auto).unwrap();
+        assert!(cassette.is_replay());
+        assert!(cassette.replay("POST", "http://x/v1/statement", Some("SELECT 2")).is_err());
+
+        let (status, body) = cassette.replay("POST", "http://x/v1/statement", Some("SELECT 1")).unwrap();
+        assert_eq!((status, body.as_str()), (200, "{}"));
+        assert!(cassette.replay("GET", "http://x/next", None).is_ok());
+        // Each exchange is served once
+        assert!(cassette.replay("GET", "http://x/next", None).is_err());
+    }
+
+    #[test]
+    fn test_record_many_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.jsonl");
+
+        let mut cassette = Cassette::open(&path, CassetteMode::Record).unwrap();
+        cassette.record("POST", "http://x/v1/statement", Some("SELECT 1"), 200, "{}").unwrap();
+        for page in 0..100 {
+            let response = format!("{{\"data\":[[{}]]}}", page);
+            cassette.record("GET", &format!("http://x/next/{}", page), None, 200, &response).unwrap();
+        }
+
+        // One line per exchange, each written once
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 101);
+        assert_eq!(content.matches("data").count(), 100);
+
+        let mut cassette = Cassette::open(&path, CassetteMode::Replay).unwrap();
+        assert_eq!(cassette.exchanges().len(), 101);
+        assert!(cassette.replay("POST", "http://x/v1/statement", Some("SELECT 1")).is_ok());
+        for page in 0..100 {
+            let (_, body) = cassette.replay("GET", &format!("http://x/next/{}", page), None).unwrap();
+            assert_eq!(body, format!("{{\"data\":[[{}]]}}", page));
+        }
+    }
+
+    #[test]
+    fn test_replay_json_array() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+        let exchange = r#"{"method":"GET","url":"http://x/next","body":null,"status":200,"response":"{}"}"#;
+        std::fs::write(&path, format!("[\n  {}\n]", exchange)).unwrap();
+
+        let mut cassette = Cassette::open(&path, CassetteMode::Replay).unwrap();
+        assert!(cassette.replay("GET", "http://x/next", None).is_ok());
+    }
+
+    #[test]
+    fn test_tokens_redacted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+
+        let mut cassette = Cassette::open(&path, CassetteMode::Record).unwrap();
+        let response = r#"{"access_token":"secret-bearer","refresh_token":"secret-refresh","expires_in":300}"#;
+        cassette.record("POST", "http://x/token", None, 200, response).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("secret-bearer") && !content.contains("secret-refresh"));
+
+        // The replayed response still parses as a token response
+        let mut cassette = Cassette::open(&path, CassetteMode::Replay).unwrap();
+        let (_, body) = cassette.replay("POST", "http://x/token", None).unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!((body["access_token"].as_str(), body["expires_in"].as_i64()), (Some(REDACTED), Some(300)));
+    }
+}
@@ -5,26 +5,337 @@
 //! - macOS: `~/Library/Application Support/opensky/settings.conf`
 //! - Windows: `%LOCALAPPDATA%\opensky\settings.conf`
 
-use crate::types::{OpenSkyError, Result};
+use crate::cache::CacheCompression;
+use crate::tables::{Table, TableNames};
+use crate::types::{OpenSkyError, Order, QueryParams, Result};
 use configparser::ini::Ini;
+use secrecy::{ExposeSecret, SecretString};
 use std::path::PathBuf;
 
+/// Environment variable overriding the token endpoints: one URL, or several
+/// separated by commas, tried in order.
+pub const AUTH_URL_ENV: &str = "OPENSKY_AUTH_URL";
+
 /// OpenSky configuration containing Trino credentials.
-#[derive(Debug, Clone, Default)]
+///
+/// `Debug` output redacts the password and client secret.
+#[derive(Clone, Default)]
 pub struct Config {
     /// Trino username
     pub username: Option<String>,
     /// Trino password
-    pub password: Option<String>,
+    pub password: Option<SecretString>,
     /// Live API client ID (optional)
     pub client_id: Option<String>,
     /// Live API client secret (optional)
-    pub client_secret: Option<String>,
+    pub client_secret: Option<SecretString>,
     /// Cache purge duration (e.g., "90 days")
     pub cache_purge: Option<String>,
+    /// Parquet codec of cache files (e.g., "zstd")
+    pub cache_compression: Option<String>,
+    /// Compression level of cache files (e.g., "19")
+    pub cache_compression_level: Option<String>,
+    /// Whether results are cached on disk (`[cache] enabled`, default true)
+    pub cache_enabled: Option<bool>,
+    /// Serve results from the cache only (`[cache] read_only`)
+    pub cache_read_only: Option<bool>,
+    /// Trino statement endpoint, defaults to the OpenSky cluster
+    pub trino_url: Option<String>,
+    /// Token endpoint, defaults to the OpenSky identity provider
+    pub auth_url: Option<String>,
+    /// Token endpoints tried in order when `auth_url` fails
+    pub auth_fallback_urls: Vec<String>,
+    /// Application name sent as `X-Trino-Source`, defaults to "opensky-rs"
+    pub source: Option<String>,
+    /// Tags sent as `X-Trino-Client-Tags` (comma-separated in the file)
+    pub client_tags: Vec<String>,
+    /// Cache directory, defaults to the platform cache directory
+    pub cache_dir: Option<PathBuf>,
+    /// Upper bound on the row limit of every query (`[query] max_limit`)
+    pub query_max_limit: Option<u32>,
+    /// Order used by queries that keep the default order (`[query] order`)
+    pub query_order: Option<Order>,
+    /// Longest time range of a single query (`[query] max_span`)
+    pub query_max_span: Option<chrono::Duration>,
+    /// What to do with longer time ranges (`[query] large_span`)
+    pub query_span_policy: Option<SpanPolicy>,
+    /// Table version and names replacing the current ones (`[tables]`)
+    pub tables: TableNames,
+}
+
+/// What to do with a query whose time range exceeds `[query] max_span`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpanPolicy {
+    /// Refuse the query (default)
+    #[default]
+    Error,
+    /// Run history queries as consecutive chunks of at most `max_span`
+    Split,
+}
+
+impl std::str::FromStr for SpanPolicy {
+    type Err = OpenSkyError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "error" => Ok(SpanPolicy::Error),
+            "split" => Ok(SpanPolicy::Split),
+            _ => Err(OpenSkyError::Config(format!(
+                "Unknown large_span '{}', expected error or split",
+                s
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for SpanPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SpanPolicy::Error => "error",
+            SpanPolicy::Split => "split",
+        })
+    }
+}
+
+/// Parse a span such as "7 days", "12 hours" or "1 week".
+fn parse_span(s: &str) -> Result<chrono::Duration> {
+    let invalid = || OpenSkyError::Config(format!("Invalid max_span: {}", s));
+    let s = s.trim().to_lowercase();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num: i64 = num.parse().map_err(|_| invalid())?;
+    let span = match unit.trim().trim_end_matches('s') {
+        "w" | "week" => chrono::Duration::weeks(num),
+        "d" | "day" => chrono::Duration::days(num),
+        "h" | "hour" => chrono::Duration::hours(num),
+        "m" | "min" | "minute" => chrono::Duration::minutes(num),
+        "" | "sec" | "second" => chrono::Duration::seconds(num),
+        _ => return Err(invalid()),
+    };
+    if span <= chrono::Duration::zero() {
+        return Err(invalid());
+    }
+    Ok(span)
+}
+
+/// Split a comma-separated list, dropping empty entries.
+fn split_list(s: &str) -> Vec<String> {
+    s.split(',').map(str::trim).filter(|t| !t.is_empty()).map(String::from).collect()
+}
+
+/// Parse the `[tables]` section: `version` and full names by table.
+fn parse_tables(ini: &Ini) -> Result<TableNames> {
+    let mut tables = TableNames::default();
+    let Some(section) = ini.get_map_ref().get("tables") else {
+        return Ok(tables);
+    };
+    let mut entries: Vec<(&String, &str)> = section
+        .iter()
+        .filter_map(|(key, value)| Some((key, value.as_deref()?.trim())))
+        .filter(|(_, value)| !value.is_empty())
+        .collect();
+    entries.sort();
+    for (key, value) in entries {
+        tables = match key.as_str() {
+            "version" => tables.version(value),
+            _ => {
+                let table: Table = key.parse().map_err(|e: OpenSkyError| OpenSkyError::Config(format!("[tables] {}", e)))?;
+                tables.table(table, value)
+            }
+        };
+    }
+    Ok(tables)
+}
+
+/// Format a span in the largest whole unit, the inverse of [`parse_span`].
+fn format_span(span: chrono::Duration) -> String {
+    let secs = span.num_seconds();
+    for (unit, n) in [("weeks", 604_800), ("days", 86_400), ("hours", 3_600), ("minutes", 60)] {
+        if secs >= n && secs % n == 0 {
+            return format!("{} {}", secs / n, unit);
+        }
+    }
+    format!("{} seconds", secs)
+}
+
+/// Builder for a [`Config`] without a settings file, see [`Config::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    /// Set the Trino username.
+    pub fn username(mut self, username: impl Into<String>) -> Self {
+        self.config.username = Some(username.into());
+        self
+    }
+
+    /// Set the Trino password.
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.config.password = Some(SecretString::from(password.into()));
+        self
+    }
+
+    /// Set the live API client ID and secret.
+    pub fn client_credentials(mut self, client_id: impl Into<String>, client_secret: impl Into<String>) -> Self {
+        self.config.client_id = Some(client_id.into());
+        self.config.client_secret = Some(SecretString::from(client_secret.into()));
+        self
+    }
+
+    /// Use a different Trino statement endpoint.
+    pub fn trino_url(mut self, url: impl Into<String>) -> Self {
+        self.config.trino_url = Some(url.into());
+        self
+    }
+
+    /// Use a different token endpoint.
+    pub fn auth_url(mut self, url: impl Into<String>) -> Self {
+        self.config.auth_url = Some(url.into());
+        self
+    }
+
+    /// Token endpoints to try in order when the main one fails.
+    pub fn auth_fallback_urls<I, S>(mut self, urls: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.auth_fallback_urls = urls.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Name the application in the Trino UI and usage statistics.
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.config.source = Some(source.into());
+        self
+    }
+
+    /// Tag every query, e.g. with a project or team name.
+    pub fn client_tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.client_tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Store cached results in `dir`.
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Enable or disable the on-disk cache.
+    pub fn cache_enabled(mut self, enabled: bool) -> Self {
+        self.config.cache_enabled = Some(enabled);
+        self
+    }
+
+    /// Serve results from the cache only, failing on misses.
+    pub fn cache_read_only(mut self, read_only: bool) -> Self {
+        self.config.cache_read_only = Some(read_only);
+        self
+    }
+
+    /// Set the cache purge duration (e.g., "90 days").
+    pub fn cache_purge(mut self, purge: impl Into<String>) -> Self {
+        self.config.cache_purge = Some(purge.into());
+        self
+    }
+
+    /// Set the cache compression codec and optional level.
+    pub fn cache_compression(mut self, codec: impl Into<String>, level: Option<i32>) -> Self {
+        self.config.cache_compression = Some(codec.into());
+        self.config.cache_compression_level = level.map(|l| l.to_string());
+        self
+    }
+
+    /// Cap the row limit of every query.
+    pub fn max_limit(mut self, limit: u32) -> Self {
+        self.config.query_max_limit = Some(limit);
+        self
+    }
+
+    /// Order used by queries that keep the default order.
+    pub fn default_order(mut self, order: Order) -> Self {
+        self.config.query_order = Some(order);
+        self
+    }
+
+    /// Limit the time range of a single query to `span`.
+    pub fn max_span(mut self, span: chrono::Duration, policy: SpanPolicy) -> Self {
+        self.config.query_max_span = Some(span);
+        self.config.query_span_policy = Some(policy);
+        self
+    }
+
+    /// Query version `version` of the tables (e.g. "data5"), see
+    /// [`crate::tables`].
+    pub fn table_version(mut self, version: impl Into<String>) -> Self {
+        self.config.tables = self.config.tables.version(version);
+        self
+    }
+
+    /// Query `table` under the full name `name`.
+    pub fn table_name(mut self, table: Table, name: impl Into<String>) -> Self {
+        self.config.tables = self.config.tables.table(table, name);
+        self
+    }
+
+    /// Build the configuration.
+    pub fn build(self) -> Config {
+        self.config
+    }
+}
+
+impl std::fmt::Debug for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redact = |secret: &Option<SecretString>| secret.as_ref().map(|_| "********");
+        f.debug_struct("Config")
+            .field("username", &self.username)
+            .field("password", &redact(&self.password))
+            .field("client_id", &self.client_id)
+            .field("client_secret", &redact(&self.client_secret))
+            .field("cache_purge", &self.cache_purge)
+            .field("cache_compression", &self.cache_compression)
+            .field("cache_compression_level", &self.cache_compression_level)
+            .field("cache_enabled", &self.cache_enabled)
+            .field("cache_read_only", &self.cache_read_only)
+            .field("trino_url", &self.trino_url)
+            .field("auth_url", &self.auth_url)
+            .field("auth_fallback_urls", &self.auth_fallback_urls)
+            .field("source", &self.source)
+            .field("client_tags", &self.client_tags)
+            .field("cache_dir", &self.cache_dir)
+            .field("query_max_limit", &self.query_max_limit)
+            .field("query_order", &self.query_order)
+            .field("query_max_span", &self.query_max_span)
+            .field("query_span_policy", &self.query_span_policy)
+            .field("tables", &self.tables)
+            .finish()
+    }
 }
 
 impl Config {
+    /// Start building a configuration in code, e.g. from a secret manager:
+    ///
+    /// ```rust
+    /// use opensky::Config;
+    ///
+    /// let config = Config::builder()
+    ///     .username("user")
+    ///     .password("secret")
+    ///     .cache_dir("/var/cache/opensky")
+    ///     .build();
+    /// assert!(config.has_credentials());
+    /// ```
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
     /// Load configuration from the default config file.
     pub fn load() -> Result<Self> {
         let config_path = Self::config_path()?;
@@ -35,20 +346,65 @@ impl Config {
     pub fn load_from_path(path: &PathBuf) -> Result<Self> {
         if !path.exists() {
             return Err(OpenSkyError::Config(format!(
-                "Config file not found: {}. Run `ostk pyopensky config set` to create it.",
+                "Config file not found: {}. Run `opensky config set` to create it.",
                 path.display()
             )));
         }
 
         let mut ini = Ini::new();
-        ini.load(path).map_err(|e| OpenSkyError::Config(e))?;
+        ini.load(path).map_err(OpenSkyError::Config)?;
 
         let config = Config {
             username: ini.get("default", "username").filter(|s| !s.is_empty()),
-            password: ini.get("default", "password").filter(|s| !s.is_empty()),
+            password: ini.get("default", "password").filter(|s| !s.is_empty()).map(SecretString::from),
             client_id: ini.get("default", "client_id").filter(|s| !s.is_empty()),
-            client_secret: ini.get("default", "client_secret").filter(|s| !s.is_empty()),
+            client_secret: ini.get("default", "client_secret").filter(|s| !s.is_empty()).map(SecretString::from),
             cache_purge: ini.get("cache", "purge").filter(|s| !s.is_empty()),
+            cache_compression: ini.get("cache", "compression").filter(|s| !s.is_empty()),
+            cache_compression_level: ini.get("cache", "compression_level").filter(|s| !s.is_empty()),
+            cache_enabled: ini
+                .get("cache", "enabled")
+                .filter(|s| !s.is_empty())
+                .map(|s| {
+                    s.trim().parse().map_err(|_| OpenSkyError::Config(format!("Invalid cache enabled: {}", s)))
+                })
+                .transpose()?,
+            cache_read_only: ini
+                .get("cache", "read_only")
+                .filter(|s| !s.is_empty())
+                .map(|s| {
+                    s.trim().parse().map_err(|_| OpenSkyError::Config(format!("Invalid cache read_only: {}", s)))
+                })
+                .transpose()?,
+            trino_url: ini.get("default", "trino_url").filter(|s| !s.is_empty()),
+            auth_url: ini.get("default", "auth_url").filter(|s| !s.is_empty()),
+            auth_fallback_urls: ini.get("default", "auth_fallback_urls").map(|s| split_list(&s)).unwrap_or_default(),
+            source: ini.get("default", "source").filter(|s| !s.is_empty()),
+            client_tags: ini.get("default", "client_tags").map(|s| split_list(&s)).unwrap_or_default(),
+            cache_dir: ini.get("cache", "dir").filter(|s| !s.is_empty()).map(PathBuf::from),
+            query_max_limit: ini
+                .get("query", "max_limit")
+                .filter(|s| !s.is_empty())
+                .map(|s| {
+                    s.trim().parse().map_err(|_| OpenSkyError::Config(format!("Invalid max_limit: {}", s)))
+                })
+                .transpose()?,
+            query_order: ini
+                .get("query", "order")
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse().map_err(|e: OpenSkyError| OpenSkyError::Config(e.to_string())))
+                .transpose()?,
+            query_max_span: ini
+                .get("query", "max_span")
+                .filter(|s| !s.is_empty())
+                .map(|s| parse_span(&s))
+                .transpose()?,
+            query_span_policy: ini
+                .get("query", "large_span")
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse())
+                .transpose()?,
+            tables: parse_tables(&ini)?,
         };
 
         Ok(config)
@@ -94,6 +450,25 @@ impl Config {
         Ok(Self::config_dir()?.join("settings.conf"))
     }
 
+    /// Token endpoints in the order to try them.
+    ///
+    /// `OPENSKY_AUTH_URL` replaces the configured endpoints when set;
+    /// otherwise `auth_url` (or the OpenSky identity provider) comes first,
+    /// followed by `auth_fallback_urls`.
+    pub fn auth_urls(&self) -> Vec<String> {
+        if let Some(urls) = std::env::var(AUTH_URL_ENV).ok().map(|s| split_list(&s)).filter(|u| !u.is_empty()) {
+            return urls;
+        }
+        let main = self.auth_url.clone().unwrap_or_else(|| crate::trino::AUTH_URL.to_string());
+        let mut urls = vec![main];
+        for url in &self.auth_fallback_urls {
+            if !urls.contains(url) {
+                urls.push(url.clone());
+            }
+        }
+        urls
+    }
+
     /// Check if credentials are configured.
     pub fn has_credentials(&self) -> bool {
         self.username.is_some() && self.password.is_some()
@@ -109,10 +484,78 @@ impl Config {
     /// Get password or return error.
     pub fn require_password(&self) -> Result<&str> {
         self.password
-            .as_deref()
+            .as_ref()
+            .map(|p| p.expose_secret())
             .ok_or_else(|| OpenSkyError::Config("Password not configured".into()))
     }
 
+    /// Parse the cache compression settings.
+    pub fn cache_compression(&self) -> Result<CacheCompression> {
+        let level = self
+            .cache_compression_level
+            .as_deref()
+            .map(|l| {
+                l.trim().parse::<i32>().map_err(|_| {
+                    OpenSkyError::Config(format!("Invalid cache compression level: {}", l))
+                })
+            })
+            .transpose()?;
+        match self.cache_compression.as_deref() {
+            Some(codec) => CacheCompression::parse(codec, level),
+            None if level.is_some() => CacheCompression::parse("zstd", level),
+            None => Ok(CacheCompression::default()),
+        }
+    }
+
+    /// Apply the `[query]` defaults and `[tables]` names to `params`.
+    ///
+    /// The limit is capped at `max_limit` (and set to it when missing), the
+    /// configured order replaces the default `Order::Time`, and tables not
+    /// named in `params` are those of `[tables]`.
+    pub fn apply_query_defaults(&self, mut params: QueryParams) -> QueryParams {
+        if params.tables.is_empty() {
+            params.tables = self.tables.clone();
+        }
+        if let Some(cap) = self.query_max_limit {
+            params.limit = Some(params.limit.map_or(cap, |limit| limit.min(cap)));
+        }
+        if let Some(order) = self.query_order {
+            if params.order == Order::Time {
+                params.order = order;
+            }
+        }
+        params
+    }
+
+    /// Check the time range of `params` against `[query] max_span`.
+    ///
+    /// Returns the queries to run: `params` itself when it is within the
+    /// limit, or its time range split into chunks with `large_span = split`
+    /// when the caller can run and concatenate chunks (`splittable`).
+    pub fn split_query(&self, params: &QueryParams, splittable: bool) -> Result<Vec<QueryParams>> {
+        let (Some(max_span), Some(span)) = (self.query_max_span, params.time_span()) else {
+            return Ok(vec![params.clone()]);
+        };
+        if span <= max_span {
+            return Ok(vec![params.clone()]);
+        }
+
+        let policy = self.query_span_policy.unwrap_or_default();
+        if policy == SpanPolicy::Split && splittable {
+            return Ok(params.split_time_range(max_span));
+        }
+        let hint = match policy {
+            SpanPolicy::Error => "narrow the range, or set `large_span = split` in [query] to run history queries in chunks",
+            SpanPolicy::Split => "only history queries are split automatically, narrow the range",
+        };
+        Err(OpenSkyError::InvalidParam(format!(
+            "Time range of {:.1} days exceeds the maximum of {} per query ([query] max_span): {}",
+            span.num_seconds() as f64 / 86_400.0,
+            format_span(max_span),
+            hint
+        )))
+    }
+
     /// Save configuration to the default config file.
     pub fn save(&self) -> Result<()> {
         let config_path = Self::config_path()?;
@@ -122,6 +565,7 @@ impl Config {
     /// Save configuration to a specific path.
     pub fn save_to_path(&self, path: &PathBuf) -> Result<()> {
         use std::fs;
+        use std::io::Write;
 
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
@@ -135,19 +579,88 @@ impl Config {
             ini.set("default", "username", Some(u.clone()));
         }
         if let Some(ref p) = self.password {
-            ini.set("default", "password", Some(p.clone()));
+            ini.set("default", "password", Some(p.expose_secret().to_string()));
         }
         if let Some(ref c) = self.client_id {
             ini.set("default", "client_id", Some(c.clone()));
         }
         if let Some(ref c) = self.client_secret {
-            ini.set("default", "client_secret", Some(c.clone()));
+            ini.set("default", "client_secret", Some(c.expose_secret().to_string()));
         }
         if let Some(ref p) = self.cache_purge {
             ini.set("cache", "purge", Some(p.clone()));
         }
+        if let Some(ref c) = self.cache_compression {
+            ini.set("cache", "compression", Some(c.clone()));
+        }
+        if let Some(ref l) = self.cache_compression_level {
+            ini.set("cache", "compression_level", Some(l.clone()));
+        }
+        if let Some(enabled) = self.cache_enabled {
+            ini.set("cache", "enabled", Some(enabled.to_string()));
+        }
+        if let Some(read_only) = self.cache_read_only {
+            ini.set("cache", "read_only", Some(read_only.to_string()));
+        }
+        if let Some(ref u) = self.trino_url {
+            ini.set("default", "trino_url", Some(u.clone()));
+        }
+        if let Some(ref u) = self.auth_url {
+            ini.set("default", "auth_url", Some(u.clone()));
+        }
+        if !self.auth_fallback_urls.is_empty() {
+            ini.set("default", "auth_fallback_urls", Some(self.auth_fallback_urls.join(",")));
+        }
+        if let Some(ref s) = self.source {
+            ini.set("default", "source", Some(s.clone()));
+        }
+        if !self.client_tags.is_empty() {
+            ini.set("default", "client_tags", Some(self.client_tags.join(",")));
+        }
+        if let Some(ref d) = self.cache_dir {
+            ini.set("cache", "dir", Some(d.display().to_string()));
+        }
+        if let Some(limit) = self.query_max_limit {
+            ini.set("query", "max_limit", Some(limit.to_string()));
+        }
+        if let Some(order) = self.query_order {
+            ini.set("query", "order", Some(order.to_string()));
+        }
+        if let Some(span) = self.query_max_span {
+            ini.set("query", "max_span", Some(format_span(span)));
+        }
+        if let Some(policy) = self.query_span_policy {
+            ini.set("query", "large_span", Some(policy.to_string()));
+        }
+        if let Some(ref v) = self.tables.version {
+            ini.set("tables", "version", Some(v.clone()));
+        }
+        for (table, name) in &self.tables.names {
+            ini.set("tables", table.base_name(), Some(name.clone()));
+        }
+
+        // Write to a temporary file next to the target and rename it, so a
+        // crash never leaves a truncated config behind. The file holds
+        // credentials: it is readable by the owner only from the start.
+        let tmp_path = path.with_extension("conf.tmp");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp_path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(ini.writes().as_bytes())?;
+        file.sync_all()?;
+        drop(file);
 
-        ini.write(path).map_err(|e| OpenSkyError::Config(e.to_string()))?;
+        fs::rename(&tmp_path, path)?;
         Ok(())
     }
 }
@@ -180,17 +693,55 @@ password = testpass
 
 [cache]
 purge = 30 days
+compression = zstd
+compression_level = 19
 "#
         )
         .unwrap();
 
         let config = Config::load_from_path(&temp_file.path().to_path_buf()).unwrap();
         assert_eq!(config.username, Some("testuser".to_string()));
-        assert_eq!(config.password, Some("testpass".to_string()));
+        assert_eq!(config.require_password().unwrap(), "testpass");
         assert_eq!(config.cache_purge, Some("30 days".to_string()));
+        assert_eq!(config.cache_compression().unwrap(), CacheCompression::Zstd(Some(19)));
+        assert_eq!(config.cache_enabled, None);
         assert!(config.has_credentials());
     }
 
+    #[test]
+    fn test_cache_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.conf");
+        std::fs::write(&path, "[cache]\nenabled = false\nread_only = true\n").unwrap();
+        let config = Config::load_from_path(&path).unwrap();
+        assert_eq!(config.cache_enabled, Some(false));
+        assert_eq!(config.cache_read_only, Some(true));
+
+        config.save_to_path(&path).unwrap();
+        let config = Config::load_from_path(&path).unwrap();
+        assert_eq!((config.cache_enabled, config.cache_read_only), (Some(false), Some(true)));
+
+        std::fs::write(&path, "[cache]\nenabled = maybe\n").unwrap();
+        assert!(Config::load_from_path(&path).is_err());
+    }
+
+    #[test]
+    fn test_tables() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.conf");
+        std::fs::write(&path, "[tables]\nversion = data5\nflights = minio.osky.flights_v2\n").unwrap();
+        let config = Config::load_from_path(&path).unwrap();
+        assert_eq!(config.tables.get(Table::StateVectors), "minio.osky.state_vectors_data5");
+        assert_eq!(config.tables.get(Table::Flights), "minio.osky.flights_v2");
+
+        config.save_to_path(&path).unwrap();
+        assert_eq!(Config::load_from_path(&path).unwrap().tables, config.tables);
+
+        std::fs::write(&path, "[tables]\naircraft = minio.osky.aircraft\n").unwrap();
+        let err = Config::load_from_path(&path).unwrap_err().to_string();
+        assert!(err.contains("Unknown table 'aircraft'"), "{}", err);
+    }
+
     #[test]
     fn test_empty_values_treated_as_none() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -205,7 +756,131 @@ password =
 
         let config = Config::load_from_path(&temp_file.path().to_path_buf()).unwrap();
         assert_eq!(config.username, None);
-        assert_eq!(config.password, None);
+        assert!(config.password.is_none());
         assert!(!config.has_credentials());
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.conf");
+        let config = Config {
+            username: Some("user".into()),
+            password: Some("secret".into()),
+            ..Default::default()
+        };
+        config.save_to_path(&path).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(Config::load_from_path(&path).unwrap().require_password().unwrap(), "secret");
+        assert!(!path.with_extension("conf.tmp").exists());
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let config = Config {
+            username: Some("user".into()),
+            password: Some("secret".into()),
+            client_secret: Some("hunter2".into()),
+            ..Default::default()
+        };
+        let debug = format!("{:?}", config);
+        assert!(debug.contains("user"));
+        assert!(!debug.contains("secret\"") && !debug.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_builder() {
+        let config = Config::builder()
+            .username("user")
+            .password("secret")
+            .trino_url("https://trino.example/v1/statement")
+            .cache_dir("/tmp/opensky-cache")
+            .build();
+        assert!(config.has_credentials());
+        assert_eq!(config.trino_url.as_deref(), Some("https://trino.example/v1/statement"));
+        assert_eq!(config.cache_dir, Some(PathBuf::from("/tmp/opensky-cache")));
+    }
+
+    #[test]
+    fn test_auth_urls() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(
+            temp_file,
+            "[default]\nauth_url = https://a.example/token\nauth_fallback_urls = https://b.example/token, https://a.example/token"
+        )
+        .unwrap();
+        let config = Config::load_from_path(&temp_file.path().to_path_buf()).unwrap();
+        assert_eq!(config.auth_urls(), ["https://a.example/token", "https://b.example/token"]);
+
+        let config = Config::builder().auth_fallback_urls(["https://b.example/token"]).build();
+        assert_eq!(config.auth_urls(), [crate::trino::AUTH_URL, "https://b.example/token"]);
+    }
+
+    #[test]
+    fn test_source_and_client_tags() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "[default]\nsource = radar-lab\nclient_tags = atm, weekly-report,").unwrap();
+        let config = Config::load_from_path(&temp_file.path().to_path_buf()).unwrap();
+        assert_eq!(config.source.as_deref(), Some("radar-lab"));
+        assert_eq!(config.client_tags, ["atm", "weekly-report"]);
+
+        let path = temp_file.path().with_extension("saved");
+        config.save_to_path(&path).unwrap();
+        assert_eq!(Config::load_from_path(&path).unwrap().client_tags, config.client_tags);
+        std::fs::remove_file(&path).unwrap();
+
+        let config = Config::builder().source("radar-lab").client_tags(["atm"]).build();
+        assert_eq!(config.client_tags, ["atm"]);
+    }
+
+    #[test]
+    fn test_query_defaults() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "[query]\nmax_limit = 1000\norder = none").unwrap();
+        let config = Config::load_from_path(&temp_file.path().to_path_buf()).unwrap();
+
+        let params = config.apply_query_defaults(QueryParams::new());
+        assert_eq!(params.limit, Some(1000));
+        assert_eq!(params.order, Order::None);
+
+        let params = config.apply_query_defaults(QueryParams::new().limit(10).order(Order::IcaoThenTime));
+        assert_eq!(params.limit, Some(10));
+        assert_eq!(params.order, Order::IcaoThenTime);
+        assert_eq!(config.apply_query_defaults(QueryParams::new().limit(5000)).limit, Some(1000));
+
+        writeln!(temp_file, "[query]\nmax_limit = lots").unwrap();
+        assert!(Config::load_from_path(&temp_file.path().to_path_buf()).is_err());
+    }
+
+    #[test]
+    fn test_max_span() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "[query]\nmax_span = 1 day").unwrap();
+        let config = Config::load_from_path(&temp_file.path().to_path_buf()).unwrap();
+        assert_eq!(config.query_max_span, Some(chrono::Duration::days(1)));
+
+        let day = QueryParams::new().time_range("2025-01-01", "2025-01-01");
+        assert_eq!(config.split_query(&day, true).unwrap().len(), 1);
+
+        let month = QueryParams::new().time_range("2025-01-01 00:00:00", "2025-01-31 23:59:59");
+        let err = config.split_query(&month, true).unwrap_err().to_string();
+        assert!(err.contains("31.0 days") && err.contains("large_span"), "{}", err);
+
+        let config = Config::builder().max_span(chrono::Duration::days(7), SpanPolicy::Split).build();
+        let parts = config.split_query(&month, true).unwrap();
+        assert_eq!(parts.len(), 5);
+        assert_eq!(parts[0].stop.as_deref(), Some("2025-01-07 23:59:59"));
+        assert_eq!(parts[1].start.as_deref(), Some("2025-01-08 00:00:00"));
+        assert_eq!(parts[4].stop.as_deref(), Some("2025-01-31 23:59:59"));
+        assert!(config.split_query(&month, false).is_err());
+
+        assert_eq!(parse_span("2 weeks").unwrap(), chrono::Duration::weeks(2));
+        assert_eq!(format_span(chrono::Duration::hours(36)), "36 hours");
+        assert!(parse_span("0 days").is_err() && parse_span("soon").is_err());
+    }
 }
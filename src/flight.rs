@@ -0,0 +1,734 @@
+//! Flights as objects instead of rows.
+//!
+//! [`FlightData::split_flights`] cuts a history result into [`Flight`]s:
+//! the trajectory of one aircraft from its first to its last position, with
+//! its callsign, time span and, when known, airports. Each flight keeps its
+//! points as a [`FlightData`], so the table methods remain available.
+//! [`Traffic`] holds many flights, e.g. all those of a region; with the
+//! `rayon` feature, its per-flight operations run in parallel.
+//!
+//! ```rust,no_run
+//! # fn example(data: &opensky::FlightData) -> opensky::Result<()> {
+//! for flight in data.split_flights(chrono::Duration::minutes(10))? {
+//!     println!("{} {:?}: {} min", flight.icao24, flight.callsign, flight.duration().num_minutes());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::types::{FlightData, OpenSkyError, Result, CRUISE_TOLERANCE, EARTH_RADIUS};
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::Serialize;
+
+/// One flight of one aircraft, see [`FlightData::split_flights`].
+#[derive(Debug, Clone)]
+pub struct Flight {
+    pub icao24: String,
+    /// First non-empty callsign of the points
+    pub callsign: Option<String>,
+    /// Time of the first point
+    pub start: DateTime<Utc>,
+    /// Time of the last point
+    pub stop: DateTime<Utc>,
+    /// `estdepartureairport`, when the points were matched to a flight
+    pub departure: Option<String>,
+    /// `estarrivalairport`, when the points were matched to a flight
+    pub arrival: Option<String>,
+    data: FlightData,
+}
+
+/// A collection of flights.
+///
+/// Built from the flights of [`FlightData::split_flights`], filtered and
+/// transformed flight by flight, and turned back into one table with
+/// [`Traffic::to_flight_data`]:
+///
+/// ```rust,no_run
+/// # fn example(data: &opensky::FlightData) -> opensky::Result<()> {
+/// use opensky::Traffic;
+///
+/// let traffic: Traffic = data.split_flights(chrono::Duration::minutes(10))?.into();
+/// let long = traffic.filter(|flight| flight.duration() > chrono::Duration::hours(1));
+/// let resampled = long.map(|flight| flight.resample(60))?;
+/// resampled.to_flight_data()?.to_parquet("long_flights.parquet")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Traffic {
+    flights: Vec<Flight>,
+}
+
+impl Traffic {
+    pub fn new(flights: Vec<Flight>) -> Self {
+        Traffic { flights }
+    }
+
+    pub fn len(&self) -> usize {
+        self.flights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flights.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Flight> {
+        self.flights.get(index)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Flight> {
+        self.flights.iter()
+    }
+
+    pub fn flights(&self) -> &[Flight] {
+        &self.flights
+    }
+
+    pub fn into_flights(self) -> Vec<Flight> {
+        self.flights
+    }
+
+    /// The flights for which `predicate` is true.
+    pub fn filter(self, mut predicate: impl FnMut(&Flight) -> bool) -> Traffic {
+        self.flights.into_iter().filter(|flight| predicate(flight)).collect()
+    }
+
+    /// Transform each flight, stopping at the first error.
+    pub fn map(self, f: impl FnMut(Flight) -> Result<Flight>) -> Result<Traffic> {
+        self.flights.into_iter().map(f).collect()
+    }
+
+    /// [`Flight::resample`] every flight.
+    pub fn resample(&self, interval: u32) -> Result<Traffic> {
+        try_each(&self.flights, |flight| flight.resample(interval)).map(Traffic::new)
+    }
+
+    /// [`Flight::remove_outliers`] of every flight.
+    pub fn remove_outliers(&self, max_speed: f64) -> Result<Traffic> {
+        try_each(&self.flights, |flight| flight.remove_outliers(max_speed)).map(Traffic::new)
+    }
+
+    /// [`Flight::label_phases`] of every flight.
+    pub fn label_phases(&self) -> Result<Traffic> {
+        try_each(&self.flights, Flight::label_phases).map(Traffic::new)
+    }
+
+    /// [`Flight::simplify`] every flight.
+    pub fn simplify(&self, tolerance: f64) -> Result<Traffic> {
+        try_each(&self.flights, |flight| flight.simplify(tolerance)).map(Traffic::new)
+    }
+
+    /// Apply `f` to every flight on the Rayon thread pool, keeping the order
+    /// of the flights.
+    #[cfg(feature = "rayon")]
+    pub fn par_map<T, F>(&self, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(&Flight) -> T + Send + Sync,
+    {
+        use rayon::prelude::*;
+        self.flights.par_iter().map(f).collect()
+    }
+
+    /// The points of all flights in one table, flight after flight.
+    ///
+    /// Fails if the flights do not share the same columns.
+    pub fn to_flight_data(&self) -> Result<FlightData> {
+        let mut flights = self.flights.iter();
+        let Some(first) = flights.next() else {
+            return Ok(FlightData::new(DataFrame::default()));
+        };
+        let mut df = first.data().dataframe().clone();
+        for flight in flights {
+            df.vstack_mut(flight.data().dataframe())
+                .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+        }
+        Ok(FlightData::new(df))
+    }
+}
+
+impl From<Vec<Flight>> for Traffic {
+    fn from(flights: Vec<Flight>) -> Self {
+        Traffic::new(flights)
+    }
+}
+
+impl FromIterator<Flight> for Traffic {
+    fn from_iter<I: IntoIterator<Item = Flight>>(iter: I) -> Self {
+        Traffic::new(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for Traffic {
+    type Item = Flight;
+    type IntoIter = std::vec::IntoIter<Flight>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.flights.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Traffic {
+    type Item = &'a Flight;
+    type IntoIter = std::slice::Iter<'a, Flight>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.flights.iter()
+    }
+}
+
+/// Key figures of a [`Flight`], see [`Flight::summary`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FlightSummary {
+    pub icao24: String,
+    pub callsign: Option<String>,
+    pub start: DateTime<Utc>,
+    pub stop: DateTime<Utc>,
+    /// Seconds from the first to the last point
+    pub duration: i64,
+    pub points: usize,
+    pub departure: Option<String>,
+    pub arrival: Option<String>,
+    /// Great-circle distance along the positions (km)
+    pub distance: f64,
+    /// Highest `baroaltitude`, or `geoaltitude` where it is null (m)
+    pub max_altitude: Option<f64>,
+}
+
+impl FlightData {
+    /// Split the rows into flights.
+    ///
+    /// Rows matched to flights by [`FlightData::attach_flight_info`] are
+    /// grouped by `flight_id`, leaving out unmatched ones. Otherwise the
+    /// positions of each aircraft are cut where no position was received
+    /// for more than `max_gap`. Flights are in order of their start.
+    pub fn split_flights(&self, max_gap: chrono::Duration) -> Result<Vec<Flight>> {
+        let max_gap = max_gap.num_seconds();
+        let mut flights: Vec<Flight> = if self.dataframe().column("flight_id").is_ok() {
+            let parts: Vec<FlightData> = self.by_flight()?.into_values().collect();
+            try_each(&parts, |data| Flight::new(data.clone()))?.into_iter().flatten().collect()
+        } else {
+            let aircraft: Vec<FlightData> = self.by_aircraft()?.into_values().collect();
+            let flights = try_each(&aircraft, |data| {
+                let parts = split_at_gaps(data, max_gap)?;
+                parts.into_iter().map(Flight::new).collect::<Result<Vec<_>>>()
+            })?;
+            flights.into_iter().flatten().flatten().collect()
+        };
+        flights.sort_by(|a, b| (a.start, &a.icao24).cmp(&(b.start, &b.icao24)));
+        Ok(flights)
+    }
+}
+
+/// Apply `f` to every item, on the Rayon thread pool with the `rayon`
+/// feature, stopping at the first error.
+fn try_each<T, U, F>(items: &[T], f: F) -> Result<Vec<U>>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> Result<U> + Send + Sync,
+{
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        items.par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        items.iter().map(f).collect()
+    }
+}
+
+/// Cut the time-sorted rows of `data` where consecutive times are more than
+/// `max_gap` seconds apart.
+fn split_at_gaps(data: &FlightData, max_gap: i64) -> Result<Vec<FlightData>> {
+    let time = data.time_column()?;
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for i in 1..time.len() {
+        if let (Some(previous), Some(t)) = (time.get(i - 1), time.get(i)) {
+            if t - previous > max_gap {
+                parts.push(FlightData::new(data.dataframe().slice(start as i64, i - start)));
+                start = i;
+            }
+        }
+    }
+    parts.push(FlightData::new(data.dataframe().slice(start as i64, time.len() - start)));
+    Ok(parts)
+}
+
+/// First non-empty value of a string column.
+fn first_string(df: &DataFrame, name: &str) -> Option<String> {
+    df.column(name)
+        .ok()?
+        .str()
+        .ok()?
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+impl Flight {
+    /// Wrap the time-sorted points of one flight; `None` without points.
+    fn new(data: FlightData) -> Result<Option<Self>> {
+        let time = data.time_column()?;
+        let (Some(start), Some(stop)) = (time.min(), time.max()) else {
+            return Ok(None);
+        };
+        let timestamp = |t: i64| DateTime::from_timestamp(t, 0).unwrap_or_default();
+        let df = data.dataframe();
+        Ok(Some(Flight {
+            icao24: first_string(df, "icao24").unwrap_or_default(),
+            callsign: first_string(df, "callsign"),
+            start: timestamp(start),
+            stop: timestamp(stop),
+            departure: first_string(df, "estdepartureairport"),
+            arrival: first_string(df, "estarrivalairport"),
+            data,
+        }))
+    }
+
+    /// The points of the flight, in time order.
+    pub fn data(&self) -> &FlightData {
+        &self.data
+    }
+
+    pub fn into_data(self) -> FlightData {
+        self.data
+    }
+
+    /// Number of points.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Time from the first to the last point.
+    pub fn duration(&self) -> chrono::Duration {
+        self.stop - self.start
+    }
+
+    /// The flight with one point every `interval` seconds from its start.
+    ///
+    /// Float columns (positions, speeds, altitudes) are linearly
+    /// interpolated between their known values and null outside them; other
+    /// columns keep the value of the last point at or before each time.
+    pub fn resample(&self, interval: u32) -> Result<Flight> {
+        if interval == 0 {
+            return Err(OpenSkyError::InvalidParam("Resampling interval must be positive".to_string()));
+        }
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+        let time = self.data.time_column()?;
+        let times: Vec<Option<i64>> = time.into_iter().collect();
+        let grid: Vec<i64> = (self.start.timestamp()..=self.stop.timestamp())
+            .step_by(interval as usize)
+            .collect();
+
+        // Last point at or before each time of the grid
+        let mut j = 0;
+        let previous: Vec<IdxSize> = grid
+            .iter()
+            .map(|&t| {
+                while j + 1 < times.len() && times[j + 1].is_some_and(|next| next <= t) {
+                    j += 1;
+                }
+                j as IdxSize
+            })
+            .collect();
+        let mut df = self
+            .data
+            .dataframe()
+            .take(&IdxCa::from_vec("rows".into(), previous))
+            .map_err(to_err)?;
+        df.with_column(Column::new("time".into(), grid.clone())).map_err(to_err)?;
+
+        let floats: Vec<String> = df
+            .get_columns()
+            .iter()
+            .filter(|c| c.dtype().is_float())
+            .map(|c| c.name().to_string())
+            .collect();
+        for name in floats {
+            let values = self.data.dataframe().column(&name).map_err(to_err)?;
+            let values = values.cast(&DataType::Float64).map_err(to_err)?;
+            let known: Vec<(i64, f64)> = times
+                .iter()
+                .zip(values.f64().map_err(to_err)?)
+                .filter_map(|(t, v)| Some(((*t)?, v?)))
+                .collect();
+            df.with_column(Column::new(name.as_str().into(), interpolate(&known, &grid)))
+                .map_err(to_err)?;
+        }
+        Ok(Flight {
+            data: FlightData::new(df),
+            ..self.clone()
+        })
+    }
+
+    /// The flight without isolated position jumps: points further from both
+    /// their neighbouring positions than `max_speed` (m/s) allows.
+    ///
+    /// The first and last positions, and points without a position, are
+    /// kept.
+    pub fn remove_outliers(&self, max_speed: f64) -> Result<Flight> {
+        let positions = self.positions()?;
+        let speed = |a: &Position, b: &Position| great_circle((a.lat, a.lon), (b.lat, b.lon)) / (b.time - a.time).abs().max(1) as f64;
+        let outliers: std::collections::HashSet<usize> = positions
+            .windows(3)
+            .filter(|w| speed(&w[0], &w[1]) > max_speed && speed(&w[1], &w[2]) > max_speed)
+            .map(|w| w[1].row)
+            .collect();
+        let rows = (0..self.len()).filter(|row| !outliers.contains(row)).collect();
+        self.with_rows(rows)
+    }
+
+    /// The flight with a `phase` column: `GROUND` on the ground, `CLIMB` or
+    /// `DESCENT` beyond 2 m/s of vertical rate, `CRUISE` when level within
+    /// 300 m of the highest altitude and `LEVEL` below; null without a
+    /// vertical rate.
+    pub fn label_phases(&self) -> Result<Flight> {
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+        let height = self.len();
+        let vertrate = self.data.float_column("vertrate")?;
+        let altitude = self.data.altitude()?;
+        let onground = match self.data.dataframe().column("onground") {
+            Ok(column) => Some(column.bool().map_err(to_err)?.clone()),
+            Err(_) => None,
+        };
+        let top = altitude.max().map(|max| max - CRUISE_TOLERANCE);
+        let phase: Vec<Option<&str>> = (0..height)
+            .map(|i| {
+                if onground.as_ref().and_then(|c| c.get(i)) == Some(true) {
+                    return Some("GROUND");
+                }
+                let rate = vertrate.as_ref()?.get(i)?;
+                Some(if rate > CLIMB_RATE {
+                    "CLIMB"
+                } else if rate < -CLIMB_RATE {
+                    "DESCENT"
+                } else if altitude.get(i).zip(top).is_some_and(|(altitude, top)| altitude >= top) {
+                    "CRUISE"
+                } else {
+                    "LEVEL"
+                })
+            })
+            .collect();
+        let mut df = self.data.dataframe().clone();
+        df.with_column(Column::new("phase".into(), phase)).map_err(to_err)?;
+        Ok(Flight {
+            data: FlightData::new(df),
+            ..self.clone()
+        })
+    }
+
+    /// The flight with fewer points, keeping its shape within `tolerance`
+    /// metres (Douglas-Peucker on the positions).
+    ///
+    /// Points without a position are dropped.
+    pub fn simplify(&self, tolerance: f64) -> Result<Flight> {
+        let positions = self.positions()?;
+        if positions.len() < 3 {
+            return self.with_rows(positions.iter().map(|p| p.row).collect());
+        }
+        // Local plane around the first position, in metres
+        let (lat0, lon0) = (positions[0].lat, positions[0].lon);
+        let xy: Vec<(f64, f64)> = positions
+            .iter()
+            .map(|p| {
+                let x = (p.lon - lon0).to_radians() * lat0.to_radians().cos() * EARTH_RADIUS;
+                let y = (p.lat - lat0).to_radians() * EARTH_RADIUS;
+                (x, y)
+            })
+            .collect();
+
+        let mut keep = vec![false; xy.len()];
+        keep[0] = true;
+        keep[xy.len() - 1] = true;
+        let mut stack = vec![(0, xy.len() - 1)];
+        while let Some((first, last)) = stack.pop() {
+            let farthest = (first + 1..last)
+                .map(|i| (i, distance_to_segment(xy[i], xy[first], xy[last])))
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((i, _)) = farthest.filter(|&(_, distance)| distance > tolerance) {
+                keep[i] = true;
+                stack.push((first, i));
+                stack.push((i, last));
+            }
+        }
+        let rows = positions.iter().zip(keep).filter(|(_, keep)| *keep).map(|(p, _)| p.row).collect();
+        self.with_rows(rows)
+    }
+
+    /// Points with a time and position, in time order.
+    fn positions(&self) -> Result<Vec<Position>> {
+        let time = self.data.time_column()?;
+        let (Some(lat), Some(lon)) = (self.data.float_column("lat")?, self.data.float_column("lon")?) else {
+            return Ok(Vec::new());
+        };
+        Ok((0..self.len())
+            .filter_map(|row| {
+                Some(Position {
+                    row,
+                    time: time.get(row)?,
+                    lat: lat.get(row)?,
+                    lon: lon.get(row)?,
+                })
+            })
+            .collect())
+    }
+
+    /// The flight with only the points at `rows`, in that order.
+    fn with_rows(&self, rows: Vec<usize>) -> Result<Flight> {
+        let rows = rows.into_iter().map(|row| row as IdxSize).collect();
+        let df = self
+            .data
+            .dataframe()
+            .take(&IdxCa::from_vec("rows".into(), rows))
+            .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+        let data = FlightData::new(df);
+        let time = data.time_column()?;
+        let timestamp = |t: i64| DateTime::from_timestamp(t, 0).unwrap_or_default();
+        Ok(Flight {
+            start: time.min().map_or(self.start, timestamp),
+            stop: time.max().map_or(self.stop, timestamp),
+            data,
+            ..self.clone()
+        })
+    }
+
+    /// Key figures: duration, points, distance flown and highest altitude.
+    pub fn summary(&self) -> FlightSummary {
+        let mut distance = 0.0;
+        let mut last: Option<(f64, f64)> = None;
+        let mut max_altitude: Option<f64> = None;
+        for sv in self.state_vectors() {
+            if let (Some(lat), Some(lon)) = (sv.lat, sv.lon) {
+                if let Some(last) = last {
+                    distance += great_circle(last, (lat, lon));
+                }
+                last = Some((lat, lon));
+            }
+            if let Some(altitude) = sv.baroaltitude.or(sv.geoaltitude) {
+                max_altitude = Some(max_altitude.map_or(altitude, |max| max.max(altitude)));
+            }
+        }
+        FlightSummary {
+            icao24: self.icao24.clone(),
+            callsign: self.callsign.clone(),
+            start: self.start,
+            stop: self.stop,
+            duration: self.duration().num_seconds(),
+            points: self.len(),
+            departure: self.departure.clone(),
+            arrival: self.arrival.clone(),
+            distance: distance / 1000.0,
+            max_altitude,
+        }
+    }
+
+    /// The flight as a GeoJSON `Feature`: a `LineString` of its positions,
+    /// with the altitude as third coordinate where known, and the
+    /// [`Flight::summary`] as properties.
+    pub fn to_geojson(&self) -> serde_json::Value {
+        let coordinates: Vec<Vec<f64>> = self
+            .state_vectors()
+            .filter_map(|sv| {
+                let mut position = vec![sv.lon?, sv.lat?];
+                position.extend(sv.baroaltitude.or(sv.geoaltitude));
+                Some(position)
+            })
+            .collect();
+        serde_json::json!({
+            "type": "Feature",
+            "geometry": { "type": "LineString", "coordinates": coordinates },
+            "properties": self.summary(),
+        })
+    }
+
+    fn state_vectors(&self) -> impl Iterator<Item = crate::types::StateVector> + '_ {
+        self.data.state_vectors().into_iter().flatten()
+    }
+}
+
+/// Linear interpolation of time-sorted `known` values at each time of
+/// `grid`, null outside them.
+fn interpolate(known: &[(i64, f64)], grid: &[i64]) -> Vec<Option<f64>> {
+    let mut j = 0;
+    grid.iter()
+        .map(|&t| {
+            while j + 1 < known.len() && known[j + 1].0 <= t {
+                j += 1;
+            }
+            let &(t0, v0) = known.get(j)?;
+            if t < t0 {
+                return None;
+            }
+            match known.get(j + 1) {
+                Some(&(t1, v1)) => Some(v0 + (v1 - v0) * (t - t0) as f64 / (t1 - t0) as f64),
+                None => (t == t0).then_some(v0),
+            }
+        })
+        .collect()
+}
+
+/// Vertical rate beyond which a flight climbs or descends (m/s, about
+/// 400 ft/min)
+const CLIMB_RATE: f64 = 2.0;
+
+/// A point of a flight with a position, see [`Flight::positions`].
+struct Position {
+    row: usize,
+    time: i64,
+    lat: f64,
+    lon: f64,
+}
+
+/// Distance from `p` to the segment from `a` to `b`, in plane coordinates.
+fn distance_to_segment(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length).clamp(0.0, 1.0)
+    };
+    ((p.0 - a.0 - t * dx).powi(2) + (p.1 - a.1 - t * dy).powi(2)).sqrt()
+}
+
+/// Great-circle distance between two positions (m).
+fn great_circle((lat0, lon0): (f64, f64), (lat1, lon1): (f64, f64)) -> f64 {
+    let (lat0, lat1) = (lat0.to_radians(), lat1.to_radians());
+    let dlat = lat1 - lat0;
+    let dlon = (lon1 - lon0).to_radians();
+    let a = (dlat / 2.0).sin().powi(2) + lat0.cos() * lat1.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track() -> FlightData {
+        let df = df!(
+            "time" => [1735732800i64, 1735732860, 1735732920, 1735740000, 1735740060, 1735732830],
+            "icao24" => ["485a32", "485a32", "485a32", "485a32", "485a32", "3c6444"],
+            "callsign" => [Some(""), Some("KLM1234 "), None, Some("KLM1235"), None, Some("DLH5")],
+            "lat" => [Some(52.0), Some(52.1), Some(52.2), Some(50.0), None, Some(48.0)],
+            "lon" => [4.0, 4.0, 4.0, 8.0, 8.1, 11.0],
+            "baroaltitude" => [Some(1000.0), None, Some(3000.0), Some(9000.0), Some(9000.0), None]
+        )
+        .unwrap();
+        FlightData::new(df)
+    }
+
+    #[test]
+    fn test_split_flights() {
+        let flights = track().split_flights(chrono::Duration::minutes(10)).unwrap();
+        let spans: Vec<_> = flights
+            .iter()
+            .map(|f| (f.icao24.as_str(), f.callsign.as_deref(), f.len(), f.duration().num_seconds()))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                ("485a32", Some("KLM1234"), 3, 120),
+                ("3c6444", Some("DLH5"), 1, 0),
+                ("485a32", Some("KLM1235"), 2, 60),
+            ]
+        );
+        assert_eq!(flights[0].start.timestamp(), 1735732800);
+        assert_eq!(flights[0].departure, None);
+
+        // Rows matched to flights are grouped by flight, gaps or not
+        let df = df!(
+            "time" => [1735732800i64, 1735740000, 1735740060],
+            "icao24" => ["485a32", "485a32", "485a32"],
+            "flight_id" => [Some("485a32_1735732800"), Some("485a32_1735732800"), None],
+            "estdepartureairport" => [Some("EHAM"), Some("EHAM"), None],
+            "estarrivalairport" => [None::<&str>, None, None]
+        )
+        .unwrap();
+        let flights = FlightData::new(df).split_flights(chrono::Duration::minutes(10)).unwrap();
+        assert_eq!(flights.len(), 1);
+        assert_eq!((flights[0].len(), flights[0].departure.as_deref(), flights[0].arrival.as_deref()), (2, Some("EHAM"), None));
+    }
+
+    #[test]
+    fn test_resample_summary_geojson() {
+        let flight = track().split_flights(chrono::Duration::minutes(10)).unwrap().remove(0);
+
+        let resampled = flight.resample(30).unwrap();
+        let df = resampled.data().dataframe();
+        let times: Vec<_> = df.column("time").unwrap().i64().unwrap().into_no_null_iter().collect();
+        assert_eq!(times, [1735732800, 1735732830, 1735732860, 1735732890, 1735732920]);
+        let altitude: Vec<_> = df.column("baroaltitude").unwrap().f64().unwrap().into_iter().collect();
+        assert_eq!(altitude, [Some(1000.0), Some(1500.0), Some(2000.0), Some(2500.0), Some(3000.0)]);
+        let callsign: Vec<_> = df.column("callsign").unwrap().str().unwrap().into_iter().collect();
+        assert_eq!(callsign, [Some(""), Some(""), Some("KLM1234 "), Some("KLM1234 "), None]);
+        assert!(flight.resample(0).is_err());
+
+        let summary = flight.summary();
+        assert_eq!((summary.duration, summary.points, summary.max_altitude), (120, 3, Some(3000.0)));
+        assert!((summary.distance - 22.24).abs() < 0.01, "{}", summary.distance);
+
+        let geojson = flight.to_geojson();
+        assert_eq!(geojson["geometry"]["coordinates"][0], serde_json::json!([4.0, 52.0, 1000.0]));
+        assert_eq!(geojson["geometry"]["coordinates"][1], serde_json::json!([4.0, 52.1]));
+        assert_eq!(geojson["properties"]["callsign"], "KLM1234");
+    }
+
+    #[test]
+    fn test_traffic() {
+        let traffic: Traffic = track().split_flights(chrono::Duration::minutes(10)).unwrap().into();
+        assert_eq!(traffic.len(), 3);
+
+        let klm = traffic.clone().filter(|flight| flight.icao24 == "485a32");
+        assert_eq!(klm.iter().map(Flight::len).collect::<Vec<_>>(), [3, 2]);
+        let data = klm.map(|flight| flight.resample(60)).unwrap().to_flight_data().unwrap();
+        let times: Vec<_> = data.dataframe().column("time").unwrap().i64().unwrap().into_no_null_iter().collect();
+        assert_eq!(times, [1735732800, 1735732860, 1735732920, 1735740000, 1735740060]);
+        assert!(Traffic::default().to_flight_data().unwrap().is_empty());
+
+        #[cfg(feature = "rayon")]
+        assert_eq!(traffic.par_map(|flight| flight.len()), [3, 1, 2]);
+    }
+
+    #[test]
+    fn test_outliers_phases_simplify() {
+        // Straight north at 200 m/s, with a jump east at the third point
+        let df = df!(
+            "time" => [0i64, 10, 20, 30, 40, 50],
+            "icao24" => ["485a32"; 6],
+            "lat" => [52.0, 52.018, 52.036, 52.054, 52.072, 52.09],
+            "lon" => [4.0, 4.0, 5.0, 4.0, 4.0, 4.0],
+            "onground" => [true, false, false, false, false, false],
+            "vertrate" => [Some(0.0), Some(10.0), Some(0.5), None, Some(-5.0), Some(0.0)],
+            "baroaltitude" => [0.0, 1000.0, 9000.0, 9000.0, 8800.0, 5000.0]
+        )
+        .unwrap();
+        let traffic: Traffic = FlightData::new(df).split_flights(chrono::Duration::minutes(10)).unwrap().into();
+        let times = |flight: &Flight| -> Vec<i64> {
+            flight.data().dataframe().column("time").unwrap().i64().unwrap().into_no_null_iter().collect()
+        };
+
+        let cleaned = traffic.remove_outliers(300.0).unwrap();
+        assert_eq!(times(&cleaned.flights()[0]), [0, 10, 30, 40, 50]);
+
+        let labeled = traffic.label_phases().unwrap();
+        let phase: Vec<_> = labeled.flights()[0].data().dataframe().column("phase").unwrap().str().unwrap().into_iter().collect();
+        assert_eq!(phase, [Some("GROUND"), Some("CLIMB"), Some("CRUISE"), None, Some("DESCENT"), Some("LEVEL")]);
+
+        // Collinear points go, the detour stays
+        let simplified = traffic.simplify(100.0).unwrap();
+        assert_eq!(times(&simplified.flights()[0]), [0, 10, 20, 30, 50]);
+        let simplified = cleaned.simplify(100.0).unwrap();
+        assert_eq!(times(&simplified.flights()[0]), [0, 50]);
+        assert_eq!((simplified.flights()[0].start.timestamp(), simplified.flights()[0].stop.timestamp()), (0, 50));
+    }
+}
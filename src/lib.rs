@@ -44,41 +44,442 @@
 //! ```
 //!
 //! Register for an account at <https://opensky-network.org/>.
+//!
+//! ## Features
+//!
+//! - `client` (default): the Trino client, result caching, config files and
+//!   Polars-backed `FlightData`. Disable default features to build only the
+//!   query builder and parameter types, e.g. for `wasm32-unknown-unknown`.
+//! - `cli`: the `opensky` command-line tool.
+//! - `testing`: `testing::MockTrino` for running against recorded responses.
+//! - `python`: Python bindings returning `polars.DataFrame`s (build with maturin).
+//! - `capi`: a C ABI exporting results through the Arrow C Data Interface.
+//! - `nats`: `sink::NatsPublisher` for forwarding result batches to NATS.
+//! - `rayon`: per-flight operations of `Traffic` run in parallel.
 
+pub mod airports;
+#[cfg(feature = "client")]
 pub mod cache;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "client")]
+pub mod cassette;
+#[cfg(feature = "client")]
 pub mod config;
+#[cfg(feature = "client")]
+pub mod flight;
+pub mod modes;
+#[cfg(feature = "python")]
+pub mod python;
 pub mod query;
+pub mod schema;
+#[cfg(feature = "client")]
+pub mod sink;
+#[cfg(feature = "client")]
+pub mod source;
+pub mod tables;
+#[cfg(all(feature = "client", any(test, feature = "testing")))]
+pub mod testing;
+#[cfg(feature = "client")]
 pub mod trino;
 pub mod types;
 
 // Re-export main types for convenience
-pub use cache::{cache_dir, cache_stats, clear_cache, purge_old_cache, CacheStats};
-pub use config::Config;
-pub use query::{build_history_query, build_flightlist_query, build_rawdata_query, build_query_preview, build_query_preview_method};
-pub use trino::{QueryStatus, Trino};
-pub use types::{Bounds, FlightData, OpenSkyError, QueryParams, RawTable, Result, FLIGHT_COLUMNS, FLIGHTLIST_COLUMNS, RAWDATA_COLUMNS};
+pub use airports::{AirportGroup, Runway};
+#[cfg(feature = "client")]
+pub use cache::{cache_dir, cache_stats, clear_cache, purge_old_cache, CacheCompression, CacheManifest, CachePolicy, CacheStats};
+#[cfg(feature = "client")]
+pub use cassette::{Cassette, CassetteMode};
+#[cfg(feature = "client")]
+pub use config::{Config, ConfigBuilder, SpanPolicy};
+#[cfg(feature = "client")]
+pub use flight::{Flight, FlightSummary, Traffic};
+#[cfg(feature = "client")]
+pub use secrecy::{ExposeSecret, SecretString};
+pub use schema::SchemaCheck;
+pub use query::{build_adsb_query, build_aggregate_query, build_airport_stats_query, build_distinct_icao24_query, build_history_query, build_flightlist_query, build_rawdata_query, build_windows_history_query, build_query_preview, build_query_preview_method};
+#[cfg(feature = "client")]
+pub use source::DataSource;
+pub use tables::{Table, TableNames};
+#[cfg(feature = "client")]
+pub use trino::{ChunkedResult, HistoryPages, ProgressReceiver, QueryCanceller, QueryEstimate, QueryReport, QueryStatus, RawPages, Trino, TrinoColumn, TrinoPage, TrinoWarning};
+#[cfg(feature = "client")]
+pub use types::{DeviationSummary, FlightData, FlightList, FlightListEntry, FuelModel, ProfilePoint, Replay, StateVector, TrajectoryComparison, VerticalProfile};
+pub use types::{Aggregation, Bounds, CacheStatus, ColumnMetadata, CsvOptions, CsvQuoting, FailureInfo, FlightTime, IntoAirports, OpenSkyError, Order, QueryError, QueryParams, RawTable, Result, ResultMetadata, StatsInterval, ADSB_POSITION_COLUMNS, ADSB_VELOCITY_COLUMNS, AIRCRAFT_COLUMNS, AIRPORT_STATS_COLUMNS, FLIGHT_COLUMNS, FLIGHTLIST_COLUMNS, RAWDATA_COLUMNS};
 
 // Re-export polars DataFrame for convenience
+#[cfg(feature = "client")]
 pub use polars::frame::DataFrame;
 
+#[cfg(feature = "client")]
 use std::path::Path;
 
 /// Write a DataFrame to a CSV file.
+///
+/// Chunks are written as they are, so the frame is neither cloned nor rechunked.
+#[cfg(feature = "client")]
 pub fn write_csv(df: &DataFrame, path: impl AsRef<Path>) -> Result<()> {
+    write_csv_with(df, path, &CsvOptions::default())
+}
+
+/// Write a DataFrame to a CSV file with custom options.
+#[cfg(feature = "client")]
+pub fn write_csv_with(df: &DataFrame, path: impl AsRef<Path>, options: &CsvOptions) -> Result<()> {
+    use polars::prelude::*;
+    let to_err = |e: PolarsError| OpenSkyError::DataConversion(format!("Failed to write CSV: {}", e));
+
+    let quote_style = match options.quoting {
+        CsvQuoting::Necessary => QuoteStyle::Necessary,
+        CsvQuoting::Always => QuoteStyle::Always,
+        CsvQuoting::NonNumeric => QuoteStyle::NonNumeric,
+        CsvQuoting::Never => QuoteStyle::Never,
+    };
+
+    let mut file = if options.append {
+        std::fs::OpenOptions::new().create(true).append(true).open(path.as_ref())?
+    } else {
+        std::fs::File::create(path.as_ref())?
+    };
+    // Only a new or empty file gets a header when appending
+    let include_header = options.include_header && file.metadata()?.len() == 0;
+
+    let write = |out: &mut dyn std::io::Write| -> Result<()> {
+        let mut writer = CsvWriter::new(out)
+            .with_separator(options.separator)
+            .with_quote_char(options.quote_char)
+            .with_quote_style(quote_style)
+            .include_header(include_header)
+            .with_float_precision(options.float_precision)
+            .with_datetime_format(options.datetime_format.clone())
+            .batched(df.schema())
+            .map_err(to_err)?;
+        writer.write_batch(df).map_err(to_err)?;
+        writer.finish().map_err(to_err)
+    };
+
+    if options.gzip {
+        let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        write(&mut encoder)?;
+        encoder.finish()?;
+    } else {
+        write(&mut file)?;
+    }
+    Ok(())
+}
+
+/// Write a DataFrame as newline-delimited JSON, one object per row.
+///
+/// With `append`, the rows are added to the end of an existing file.
+#[cfg(feature = "client")]
+pub fn write_ndjson(df: &DataFrame, path: impl AsRef<Path>, append: bool) -> Result<()> {
+    use std::io::Write;
+
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path.as_ref())?;
+    let mut out = std::io::BufWriter::new(file);
+    encode_ndjson(df, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Encode the rows of a DataFrame as NDJSON into `out`.
+#[cfg(feature = "client")]
+pub(crate) fn encode_ndjson(df: &DataFrame, out: &mut impl std::io::Write) -> Result<()> {
+    use polars::prelude::AnyValue;
+
+    let to_json = |value: AnyValue| -> serde_json::Value {
+        match value {
+            AnyValue::Null => serde_json::Value::Null,
+            AnyValue::Boolean(v) => v.into(),
+            AnyValue::String(v) => v.into(),
+            AnyValue::Int8(v) => v.into(),
+            AnyValue::Int16(v) => v.into(),
+            AnyValue::Int32(v) => v.into(),
+            AnyValue::Int64(v) => v.into(),
+            AnyValue::UInt8(v) => v.into(),
+            AnyValue::UInt16(v) => v.into(),
+            AnyValue::UInt32(v) => v.into(),
+            AnyValue::UInt64(v) => v.into(),
+            AnyValue::Float32(v) => v.into(),
+            AnyValue::Float64(v) => v.into(),
+            other => other.to_string().into(),
+        }
+    };
+
+    let columns = df.get_columns();
+    // Written field by field to keep the column order
+    for i in 0..df.height() {
+        out.write_all(b"{")?;
+        for (j, column) in columns.iter().enumerate() {
+            if j > 0 {
+                out.write_all(b",")?;
+            }
+            let value = column.get(i).map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+            serde_json::to_writer(&mut *out, column.name().as_str())?;
+            out.write_all(b":")?;
+            serde_json::to_writer(&mut *out, &to_json(value))?;
+        }
+        out.write_all(b"}\n")?;
+    }
+    Ok(())
+}
+
+/// Append a DataFrame to a Parquet file or directory dataset.
+///
+/// If `path` is a directory, or has no extension, the rows go to a new
+/// `part-NNNNN.parquet` file in it. An existing Parquet file is rewritten with
+/// the rows added as new row groups; its schema must match.
+#[cfg(feature = "client")]
+pub fn append_parquet(df: &DataFrame, path: impl AsRef<Path>) -> Result<()> {
     use polars::prelude::*;
-    let mut file = std::fs::File::create(path.as_ref())?;
-    CsvWriter::new(&mut file)
-        .finish(&mut df.clone())
-        .map_err(|e| OpenSkyError::DataConversion(format!("Failed to write CSV: {}", e)))?;
+    let path = path.as_ref();
+
+    if path.is_dir() || path.extension().is_none() {
+        std::fs::create_dir_all(path)?;
+        let parts = std::fs::read_dir(path)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|e| e == "parquet"))
+            .count();
+        return write_parquet(df, path.join(format!("part-{:05}.parquet", parts)));
+    }
+    if !path.exists() {
+        return write_parquet(df, path);
+    }
+
+    let existing = FlightData::from_parquet(path)?.into_dataframe();
+    if existing.schema() != df.schema() {
+        return Err(OpenSkyError::DataConversion(format!(
+            "Cannot append to {}: columns or types differ",
+            path.display()
+        )));
+    }
+    let to_err = |e: PolarsError| OpenSkyError::DataConversion(format!("Failed to write Parquet: {}", e));
+    // Written next to the file and renamed, so a failure leaves the original intact
+    let tmp = path.with_extension("parquet.tmp");
+    let mut writer = ParquetWriter::new(std::fs::File::create(&tmp)?)
+        .batched(df.schema())
+        .map_err(to_err)?;
+    writer.write_batch(&existing).map_err(to_err)?;
+    writer.write_batch(df).map_err(to_err)?;
+    writer.finish().map_err(to_err)?;
+    std::fs::rename(&tmp, path)?;
     Ok(())
 }
 
+/// Time span (Unix seconds) of the rows already in an output file.
+///
+/// Reads CSV (with a header, plain or gzipped), NDJSON (`.ndjson`, `.jsonl`),
+/// Parquet files and directory datasets; `None` if the output does not exist
+/// or has no time column. See [`output_time_spans`] for the span of each
+/// aircraft.
+#[cfg(feature = "client")]
+pub fn output_time_range(path: impl AsRef<Path>) -> Result<Option<(i64, i64)>> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let merge = |a: Option<(i64, i64)>, b: Option<(i64, i64)>| match (a, b) {
+        (Some(a), Some(b)) => Some((a.0.min(b.0), a.1.max(b.1))),
+        (a, b) => a.or(b),
+    };
+    let span = |data: FlightData| {
+        let metadata = data.metadata();
+        metadata.start.zip(metadata.stop)
+    };
+
+    if path.is_dir() {
+        let mut range = None;
+        for entry in std::fs::read_dir(path)? {
+            let part = entry?.path();
+            if part.extension().is_some_and(|e| e == "parquet") {
+                range = merge(range, span(FlightData::from_parquet(&part)?));
+            }
+        }
+        return Ok(range);
+    }
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("parquet") => Ok(span(FlightData::from_parquet(path)?)),
+        Some("ndjson" | "jsonl") => {
+            let content = std::fs::read_to_string(path)?;
+            let mut range = None;
+            for line in content.lines().filter(|line| !line.trim().is_empty()) {
+                let row: serde_json::Value = serde_json::from_str(line)?;
+                let time = |name: &str| row.get(name).and_then(|v| v.as_f64()).map(|t| t as i64);
+                let row_span = time("time")
+                    .or_else(|| time("mintime"))
+                    .map(|t| (t, t))
+                    .or_else(|| time("firstseen").zip(time("lastseen")));
+                range = merge(range, row_span);
+            }
+            Ok(range)
+        }
+        _ => Ok(span(FlightData::from_csv(path)?)),
+    }
+}
+
+/// Time span (Unix seconds) of each aircraft's rows already in an output
+/// file, see [`FlightData::time_spans`].
+///
+/// Reads the same outputs as [`output_time_range`]; empty if the output
+/// does not exist. Used to skip rows that an append would duplicate,
+/// without dropping other aircraft seen at the same time.
+#[cfg(feature = "client")]
+pub fn output_time_spans(path: impl AsRef<Path>) -> Result<std::collections::HashMap<String, (i64, i64)>> {
+    let path = path.as_ref();
+    let mut spans = std::collections::HashMap::new();
+    if !path.exists() {
+        return Ok(spans);
+    }
+    let mut merge = |icao24: String, first: i64, last: i64| {
+        let span = spans.entry(icao24).or_insert((first, last));
+        *span = (span.0.min(first), span.1.max(last));
+    };
+
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            let part = entry?.path();
+            if part.extension().is_some_and(|e| e == "parquet") {
+                for (icao24, (first, last)) in FlightData::from_parquet(&part)?.time_spans()? {
+                    merge(icao24, first, last);
+                }
+            }
+        }
+    } else {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("ndjson" | "jsonl") => {
+                let content = std::fs::read_to_string(path)?;
+                for line in content.lines().filter(|line| !line.trim().is_empty()) {
+                    let row: serde_json::Value = serde_json::from_str(line)?;
+                    let time = |name: &str| row.get(name).and_then(|v| v.as_f64()).map(|t| t as i64);
+                    let row_span = time("time")
+                        .or_else(|| time("mintime"))
+                        .map(|t| (t, t))
+                        .or_else(|| time("firstseen").zip(time("lastseen")));
+                    if let (Some(icao24), Some((first, last))) = (row.get("icao24").and_then(|v| v.as_str()), row_span) {
+                        merge(icao24.to_string(), first, last);
+                    }
+                }
+            }
+            Some("parquet") => {
+                for (icao24, (first, last)) in FlightData::from_parquet(path)?.time_spans()? {
+                    merge(icao24, first, last);
+                }
+            }
+            _ => {
+                for (icao24, (first, last)) in FlightData::from_csv(path)?.time_spans()? {
+                    merge(icao24, first, last);
+                }
+            }
+        }
+    }
+    Ok(spans)
+}
+
 /// Write a DataFrame to a Parquet file.
+///
+/// Each chunk becomes a row group; the frame is not cloned.
+#[cfg(feature = "client")]
 pub fn write_parquet(df: &DataFrame, path: impl AsRef<Path>) -> Result<()> {
+    write_parquet_with(df, path, Default::default())
+}
+
+/// Write a DataFrame to a Parquet file with the given compression.
+#[cfg(feature = "client")]
+pub fn write_parquet_with(
+    df: &DataFrame,
+    path: impl AsRef<Path>,
+    compression: polars::prelude::ParquetCompression,
+) -> Result<()> {
+    use polars::prelude::*;
+    let to_err = |e: PolarsError| OpenSkyError::DataConversion(format!("Failed to write Parquet: {}", e));
+    let file = std::fs::File::create(path.as_ref())?;
+    let mut writer = ParquetWriter::new(file)
+        .with_compression(compression)
+        .batched(df.schema())
+        .map_err(to_err)?;
+    writer.write_batch(df).map_err(to_err)?;
+    writer.finish().map_err(to_err)?;
+    Ok(())
+}
+
+/// Write a DataFrame as a Hive-partitioned Parquet dataset under `dir`.
+///
+/// Rows are split by the values of the `partition_by` columns into
+/// `dir/<column>=<value>/.../part-NNNNN.parquet`, the layout scanned by
+/// Polars, DuckDB (`hive_partitioning = true`) and Spark. The partition
+/// columns are left out of the files, since readers restore them from the
+/// paths. `date` needs no such column: it is the UTC day of `time`.
+/// Partitions that already exist get a new part file, so a dataset can be
+/// filled by several runs.
+#[cfg(feature = "client")]
+pub fn write_parquet_dataset(df: &DataFrame, dir: impl AsRef<Path>, partition_by: &[&str]) -> Result<()> {
     use polars::prelude::*;
-    let mut file = std::fs::File::create(path.as_ref())?;
-    ParquetWriter::new(&mut file)
-        .finish(&mut df.clone())
-        .map_err(|e| OpenSkyError::DataConversion(format!("Failed to write Parquet: {}", e)))?;
+    let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+
+    // Partition values of each row, one list per partition column
+    let mut values: Vec<Vec<String>> = Vec::with_capacity(partition_by.len());
+    for &name in partition_by {
+        let column = match df.column(name) {
+            Ok(column) => column.cast(&DataType::String).map_err(to_err)?,
+            Err(_) if name == "date" => {
+                let time = df.column("time").map_err(to_err)?.cast(&DataType::Int64).map_err(to_err)?;
+                let dates: Vec<Option<String>> = time
+                    .i64()
+                    .map_err(to_err)?
+                    .into_iter()
+                    .map(|t| Some(chrono::DateTime::from_timestamp(t?, 0)?.date_naive().to_string()))
+                    .collect();
+                Column::new("date".into(), dates)
+            }
+            Err(_) => return Err(OpenSkyError::InvalidParam(format!("No column '{}' to partition by", name))),
+        };
+        let column = column.str().map_err(to_err)?;
+        values.push(
+            column
+                .into_iter()
+                .map(|value| value.map_or("__HIVE_DEFAULT_PARTITION__".to_string(), hive_escape))
+                .collect(),
+        );
+    }
+    let mut partitions: std::collections::BTreeMap<Vec<&str>, Vec<IdxSize>> = std::collections::BTreeMap::new();
+    for row in 0..df.height() {
+        let key = values.iter().map(|column| column[row].as_str()).collect();
+        partitions.entry(key).or_default().push(row as IdxSize);
+    }
+
+    let stored: Vec<&str> = partition_by.iter().copied().filter(|name| df.column(name).is_ok()).collect();
+    for (key, rows) in partitions {
+        let part = df
+            .take(&IdxCa::from_vec("rows".into(), rows))
+            .map_err(to_err)?
+            .drop_many(stored.iter().copied());
+        let mut path = dir.as_ref().to_path_buf();
+        for (name, value) in partition_by.iter().zip(key) {
+            path.push(format!("{}={}", name, value));
+        }
+        // Created first, so that a value with a dot is not taken for a file
+        std::fs::create_dir_all(&path)?;
+        append_parquet(&part, path)?;
+    }
     Ok(())
 }
+
+/// Percent-encode the characters of a partition value that are not safe in
+/// a path segment.
+#[cfg(feature = "client")]
+fn hive_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.".contains(&byte) {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
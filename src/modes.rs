@@ -0,0 +1,183 @@
+//! Mode S message classification for raw data results.
+//!
+//! Raw data queries return messages as hex strings (`rawmsg`). These
+//! helpers read their downlink format and, for Comm-B replies (DF 20 and
+//! 21), infer which BDS register the 56-bit MB field carries, as needed
+//! for Mode S EHS studies. Registers 1,0, 2,0 and 3,0 name themselves in
+//! their first byte; 4,0, 5,0 and 6,0 are inferred from their status bits
+//! and value ranges, so a message may match more than one of them.
+
+/// Downlink format of a hex-encoded Mode S message.
+///
+/// Formats 24 and above (Comm-D) share the value 24, as in the message
+/// itself only their first two bits are set.
+pub fn downlink_format(msg: &str) -> Option<u8> {
+    let first = u8::from_str_radix(msg.get(..2)?, 16).ok()?;
+    Some((first >> 3).min(24))
+}
+
+/// A Comm-B register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bds {
+    /// Data link capability
+    Bds10,
+    /// Aircraft identification
+    Bds20,
+    /// ACAS active resolution advisory
+    Bds30,
+    /// Selected vertical intention
+    Bds40,
+    /// Track and turn report
+    Bds50,
+    /// Heading and speed report
+    Bds60,
+}
+
+impl Bds {
+    /// Every register [`infer_bds`] knows.
+    pub const ALL: [Bds; 6] = [Bds::Bds10, Bds::Bds20, Bds::Bds30, Bds::Bds40, Bds::Bds50, Bds::Bds60];
+
+    /// Whether the MB field `mb` fits this register.
+    fn fits(self, mb: u64) -> bool {
+        match self {
+            Bds::Bds10 => is_bds10(mb),
+            Bds::Bds20 => is_bds20(mb),
+            Bds::Bds30 => is_bds30(mb),
+            Bds::Bds40 => is_bds40(mb),
+            Bds::Bds50 => is_bds50(mb),
+            Bds::Bds60 => is_bds60(mb),
+        }
+    }
+}
+
+impl std::fmt::Display for Bds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Bds::Bds10 => "1,0",
+            Bds::Bds20 => "2,0",
+            Bds::Bds30 => "3,0",
+            Bds::Bds40 => "4,0",
+            Bds::Bds50 => "5,0",
+            Bds::Bds60 => "6,0",
+        })
+    }
+}
+
+/// The MB field of a Comm-B reply, or `None` for other messages.
+fn comm_b(msg: &str) -> Option<u64> {
+    if msg.len() != 28 || !matches!(downlink_format(msg)?, 20 | 21) {
+        return None;
+    }
+    let bits = u128::from_str_radix(msg, 16).ok()?;
+    Some(((bits >> 24) & ((1 << 56) - 1)) as u64)
+}
+
+/// Bits `msb..=lsb` of the MB field, numbered from 1.
+fn field(mb: u64, msb: u32, lsb: u32) -> u64 {
+    (mb >> (56 - lsb)) & ((1 << (lsb - msb + 1)) - 1)
+}
+
+/// Signed value of bits `msb..=lsb`, the first of which is the sign.
+fn signed(mb: u64, msb: u32, lsb: u32) -> i64 {
+    let value = field(mb, msb, lsb) as i64;
+    if field(mb, msb, msb) == 1 {
+        value - (1 << (lsb - msb + 1))
+    } else {
+        value
+    }
+}
+
+/// Whether any `(status, msb, lsb)` field has a value while its status bit
+/// says it is unavailable.
+fn wrong_status(mb: u64, fields: &[(u32, u32, u32)]) -> bool {
+    fields
+        .iter()
+        .any(|&(status, msb, lsb)| field(mb, status, status) == 0 && field(mb, msb, lsb) != 0)
+}
+
+/// Value of a field when its status bit is set.
+fn available(mb: u64, status: u32, value: i64) -> Option<i64> {
+    (field(mb, status, status) == 1).then_some(value)
+}
+
+fn is_bds10(mb: u64) -> bool {
+    field(mb, 1, 8) == 0x10 && field(mb, 10, 14) == 0
+}
+
+fn is_bds20(mb: u64) -> bool {
+    const CHARS: &[u8; 64] = b"#ABCDEFGHIJKLMNOPQRSTUVWXYZ##### ###############0123456789######";
+    field(mb, 1, 8) == 0x20
+        && (0..8).all(|i| CHARS[field(mb, 9 + 6 * i, 14 + 6 * i) as usize] != b'#')
+}
+
+fn is_bds30(mb: u64) -> bool {
+    // Threat type 3 is not assigned
+    field(mb, 1, 8) == 0x30 && field(mb, 29, 30) != 3
+}
+
+fn is_bds40(mb: u64) -> bool {
+    !wrong_status(mb, &[(1, 2, 13), (14, 15, 26), (27, 28, 39), (48, 49, 51), (54, 55, 56)])
+        && field(mb, 40, 47) == 0
+        && field(mb, 52, 53) == 0
+}
+
+fn is_bds50(mb: u64) -> bool {
+    if wrong_status(mb, &[(1, 3, 11), (12, 13, 23), (24, 25, 34), (35, 36, 45), (46, 47, 56)]) {
+        return false;
+    }
+    // Roll angle in degrees, ground speed and true airspeed in knots
+    let roll = available(mb, 1, signed(mb, 2, 11)).map(|roll| roll as f64 * 45.0 / 256.0);
+    let gs = available(mb, 24, field(mb, 25, 34) as i64 * 2);
+    let tas = available(mb, 46, field(mb, 47, 56) as i64 * 2);
+    roll.map_or(true, |roll| roll.abs() <= 50.0)
+        && gs.map_or(true, |gs| gs <= 600)
+        && tas.map_or(true, |tas| tas <= 600)
+        && !matches!((gs, tas), (Some(gs), Some(tas)) if (gs - tas).abs() > 200)
+}
+
+fn is_bds60(mb: u64) -> bool {
+    if wrong_status(mb, &[(1, 2, 12), (13, 14, 23), (24, 25, 34), (35, 36, 45), (46, 47, 56)]) {
+        return false;
+    }
+    // Indicated airspeed in knots, Mach number, vertical rates in ft/min
+    let ias = available(mb, 13, field(mb, 14, 23) as i64);
+    let mach = available(mb, 24, field(mb, 25, 34) as i64).map(|mach| mach as f64 * 2.048 / 512.0);
+    let baro = available(mb, 35, signed(mb, 36, 45) * 32);
+    let inertial = available(mb, 46, signed(mb, 47, 56) * 32);
+    ias.map_or(true, |ias| ias <= 500)
+        && mach.map_or(true, |mach| mach <= 1.0)
+        && !matches!((baro, inertial), (Some(baro), Some(inertial)) if (baro - inertial).abs() > 2000)
+}
+
+/// Candidate registers of a hex-encoded Comm-B reply.
+///
+/// Empty for other downlink formats, malformed messages, an empty MB
+/// field, or when no register fits.
+pub fn infer_bds(msg: &str) -> Vec<Bds> {
+    let Some(mb) = comm_b(msg).filter(|&mb| mb != 0) else {
+        return Vec::new();
+    };
+    Bds::ALL.into_iter().filter(|bds| bds.fits(mb)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_bds() {
+        assert_eq!(downlink_format("8d406b902015a678d4d220aa4bda"), Some(17));
+        assert_eq!(downlink_format("f8000000000000"), Some(24));
+        assert_eq!(downlink_format("x"), None);
+
+        assert_eq!(infer_bds("A000083E202CC371C31DE0AA1CCF"), [Bds::Bds20]);
+        assert_eq!(infer_bds("a0001839ca3800315800007448d9"), [Bds::Bds40]);
+        assert_eq!(infer_bds("A000139381951536E024D4CCF6B5"), [Bds::Bds50]);
+        assert_eq!(infer_bds("A00004128F39F91A7E27C46ADC21"), [Bds::Bds60]);
+        assert_eq!(Bds::Bds60.to_string(), "6,0");
+
+        // Not a Comm-B reply, or nothing in the MB field
+        assert!(infer_bds("8D406B902015A678D4D220AA4BDA").is_empty());
+        assert!(infer_bds("A000000000000000000000000000").is_empty());
+    }
+}
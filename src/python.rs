@@ -0,0 +1,210 @@
+//! Python bindings (enabled with the `python` feature).
+//!
+//! Exposes a pyopensky-like interface backed by this crate. Results are
+//! returned as `polars.DataFrame` objects, exchanged through the Arrow C
+//! Data Interface without copying the column buffers.
+//!
+//! Build an extension module with [maturin](https://www.maturin.rs/):
+//!
+//! ```bash
+//! maturin develop --release
+//! ```
+//!
+//! ```python
+//! from opensky import Trino
+//!
+//! trino = Trino()
+//! df = trino.history("2025-01-01 10:00:00", "2025-01-01 12:00:00", icao24="485a32")
+//! ```
+
+use crate::query::{build_history_query, build_query_preview};
+use crate::trino::Trino;
+use crate::types::{Bounds, OpenSkyError, QueryParams, RawTable};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3_polars::PyDataFrame;
+
+fn to_py_err(err: OpenSkyError) -> PyErr {
+    match err {
+        OpenSkyError::InvalidParam(msg) => PyValueError::new_err(msg),
+        other => PyRuntimeError::new_err(other.to_string()),
+    }
+}
+
+/// One airport code or a list of them.
+#[derive(FromPyObject, Clone)]
+enum Airports {
+    One(String),
+    Many(Vec<String>),
+}
+
+fn airports(value: Option<Airports>) -> Vec<String> {
+    match value {
+        None => Vec::new(),
+        Some(Airports::One(airport)) => vec![airport],
+        Some(Airports::Many(airports)) => airports,
+    }
+}
+
+/// Query parameters, mirroring the keyword arguments of `Trino.history()`.
+#[pyclass(name = "QueryParams", module = "opensky")]
+#[derive(Clone)]
+pub struct PyQueryParams {
+    inner: QueryParams,
+}
+
+#[pymethods]
+impl PyQueryParams {
+    #[new]
+    #[pyo3(signature = (
+        start=None, stop=None, *, icao24=None, callsign=None,
+        departure_airport=None, arrival_airport=None, airport=None,
+        bounds=None, limit=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        start: Option<String>,
+        stop: Option<String>,
+        icao24: Option<String>,
+        callsign: Option<String>,
+        departure_airport: Option<Airports>,
+        arrival_airport: Option<Airports>,
+        airport: Option<Airports>,
+        bounds: Option<(f64, f64, f64, f64)>,
+        limit: Option<u32>,
+    ) -> Self {
+        let inner = QueryParams {
+            start,
+            stop,
+            icao24,
+            callsign,
+            departure_airport: airports(departure_airport),
+            arrival_airport: airports(arrival_airport),
+            airport: airports(airport),
+            bounds: bounds.map(|(west, south, east, north)| Bounds::new(west, south, east, north)),
+            limit,
+            ..Default::default()
+        };
+        Self { inner }
+    }
+
+    /// The SQL statement a history query with these parameters would run.
+    fn sql(&self) -> PyResult<String> {
+        build_history_query(&self.inner).map_err(to_py_err)
+    }
+
+    fn __repr__(&self) -> String {
+        build_query_preview(&self.inner).replacen("trino.history(", "QueryParams(", 1)
+    }
+}
+
+/// Trino client for the OpenSky database.
+///
+/// Queries block the calling thread but release the GIL while running.
+#[pyclass(name = "Trino", module = "opensky")]
+pub struct PyTrino {
+    inner: Trino,
+    runtime: tokio::runtime::Runtime,
+}
+
+#[pymethods]
+impl PyTrino {
+    /// Create a client using credentials from the OpenSky config file.
+    #[new]
+    fn new() -> PyResult<Self> {
+        let runtime = tokio::runtime::Runtime::new()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        let inner = runtime.block_on(Trino::new()).map_err(to_py_err)?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// Query state vectors; returns a `polars.DataFrame`.
+    #[pyo3(signature = (
+        start=None, stop=None, *, icao24=None, callsign=None,
+        departure_airport=None, arrival_airport=None, airport=None,
+        bounds=None, limit=None, params=None, cached=true
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn history(
+        &mut self,
+        py: Python<'_>,
+        start: Option<String>,
+        stop: Option<String>,
+        icao24: Option<String>,
+        callsign: Option<String>,
+        departure_airport: Option<Airports>,
+        arrival_airport: Option<Airports>,
+        airport: Option<Airports>,
+        bounds: Option<(f64, f64, f64, f64)>,
+        limit: Option<u32>,
+        params: Option<PyQueryParams>,
+        cached: bool,
+    ) -> PyResult<PyDataFrame> {
+        let params = params.map(|p| p.inner).unwrap_or_else(|| {
+            PyQueryParams::new(
+                start, stop, icao24, callsign, departure_airport, arrival_airport, airport, bounds, limit,
+            )
+            .inner
+        });
+        let (runtime, trino) = (&self.runtime, &mut self.inner);
+        let data = py
+            .allow_threads(|| runtime.block_on(trino.history_cached(params, cached)))
+            .map_err(to_py_err)?;
+        Ok(PyDataFrame(data.into_dataframe()))
+    }
+
+    /// Query the flight list; returns a `polars.DataFrame`.
+    #[pyo3(signature = (
+        start=None, stop=None, *, icao24=None, callsign=None,
+        departure_airport=None, arrival_airport=None, airport=None, limit=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn flightlist(
+        &mut self,
+        py: Python<'_>,
+        start: Option<String>,
+        stop: Option<String>,
+        icao24: Option<String>,
+        callsign: Option<String>,
+        departure_airport: Option<Airports>,
+        arrival_airport: Option<Airports>,
+        airport: Option<Airports>,
+        limit: Option<u32>,
+    ) -> PyResult<PyDataFrame> {
+        let params = PyQueryParams::new(
+            start, stop, icao24, callsign, departure_airport, arrival_airport, airport, None, limit,
+        )
+        .inner;
+        let (runtime, trino) = (&self.runtime, &mut self.inner);
+        let data = py
+            .allow_threads(|| runtime.block_on(trino.flightlist(params)))
+            .map_err(to_py_err)?;
+        Ok(PyDataFrame(data.into_dataframe()))
+    }
+
+    /// Query raw messages from the rollcall replies table.
+    #[pyo3(signature = (start=None, stop=None, *, icao24=None, limit=None))]
+    fn rawdata(
+        &mut self,
+        py: Python<'_>,
+        start: Option<String>,
+        stop: Option<String>,
+        icao24: Option<String>,
+        limit: Option<u32>,
+    ) -> PyResult<PyDataFrame> {
+        let params = PyQueryParams::new(start, stop, icao24, None, None, None, None, None, limit).inner;
+        let (runtime, trino) = (&self.runtime, &mut self.inner);
+        let data = py
+            .allow_threads(|| runtime.block_on(trino.rawdata_table(params, RawTable::default())))
+            .map_err(to_py_err)?;
+        Ok(PyDataFrame(data.into_dataframe()))
+    }
+}
+
+/// Python module definition.
+#[pymodule]
+fn opensky(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyTrino>()?;
+    m.add_class::<PyQueryParams>()?;
+    Ok(())
+}
@@ -2,45 +2,145 @@
 //!
 //! Note: OpenSky stores timestamps as Unix epoch integers, not SQL TIMESTAMP types.
 
-use crate::types::{QueryParams, RawTable, FLIGHT_COLUMNS, FLIGHTLIST_COLUMNS, RAWDATA_COLUMNS};
-use chrono::{NaiveDateTime, Duration, Timelike};
-
-/// The main table for state vector data.
-const STATE_VECTORS_TABLE: &str = "minio.osky.state_vectors_data4";
-
-/// The flights table for flight lists and airport filtering.
-const FLIGHTS_TABLE: &str = "minio.osky.flights_data4";
+use crate::schema::{ADSB_POSITION_COLUMNS, ADSB_VELOCITY_COLUMNS, FLIGHT_COLUMNS, FLIGHTLIST_COLUMNS, RAWDATA_COLUMNS};
+use crate::tables::{Table, TableNames};
+use crate::types::{normalize_icao24, parse_time, Aggregation, OpenSkyError, Result, FlightTime, Order, QueryParams, RawTable, StatsInterval};
+use chrono::{NaiveDate, NaiveDateTime, Duration, Timelike};
 
 /// Build a SQL query for the history() method.
 ///
 /// This generates a SELECT statement against state_vectors_data4,
 /// optionally joining with flights_data4 for airport filtering.
-pub fn build_history_query(params: &QueryParams) -> String {
-    let columns = FLIGHT_COLUMNS.join(", ");
+pub fn build_history_query(params: &QueryParams) -> Result<String> {
+    build_history(params, |sql, prefix| push_order(sql, params.order, prefix, "time"))
+}
+
+/// Build one page of a paged history query.
+///
+/// Whatever `params.order`, rows are ordered by time, then aircraft, so
+/// that successive OFFSET/LIMIT windows split one stable order without
+/// repeating or skipping rows.
+#[cfg(feature = "client")]
+pub(crate) fn build_history_page_query(params: &QueryParams) -> Result<String> {
+    build_history(params, |sql, prefix| sql.push_str(&format!("\nORDER BY {prefix}time, {prefix}icao24")))
+}
 
-    let has_airport_filter = params.departure_airport.is_some()
-        || params.arrival_airport.is_some()
-        || params.airport.is_some();
+/// Build a history query, ordering it with `order`.
+fn build_history(params: &QueryParams, order: impl FnOnce(&mut String, &str)) -> Result<String> {
+    let columns = history_columns(params).join(", ");
 
-    if has_airport_filter {
-        build_airport_join_query(params, &columns)
+    let has_airport_filter = params.has_airport_filter();
+
+    let (mut sql, prefix) = if has_airport_filter {
+        build_airport_join_query(params, &columns)?
     } else {
-        build_simple_query(params, &columns)
+        build_simple_query(params, &columns)?
+    };
+
+    // Order and limit
+    order(&mut sql, prefix);
+
+    push_limit(&mut sql, params);
+
+    Ok(sql)
+}
+
+/// Columns a history query over `params` selects, see
+/// [`QueryParams::columns`].
+pub fn history_columns(params: &QueryParams) -> Vec<&'static str> {
+    selected_columns(params, FLIGHT_COLUMNS, &["time", "icao24"])
+}
+
+/// Columns a raw data query over `params` selects.
+pub fn rawdata_columns(params: &QueryParams) -> Vec<&'static str> {
+    selected_columns(params, RAWDATA_COLUMNS, &[])
+}
+
+/// Decoded columns of `table`: those of [`ADSB_POSITION_COLUMNS`] and
+/// [`ADSB_VELOCITY_COLUMNS`], the raw message columns for other tables.
+pub fn adsb_table_columns(table: RawTable) -> &'static [&'static str] {
+    match table {
+        RawTable::Position => ADSB_POSITION_COLUMNS,
+        RawTable::Velocity => ADSB_VELOCITY_COLUMNS,
+        _ => RAWDATA_COLUMNS,
+    }
+}
+
+/// Columns an ADS-B query of `table` over `params` selects.
+pub fn adsb_columns(params: &QueryParams, table: RawTable) -> Vec<&'static str> {
+    selected_columns(params, adsb_table_columns(table), &["mintime", "icao24"])
+}
+
+/// The columns of `all` chosen in `params.columns`, plus `required`, in
+/// table order; all of them when none are chosen.
+fn selected_columns(params: &QueryParams, all: &[&'static str], required: &[&str]) -> Vec<&'static str> {
+    if params.columns.is_empty() {
+        return all.to_vec();
     }
+    all.iter()
+        .copied()
+        .filter(|name| {
+            required.contains(name) || params.columns.iter().any(|column| column.trim().eq_ignore_ascii_case(name))
+        })
+        .collect()
+}
+
+/// Build a history query for several aircraft, each within its own window.
+///
+/// `windows` holds `(icao24, start, stop)` as Unix timestamps. Every window
+/// carries its own `hour` bounds, so only the partitions it overlaps are
+/// scanned even when the flights are days apart. The state vectors are read
+/// from the table named in `tables`.
+pub fn build_windows_history_query(windows: &[(String, i64, i64)], tables: &TableNames) -> String {
+    let columns = FLIGHT_COLUMNS.join(", ");
+    // Same rounding as compute_hour_bounds_unix: the stop's hour is included
+    let hours = |start: i64, stop: i64| {
+        let start_hour = start - start.rem_euclid(3600);
+        let stop_hour = stop - stop.rem_euclid(3600) + 3600;
+        (start_hour, stop_hour.max(start_hour + 3600))
+    };
+
+    let conditions: Vec<String> = windows
+        .iter()
+        .map(|(icao24, start, stop)| {
+            let (start_hour, stop_hour) = hours(*start, *stop);
+            format!(
+                "(icao24 = '{}' AND time >= {start} AND time <= {stop} AND hour >= {start_hour} AND hour < {stop_hour})",
+                escape_sql(&normalize_icao24(icao24))
+            )
+        })
+        .collect();
+    let start_hour = windows.iter().map(|w| hours(w.1, w.2).0).min().unwrap_or(0);
+    let stop_hour = windows.iter().map(|w| hours(w.1, w.2).1).max().unwrap_or(0);
+
+    let state_vectors = tables.get(Table::StateVectors);
+    let mut sql = format!(
+        "SELECT {columns}\nFROM {state_vectors}\nWHERE hour >= {start_hour}\n  AND hour < {stop_hour}\n  AND ({})",
+        conditions.join("\n    OR ")
+    );
+    push_order(&mut sql, Order::IcaoThenTime, "", "time");
+    sql
 }
 
 /// Build a simple query without airport join.
-fn build_simple_query(params: &QueryParams, columns: &str) -> String {
+///
+/// Returns the query without ORDER BY/LIMIT, and the table alias prefix.
+fn build_simple_query(params: &QueryParams, columns: &str) -> Result<(String, &'static str)> {
+    let anti_join = exclude_airports_anti_join(params, "sv", "time", true)?;
+    // The anti-join needs an alias to refer to the outer row
+    let alias = if anti_join.is_some() { " sv" } else { "" };
+
+    let state_vectors = params.tables.get(Table::StateVectors);
     let mut sql = format!(
-        "SELECT {columns}\nFROM {STATE_VECTORS_TABLE}\nWHERE 1=1"
+        "SELECT {columns}\nFROM {state_vectors}{alias}\nWHERE 1=1"
     );
 
     // Time filters (required for partition pruning)
     // Note: OpenSky stores time/hour as Unix timestamps (integers)
     if let (Some(start), Some(stop)) = (&params.start, &params.stop) {
-        let start_ts = datetime_to_unix(start);
-        let stop_ts = datetime_to_unix(stop);
-        let (start_hour_ts, stop_hour_ts) = compute_hour_bounds_unix(start, stop);
+        let start_ts = datetime_to_unix(start, false)?;
+        let stop_ts = datetime_to_unix(stop, true)?;
+        let (start_hour_ts, stop_hour_ts) = compute_hour_bounds_unix(start, stop)?;
 
         sql.push_str(&format!("\n  AND time >= {start_ts}"));
         sql.push_str(&format!("\n  AND time <= {stop_ts}"));
@@ -50,7 +150,7 @@ fn build_simple_query(params: &QueryParams, columns: &str) -> String {
 
     // ICAO24 filter
     if let Some(icao24) = &params.icao24 {
-        let icao24_lower = icao24.to_lowercase();
+        let icao24_lower = normalize_icao24(icao24);
         if icao24_lower.contains('%') || icao24_lower.contains('_') {
             sql.push_str(&format!("\n  AND icao24 LIKE '{}'", escape_sql(&icao24_lower)));
         } else {
@@ -75,56 +175,59 @@ fn build_simple_query(params: &QueryParams, columns: &str) -> String {
         sql.push_str(&format!("\n  AND lat <= {}", bounds.north));
     }
 
-    // Order and limit
-    sql.push_str("\nORDER BY time");
+    // Downsampling
+    if let Some(n) = params.resolution.filter(|&n| n > 1) {
+        sql.push_str(&format!("\n  AND time % {n} = 0"));
+    }
 
-    if let Some(limit) = params.limit {
-        sql.push_str(&format!("\nLIMIT {limit}"));
+    push_position_filters(&mut sql, params, "");
+
+    // Excluded airports
+    if let Some(anti_join) = anti_join {
+        sql.push_str(&format!("\n  AND {anti_join}"));
     }
 
-    sql
+    Ok((sql, ""))
 }
 
 /// Build a query with airport join.
-fn build_airport_join_query(params: &QueryParams, columns: &str) -> String {
+///
+/// Returns the query without ORDER BY/LIMIT, and the table alias prefix.
+fn build_airport_join_query(params: &QueryParams, columns: &str) -> Result<(String, &'static str)> {
     let (start, stop) = match (&params.start, &params.stop) {
         (Some(s), Some(e)) => (s.as_str(), e.as_str()),
         _ => return build_simple_query(params, columns),
     };
 
-    let start_ts = datetime_to_unix(start);
-    let stop_ts = datetime_to_unix(stop);
-    let (start_hour_ts, stop_hour_ts) = compute_hour_bounds_unix(start, stop);
-    let (start_day_ts, stop_day_ts) = compute_day_bounds_unix(start, stop);
+    let start_ts = datetime_to_unix(start, false)?;
+    let stop_ts = datetime_to_unix(stop, true)?;
+    let (start_hour_ts, stop_hour_ts) = compute_hour_bounds_unix(start, stop)?;
+    let (_, stop_day_ts) = compute_day_bounds_unix(start, stop)?;
+    // Flights still airborne at `start` may have taken off `time_buffer`
+    // earlier
+    let flights_day_ts = (start_ts - time_buffer_seconds(params)?).div_euclid(86_400) * 86_400;
 
     // Build the flights subquery
     let mut flights_where = vec![
-        format!("day >= {start_day_ts}"),
+        format!("day >= {flights_day_ts}"),
         format!("day <= {stop_day_ts}"),
     ];
 
     if let Some(icao24) = &params.icao24 {
-        flights_where.push(format!("icao24 = '{}'", escape_sql(&icao24.to_lowercase())));
+        flights_where.push(format!("icao24 = '{}'", escape_sql(&normalize_icao24(icao24))));
     }
     if let Some(callsign) = &params.callsign {
         flights_where.push(format!("callsign = '{}'", escape_sql(callsign)));
     }
-    if let Some(dep) = &params.departure_airport {
-        flights_where.push(format!("estdepartureairport = '{}'", escape_sql(dep)));
-    }
-    if let Some(arr) = &params.arrival_airport {
-        flights_where.push(format!("estarrivalairport = '{}'", escape_sql(arr)));
-    }
-    if let Some(airport) = &params.airport {
-        flights_where.push(format!(
-            "(estdepartureairport = '{}' OR estarrivalairport = '{}')",
-            escape_sql(airport), escape_sql(airport)
-        ));
+    flights_where.extend(airport_conditions(params));
+    if let Some(exclude) = exclude_airports_predicate(params, "") {
+        flights_where.push(exclude);
     }
 
+    let flights = params.tables.get(Table::Flights);
     let flights_subquery = format!(
         r#"SELECT icao24, callsign, firstseen, lastseen
-FROM {FLIGHTS_TABLE}
+FROM {flights}
 WHERE {}"#,
         flights_where.join("\n  AND ")
     );
@@ -133,9 +236,10 @@ WHERE {}"#,
     // Prefix all columns with sv. alias
     let prefixed_columns = columns.split(", ").map(|c| format!("sv.{c}")).collect::<Vec<_>>().join(", ");
 
+    let state_vectors = params.tables.get(Table::StateVectors);
     let mut sql = format!(
         r#"SELECT {prefixed_columns}
-FROM {STATE_VECTORS_TABLE} sv
+FROM {state_vectors} sv
 JOIN ({flights_subquery}) fl
   ON sv.icao24 = fl.icao24 AND sv.callsign = fl.callsign
 WHERE sv.time >= fl.firstseen
@@ -154,64 +258,250 @@ WHERE sv.time >= fl.firstseen
         sql.push_str(&format!("\n  AND sv.lat <= {}", bounds.north));
     }
 
-    sql.push_str("\nORDER BY sv.time");
+    // Downsampling
+    if let Some(n) = params.resolution.filter(|&n| n > 1) {
+        sql.push_str(&format!("\n  AND sv.time % {n} = 0"));
+    }
 
-    if let Some(limit) = params.limit {
-        sql.push_str(&format!("\nLIMIT {limit}"));
+    push_position_filters(&mut sql, params, "sv.");
+
+    Ok((sql, "sv."))
+}
+
+/// Exclude null positions, and (0, 0) or out-of-range ones with `valid_positions`.
+fn push_position_filters(sql: &mut String, params: &QueryParams, prefix: &str) {
+    if params.valid_positions {
+        // BETWEEN is never true for nulls
+        sql.push_str(&format!("\n  AND {prefix}lat BETWEEN -90 AND 90"));
+        sql.push_str(&format!("\n  AND {prefix}lon BETWEEN -180 AND 180"));
+        sql.push_str(&format!("\n  AND NOT ({prefix}lat = 0 AND {prefix}lon = 0)"));
+    } else if params.require_position {
+        sql.push_str(&format!("\n  AND {prefix}lat IS NOT NULL"));
+        sql.push_str(&format!("\n  AND {prefix}lon IS NOT NULL"));
     }
+}
 
-    sql
+/// Parse a time bound; a date-only value is the start of the day, or the
+/// end of the day when `end_of_day` is set.
+///
+/// Fails with [`OpenSkyError::InvalidParam`] for values [`parse_time`]
+/// does not read.
+pub(crate) fn parse_bound(value: &str, end_of_day: bool) -> Result<NaiveDateTime> {
+    match NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d") {
+        Ok(date) if end_of_day => Ok(date.and_hms_opt(23, 59, 59).unwrap()),
+        Ok(date) => Ok(date.and_hms_opt(0, 0, 0).unwrap()),
+        Err(_) => parse_time(value)
+            .ok_or_else(|| OpenSkyError::InvalidParam(format!("Invalid time '{}'", value))),
+    }
 }
 
-/// Convert datetime string to Unix timestamp.
-fn datetime_to_unix(dt_str: &str) -> i64 {
-    let dt = NaiveDateTime::parse_from_str(dt_str, "%Y-%m-%d %H:%M:%S")
-        .unwrap_or_else(|_| {
-            NaiveDateTime::parse_from_str(&format!("{} 00:00:00", dt_str), "%Y-%m-%d %H:%M:%S")
-                .unwrap()
-        });
-    dt.and_utc().timestamp()
+/// Seconds of the `time_buffer` of `params`, e.g. "30m", "6h" or "1d";
+/// 0 when unset.
+pub(crate) fn time_buffer_seconds(params: &QueryParams) -> Result<i64> {
+    let Some(buffer) = params.time_buffer.as_deref() else {
+        return Ok(0);
+    };
+    let invalid = || OpenSkyError::InvalidParam(format!("Invalid time_buffer '{}', expected e.g. 30m, 6h or 1d", buffer));
+    let buffer = buffer.trim();
+    let split = buffer.find(|c: char| !c.is_ascii_digit()).unwrap_or(buffer.len());
+    let (num, unit) = buffer.split_at(split);
+    let num: i64 = num.parse().map_err(|_| invalid())?;
+    let unit = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        _ => return Err(invalid()),
+    };
+    Ok(num * unit)
+}
+
+/// Convert a time bound to a Unix timestamp; a date-only value is the
+/// start of the day, or the end of the day when `end_of_day` is set.
+fn datetime_to_unix(dt_str: &str, end_of_day: bool) -> Result<i64> {
+    Ok(parse_bound(dt_str, end_of_day)?.and_utc().timestamp())
 }
 
 /// Compute hour bounds as Unix timestamps for partition pruning.
-/// Returns (floor to hour, ceil to hour + 1).
-fn compute_hour_bounds_unix(start: &str, stop: &str) -> (i64, i64) {
-    let start_dt = NaiveDateTime::parse_from_str(start, "%Y-%m-%d %H:%M:%S")
-        .unwrap_or_else(|_| NaiveDateTime::parse_from_str(&format!("{} 00:00:00", start), "%Y-%m-%d %H:%M:%S").unwrap());
-    let stop_dt = NaiveDateTime::parse_from_str(stop, "%Y-%m-%d %H:%M:%S")
-        .unwrap_or_else(|_| NaiveDateTime::parse_from_str(&format!("{} 23:59:59", stop), "%Y-%m-%d %H:%M:%S").unwrap());
+/// Returns (floor start to hour, end of the stop's hour).
+///
+/// The stop is inclusive, so its hour partition is always scanned, even
+/// for a stop on an exact hour: a 10:00-11:00 query scans two partitions,
+/// the second for the rows at 11:00:00.
+fn compute_hour_bounds_unix(start: &str, stop: &str) -> Result<(i64, i64)> {
+    let start_dt = parse_bound(start, false)?;
+    let stop_dt = parse_bound(stop, true)?;
 
     // Floor start to hour
     let start_hour = start_dt
         .with_minute(0).unwrap()
         .with_second(0).unwrap();
 
-    // Ceil stop to next hour
-    let stop_hour = stop_dt
+    // End of the hour holding the stop
+    let stop_floor = stop_dt
         .with_minute(0).unwrap()
-        .with_second(0).unwrap()
-        + Duration::hours(1);
+        .with_second(0).unwrap();
+    let stop_hour = (stop_floor + Duration::hours(1)).max(start_hour + Duration::hours(1));
 
-    (
+    Ok((
         start_hour.and_utc().timestamp(),
         stop_hour.and_utc().timestamp(),
-    )
+    ))
+}
+
+/// Number of hour partitions a history query over `params` scans, `None`
+/// without both valid time bounds.
+#[cfg(feature = "client")]
+pub(crate) fn hour_partitions(params: &QueryParams) -> Option<u64> {
+    let (start_hour, stop_hour) = compute_hour_bounds_unix(params.start.as_deref()?, params.stop.as_deref()?).ok()?;
+    Some(((stop_hour - start_hour) / 3600).max(0) as u64)
 }
 
 /// Compute day bounds as Unix timestamps for flights table.
-fn compute_day_bounds_unix(start: &str, stop: &str) -> (i64, i64) {
-    let start_dt = NaiveDateTime::parse_from_str(start, "%Y-%m-%d %H:%M:%S")
-        .unwrap_or_else(|_| NaiveDateTime::parse_from_str(&format!("{} 00:00:00", start), "%Y-%m-%d %H:%M:%S").unwrap());
-    let stop_dt = NaiveDateTime::parse_from_str(stop, "%Y-%m-%d %H:%M:%S")
-        .unwrap_or_else(|_| NaiveDateTime::parse_from_str(&format!("{} 23:59:59", stop), "%Y-%m-%d %H:%M:%S").unwrap());
+fn compute_day_bounds_unix(start: &str, stop: &str) -> Result<(i64, i64)> {
+    let start_dt = parse_bound(start, false)?;
+    let stop_dt = parse_bound(stop, true)?;
 
     let start_day = start_dt.date().and_hms_opt(0, 0, 0).unwrap();
     let stop_day = (stop_dt.date() + Duration::days(1)).and_hms_opt(0, 0, 0).unwrap();
 
-    (
+    Ok((
         start_day.and_utc().timestamp(),
         stop_day.and_utc().timestamp(),
-    )
+    ))
+}
+
+/// Append the ORDER BY clause for `order`, if any.
+fn push_order(sql: &mut String, order: Order, prefix: &str, time_column: &str) {
+    match order {
+        Order::Time => sql.push_str(&format!("\nORDER BY {prefix}{time_column}")),
+        Order::IcaoThenTime => sql.push_str(&format!("\nORDER BY {prefix}icao24, {prefix}{time_column}")),
+        Order::None => {}
+    }
+}
+
+/// `column = 'X'` for one airport, `column IN ('X', 'Y')` for several, and
+/// `column LIKE 'EH%'` for patterns, OR-ed together.
+fn airport_condition(column: &str, airports: &[String]) -> String {
+    let (patterns, codes): (Vec<String>, Vec<String>) =
+        airports.iter().cloned().partition(|a| a.contains('%'));
+
+    let mut conditions = match codes.as_slice() {
+        [] => Vec::new(),
+        [airport] => vec![format!("{column} = '{}'", escape_sql(airport))],
+        _ => vec![format!("{column} IN ({})", sql_list(&codes))],
+    };
+    conditions.extend(
+        patterns
+            .iter()
+            .map(|p| format!("{column} LIKE '{}'", escape_sql(p))),
+    );
+
+    match conditions.as_slice() {
+        [condition] => condition.clone(),
+        _ => format!("({})", conditions.join(" OR ")),
+    }
+}
+
+/// Conditions on flights_data4 rows for the departure/arrival/airport filters.
+fn airport_conditions(params: &QueryParams) -> Vec<String> {
+    let mut conditions = Vec::new();
+    if !params.departure_airport.is_empty() {
+        conditions.push(airport_condition("estdepartureairport", &params.departure_airport));
+    }
+    if !params.arrival_airport.is_empty() {
+        conditions.push(airport_condition("estarrivalairport", &params.arrival_airport));
+    }
+    if !params.airport.is_empty() {
+        conditions.push(format!(
+            "({} OR {})",
+            airport_condition("estdepartureairport", &params.airport),
+            airport_condition("estarrivalairport", &params.airport)
+        ));
+    }
+    conditions
+}
+
+/// Predicate on flights_data4 rows excluding the airports in
+/// `exclude_departure`/`exclude_arrival`.
+///
+/// Flights with an unknown airport are kept.
+fn exclude_airports_predicate(params: &QueryParams, prefix: &str) -> Option<String> {
+    let mut conditions = Vec::new();
+    if !params.exclude_departure.is_empty() {
+        conditions.push(format!(
+            "coalesce({prefix}estdepartureairport, '') NOT IN ({})",
+            sql_list(&params.exclude_departure)
+        ));
+    }
+    if !params.exclude_arrival.is_empty() {
+        conditions.push(format!(
+            "coalesce({prefix}estarrivalairport, '') NOT IN ({})",
+            sql_list(&params.exclude_arrival)
+        ));
+    }
+    (!conditions.is_empty()).then(|| conditions.join(" AND "))
+}
+
+/// Anti-join dropping rows of `outer` that belong to a flight from or to an
+/// excluded airport.
+///
+/// `time_column` is matched against the flight's firstseen/lastseen, and
+/// callsigns are matched too when `match_callsign` is set.
+fn exclude_airports_anti_join(
+    params: &QueryParams,
+    outer: &str,
+    time_column: &str,
+    match_callsign: bool,
+) -> Result<Option<String>> {
+    let mut airports = Vec::new();
+    if !params.exclude_departure.is_empty() {
+        airports.push(format!("fx.estdepartureairport IN ({})", sql_list(&params.exclude_departure)));
+    }
+    if !params.exclude_arrival.is_empty() {
+        airports.push(format!("fx.estarrivalairport IN ({})", sql_list(&params.exclude_arrival)));
+    }
+    if airports.is_empty() {
+        return Ok(None);
+    }
+
+    let mut conditions = vec![format!("fx.icao24 = {outer}.icao24")];
+    if match_callsign {
+        conditions.push(format!("fx.callsign = {outer}.callsign"));
+    }
+    conditions.push(format!("{outer}.{time_column} >= fx.firstseen"));
+    conditions.push(format!("{outer}.{time_column} <= fx.lastseen"));
+    if let (Some(start), Some(stop)) = (&params.start, &params.stop) {
+        let (start_day_ts, stop_day_ts) = compute_day_bounds_unix(start, stop)?;
+        conditions.push(format!("fx.day >= {start_day_ts}"));
+        conditions.push(format!("fx.day <= {stop_day_ts}"));
+    }
+    conditions.push(format!("({})", airports.join(" OR ")));
+
+    let flights = params.tables.get(Table::Flights);
+    Ok(Some(format!(
+        "NOT EXISTS (\n    SELECT 1 FROM {flights} fx\n    WHERE {}\n  )",
+        conditions.join("\n      AND ")
+    )))
+}
+
+/// Quote and comma-separate values for an IN list.
+fn sql_list(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|v| format!("'{}'", escape_sql(v)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Append the OFFSET and LIMIT clauses, if set.
+fn push_limit(sql: &mut String, params: &QueryParams) {
+    if let Some(offset) = params.offset.filter(|&o| o > 0) {
+        sql.push_str(&format!("\nOFFSET {offset}"));
+    }
+    if let Some(limit) = params.limit {
+        sql.push_str(&format!("\nLIMIT {limit}"));
+    }
 }
 
 /// Escape single quotes in SQL strings.
@@ -226,11 +516,22 @@ fn escape_sql(s: &str) -> String {
 /// otherwise filters by lastseen.
 ///
 /// If only start time is provided (no stop), defaults to end of the same day (23:59:59).
-pub fn build_flightlist_query(params: &QueryParams) -> String {
-    let columns = FLIGHTLIST_COLUMNS.join(", ");
+pub fn build_flightlist_query(params: &QueryParams) -> Result<String> {
+    let mut sql = build_flightlist_body(params, &FLIGHTLIST_COLUMNS.join(", "))?;
+
+    // Order by firstseen
+    push_order(&mut sql, params.order, "", "firstseen");
+
+    push_limit(&mut sql, params);
+
+    Ok(sql)
+}
 
+/// Build the flight list query without ORDER BY/LIMIT.
+fn build_flightlist_body(params: &QueryParams, columns: &str) -> Result<String> {
+    let flights = params.tables.get(Table::Flights);
     let mut sql = format!(
-        "SELECT {columns}\nFROM {FLIGHTS_TABLE}\nWHERE 1=1"
+        "SELECT {columns}\nFROM {flights}\nWHERE 1=1"
     );
 
     // Time and day bounds (required for partition pruning)
@@ -239,35 +540,49 @@ pub fn build_flightlist_query(params: &QueryParams) -> String {
         (Some(start), Some(stop)) => (Some(start.clone()), Some(stop.clone())),
         (Some(start), None) => {
             // Default stop to end of start day (23:59:59)
-            let day = &start[..10]; // Extract YYYY-MM-DD
+            let day = parse_bound(start, false)?.date();
             (Some(start.clone()), Some(format!("{} 23:59:59", day)))
         }
         _ => (None, None),
     };
 
-    // pyopensky behavior: filter on firstseen if departure_airport is set, else lastseen
+    // pyopensky behavior: by default filter on firstseen if departure_airport
+    // is set, else lastseen
     if let (Some(start), Some(stop)) = (start_opt, stop_opt) {
-        let start_ts = datetime_to_unix(&start);
-        let stop_ts = datetime_to_unix(&stop);
-        let (start_day_ts, stop_day_ts) = compute_day_bounds_unix(&start, &stop);
+        let start_ts = datetime_to_unix(&start, false)?;
+        let stop_ts = datetime_to_unix(&stop, true)?;
+        let (start_day_ts, mut stop_day_ts) = compute_day_bounds_unix(&start, &stop)?;
+        let flight_time = params.flight_time.resolve(params);
+
+        // `day` is the day of lastseen: flights selected on firstseen may
+        // land on the day after the range
+        if flight_time != FlightTime::LastSeen {
+            stop_day_ts += 86400;
+        }
 
         // Day partition filter
         sql.push_str(&format!("\n  AND day >= {start_day_ts}"));
         sql.push_str(&format!("\n  AND day < {stop_day_ts}"));
 
-        // Time filter: firstseen if departure filter, else lastseen
-        if params.departure_airport.is_some() {
-            sql.push_str(&format!("\n  AND firstseen >= {start_ts}"));
-            sql.push_str(&format!("\n  AND firstseen <= {stop_ts}"));
-        } else {
-            sql.push_str(&format!("\n  AND lastseen >= {start_ts}"));
-            sql.push_str(&format!("\n  AND lastseen <= {stop_ts}"));
+        match flight_time {
+            FlightTime::FirstSeen => {
+                sql.push_str(&format!("\n  AND firstseen >= {start_ts}"));
+                sql.push_str(&format!("\n  AND firstseen <= {stop_ts}"));
+            }
+            FlightTime::Overlap => {
+                sql.push_str(&format!("\n  AND lastseen >= {start_ts}"));
+                sql.push_str(&format!("\n  AND firstseen <= {stop_ts}"));
+            }
+            FlightTime::LastSeen | FlightTime::Auto => {
+                sql.push_str(&format!("\n  AND lastseen >= {start_ts}"));
+                sql.push_str(&format!("\n  AND lastseen <= {stop_ts}"));
+            }
         }
     }
 
     // ICAO24 filter
     if let Some(icao24) = &params.icao24 {
-        let icao24_lower = icao24.to_lowercase();
+        let icao24_lower = normalize_icao24(icao24);
         if icao24_lower.contains('%') || icao24_lower.contains('_') {
             sql.push_str(&format!("\n  AND icao24 LIKE '{}'", escape_sql(&icao24_lower)));
         } else {
@@ -284,65 +599,67 @@ pub fn build_flightlist_query(params: &QueryParams) -> String {
         }
     }
 
-    // Departure airport
-    if let Some(dep) = &params.departure_airport {
-        sql.push_str(&format!("\n  AND estdepartureairport = '{}'", escape_sql(dep)));
+    // Departure, arrival or either airport
+    for condition in airport_conditions(params) {
+        sql.push_str(&format!("\n  AND {condition}"));
     }
 
-    // Arrival airport
-    if let Some(arr) = &params.arrival_airport {
-        sql.push_str(&format!("\n  AND estarrivalairport = '{}'", escape_sql(arr)));
-    }
-
-    // Either airport
-    if let Some(airport) = &params.airport {
-        sql.push_str(&format!(
-            "\n  AND (estdepartureairport = '{}' OR estarrivalairport = '{}')",
-            escape_sql(airport), escape_sql(airport)
-        ));
+    // Excluded airports
+    if let Some(exclude) = exclude_airports_predicate(params, "") {
+        sql.push_str(&format!("\n  AND {exclude}"));
     }
 
-    // Order by firstseen
-    sql.push_str("\nORDER BY firstseen");
-
-    if let Some(limit) = params.limit {
-        sql.push_str(&format!("\nLIMIT {limit}"));
-    }
-
-    sql
+    Ok(sql)
 }
 
 /// Build a SQL query for the rawdata() method.
 ///
 /// This generates a SELECT statement against raw message tables (e.g., rollcall_replies_data4).
 /// Behavior matches pyopensky: when airport filters are set, joins with flights_data4.
-pub fn build_rawdata_query(params: &QueryParams, table: RawTable) -> String {
-    let table_name = table.table_name();
-    let columns = RAWDATA_COLUMNS.join(", ");
+pub fn build_rawdata_query(params: &QueryParams, table: RawTable) -> Result<String> {
+    let table_name = table.table_name(&params.tables);
+    let columns = rawdata_columns(params).join(", ");
 
-    let has_airport_filter = params.departure_airport.is_some()
-        || params.arrival_airport.is_some()
-        || params.airport.is_some();
+    let has_airport_filter = params.has_airport_filter();
 
     if has_airport_filter {
-        build_rawdata_airport_join_query(params, table_name, &columns)
+        build_rawdata_airport_join_query(params, &table_name, &columns)
     } else {
-        build_rawdata_simple_query(params, table_name, &columns)
+        build_rawdata_simple_query(params, &table_name, &columns)
+    }
+}
+
+/// Build a SQL query for decoded ADS-B messages of `table`.
+///
+/// Same filters as [`build_rawdata_query`], selecting the decoded columns
+/// of `position_data4` or `velocity_data4` instead of the raw messages.
+pub fn build_adsb_query(params: &QueryParams, table: RawTable) -> Result<String> {
+    let table_name = table.table_name(&params.tables);
+    let columns = adsb_columns(params, table).join(", ");
+
+    if params.has_airport_filter() {
+        build_rawdata_airport_join_query(params, &table_name, &columns)
+    } else {
+        build_rawdata_simple_query(params, &table_name, &columns)
     }
 }
 
 /// Build a simple raw data query without airport join.
-fn build_rawdata_simple_query(params: &QueryParams, table_name: &str, columns: &str) -> String {
+fn build_rawdata_simple_query(params: &QueryParams, table_name: &str, columns: &str) -> Result<String> {
+    let anti_join = exclude_airports_anti_join(params, "raw", "mintime", false)?;
+    // The anti-join needs an alias to refer to the outer row
+    let alias = if anti_join.is_some() { " raw" } else { "" };
+
     let mut sql = format!(
-        "SELECT {columns}\nFROM {table_name}\nWHERE rawmsg IS NOT NULL"
+        "SELECT {columns}\nFROM {table_name}{alias}\nWHERE rawmsg IS NOT NULL"
     );
 
     // Time filters (required for partition pruning)
     // Raw tables use mintime (float) instead of time (int)
     if let (Some(start), Some(stop)) = (&params.start, &params.stop) {
-        let start_ts = datetime_to_unix(start);
-        let stop_ts = datetime_to_unix(stop);
-        let (start_hour_ts, stop_hour_ts) = compute_hour_bounds_unix(start, stop);
+        let start_ts = datetime_to_unix(start, false)?;
+        let stop_ts = datetime_to_unix(stop, true)?;
+        let (start_hour_ts, stop_hour_ts) = compute_hour_bounds_unix(start, stop)?;
 
         sql.push_str(&format!("\n  AND mintime >= {start_ts}"));
         sql.push_str(&format!("\n  AND mintime <= {stop_ts}"));
@@ -352,7 +669,7 @@ fn build_rawdata_simple_query(params: &QueryParams, table_name: &str, columns: &
 
     // ICAO24 filter
     if let Some(icao24) = &params.icao24 {
-        let icao24_lower = icao24.to_lowercase();
+        let icao24_lower = normalize_icao24(icao24);
         if icao24_lower.contains('%') || icao24_lower.contains('_') {
             sql.push_str(&format!("\n  AND icao24 LIKE '{}'", escape_sql(&icao24_lower)));
         } else {
@@ -360,27 +677,50 @@ fn build_rawdata_simple_query(params: &QueryParams, table_name: &str, columns: &
         }
     }
 
-    // Order and limit
-    sql.push_str("\nORDER BY mintime");
+    if let Some(condition) = downlink_format_condition(params, "") {
+        sql.push_str(&format!("\n  AND {condition}"));
+    }
 
-    if let Some(limit) = params.limit {
-        sql.push_str(&format!("\nLIMIT {limit}"));
+    // Excluded airports
+    if let Some(anti_join) = anti_join {
+        sql.push_str(&format!("\n  AND {anti_join}"));
     }
 
-    sql
+    // Order and limit
+    push_order(&mut sql, params.order, "", "mintime");
+
+    push_limit(&mut sql, params);
+
+    Ok(sql)
+}
+
+/// Condition on the downlink format of `rawmsg`, its first five bits.
+///
+/// Comm-D formats set only the first two bits, so 24 covers 24 to 31.
+fn downlink_format_condition(params: &QueryParams, prefix: &str) -> Option<String> {
+    if params.downlink_formats.is_empty() {
+        return None;
+    }
+    let formats: Vec<String> = params
+        .downlink_formats
+        .iter()
+        .flat_map(|&format| if format >= 24 { 24..32 } else { format..format + 1 })
+        .map(|format| format.to_string())
+        .collect();
+    Some(format!("from_base(substr({prefix}rawmsg, 1, 2), 16) / 8 IN ({})", formats.join(", ")))
 }
 
 /// Build a raw data query with airport join.
-fn build_rawdata_airport_join_query(params: &QueryParams, table_name: &str, columns: &str) -> String {
+fn build_rawdata_airport_join_query(params: &QueryParams, table_name: &str, columns: &str) -> Result<String> {
     let (start, stop) = match (&params.start, &params.stop) {
         (Some(s), Some(e)) => (s.as_str(), e.as_str()),
         _ => return build_rawdata_simple_query(params, table_name, columns),
     };
 
-    let start_ts = datetime_to_unix(start);
-    let stop_ts = datetime_to_unix(stop);
-    let (start_hour_ts, stop_hour_ts) = compute_hour_bounds_unix(start, stop);
-    let (start_day_ts, stop_day_ts) = compute_day_bounds_unix(start, stop);
+    let start_ts = datetime_to_unix(start, false)?;
+    let stop_ts = datetime_to_unix(stop, true)?;
+    let (start_hour_ts, stop_hour_ts) = compute_hour_bounds_unix(start, stop)?;
+    let (start_day_ts, stop_day_ts) = compute_day_bounds_unix(start, stop)?;
 
     // Build the flights subquery
     let mut flights_where = vec![
@@ -389,24 +729,17 @@ fn build_rawdata_airport_join_query(params: &QueryParams, table_name: &str, colu
     ];
 
     if let Some(icao24) = &params.icao24 {
-        flights_where.push(format!("icao24 = '{}'", escape_sql(&icao24.to_lowercase())));
-    }
-    if let Some(dep) = &params.departure_airport {
-        flights_where.push(format!("estdepartureairport = '{}'", escape_sql(dep)));
+        flights_where.push(format!("icao24 = '{}'", escape_sql(&normalize_icao24(icao24))));
     }
-    if let Some(arr) = &params.arrival_airport {
-        flights_where.push(format!("estarrivalairport = '{}'", escape_sql(arr)));
-    }
-    if let Some(airport) = &params.airport {
-        flights_where.push(format!(
-            "(estdepartureairport = '{}' OR estarrivalairport = '{}')",
-            escape_sql(airport), escape_sql(airport)
-        ));
+    flights_where.extend(airport_conditions(params));
+    if let Some(exclude) = exclude_airports_predicate(params, "") {
+        flights_where.push(exclude);
     }
 
+    let flights = params.tables.get(Table::Flights);
     let flights_subquery = format!(
         r#"SELECT icao24, firstseen, lastseen
-FROM {FLIGHTS_TABLE}
+FROM {flights}
 WHERE {}"#,
         flights_where.join("\n  AND ")
     );
@@ -428,14 +761,118 @@ WHERE raw.mintime >= fl.firstseen
   AND raw.hour < {stop_hour_ts}
   AND raw.rawmsg IS NOT NULL"#
     );
+    if let Some(condition) = downlink_format_condition(params, "raw.") {
+        sql.push_str(&format!("\n  AND {condition}"));
+    }
 
-    sql.push_str("\nORDER BY raw.mintime");
+    push_order(&mut sql, params.order, "raw.", "mintime");
 
-    if let Some(limit) = params.limit {
-        sql.push_str(&format!("\nLIMIT {limit}"));
-    }
+    push_limit(&mut sql, params);
 
-    sql
+    Ok(sql)
+}
+
+/// Build a SQL query for the distinct_icao24() method.
+///
+/// Returns each aircraft matching the history filters once, with the first
+/// and last time it was seen, instead of all of its state vectors.
+pub fn build_distinct_icao24_query(params: &QueryParams) -> Result<String> {
+    let has_airport_filter = params.has_airport_filter();
+
+    let (subquery, _) = if has_airport_filter {
+        build_airport_join_query(params, "icao24, time")?
+    } else {
+        build_simple_query(params, "icao24, time")?
+    };
+
+    let mut sql = format!(
+        "SELECT icao24, min(time) AS firstseen, max(time) AS lastseen\nFROM (\n{subquery}\n) t\nGROUP BY icao24\nORDER BY icao24"
+    );
+
+    push_limit(&mut sql, params);
+
+    Ok(sql)
+}
+
+/// Build a SQL query for the aggregate() method.
+///
+/// The filtered history or flight list query becomes a subquery, and the
+/// grouping and counting happen on the server.
+pub fn build_aggregate_query(params: &QueryParams, aggregation: Aggregation) -> Result<String> {
+    let (select, group_by, order_by) = match aggregation {
+        Aggregation::MessagesPerHour => ("hour, count(*) AS messages", "hour", "hour"),
+        Aggregation::AircraftPerHour => ("hour, count(DISTINCT icao24) AS aircraft", "hour", "hour"),
+        Aggregation::AircraftPerDay => (
+            "hour - hour % 86400 AS day, count(DISTINCT icao24) AS aircraft",
+            "1",
+            "day",
+        ),
+        Aggregation::FlightsPerDay => ("day, count(*) AS flights", "day", "day"),
+        Aggregation::FlightsPerDeparture => (
+            "estdepartureairport AS airport, count(*) AS flights",
+            "estdepartureairport",
+            "flights DESC",
+        ),
+        Aggregation::FlightsPerArrival => (
+            "estarrivalairport AS airport, count(*) AS flights",
+            "estarrivalairport",
+            "flights DESC",
+        ),
+    };
+
+    let subquery = match aggregation {
+        Aggregation::MessagesPerHour | Aggregation::AircraftPerHour | Aggregation::AircraftPerDay => {
+            let has_airport_filter = params.has_airport_filter();
+            let (sql, _) = if has_airport_filter {
+                build_airport_join_query(params, "icao24, hour")?
+            } else {
+                build_simple_query(params, "icao24, hour")?
+            };
+            sql
+        }
+        Aggregation::FlightsPerDay | Aggregation::FlightsPerDeparture | Aggregation::FlightsPerArrival => {
+            build_flightlist_body(params, "day, estdepartureairport, estarrivalairport")?
+        }
+    };
+
+    let mut sql = format!(
+        "SELECT {select}\nFROM (\n{subquery}\n) t\nGROUP BY {group_by}\nORDER BY {order_by}"
+    );
+
+    push_limit(&mut sql, params);
+
+    Ok(sql)
+}
+
+/// Build a SQL query counting departures and arrivals at an airport.
+///
+/// Returns one row per `interval` bucket (`period` as unix seconds,
+/// `departures`, `arrivals`). Departures are bucketed by `firstseen` and
+/// arrivals by `lastseen`. Flights are read from the table named in `tables`.
+pub fn build_airport_stats_query(
+    airport: &str,
+    start: &str,
+    stop: &str,
+    interval: StatsInterval,
+    tables: &TableNames,
+) -> Result<String> {
+    let n = interval.seconds();
+    let range = QueryParams::new().time_range(start, stop).tables(tables.clone());
+    let departures = range.clone().departure(airport);
+    let arrivals = range.arrival(airport);
+
+    let departures = build_flightlist_body(
+        &departures,
+        &format!("firstseen - firstseen % {n} AS period, 1 AS departure, 0 AS arrival"),
+    )?;
+    let arrivals = build_flightlist_body(
+        &arrivals,
+        &format!("lastseen - lastseen % {n} AS period, 0 AS departure, 1 AS arrival"),
+    )?;
+
+    Ok(format!(
+        "SELECT period, sum(departure) AS departures, sum(arrival) AS arrivals\nFROM (\n{departures}\nUNION ALL\n{arrivals}\n) t\nGROUP BY period\nORDER BY period"
+    ))
 }
 
 /// Build a preview of the query (for display purposes).
@@ -464,14 +901,22 @@ pub fn build_query_preview_method(params: &QueryParams, method: &str) -> String
     if let Some(callsign) = &params.callsign {
         parts.push(format!("    callsign=\"{callsign}\","));
     }
-    if let Some(dep) = &params.departure_airport {
-        parts.push(format!("    departure_airport=\"{dep}\","));
+    for (name, airports) in [
+        ("departure_airport", &params.departure_airport),
+        ("arrival_airport", &params.arrival_airport),
+        ("airport", &params.airport),
+    ] {
+        match airports.as_slice() {
+            [] => {}
+            [airport] => parts.push(format!("    {name}=\"{airport}\",")),
+            _ => parts.push(format!("    {name}={airports:?},")),
+        }
     }
-    if let Some(arr) = &params.arrival_airport {
-        parts.push(format!("    arrival_airport=\"{arr}\","));
+    if !params.exclude_departure.is_empty() {
+        parts.push(format!("    exclude_departure={:?},", params.exclude_departure));
     }
-    if let Some(airport) = &params.airport {
-        parts.push(format!("    airport=\"{airport}\","));
+    if !params.exclude_arrival.is_empty() {
+        parts.push(format!("    exclude_arrival={:?},", params.exclude_arrival));
     }
     if let Some(bounds) = &params.bounds {
         parts.push(format!(
@@ -482,6 +927,27 @@ pub fn build_query_preview_method(params: &QueryParams, method: &str) -> String
     if let Some(limit) = params.limit {
         parts.push(format!("    limit={limit},"));
     }
+    if let Some(offset) = params.offset {
+        parts.push(format!("    offset={offset},"));
+    }
+    if let Some(resolution) = params.resolution {
+        parts.push(format!("    resolution={resolution},"));
+    }
+    if params.require_position {
+        parts.push("    require_position=True,".to_string());
+    }
+    if params.valid_positions {
+        parts.push("    valid_positions=True,".to_string());
+    }
+    if params.order != Order::Time {
+        parts.push(format!("    order={:?},", params.order));
+    }
+    if params.flight_time != FlightTime::Auto {
+        parts.push(format!("    flight_time={:?},", params.flight_time));
+    }
+    if !params.columns.is_empty() {
+        parts.push(format!("    columns={:?},", params.columns));
+    }
 
     parts.push(")".to_string());
     parts.join("\n")
@@ -497,7 +963,7 @@ mod tests {
             .icao24("485a32")
             .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00");
 
-        let sql = build_history_query(&params);
+        let sql = build_history_query(&params).unwrap();
 
         assert!(sql.contains("SELECT time, icao24"));
         assert!(sql.contains("FROM minio.osky.state_vectors_data4"));
@@ -514,12 +980,20 @@ mod tests {
             .departure("EHAM")
             .arrival("EGLL");
 
-        let sql = build_history_query(&params);
+        let sql = build_history_query(&params).unwrap();
 
         assert!(sql.contains("JOIN"));
         assert!(sql.contains("flights_data4"));
         assert!(sql.contains("estdepartureairport = 'EHAM'"));
         assert!(sql.contains("estarrivalairport = 'EGLL'"));
+        assert!(sql.contains("day >= 1735689600"));
+
+        // Flights that took off before the range, with a time buffer
+        let params = QueryParams {
+            time_buffer: Some("1d".to_string()),
+            ..params
+        };
+        assert!(build_history_query(&params).unwrap().contains("day >= 1735603200"));
     }
 
     #[test]
@@ -528,14 +1002,14 @@ mod tests {
             .icao24("485%")
             .time_range("2025-01-01 00:00:00", "2025-01-01 23:59:59");
 
-        let sql = build_history_query(&params);
+        let sql = build_history_query(&params).unwrap();
 
         assert!(sql.contains("icao24 LIKE '485%'"));
     }
 
     #[test]
     fn test_hour_bounds_unix() {
-        let (start, stop) = compute_hour_bounds_unix("2025-01-01 10:30:00", "2025-01-01 12:45:00");
+        let (start, stop) = compute_hour_bounds_unix("2025-01-01 10:30:00", "2025-01-01 12:45:00").unwrap();
 
         // 2025-01-01 10:00:00 UTC = 1735725600
         // 2025-01-01 13:00:00 UTC = 1735736400
@@ -543,11 +1017,53 @@ mod tests {
         assert_eq!(stop, 1735736400);
     }
 
+    #[test]
+    fn test_hour_bounds_exact_hour() {
+        // The stop is inclusive: rows at 11:00:00 are in the 11:00 partition
+        let (start, stop) = compute_hour_bounds_unix("2025-01-01 10:00:00", "2025-01-01 11:00:00").unwrap();
+        assert_eq!(stop - start, 7200);
+
+        // One second before the hour stays within the 10:00 partition
+        let (_, stop) = compute_hour_bounds_unix("2025-01-01 10:00:00", "2025-01-01 10:59:59").unwrap();
+        assert_eq!(stop, 1735729200);
+
+        // Date-only stop ends at 23:59:59, so the bound is the next midnight
+        let (_, stop) = compute_hour_bounds_unix("2025-01-01", "2025-01-01").unwrap();
+        assert_eq!(stop, 1735776000);
+
+        // Start and stop on the same exact hour
+        let (start, stop) = compute_hour_bounds_unix("2025-01-01 10:00:00", "2025-01-01 10:00:00").unwrap();
+        assert_eq!(stop - start, 3600);
+
+        let sql = build_history_query(
+            &QueryParams::new().time_range("2025-01-01 10:00:00", "2025-01-01 11:00:00"),
+        ).unwrap();
+        assert!(sql.contains("hour >= 1735725600\n  AND hour < 1735732800"));
+    }
+
     #[test]
     fn test_datetime_to_unix() {
         // 2024-11-08 10:00:00 UTC = 1731060000
-        let ts = datetime_to_unix("2024-11-08 10:00:00");
+        let ts = datetime_to_unix("2024-11-08 10:00:00", false).unwrap();
         assert_eq!(ts, 1731060000);
+
+        // No silent fallback to the epoch
+        assert!(matches!(datetime_to_unix("yesterday", false), Err(OpenSkyError::InvalidParam(_))));
+
+        // A date-only stop covers the whole day
+        assert_eq!(datetime_to_unix("2024-11-08", true).unwrap(), 1731110399);
+        let params = QueryParams::new().time_range("2025-01-01", "2025-01-01");
+        let sql = build_history_query(&params).unwrap();
+        assert!(sql.contains("time >= 1735689600\n  AND time <= 1735775999"), "{}", sql);
+        assert!(sql.contains("hour < 1735776000"));
+        let sql = build_history_query(&params.clone().departure("EHAM")).unwrap();
+        assert!(sql.contains("sv.time <= 1735775999"), "{}", sql);
+        assert!(build_rawdata_query(&params, RawTable::default()).unwrap().contains("mintime <= 1735775999"));
+        assert!(build_adsb_query(&params, RawTable::Position).unwrap().contains("mintime <= 1735775999"));
+        assert!(build_flightlist_query(&params).unwrap().contains("<= 1735775999"));
+        let params = QueryParams::new().time_range("yesterday", "2024-11-08");
+        assert!(build_history_query(&params).is_err());
+        assert!(build_flightlist_query(&params).is_err());
     }
 
     #[test]
@@ -570,7 +1086,7 @@ mod tests {
             .time_range("2025-01-01 00:00:00", "2025-01-01 23:59:59")
             .departure("EHAM");
 
-        let sql = build_flightlist_query(&params);
+        let sql = build_flightlist_query(&params).unwrap();
 
         assert!(sql.contains("SELECT icao24, callsign, firstseen, lastseen"));
         assert!(sql.contains("FROM minio.osky.flights_data4"));
@@ -586,7 +1102,7 @@ mod tests {
             .departure("EHAM")
             .arrival("EGLL");
 
-        let sql = build_flightlist_query(&params);
+        let sql = build_flightlist_query(&params).unwrap();
 
         assert!(sql.contains("estdepartureairport = 'EHAM'"));
         assert!(sql.contains("estarrivalairport = 'EGLL'"));
@@ -598,7 +1114,7 @@ mod tests {
             .icao24("485a32")
             .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00");
 
-        let sql = build_rawdata_query(&params, RawTable::RollcallReplies);
+        let sql = build_rawdata_query(&params, RawTable::RollcallReplies).unwrap();
 
         assert!(sql.contains("SELECT mintime, rawmsg, icao24"));
         assert!(sql.contains("FROM minio.osky.rollcall_replies_data4"));
@@ -613,18 +1129,48 @@ mod tests {
             .icao24("485a32")
             .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00");
 
-        let sql = build_rawdata_query(&params, RawTable::Position);
+        let sql = build_rawdata_query(&params, RawTable::Position).unwrap();
 
         assert!(sql.contains("FROM minio.osky.position_data4"));
     }
 
+    #[test]
+    fn test_rawdata_downlink_formats() {
+        let params = QueryParams::new()
+            .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00")
+            .downlink_formats([21, 20, 24]);
+
+        let sql = build_rawdata_query(&params, RawTable::RollcallReplies).unwrap();
+        assert!(sql.contains("\n  AND from_base(substr(rawmsg, 1, 2), 16) / 8 IN (21, 20, 24, 25, 26, 27, 28, 29, 30, 31)\n"));
+
+        let sql = build_rawdata_query(&params.canonical().departure("EHAM"), RawTable::RollcallReplies).unwrap();
+        assert!(sql.contains("\n  AND from_base(substr(raw.rawmsg, 1, 2), 16) / 8 IN (20, 21, 24, 25"));
+
+        assert!(QueryParams::new().downlink_formats([25]).validate().is_err());
+        assert!(QueryParams::new().downlink_formats([20]).validate_flightlist().is_err());
+    }
+
+    #[test]
+    fn test_adsb_query() {
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00");
+
+        let sql = build_adsb_query(&params, RawTable::Position).unwrap();
+        assert!(sql.starts_with("SELECT mintime, maxtime, msgcount, icao24, lat, lon, alt, surface\nFROM minio.osky.position_data4\n"));
+        assert!(sql.contains("hour >= 1735725600"));
+
+        let sql = build_adsb_query(&params.columns(["velocity"]), RawTable::Velocity).unwrap();
+        assert!(sql.starts_with("SELECT mintime, icao24, velocity\nFROM minio.osky.velocity_data4\n"));
+    }
+
     #[test]
     fn test_rawdata_with_airport() {
         let params = QueryParams::new()
             .time_range("2025-01-01 00:00:00", "2025-01-01 23:59:59")
             .departure("EHAM");
 
-        let sql = build_rawdata_query(&params, RawTable::RollcallReplies);
+        let sql = build_rawdata_query(&params, RawTable::RollcallReplies).unwrap();
 
         assert!(sql.contains("JOIN"));
         assert!(sql.contains("flights_data4"));
@@ -641,7 +1187,7 @@ mod tests {
         params.start = Some("2025-01-15 10:00:00".to_string());
         // Note: no stop time set
 
-        let sql = build_flightlist_query(&params);
+        let sql = build_flightlist_query(&params).unwrap();
 
         // Should still have day partition filter
         assert!(sql.contains("day >="));
@@ -650,4 +1196,268 @@ mod tests {
         assert!(sql.contains("lastseen >="));
         assert!(sql.contains("lastseen <="));
     }
+
+    #[test]
+    fn test_aggregate_query() {
+        let params = QueryParams::new()
+            .time_range("2025-01-01 00:00:00", "2025-01-01 23:59:59")
+            .bounds(-10.0, 35.0, 30.0, 60.0)
+            .limit(5);
+
+        let sql = build_aggregate_query(&params, Aggregation::AircraftPerHour).unwrap();
+        assert!(sql.starts_with("SELECT hour, count(DISTINCT icao24) AS aircraft\nFROM (\nSELECT icao24, hour\nFROM minio.osky.state_vectors_data4"));
+        assert!(sql.contains("AND lat >= 35"));
+        assert!(sql.ends_with(") t\nGROUP BY hour\nORDER BY hour\nLIMIT 5"));
+        // The subquery is neither sorted nor limited
+        assert_eq!(sql.matches("ORDER BY").count(), 1);
+        assert_eq!(sql.matches("LIMIT").count(), 1);
+
+        let sql = build_aggregate_query(&params.departure("EHAM"), Aggregation::FlightsPerArrival).unwrap();
+        assert!(sql.contains("FROM minio.osky.flights_data4"));
+        assert!(sql.contains("estdepartureairport = 'EHAM'"));
+        assert!(sql.contains("GROUP BY estarrivalairport\nORDER BY flights DESC"));
+    }
+
+    #[test]
+    fn test_columns() {
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00")
+            .columns(["baroaltitude", "LAT", "lon"]);
+        assert_eq!(history_columns(&params), ["time", "icao24", "lat", "lon", "baroaltitude"]);
+        assert!(build_history_query(&params).unwrap().starts_with("SELECT time, icao24, lat, lon, baroaltitude\n"));
+        // The join selects the same columns from the state vectors
+        let sql = build_history_query(&params.clone().departure("EHAM")).unwrap();
+        assert!(sql.starts_with("SELECT sv.time, sv.icao24, sv.lat, sv.lon, sv.baroaltitude\n"));
+        assert_eq!(history_columns(&QueryParams::new()), FLIGHT_COLUMNS);
+
+        let raw = QueryParams::new().time_range("2025-01-01", "2025-01-01").columns(["rawmsg"]);
+        assert!(build_rawdata_query(&raw, RawTable::default()).unwrap().starts_with("SELECT rawmsg\n"));
+
+        assert!(params.validate_columns(FLIGHT_COLUMNS).is_ok());
+        let err = params.clone().columns(["lat", "altitude"]).validate_columns(FLIGHT_COLUMNS).unwrap_err();
+        assert!(err.to_string().contains("Unknown column 'altitude'; valid columns are: time, icao24, lat"));
+        assert!(params.validate_flightlist().is_err());
+    }
+
+    #[test]
+    fn test_resolution() {
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00")
+            .resolution(10);
+
+        assert!(build_history_query(&params).unwrap().contains("AND time % 10 = 0"));
+        assert!(build_history_query(&params.clone().departure("EHAM")).unwrap().contains("AND sv.time % 10 = 0"));
+        assert!(!build_history_query(&params.resolution(1)).unwrap().contains('%'));
+    }
+
+    #[test]
+    fn test_valid_positions() {
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01 10:00:00", "2025-01-01 11:00:00");
+        assert!(!build_history_query(&params).unwrap().contains("BETWEEN"));
+
+        let sql = build_history_query(&params.clone().valid_positions(true)).unwrap();
+        assert!(sql.contains("AND lat BETWEEN -90 AND 90"));
+        assert!(sql.contains("AND NOT (lat = 0 AND lon = 0)"));
+
+        let sql = build_history_query(&params.clone().require_position(true)).unwrap();
+        assert!(sql.contains("AND lat IS NOT NULL\n  AND lon IS NOT NULL"));
+
+        let sql = build_history_query(&params.departure("EHAM").valid_positions(true).require_position(true)).unwrap();
+        assert!(sql.contains("AND sv.lon BETWEEN -180 AND 180"));
+        assert!(!sql.contains("IS NOT NULL"));
+    }
+
+    #[test]
+    fn test_distinct_icao24_query() {
+        let params = QueryParams::new()
+            .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00")
+            .bounds(4.0, 52.0, 5.0, 53.0);
+
+        let sql = build_distinct_icao24_query(&params).unwrap();
+
+        assert!(sql.starts_with("SELECT icao24, min(time) AS firstseen, max(time) AS lastseen\nFROM (\nSELECT icao24, time\n"));
+        assert!(sql.contains("AND lon >= 4"));
+        assert!(sql.ends_with("GROUP BY icao24\nORDER BY icao24"));
+    }
+
+    #[test]
+    fn test_order() {
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00")
+            .limit(10);
+
+        assert!(build_history_query(&params).unwrap().ends_with("ORDER BY time\nLIMIT 10"));
+
+        let unordered = params.clone().order(Order::None);
+        assert!(!build_history_query(&unordered).unwrap().contains("ORDER BY"));
+        assert!(!build_flightlist_query(&unordered).unwrap().contains("ORDER BY"));
+        assert!(!build_rawdata_query(&unordered, RawTable::Position).unwrap().contains("ORDER BY"));
+
+        let by_icao = params.order(Order::IcaoThenTime);
+        assert!(build_history_query(&by_icao).unwrap().contains("ORDER BY icao24, time"));
+        assert!(build_history_query(&by_icao.clone().departure("EHAM")).unwrap().contains("ORDER BY sv.icao24, sv.time"));
+        assert!(build_flightlist_query(&by_icao).unwrap().contains("ORDER BY icao24, firstseen"));
+    }
+
+    #[test]
+    fn test_offset() {
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00")
+            .offset(2000)
+            .limit(1000);
+
+        assert!(build_history_query(&params).unwrap().ends_with("ORDER BY time\nOFFSET 2000\nLIMIT 1000"));
+        assert!(!build_history_query(&params.offset(0)).unwrap().contains("OFFSET"));
+    }
+
+    #[test]
+    fn test_exclude_airports() {
+        let params = QueryParams::new()
+            .time_range("2025-01-01 00:00:00", "2025-01-01 23:59:59")
+            .arrival("EHAM")
+            .exclude_departure("EGLL");
+
+        // Positive airport filter: exclusion goes into the flights subquery
+        let sql = build_history_query(&params).unwrap();
+        assert!(sql.contains("estarrivalairport = 'EHAM'"));
+        assert!(sql.contains("coalesce(estdepartureairport, '') NOT IN ('EGLL')"));
+        assert!(!sql.contains("NOT EXISTS"));
+
+        let sql = build_flightlist_query(&params).unwrap();
+        assert!(sql.contains("AND coalesce(estdepartureairport, '') NOT IN ('EGLL')"));
+
+        // No positive filter: anti-join against flights_data4
+        let params = QueryParams::new()
+            .time_range("2025-01-01 00:00:00", "2025-01-01 23:59:59")
+            .bounds(4.0, 52.0, 5.0, 53.0)
+            .exclude_arrival("EHAM")
+            .exclude_arrival("EHRD");
+        let sql = build_history_query(&params).unwrap();
+        assert!(sql.contains("FROM minio.osky.state_vectors_data4 sv\n"));
+        assert!(sql.contains("AND NOT EXISTS (\n    SELECT 1 FROM minio.osky.flights_data4 fx"));
+        assert!(sql.contains("fx.callsign = sv.callsign"));
+        assert!(sql.contains("(fx.estarrivalairport IN ('EHAM', 'EHRD'))"));
+
+        let sql = build_rawdata_query(&params, RawTable::Position).unwrap();
+        assert!(sql.contains("FROM minio.osky.position_data4 raw\n"));
+        assert!(sql.contains("raw.mintime >= fx.firstseen"));
+        assert!(!sql.contains("fx.callsign"));
+    }
+
+    #[test]
+    fn test_multi_airport() {
+        let london = ["EGLL", "EGKK", "EGSS"];
+        let params = QueryParams::new()
+            .time_range("2025-01-01 00:00:00", "2025-01-01 23:59:59")
+            .departure(london)
+            .arrival("EHAM");
+
+        let sql = build_history_query(&params).unwrap();
+        assert!(sql.contains("estdepartureairport IN ('EGLL', 'EGKK', 'EGSS')"));
+        assert!(sql.contains("estarrivalairport = 'EHAM'"));
+
+        let sql = build_flightlist_query(&QueryParams::new().airport(vec!["EHAM", "EHRD"])).unwrap();
+        assert!(sql.contains("AND (estdepartureairport IN ('EHAM', 'EHRD') OR estarrivalairport IN ('EHAM', 'EHRD'))"));
+
+        let preview = build_query_preview(&params);
+        assert!(preview.contains("departure_airport=[\"EGLL\", \"EGKK\", \"EGSS\"]"));
+        assert!(preview.contains("arrival_airport=\"EHAM\""));
+    }
+
+    #[test]
+    fn test_airport_groups_and_countries() {
+        use crate::airports::AirportGroup;
+
+        let params = QueryParams::new()
+            .time_range("2025-01-01 00:00:00", "2025-01-01 23:59:59")
+            .departure(AirportGroup::ParisArea)
+            .arrival_country("DE")
+            .unwrap()
+            .arrival_country("NL")
+            .unwrap();
+
+        let sql = build_flightlist_query(&params).unwrap();
+        assert!(sql.contains("estdepartureairport IN ('LFPG', 'LFPO', 'LFPB', 'LFOB')"));
+        assert!(sql.contains(
+            "AND (estarrivalairport LIKE 'ED%' OR estarrivalairport LIKE 'ET%' OR estarrivalairport LIKE 'EH%')"
+        ));
+
+        let sql = build_flightlist_query(&QueryParams::new().departure(vec!["EGLL", "LF%"])).unwrap();
+        assert!(sql.contains("(estdepartureairport = 'EGLL' OR estdepartureairport LIKE 'LF%')"));
+
+        assert!(QueryParams::new().departure_country("XX").is_err());
+    }
+
+    #[test]
+    fn test_airport_stats_query() {
+        let sql = build_airport_stats_query(
+            "EHAM",
+            "2025-01-01 00:00:00",
+            "2025-01-07 23:59:59",
+            StatsInterval::Hourly,
+            &TableNames::default(),
+        ).unwrap();
+        assert!(sql.starts_with("SELECT period, sum(departure) AS departures, sum(arrival) AS arrivals"));
+        assert!(sql.contains("firstseen - firstseen % 3600 AS period"));
+        assert!(sql.contains("estdepartureairport = 'EHAM'"));
+        assert!(sql.contains("AND firstseen >= 1735689600"));
+        assert!(sql.contains("UNION ALL"));
+        assert!(sql.contains("estarrivalairport = 'EHAM'"));
+        assert!(sql.contains("AND lastseen <= 1736294399"));
+        assert!(sql.ends_with("GROUP BY period\nORDER BY period"));
+    }
+
+    #[test]
+    fn test_windows_history_query() {
+        let windows = vec![
+            ("485A32".to_string(), 1735725000, 1735731600),
+            ("3c6444".to_string(), 1735812000, 1735812600),
+        ];
+        let sql = build_windows_history_query(&windows, &TableNames::default());
+        assert!(sql.contains("FROM minio.osky.state_vectors_data4\nWHERE hour >= 1735722000\n  AND hour < 1735815600"));
+        assert!(sql.contains(
+            "(icao24 = '485a32' AND time >= 1735725000 AND time <= 1735731600 AND hour >= 1735722000 AND hour < 1735732800)"
+        ));
+        assert!(sql.contains("\n    OR (icao24 = '3c6444' AND time >= 1735812000"));
+        assert!(sql.ends_with("ORDER BY icao24, time"));
+
+        let tables = TableNames::default().version("data5");
+        assert!(build_windows_history_query(&windows, &tables).contains("FROM minio.osky.state_vectors_data5\n"));
+    }
+
+    #[test]
+    fn test_flight_time() {
+        let params = QueryParams::new().time_range("2025-01-01 00:00:00", "2025-01-01 23:59:59");
+
+        // Default without departure filter: lastseen, day partition of the range
+        let sql = build_flightlist_query(&params).unwrap();
+        assert!(sql.contains("AND day < 1735776000"));
+        assert!(sql.contains("AND lastseen <= 1735775999"));
+
+        let sql = build_flightlist_query(&params.clone().flight_time(FlightTime::FirstSeen)).unwrap();
+        assert!(sql.contains("AND day < 1735862400"));
+        assert!(sql.contains("AND firstseen >= 1735689600"));
+        assert!(!sql.contains("lastseen >="));
+
+        let sql = build_flightlist_query(&params.clone().flight_time(FlightTime::Overlap)).unwrap();
+        assert!(sql.contains("AND lastseen >= 1735689600"));
+        assert!(sql.contains("AND firstseen <= 1735775999"));
+
+        let sql = build_flightlist_query(&params.clone().departure("EHAM")).unwrap();
+        assert!(sql.contains("AND day < 1735862400"));
+        assert!(sql.contains("AND firstseen >= 1735689600"));
+
+        assert!(params.validate_flightlist().is_ok());
+        assert!(params.clone().resolution(10).validate_flightlist().is_err());
+        assert!(QueryParams::new()
+            .time_range("2025-01-02 00:00:00", "2025-01-01 00:00:00")
+            .validate_flightlist()
+            .is_err());
+    }
 }
@@ -0,0 +1,355 @@
+//! Canonical column schema of the OpenSky tables.
+//!
+//! Every component that names or types a column goes through this module:
+//! the query builder selects [`FLIGHT_COLUMNS`] and friends, Trino results
+//! are converted using [`column_type`], empty results are built with
+//! [`empty_frame`], and files read back from disk are passed through
+//! [`coerce`].
+
+#[cfg(feature = "client")]
+use crate::types::{OpenSkyError, Result};
+#[cfg(feature = "client")]
+use polars::prelude::*;
+
+/// Type of a column, independent of Polars so the query builder stays light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Int64,
+    Float64,
+    Boolean,
+    String,
+}
+
+impl ColumnType {
+    /// Map a Trino type name (e.g. `bigint`, `varchar(8)`) to a column type.
+    pub fn from_trino(type_name: &str) -> Self {
+        match type_name {
+            "double" | "real" => ColumnType::Float64,
+            "bigint" | "integer" | "smallint" | "tinyint" => ColumnType::Int64,
+            "boolean" => ColumnType::Boolean,
+            // varchar, timestamp, etc.
+            _ => ColumnType::String,
+        }
+    }
+
+    /// The Polars dtype used for this column type.
+    #[cfg(feature = "client")]
+    pub fn to_polars(self) -> DataType {
+        match self {
+            ColumnType::Int64 => DataType::Int64,
+            ColumnType::Float64 => DataType::Float64,
+            ColumnType::Boolean => DataType::Boolean,
+            ColumnType::String => DataType::String,
+        }
+    }
+}
+
+/// A named, typed column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnDef {
+    pub name: &'static str,
+    pub dtype: ColumnType,
+}
+
+const fn col(name: &'static str, dtype: ColumnType) -> ColumnDef {
+    ColumnDef { name, dtype }
+}
+
+/// Columns of `state_vectors_data4` returned by history queries.
+pub const STATE_VECTORS: &[ColumnDef] = &[
+    col("time", ColumnType::Int64),
+    col("icao24", ColumnType::String),
+    col("lat", ColumnType::Float64),
+    col("lon", ColumnType::Float64),
+    col("velocity", ColumnType::Float64),
+    col("heading", ColumnType::Float64),
+    col("vertrate", ColumnType::Float64),
+    col("callsign", ColumnType::String),
+    col("onground", ColumnType::Boolean),
+    col("squawk", ColumnType::String),
+    col("baroaltitude", ColumnType::Float64),
+    col("geoaltitude", ColumnType::Float64),
+    col("hour", ColumnType::Int64),
+];
+
+/// Columns of `flights_data4` returned by flightlist queries.
+pub const FLIGHTS: &[ColumnDef] = &[
+    col("icao24", ColumnType::String),
+    col("callsign", ColumnType::String),
+    col("firstseen", ColumnType::Int64),
+    col("lastseen", ColumnType::Int64),
+    col("estdepartureairport", ColumnType::String),
+    col("estarrivalairport", ColumnType::String),
+    col("day", ColumnType::Int64),
+];
+
+/// Columns returned by raw data queries.
+pub const RAWDATA: &[ColumnDef] = &[
+    col("mintime", ColumnType::Float64),
+    col("rawmsg", ColumnType::String),
+    col("icao24", ColumnType::String),
+];
+
+/// Decoded columns of `position_data4` returned by ADS-B position queries.
+pub const ADSB_POSITIONS: &[ColumnDef] = &[
+    col("mintime", ColumnType::Float64),
+    col("maxtime", ColumnType::Float64),
+    col("msgcount", ColumnType::Int64),
+    col("icao24", ColumnType::String),
+    col("lat", ColumnType::Float64),
+    col("lon", ColumnType::Float64),
+    col("alt", ColumnType::Float64),
+    col("surface", ColumnType::Boolean),
+];
+
+/// Decoded columns of `velocity_data4` returned by ADS-B velocity queries.
+pub const ADSB_VELOCITIES: &[ColumnDef] = &[
+    col("mintime", ColumnType::Float64),
+    col("maxtime", ColumnType::Float64),
+    col("msgcount", ColumnType::Int64),
+    col("icao24", ColumnType::String),
+    col("velocity", ColumnType::Float64),
+    col("heading", ColumnType::Float64),
+    col("vertrate", ColumnType::Float64),
+    col("ewvelocity", ColumnType::Float64),
+    col("nsvelocity", ColumnType::Float64),
+    col("geominurbaro", ColumnType::Float64),
+];
+
+/// Columns produced by server-side aggregations.
+pub const AGGREGATES: &[ColumnDef] = &[
+    col("messages", ColumnType::Int64),
+    col("aircraft", ColumnType::Int64),
+    col("flights", ColumnType::Int64),
+    col("airport", ColumnType::String),
+    col("period", ColumnType::Int64),
+    col("departures", ColumnType::Int64),
+    col("arrivals", ColumnType::Int64),
+];
+
+/// Default columns for history queries (state vectors).
+pub const FLIGHT_COLUMNS: &[&str] = &[
+    "time",
+    "icao24",
+    "lat",
+    "lon",
+    "velocity",
+    "heading",
+    "vertrate",
+    "callsign",
+    "onground",
+    "squawk",
+    "baroaltitude",
+    "geoaltitude",
+    "hour",
+];
+
+/// Flight list columns returned by flightlist queries.
+pub const FLIGHTLIST_COLUMNS: &[&str] = &[
+    "icao24",
+    "callsign",
+    "firstseen",
+    "lastseen",
+    "estdepartureairport",
+    "estarrivalairport",
+    "day",
+];
+
+/// Columns returned by distinct aircraft queries.
+pub const AIRCRAFT_COLUMNS: &[&str] = &[
+    "icao24",
+    "firstseen",
+    "lastseen",
+];
+
+/// Columns returned by airport statistics queries.
+pub const AIRPORT_STATS_COLUMNS: &[&str] = &[
+    "period",
+    "departures",
+    "arrivals",
+];
+
+/// Default columns for raw data queries.
+pub const RAWDATA_COLUMNS: &[&str] = &[
+    "mintime",
+    "rawmsg",
+    "icao24",
+];
+
+/// Columns returned by ADS-B position queries.
+pub const ADSB_POSITION_COLUMNS: &[&str] = &[
+    "mintime",
+    "maxtime",
+    "msgcount",
+    "icao24",
+    "lat",
+    "lon",
+    "alt",
+    "surface",
+];
+
+/// Columns returned by ADS-B velocity queries.
+pub const ADSB_VELOCITY_COLUMNS: &[&str] = &[
+    "mintime",
+    "maxtime",
+    "msgcount",
+    "icao24",
+    "velocity",
+    "heading",
+    "vertrate",
+    "ewvelocity",
+    "nsvelocity",
+    "geominurbaro",
+];
+
+/// Canonical type of a column name, looked up across all tables.
+///
+/// Columns shared between tables (`icao24`, `callsign`) have the same type
+/// everywhere.
+pub fn column_type(name: &str) -> Option<ColumnType> {
+    STATE_VECTORS
+        .iter()
+        .chain(FLIGHTS)
+        .chain(RAWDATA)
+        .chain(ADSB_POSITIONS)
+        .chain(ADSB_VELOCITIES)
+        .chain(AGGREGATES)
+        .find(|c| c.name == name)
+        .map(|c| c.dtype)
+}
+
+/// What to do when a result's columns differ from the expected ones.
+///
+/// Set with [`crate::Trino::set_schema_check`]. Differences come from
+/// schema changes on the server, or custom SQL run with the wrong default
+/// columns; either way the converter would otherwise produce whatever
+/// arrived, with values of an unexpected type turned into nulls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchemaCheck {
+    /// Log the differences and record them in the result's metadata
+    #[default]
+    Lenient,
+    /// Fail the query
+    Strict,
+}
+
+/// Differences between the expected column names and the `(name, Trino
+/// type)` columns of a result, one message each.
+///
+/// Columns missing or not expected are reported, as are columns whose
+/// Trino type does not map to their canonical type.
+pub fn schema_mismatches<'a>(expected: &[&str], actual: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<String> {
+    let actual: Vec<(&str, &str)> = actual.into_iter().collect();
+    let mut mismatches: Vec<String> = expected
+        .iter()
+        .filter(|name| !actual.iter().any(|(actual, _)| actual == *name))
+        .map(|name| format!("missing column '{}'", name))
+        .collect();
+    for (name, trino_type) in actual {
+        if !expected.contains(&name) {
+            mismatches.push(format!("unexpected column '{}' ({})", name, trino_type));
+        } else if let Some(dtype) = column_type(name).filter(|&dtype| dtype != ColumnType::from_trino(trino_type)) {
+            mismatches.push(format!("column '{}' is {}, expected {:?}", name, trino_type, dtype));
+        }
+    }
+    mismatches
+}
+
+/// Polars schema of a table.
+#[cfg(feature = "client")]
+pub fn polars_schema(columns: &[ColumnDef]) -> Schema {
+    columns
+        .iter()
+        .map(|c| Field::new(c.name.into(), c.dtype.to_polars()))
+        .collect()
+}
+
+/// An empty frame with the given columns, typed canonically where known.
+#[cfg(feature = "client")]
+pub fn empty_frame(names: &[&str]) -> Result<DataFrame> {
+    let columns: Vec<Column> = names
+        .iter()
+        .map(|name| {
+            let dtype = column_type(name).unwrap_or(ColumnType::String).to_polars();
+            Column::new_empty((*name).into(), &dtype)
+        })
+        .collect();
+    DataFrame::new(columns).map_err(|e| OpenSkyError::DataConversion(e.to_string()))
+}
+
+/// Cast known columns of a frame to their canonical types.
+///
+/// Unknown columns are left untouched.
+#[cfg(feature = "client")]
+pub fn coerce(mut df: DataFrame) -> Result<DataFrame> {
+    let casts: Vec<Column> = df
+        .get_columns()
+        .iter()
+        .filter_map(|column| {
+            let dtype = column_type(column.name())?.to_polars();
+            (column.dtype() != &dtype).then(|| {
+                column
+                    .strict_cast(&dtype)
+                    .map_err(|e| OpenSkyError::DataConversion(format!("Column '{}': {}", column.name(), e)))
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    for column in casts {
+        df.with_column(column)
+            .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+    }
+    Ok(df)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_match_schema() {
+        let names = |cols: &[ColumnDef]| cols.iter().map(|c| c.name).collect::<Vec<_>>();
+        assert_eq!(names(STATE_VECTORS), FLIGHT_COLUMNS);
+        assert_eq!(names(FLIGHTS), FLIGHTLIST_COLUMNS);
+        assert_eq!(names(RAWDATA), RAWDATA_COLUMNS);
+        assert_eq!(names(ADSB_POSITIONS), ADSB_POSITION_COLUMNS);
+        assert_eq!(names(ADSB_VELOCITIES), ADSB_VELOCITY_COLUMNS);
+    }
+
+    #[test]
+    fn test_shared_columns_agree() {
+        for c in STATE_VECTORS.iter().chain(FLIGHTS).chain(RAWDATA).chain(ADSB_POSITIONS).chain(ADSB_VELOCITIES).chain(AGGREGATES) {
+            assert_eq!(column_type(c.name), Some(c.dtype), "{}", c.name);
+        }
+        assert_eq!(ColumnType::from_trino("varchar(8)"), ColumnType::String);
+    }
+
+    #[test]
+    fn test_schema_mismatches() {
+        let expected = ["time", "icao24", "lat"];
+        assert!(schema_mismatches(&expected, [("time", "bigint"), ("icao24", "varchar(6)"), ("lat", "double")]).is_empty());
+        assert_eq!(
+            schema_mismatches(&expected, [("time", "timestamp(3)"), ("icao24", "varchar"), ("alt", "double")]),
+            [
+                "missing column 'lat'",
+                "column 'time' is timestamp(3), expected Int64",
+                "unexpected column 'alt' (double)",
+            ]
+        );
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_empty_frame_and_coerce() {
+        let df = empty_frame(FLIGHTLIST_COLUMNS).unwrap();
+        assert_eq!(df.schema().as_ref(), &polars_schema(FLIGHTS));
+
+        let df = df!("time" => [1i32], "squawk" => ["7000"], "extra" => [1u32]).unwrap();
+        let df = coerce(df).unwrap();
+        assert_eq!(df.column("time").unwrap().dtype(), &DataType::Int64);
+        assert_eq!(df.column("extra").unwrap().dtype(), &DataType::UInt32);
+
+        let bad = df!("lat" => ["north"]).unwrap();
+        assert!(coerce(bad).is_err());
+    }
+}
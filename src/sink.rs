@@ -0,0 +1,426 @@
+//! Destinations for query results, fed batch by batch.
+//!
+//! [`Trino::execute_into`] converts each response page to a DataFrame and
+//! hands it to a [`ResultSink`], so results can be written out while the
+//! query runs instead of being collected first. The crate provides sinks
+//! for a [`FlightData`] ([`DataFrameSink`]), a Parquet file
+//! ([`ParquetSink`]) and a CSV file ([`CsvSink`]); other destinations such
+//! as a database or a network forwarder implement the trait themselves:
+//!
+//! ```rust,no_run
+//! use opensky::sink::ResultSink;
+//! use polars::prelude::{DataFrame, Schema};
+//!
+//! /// Counts rows without keeping them.
+//! struct RowCounter(usize);
+//!
+//! impl ResultSink for RowCounter {
+//!     type Output = usize;
+//!
+//!     fn on_schema(&mut self, _schema: &Schema) -> opensky::Result<()> {
+//!         Ok(())
+//!     }
+//!
+//!     fn on_batch(&mut self, batch: &DataFrame) -> opensky::Result<()> {
+//!         self.0 += batch.height();
+//!         Ok(())
+//!     }
+//!
+//!     fn finish(self) -> opensky::Result<usize> {
+//!         Ok(self.0)
+//!     }
+//! }
+//! ```
+//!
+//! [`ForwardSink`] sends each batch on to a message system instead, through
+//! a [`Publisher`]; with the `nats` feature, [`NatsPublisher`] publishes to
+//! a NATS subject.
+//!
+//! [`Trino::execute_into`]: crate::Trino::execute_into
+
+use crate::types::{CsvOptions, FlightData, OpenSkyError, Result};
+use async_trait::async_trait;
+use polars::prelude::*;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use tokio::sync::mpsc;
+
+/// A destination for the rows of a query.
+///
+/// [`ResultSink::on_schema`] is called once, before any batch; batches
+/// always have that schema.
+pub trait ResultSink {
+    /// What the sink produces once all rows are in.
+    type Output;
+
+    /// Receive the schema of the result.
+    fn on_schema(&mut self, schema: &Schema) -> Result<()>;
+
+    /// Receive the next non-empty batch of rows.
+    fn on_batch(&mut self, batch: &DataFrame) -> Result<()>;
+
+    /// Wait until the sink can take another batch; awaited before each
+    /// [`ResultSink::on_batch`], so a slow sink holds up the query instead
+    /// of letting batches pile up in memory. Ready at once by default.
+    fn ready(&mut self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Complete the output after the last batch.
+    fn finish(self) -> Result<Self::Output>;
+}
+
+/// Collects the batches into a [`FlightData`].
+#[derive(Debug, Default)]
+pub struct DataFrameSink {
+    df: Option<DataFrame>,
+}
+
+impl DataFrameSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ResultSink for DataFrameSink {
+    type Output = FlightData;
+
+    fn on_schema(&mut self, schema: &Schema) -> Result<()> {
+        self.df = Some(DataFrame::empty_with_schema(schema));
+        Ok(())
+    }
+
+    fn on_batch(&mut self, batch: &DataFrame) -> Result<()> {
+        match self.df.as_mut() {
+            Some(df) => {
+                df.vstack_mut(batch)
+                    .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+            }
+            None => self.df = Some(batch.clone()),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<FlightData> {
+        Ok(FlightData::new(self.df.unwrap_or_default()))
+    }
+}
+
+/// Writes the batches to a Parquet file, one row group per batch.
+pub struct ParquetSink {
+    path: PathBuf,
+    compression: ParquetCompression,
+    writer: Option<polars::io::parquet::write::BatchedWriter<std::fs::File>>,
+}
+
+impl ParquetSink {
+    /// Write to `path`, replacing an existing file once the schema is known.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        ParquetSink {
+            path: path.into(),
+            compression: ParquetCompression::default(),
+            writer: None,
+        }
+    }
+
+    pub fn compression(mut self, compression: ParquetCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+}
+
+impl ResultSink for ParquetSink {
+    type Output = ();
+
+    fn on_schema(&mut self, schema: &Schema) -> Result<()> {
+        let file = std::fs::File::create(&self.path)?;
+        let writer = ParquetWriter::new(file)
+            .with_compression(self.compression)
+            .batched(schema)
+            .map_err(parquet_error)?;
+        self.writer = Some(writer);
+        Ok(())
+    }
+
+    fn on_batch(&mut self, batch: &DataFrame) -> Result<()> {
+        if self.writer.is_none() {
+            self.on_schema(batch.schema())?;
+        }
+        match self.writer.as_mut() {
+            Some(writer) => writer.write_batch(batch).map_err(parquet_error),
+            None => Ok(()),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self.writer {
+            Some(writer) => writer.finish().map(|_| ()).map_err(parquet_error),
+            None => Ok(()),
+        }
+    }
+}
+
+fn parquet_error(e: PolarsError) -> OpenSkyError {
+    OpenSkyError::DataConversion(format!("Failed to write Parquet: {}", e))
+}
+
+/// Writes the batches to a CSV file, gzipped if the options say so.
+///
+/// The header is written with the schema; each batch is then appended as
+/// with [`CsvOptions::append`], so a gzipped file holds one gzip member per
+/// batch, which [`FlightData::from_csv`] reads back as a whole.
+pub struct CsvSink {
+    path: PathBuf,
+    options: CsvOptions,
+}
+
+impl CsvSink {
+    pub fn new(path: impl Into<PathBuf>, options: CsvOptions) -> Self {
+        CsvSink {
+            path: path.into(),
+            options,
+        }
+    }
+}
+
+impl ResultSink for CsvSink {
+    type Output = ();
+
+    fn on_schema(&mut self, schema: &Schema) -> Result<()> {
+        crate::write_csv_with(&DataFrame::empty_with_schema(schema), &self.path, &self.options)
+    }
+
+    fn on_batch(&mut self, batch: &DataFrame) -> Result<()> {
+        let options = self.options.clone().append(true);
+        crate::write_csv_with(batch, &self.path, &options)
+    }
+
+    fn finish(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// How [`ForwardSink`] encodes a batch into a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BatchFormat {
+    /// Newline-delimited JSON, one object per row.
+    #[default]
+    Ndjson,
+    /// An Arrow IPC file holding the batch.
+    ArrowIpc,
+}
+
+impl BatchFormat {
+    /// Encode a batch as one message payload.
+    pub fn encode(&self, batch: &DataFrame) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        match self {
+            BatchFormat::Ndjson => crate::encode_ndjson(batch, &mut buf)?,
+            BatchFormat::ArrowIpc => {
+                IpcWriter::new(&mut buf)
+                    .finish(&mut batch.clone())
+                    .map_err(|e| OpenSkyError::DataConversion(format!("Failed to write Arrow IPC: {}", e)))?;
+            }
+        }
+        Ok(buf)
+    }
+}
+
+/// A message destination for [`ForwardSink`], such as a Kafka topic or a
+/// NATS subject.
+///
+/// ```rust,no_run
+/// use async_trait::async_trait;
+/// use opensky::sink::Publisher;
+///
+/// /// Prints the size of each message.
+/// struct Printer;
+///
+/// #[async_trait]
+/// impl Publisher for Printer {
+///     async fn publish(&mut self, payload: Vec<u8>) -> opensky::Result<()> {
+///         println!("{} bytes", payload.len());
+///         Ok(())
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait Publisher: Send + 'static {
+    /// Send one encoded batch.
+    async fn publish(&mut self, payload: Vec<u8>) -> Result<()>;
+
+    /// Wait until all published messages are delivered.
+    async fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Resolves once all batches of a [`ForwardSink`] are published, to the
+/// number of messages sent.
+pub type Forwarding = Pin<Box<dyn Future<Output = Result<usize>> + Send>>;
+
+/// Batches encoded but not yet published by a [`ForwardSink`].
+const FORWARD_QUEUE: usize = 8;
+
+/// Forwards each batch, encoded as a message, to a [`Publisher`].
+///
+/// Messages are published by a background task, so the query goes on
+/// while a message is in flight. At most eight encoded batches wait for
+/// the publisher: when the destination falls behind, [`ResultSink::ready`]
+/// waits for room and the query is read no further until then. Calling
+/// [`ResultSink::on_batch`] without awaiting `ready` first fails once the
+/// queue is full. The output of [`ResultSink::finish`] is a [`Forwarding`]
+/// to await for delivery. Must be used within a Tokio runtime.
+///
+/// ```rust,no_run
+/// # async fn example(trino: &mut opensky::Trino, publisher: impl opensky::sink::Publisher) -> opensky::Result<()> {
+/// use opensky::sink::{BatchFormat, ForwardSink};
+///
+/// let sql = opensky::build_history_query(&opensky::QueryParams::new().icao24("485a32"))?;
+/// let sink = ForwardSink::new(publisher, BatchFormat::Ndjson);
+/// let sent = trino.execute_into(&sql, opensky::FLIGHT_COLUMNS, sink).await?.await?;
+/// println!("{} messages", sent);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ForwardSink<P: Publisher> {
+    format: BatchFormat,
+    publisher: Option<P>,
+    sender: Option<mpsc::Sender<Vec<u8>>>,
+    /// Room in the queue reserved by [`ResultSink::ready`]
+    permit: Option<mpsc::OwnedPermit<Vec<u8>>>,
+    task: Option<tokio::task::JoinHandle<Result<usize>>>,
+}
+
+impl<P: Publisher> ForwardSink<P> {
+    pub fn new(publisher: P, format: BatchFormat) -> Self {
+        ForwardSink {
+            format,
+            publisher: Some(publisher),
+            sender: None,
+            permit: None,
+            task: None,
+        }
+    }
+
+    /// Start the publishing task on first use.
+    fn start(&mut self) {
+        if let Some(mut publisher) = self.publisher.take() {
+            let (sender, mut receiver) = mpsc::channel::<Vec<u8>>(FORWARD_QUEUE);
+            self.sender = Some(sender);
+            self.task = Some(tokio::spawn(async move {
+                let mut sent = 0;
+                while let Some(payload) = receiver.recv().await {
+                    publisher.publish(payload).await?;
+                    sent += 1;
+                }
+                publisher.flush().await?;
+                Ok(sent)
+            }));
+        }
+    }
+}
+
+impl<P: Publisher> ResultSink for ForwardSink<P> {
+    type Output = Forwarding;
+
+    fn on_schema(&mut self, _schema: &Schema) -> Result<()> {
+        self.start();
+        Ok(())
+    }
+
+    fn on_batch(&mut self, batch: &DataFrame) -> Result<()> {
+        self.start();
+        let payload = self.format.encode(batch)?;
+        if let Some(permit) = self.permit.take() {
+            permit.send(payload);
+            return Ok(());
+        }
+        // A closed channel means the task stopped on an error, which
+        // `finish` reports
+        if let Some(sender) = &self.sender {
+            match sender.try_send(payload) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    return Err(OpenSkyError::Io(std::io::Error::new(
+                        std::io::ErrorKind::WouldBlock,
+                        "Forwarding queue is full; await ResultSink::ready before each batch",
+                    )))
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => self.sender = None,
+            }
+        }
+        Ok(())
+    }
+
+    fn ready(&mut self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        self.start();
+        Box::pin(async move {
+            if self.permit.is_none() {
+                if let Some(sender) = self.sender.clone() {
+                    match sender.reserve_owned().await {
+                        Ok(permit) => self.permit = Some(permit),
+                        Err(_) => self.sender = None,
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn finish(mut self) -> Result<Forwarding> {
+        self.start();
+        drop(self.permit.take());
+        drop(self.sender.take());
+        let task = self.task.take().expect("publishing task is started");
+        Ok(Box::pin(async move {
+            task.await
+                .map_err(|e| OpenSkyError::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))?
+        }))
+    }
+}
+
+/// Publishes messages to a NATS subject.
+#[cfg(feature = "nats")]
+pub struct NatsPublisher {
+    client: async_nats::Client,
+    subject: String,
+}
+
+#[cfg(feature = "nats")]
+impl NatsPublisher {
+    /// Connect to the NATS server at `url`, publishing to `subject`.
+    pub async fn connect(url: &str, subject: impl Into<String>) -> Result<Self> {
+        let client = async_nats::connect(url)
+            .await
+            .map_err(|e| OpenSkyError::Config(format!("Cannot connect to NATS at {}: {}", url, e)))?;
+        Ok(Self::new(client, subject))
+    }
+
+    /// Publish with an existing client.
+    pub fn new(client: async_nats::Client, subject: impl Into<String>) -> Self {
+        NatsPublisher {
+            client,
+            subject: subject.into(),
+        }
+    }
+}
+
+#[cfg(feature = "nats")]
+#[async_trait]
+impl Publisher for NatsPublisher {
+    async fn publish(&mut self, payload: Vec<u8>) -> Result<()> {
+        self.client
+            .publish(self.subject.clone(), payload.into())
+            .await
+            .map_err(|e| OpenSkyError::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.client
+            .flush()
+            .await
+            .map_err(|e| OpenSkyError::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))
+    }
+}
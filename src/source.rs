@@ -0,0 +1,98 @@
+//! Backend abstraction for OpenSky data access.
+//!
+//! Application code that depends on [`DataSource`] instead of [`Trino`]
+//! directly can be tested against a mock or fixture-backed implementation
+//! without credentials or network access.
+
+use crate::trino::Trino;
+use crate::types::{FlightData, FlightList, QueryParams, RawTable, Result};
+use async_trait::async_trait;
+
+/// A backend that can answer OpenSky queries.
+///
+/// Implemented by [`Trino`]; implement it yourself to inject canned data:
+///
+/// ```rust
+/// use opensky::{DataSource, FlightData, FlightList, QueryParams, RawTable, Result};
+/// use async_trait::async_trait;
+///
+/// struct Empty;
+///
+/// #[async_trait]
+/// impl DataSource for Empty {
+///     async fn history(&mut self, _: QueryParams) -> Result<FlightData> {
+///         Ok(FlightData::new(Default::default()))
+///     }
+///     async fn flightlist(&mut self, _: QueryParams) -> Result<FlightList> {
+///         FlightList::new(opensky::schema::empty_frame(opensky::FLIGHTLIST_COLUMNS)?)
+///     }
+///     async fn rawdata(&mut self, _: QueryParams, _: RawTable) -> Result<FlightData> {
+///         Ok(FlightData::new(Default::default()))
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait DataSource: Send {
+    /// Query state vectors, see [`Trino::history`].
+    async fn history(&mut self, params: QueryParams) -> Result<FlightData>;
+
+    /// Query the flight list, see [`Trino::flightlist`].
+    async fn flightlist(&mut self, params: QueryParams) -> Result<FlightList>;
+
+    /// Query raw messages from a table, see [`Trino::rawdata_table`].
+    async fn rawdata(&mut self, params: QueryParams, table: RawTable) -> Result<FlightData>;
+}
+
+#[async_trait]
+impl DataSource for Trino {
+    async fn history(&mut self, params: QueryParams) -> Result<FlightData> {
+        Trino::history(self, params).await
+    }
+
+    async fn flightlist(&mut self, params: QueryParams) -> Result<FlightList> {
+        Trino::flightlist(self, params).await
+    }
+
+    async fn rawdata(&mut self, params: QueryParams, table: RawTable) -> Result<FlightData> {
+        Trino::rawdata_table(self, params, table).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::*;
+
+    struct Fixture {
+        calls: usize,
+    }
+
+    #[async_trait]
+    impl DataSource for Fixture {
+        async fn history(&mut self, _params: QueryParams) -> Result<FlightData> {
+            self.calls += 1;
+            let df = df!("icao24" => ["485a32", "485a32"], "time" => [1i64, 2]).unwrap();
+            Ok(FlightData::new(df))
+        }
+
+        async fn flightlist(&mut self, _params: QueryParams) -> Result<FlightList> {
+            FlightList::new(crate::schema::empty_frame(crate::FLIGHTLIST_COLUMNS)?)
+        }
+
+        async fn rawdata(&mut self, _params: QueryParams, _table: RawTable) -> Result<FlightData> {
+            Ok(FlightData::new(DataFrame::default()))
+        }
+    }
+
+    async fn count_rows(source: &mut dyn DataSource) -> Result<usize> {
+        let data = source.history(QueryParams::new().icao24("485a32")).await?;
+        Ok(data.len())
+    }
+
+    #[tokio::test]
+    async fn test_mock_data_source() {
+        let mut fixture = Fixture { calls: 0 };
+        assert_eq!(count_rows(&mut fixture).await.unwrap(), 2);
+        assert_eq!(fixture.calls, 1);
+    }
+}
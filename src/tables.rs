@@ -0,0 +1,166 @@
+//! Tables of the OpenSky Trino database.
+//!
+//! Every query names its tables through the [`TableNames`] of its
+//! parameters: by default `minio.osky.<table>_data4`. When OpenSky moves to
+//! a new version of its tables, switch to it without waiting for a release,
+//! with [`crate::QueryParams::tables`], or for every query of a
+//! [`crate::Trino`] client with the `[tables]` section of its config file:
+//!
+//! ```ini
+//! [tables]
+//! version = data5
+//! # or single tables, by full name
+//! flights = minio.osky.flights_data5
+//! ```
+
+use crate::types::OpenSkyError;
+
+/// Trino catalog holding the OpenSky tables.
+pub const CATALOG: &str = "minio";
+
+/// Trino schema holding the OpenSky tables.
+pub const SCHEMA: &str = "osky";
+
+/// Current version suffix of the table names.
+pub const VERSION: &str = "data4";
+
+/// A table of the OpenSky database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Table {
+    /// State vectors, partitioned by `hour`
+    StateVectors,
+    /// Flights, partitioned by `day`
+    Flights,
+    RollcallReplies,
+    Acas,
+    AllcallReplies,
+    Identification,
+    OperationalStatus,
+    Position,
+    Velocity,
+}
+
+impl Table {
+    /// Every known table.
+    pub const ALL: [Table; 9] = [
+        Table::StateVectors,
+        Table::Flights,
+        Table::RollcallReplies,
+        Table::Acas,
+        Table::AllcallReplies,
+        Table::Identification,
+        Table::OperationalStatus,
+        Table::Position,
+        Table::Velocity,
+    ];
+
+    /// Name without catalog, schema and version, e.g. "state_vectors"; also
+    /// the key of the table in the `[tables]` config section.
+    pub fn base_name(self) -> &'static str {
+        match self {
+            Table::StateVectors => "state_vectors",
+            Table::Flights => "flights",
+            Table::RollcallReplies => "rollcall_replies",
+            Table::Acas => "acas",
+            Table::AllcallReplies => "allcall_replies",
+            Table::Identification => "identification",
+            Table::OperationalStatus => "operational_status",
+            Table::Position => "position",
+            Table::Velocity => "velocity",
+        }
+    }
+
+    /// Columns the table is partitioned by, which every query should filter
+    /// on to keep the scan small.
+    pub fn partition_columns(self) -> &'static [&'static str] {
+        match self {
+            Table::Flights => &["day"],
+            _ => &["hour"],
+        }
+    }
+
+    /// Fully qualified name of the current version, e.g.
+    /// "minio.osky.state_vectors_data4".
+    pub fn default_name(self) -> String {
+        TableNames::default().get(self)
+    }
+}
+
+impl std::str::FromStr for Table {
+    type Err = OpenSkyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        Table::ALL
+            .into_iter()
+            .find(|table| table.base_name() == s)
+            .ok_or_else(|| {
+                let known: Vec<&str> = Table::ALL.iter().map(|table| table.base_name()).collect();
+                OpenSkyError::InvalidParam(format!("Unknown table '{}', expected one of: {}", s, known.join(", ")))
+            })
+    }
+}
+
+/// Table names to use instead of the current version; the default names
+/// every table by [`Table::default_name`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableNames {
+    /// Version suffix of every table, e.g. "data5"
+    pub version: Option<String>,
+    /// Full names of single tables, taking precedence over `version`
+    pub names: Vec<(Table, String)>,
+}
+
+impl TableNames {
+    /// Use version `version` (e.g. "data5") of every table.
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Use `name` for `table`, e.g. "minio.osky.flights_data5".
+    pub fn table(mut self, table: Table, name: impl Into<String>) -> Self {
+        self.names.retain(|(t, _)| *t != table);
+        self.names.push((table, name.into()));
+        self
+    }
+
+    /// Whether nothing is overridden.
+    pub fn is_empty(&self) -> bool {
+        self.version.is_none() && self.names.is_empty()
+    }
+
+    /// Fully qualified name of `table`.
+    pub fn get(&self, table: Table) -> String {
+        match self.names.iter().find(|(t, _)| *t == table) {
+            Some((_, name)) => name.clone(),
+            None => format!(
+                "{}.{}.{}_{}",
+                CATALOG,
+                SCHEMA,
+                table.base_name(),
+                self.version.as_deref().unwrap_or(VERSION)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_names() {
+        assert_eq!(Table::StateVectors.default_name(), "minio.osky.state_vectors_data4");
+        assert_eq!(Table::Flights.partition_columns(), ["day"]);
+        assert_eq!("Operational_Status".parse::<Table>().unwrap(), Table::OperationalStatus);
+        assert!("aircraft".parse::<Table>().unwrap_err().to_string().contains("state_vectors, flights"));
+
+        let names = TableNames::default()
+            .version("data5")
+            .table(Table::Flights, "minio.osky.flights_v2");
+        assert_eq!(names.get(Table::StateVectors), "minio.osky.state_vectors_data5");
+        assert_eq!(names.get(Table::Flights), "minio.osky.flights_v2");
+        assert!(TableNames::default().is_empty() && !names.is_empty());
+    }
+}
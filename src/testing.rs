@@ -0,0 +1,369 @@
+//! Offline test support: a mock Trino server and fixture recording.
+//!
+//! Enabled with the `testing` feature. [`MockTrino`] serves recorded Trino
+//! protocol pages over an in-process HTTP server, so a real [`Trino`] client
+//! can run end-to-end without credentials or network access:
+//!
+//! ```rust,no_run
+//! use opensky::testing::{Fixture, MockTrino};
+//! use opensky::QueryParams;
+//!
+//! # async fn example() -> opensky::Result<()> {
+//! let fixture = Fixture::load("tests/fixtures/485a32.json")?;
+//! let mock = MockTrino::start(fixture).await?;
+//!
+//! let mut trino = mock.client().await?;
+//! let flights = trino.flightlist(QueryParams::new().icao24("485a32")).await?;
+//! assert_eq!(mock.statements().len(), 1);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Fixtures are captured from the real service with [`record_fixture`].
+
+use crate::config::Config;
+use crate::trino::Trino;
+use crate::types::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// Path the mock serves the OAuth token from.
+const MOCK_AUTH_PATH: &str = "/auth/token";
+
+/// The recorded Trino response pages of a single statement.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Fixture {
+    /// Raw JSON pages, in the order the server returned them.
+    pub pages: Vec<Value>,
+}
+
+impl Fixture {
+    /// Build a single-page fixture from column definitions and rows.
+    ///
+    /// Columns are `(name, trino_type)` pairs, e.g. `("time", "bigint")`.
+    pub fn from_rows(columns: &[(&str, &str)], rows: Vec<Vec<Value>>) -> Self {
+        let columns: Vec<Value> = columns
+            .iter()
+            .map(|(name, col_type)| json!({ "name": name, "type": col_type }))
+            .collect();
+
+        Self {
+            pages: vec![json!({
+                "id": "mock",
+                "columns": columns,
+                "data": rows,
+                "stats": { "state": "FINISHED", "progressPercentage": 100.0 },
+            })],
+        }
+    }
+
+    /// Build a fixture whose statement fails with the given Trino error.
+    pub fn error(message: &str, error_name: &str) -> Self {
+        Self {
+            pages: vec![json!({
+                "id": "mock",
+                "stats": { "state": "FAILED" },
+                "error": { "message": message, "errorName": error_name },
+            })],
+        }
+    }
+
+    /// Load a fixture from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Save the fixture as pretty-printed JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Execute `sql` against the real service and capture every response page.
+pub async fn record_fixture(trino: &mut Trino, sql: &str) -> Result<Fixture> {
+    let mut pages = Vec::new();
+    trino.run_query(sql, |_| {}, |page| pages.push(page.clone())).await?;
+    Ok(Fixture { pages })
+}
+
+#[derive(Default)]
+struct MockState {
+    fixtures: Vec<Fixture>,
+    statements: Mutex<Vec<String>>,
+    grants: Mutex<Vec<String>>,
+    deletes: Mutex<Vec<String>>,
+}
+
+impl MockState {
+    /// The fixture for the n-th statement; the last one repeats.
+    fn fixture(&self, statement: usize) -> Option<&Fixture> {
+        self.fixtures
+            .get(statement)
+            .or_else(|| self.fixtures.last())
+    }
+}
+
+/// In-process HTTP server speaking enough of the Trino protocol to replay fixtures.
+pub struct MockTrino {
+    addr: SocketAddr,
+    state: Arc<MockState>,
+    handle: JoinHandle<()>,
+}
+
+impl MockTrino {
+    /// Start a server that answers every statement with `fixture`.
+    pub async fn start(fixture: Fixture) -> Result<Self> {
+        Self::with_fixtures(vec![fixture]).await
+    }
+
+    /// Start a server answering the n-th submitted statement with the n-th
+    /// fixture. Once exhausted, the last fixture is repeated.
+    pub async fn with_fixtures(fixtures: Vec<Fixture>) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(MockState {
+            fixtures,
+            ..Default::default()
+        });
+
+        let server_state = state.clone();
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = server_state.clone();
+                tokio::spawn(async move {
+                    let _ = serve(stream, addr, &state).await;
+                });
+            }
+        });
+
+        Ok(Self { addr, state, handle })
+    }
+
+    /// URL of the mock OAuth token endpoint.
+    pub fn auth_url(&self) -> String {
+        format!("http://{}{}", self.addr, MOCK_AUTH_PATH)
+    }
+
+    /// URL of the mock statement endpoint.
+    pub fn trino_url(&self) -> String {
+        format!("http://{}/v1/statement", self.addr)
+    }
+
+    /// Create a [`Trino`] client with dummy credentials pointed at this server.
+    pub async fn client(&self) -> Result<Trino> {
+        let config = Config {
+            username: Some("mock".to_string()),
+            password: Some("mock".into()),
+            ..Default::default()
+        };
+        let mut trino = Trino::with_config(config).await?;
+        trino.set_endpoints(self.auth_url(), self.trino_url());
+        Ok(trino)
+    }
+
+    /// SQL statements submitted so far, in order.
+    pub fn statements(&self) -> Vec<String> {
+        self.state.statements.lock().unwrap().clone()
+    }
+
+    /// OAuth grant types of the token requests so far, in order.
+    pub fn grants(&self) -> Vec<String> {
+        self.state.grants.lock().unwrap().clone()
+    }
+
+    /// Paths of the DELETE requests so far, in order.
+    pub fn deletes(&self) -> Vec<String> {
+        self.state.deletes.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockTrino {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Handle a single HTTP/1.1 request and close the connection.
+async fn serve(stream: TcpStream, addr: SocketAddr, state: &MockState) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line == "\r\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+
+    let (status, payload) = route(&method, &path, body, addr, state);
+    let payload = payload.map(|v| v.to_string()).unwrap_or_default();
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{payload}",
+        payload.len()
+    );
+
+    let mut stream = reader.into_inner();
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Map a request onto a response status and JSON body.
+fn route(
+    method: &str,
+    path: &str,
+    body: Vec<u8>,
+    addr: SocketAddr,
+    state: &MockState,
+) -> (&'static str, Option<Value>) {
+    match (method, path) {
+        ("POST", MOCK_AUTH_PATH) => {
+            let body = String::from_utf8_lossy(&body);
+            let field = |name: &str| {
+                body.split('&')
+                    .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            let grant = field("grant_type");
+            state.grants.lock().unwrap().push(grant.clone());
+            if grant == "refresh_token" && field("refresh_token") != "mock-refresh" {
+                return ("400 Bad Request", Some(json!({ "error": "invalid_grant" })));
+            }
+            (
+                "200 OK",
+                Some(json!({
+                    "access_token": "mock-token",
+                    "expires_in": 3600,
+                    "refresh_token": "mock-refresh",
+                    "refresh_expires_in": 7200,
+                })),
+            )
+        }
+        ("POST", "/v1/statement") => {
+            let mut statements = state.statements.lock().unwrap();
+            statements.push(String::from_utf8_lossy(&body).into_owned());
+            let statement = statements.len() - 1;
+            match page(state, addr, statement, 0) {
+                Some(page) => ("200 OK", Some(page)),
+                None => ("404 Not Found", None),
+            }
+        }
+        ("GET", path) => {
+            // Pages are served from /v1/statement/mock/{statement}/{page}
+            let indices: Vec<usize> = path
+                .strip_prefix("/v1/statement/mock/")
+                .map(|rest| rest.split('/').filter_map(|p| p.parse().ok()).collect())
+                .unwrap_or_default();
+            match indices[..] {
+                [statement, index] => match page(state, addr, statement, index) {
+                    Some(page) => ("200 OK", Some(page)),
+                    None => ("404 Not Found", None),
+                },
+                _ => ("404 Not Found", None),
+            }
+        }
+        ("DELETE", path) => {
+            state.deletes.lock().unwrap().push(path.to_string());
+            ("204 No Content", None)
+        }
+        _ => ("404 Not Found", None),
+    }
+}
+
+/// Fetch a fixture page with its `nextUri` rewritten to point at the mock.
+fn page(state: &MockState, addr: SocketAddr, statement: usize, index: usize) -> Option<Value> {
+    let fixture = state.fixture(statement)?;
+    let mut page = fixture.pages.get(index)?.clone();
+    let object = page.as_object_mut()?;
+
+    if index + 1 < fixture.pages.len() {
+        object.insert(
+            "nextUri".to_string(),
+            Value::String(format!("http://{addr}/v1/statement/mock/{statement}/{}", index + 1)),
+        );
+    } else {
+        object.remove("nextUri");
+    }
+
+    Some(page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{OpenSkyError, QueryParams};
+
+    fn two_page_fixture() -> Fixture {
+        let columns = json!([
+            { "name": "icao24", "type": "varchar" },
+            { "name": "firstseen", "type": "bigint" },
+            { "name": "lastseen", "type": "bigint" },
+        ]);
+        Fixture {
+            pages: vec![
+                json!({ "id": "q1", "stats": { "state": "QUEUED" }, "nextUri": "https://example/1" }),
+                json!({ "id": "q1", "columns": columns, "data": [["485a32", 1, 2]], "stats": { "state": "RUNNING", "progressPercentage": 50.0 } }),
+                json!({ "id": "q1", "columns": columns, "data": [["485a33", 2, 3]], "stats": { "state": "FINISHED", "progressPercentage": 100.0 } }),
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_replays_pages() {
+        let mock = MockTrino::start(two_page_fixture()).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+
+        let mut states = Vec::new();
+        let data = trino
+            .flightlist_with_progress(QueryParams::new().icao24("485a32"), |s| states.push(s.state))
+            .await
+            .unwrap();
+
+        assert_eq!(data.len(), 2);
+        assert_eq!(states, vec!["QUEUED", "RUNNING", "FINISHED"]);
+
+        let statements = mock.statements();
+        assert_eq!(statements.len(), 1);
+        assert!(statements[0].contains("FROM minio.osky.flights_data4"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_error_fixture() {
+        let mock = MockTrino::start(Fixture::error("line 1:1: boom", "SYNTAX_ERROR"))
+            .await
+            .unwrap();
+        let mut trino = mock.client().await.unwrap();
+
+        let err = trino.execute_query("SELECT", &[]).await.unwrap_err();
+        match err {
+            OpenSkyError::Query(error) => {
+                assert_eq!(error.message, "line 1:1: boom");
+                assert_eq!(error.error_name.as_deref(), Some("SYNTAX_ERROR"));
+                assert_eq!(error.query_id.as_deref(), Some("mock"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -1,40 +1,140 @@
 //! Trino HTTP client for OpenSky database.
 
-use crate::cache;
+use crate::cache::{self, CachePolicy};
+use crate::cassette::Cassette;
 use crate::config::Config;
-use crate::query::{build_history_query, build_flightlist_query, build_rawdata_query};
-use crate::types::{FlightData, OpenSkyError, QueryParams, RawTable, Result, FLIGHT_COLUMNS, FLIGHTLIST_COLUMNS, RAWDATA_COLUMNS};
+use crate::query::{build_history_page_query, adsb_columns, adsb_table_columns, build_adsb_query, history_columns, hour_partitions, parse_bound, rawdata_columns, time_buffer_seconds, build_aggregate_query, build_airport_stats_query, build_distinct_icao24_query, build_history_query, build_flightlist_query, build_rawdata_query, build_windows_history_query};
+use crate::sink::ResultSink;
+use crate::tables::{self, Table};
+use crate::schema::{self, ColumnType, SchemaCheck, AIRCRAFT_COLUMNS, AIRPORT_STATS_COLUMNS, FLIGHT_COLUMNS, FLIGHTLIST_COLUMNS, RAWDATA_COLUMNS};
+use crate::types::{Aggregation, CacheStatus, FlightData, FlightList, OpenSkyError, Order, QueryError, QueryParams, RawTable, Result, StatsInterval};
 
 use polars::prelude::*;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
+use secrecy::zeroize::Zeroizing;
+use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::path::PathBuf;
 use std::time::Duration;
+use tokio::sync::mpsc;
 
 /// OpenSky authentication endpoint.
-const AUTH_URL: &str = "https://auth.opensky-network.org/auth/realms/opensky-network/protocol/openid-connect/token";
+pub(crate) const AUTH_URL: &str = "https://auth.opensky-network.org/auth/realms/opensky-network/protocol/openid-connect/token";
 
 /// Trino query endpoint.
 const TRINO_URL: &str = "https://trino.opensky-network.org/v1/statement";
 
+/// How long the answer of [`Trino::latest_available_time`] is reused.
+const AVAILABILITY_TTL: Duration = Duration::from_secs(600);
+
+/// Aircraft time windows per query in [`Trino::history_for_flights`].
+const WINDOWS_PER_QUERY: usize = 50;
+
+/// Airport-join history queries over a longer time range run as one query
+/// per UTC day.
+const AIRPORT_JOIN_MAX_SPAN: chrono::Duration = chrono::Duration::days(2);
+
+/// How many times a history query running out of cluster memory or time is
+/// split in halves before giving up, i.e. into at most 8 parts.
+const NARROW_DEPTH: u32 = 3;
+
+/// Shortest time range split further after a resource error.
+const NARROW_MIN_SPAN: chrono::Duration = chrono::Duration::hours(1);
+
+/// How long Trino keeps a query that is no longer polled before abandoning
+/// it (its default `query.client.timeout`).
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(300);
+
 /// Trino client for OpenSky database queries.
 pub struct Trino {
     client: Client,
     config: Config,
     token: Option<TokenInfo>,
     source: String,
+    client_tags: Vec<String>,
+    /// Token endpoints in the order to try them; the last one to work first
+    auth_urls: Vec<String>,
+    trino_url: String,
+    cassette: Option<Cassette>,
+    /// Latest available hour partition and when it was looked up
+    latest_available: Option<(std::time::Instant, i64)>,
+    max_rows: Option<usize>,
+    schema_check: SchemaCheck,
+    cache_policy: CachePolicy,
+    /// Statements not read to the end, by SQL hash: query id and last poll
+    in_flight: HashMap<u64, (String, std::time::Instant)>,
+    last_report: Option<QueryReport>,
+    /// Next page of the statement being polled, shared with cancellers
+    pending: Arc<Mutex<Option<PendingPage>>>,
+}
+
+/// Handle to cancel the statement a [`Trino`] client is polling, from
+/// another task, e.g. on Ctrl-C. See [`Trino::canceller`].
+#[derive(Debug, Clone)]
+pub struct QueryCanceller {
+    client: Client,
+    pending: Arc<Mutex<Option<PendingPage>>>,
+}
+
+/// The `nextUri` of a running statement and the headers to reach it.
+#[derive(Debug)]
+struct PendingPage {
+    next_uri: String,
+    authorization: reqwest::header::HeaderValue,
+    user: String,
+}
+
+impl QueryCanceller {
+    /// Cancel the running statement, if any, with a DELETE on its
+    /// `nextUri`. Returns whether there was one to cancel.
+    pub async fn cancel(&self) -> Result<bool> {
+        let Some(page) = self.pending.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return Ok(false);
+        };
+        tracing::debug!("Cancelling {}", page.next_uri);
+        let response = self
+            .client
+            .delete(&page.next_uri)
+            .header("Authorization", page.authorization)
+            .header("X-Trino-User", page.user)
+            .send()
+            .await?;
+        let status = response.status().as_u16();
+        if (200..300).contains(&status) {
+            Ok(true)
+        } else {
+            Err(OpenSkyError::Query(Box::new(QueryError::new(format!(
+                "Failed to cancel query: HTTP {}",
+                status
+            )))))
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 struct TokenInfo {
-    access_token: String,
+    access_token: SecretString,
     expires_at: chrono::DateTime<chrono::Utc>,
+    refresh_token: Option<SecretString>,
+    /// `None` when the refresh token does not expire
+    refresh_expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Set with [`Trino::set_token`] rather than requested by the client
+    external: bool,
 }
 
 /// OAuth token response.
 #[derive(Debug, Deserialize)]
 struct TokenResponse {
-    access_token: String,
+    access_token: SecretString,
     expires_in: u64,
+    refresh_token: Option<SecretString>,
+    /// Seconds, 0 for refresh tokens that do not expire
+    refresh_expires_in: Option<u64>,
 }
 
 /// Trino query response.
@@ -48,15 +148,35 @@ struct TrinoResponse {
     columns: Option<Vec<TrinoColumn>>,
     data: Option<Vec<Vec<serde_json::Value>>>,
     stats: Option<TrinoStats>,
-    error: Option<TrinoError>,
+    error: Option<QueryError>,
+    #[serde(default)]
+    warnings: Vec<TrinoWarning>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Name and Trino type of a result column, e.g. `("time", "bigint")`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
-struct TrinoColumn {
-    name: String,
+pub struct TrinoColumn {
+    pub name: String,
     #[serde(rename = "type")]
-    col_type: String,
+    pub col_type: String,
+}
+
+/// A warning Trino attached to a query, e.g. for deprecated syntax.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TrinoWarning {
+    /// Name of the warning code, e.g. `DEPRECATED_FUNCTION`
+    #[serde(rename(deserialize = "warningCode"), deserialize_with = "warning_code_name")]
+    pub code: String,
+    pub message: String,
+}
+
+fn warning_code_name<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<String, D::Error> {
+    #[derive(Deserialize)]
+    struct WarningCode {
+        name: String,
+    }
+    Ok(WarningCode::deserialize(deserializer)?.name)
 }
 
 #[derive(Debug, Deserialize)]
@@ -64,14 +184,8 @@ struct TrinoColumn {
 struct TrinoStats {
     state: String,
     progress_percentage: Option<f64>,
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct TrinoError {
-    message: String,
-    #[allow(dead_code)]
-    error_name: Option<String>,
+    processed_rows: Option<u64>,
+    processed_bytes: Option<u64>,
 }
 
 /// Query execution status.
@@ -81,8 +195,367 @@ pub struct QueryStatus {
     pub state: String,
     pub progress: f64,
     pub row_count: usize,
+    /// Seconds since the query was submitted
+    pub elapsed: f64,
+    /// Rows scanned by the cluster per second
+    pub rows_per_sec: Option<f64>,
+    /// Bytes scanned by the cluster per second
+    pub bytes_per_sec: Option<f64>,
+    /// Estimated seconds until the query completes, from the progress so far
+    pub eta: Option<f64>,
+    /// Cache entry the rows were read from, in `CACHED` updates
+    pub cache_file: Option<PathBuf>,
+    /// Distinct `icao24` values among the rows received so far, for results
+    /// with that column
+    pub aircraft: Option<usize>,
+    /// Earliest `time` among the rows received so far (Unix seconds)
+    pub first_time: Option<i64>,
+    /// Latest `time` among the rows received so far (Unix seconds)
+    pub last_time: Option<i64>,
+}
+
+/// Outcome of the last statement run by a client, see [`Trino::last_report`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QueryReport {
+    /// `None` for results read from the cache
+    pub query_id: Option<String>,
+    /// Seconds from submission to the last response page
+    pub wall_time: f64,
+    pub rows: usize,
+    /// Bytes scanned by the cluster, when reported
+    pub bytes: Option<u64>,
+    /// Whether the result came from the local cache; for a history query
+    /// split in chunks, whether all, some or none of them did
+    pub cache: CacheStatus,
+    /// Cache entries read, or written for fresh results
+    pub cache_files: Vec<PathBuf>,
+    /// Hash of the SQL statement, as in its `sql-<hash>` client tag
+    pub sql_hash: String,
+    /// The SQL that ran, or for cached results the SQL stored with the
+    /// entry; statements of split queries are separated by `;`
+    pub sql: String,
+    /// Whether the rows were cut at [`Trino::set_max_rows`]
+    pub truncated: bool,
+    /// Warnings Trino raised for the query, each logged as it arrived
+    pub warnings: Vec<TrinoWarning>,
+}
+
+/// Expected cost of a history query, see [`Trino::estimate`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QueryEstimate {
+    /// Hour partitions of `state_vectors_data4` in the time range
+    pub hours: Option<u64>,
+    /// Queries the range is split into by `[query] max_span` and daily
+    /// airport joins
+    pub queries: usize,
+    /// Rows read from the tables, from the cluster's statistics
+    pub rows: Option<u64>,
+    /// Bytes read from the tables, from the cluster's statistics
+    pub bytes: Option<u64>,
+}
+
+impl std::fmt::Display for QueryEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.hours {
+            Some(hours) => write!(f, "{} hour partitions", hours)?,
+            None => write!(f, "all hour partitions")?,
+        }
+        write!(f, " in {} {}", self.queries, if self.queries == 1 { "query" } else { "queries" })?;
+        if let Some(rows) = self.rows {
+            write!(f, ", ~{} rows", rows)?;
+        }
+        if let Some(bytes) = self.bytes {
+            write!(f, ", ~{} scanned", cache::format_size(bytes))?;
+        }
+        Ok(())
+    }
+}
+
+/// Rows of a history query run in chunks, and the chunks that failed.
+///
+/// Returned by [`Trino::history_chunked`], which keeps going when a chunk
+/// fails instead of dropping the rows of the others. Chunks are cached as
+/// they complete, so [`ChunkedResult::retry_failed`] only queries the
+/// failed ones.
+#[derive(Debug)]
+pub struct ChunkedResult {
+    pub data: FlightData,
+    /// Start and stop of each failed chunk, with its error
+    pub failed: Vec<((String, String), OpenSkyError)>,
+    /// The parameters of the failed chunks, in the order of `failed`
+    failed_parts: Vec<QueryParams>,
+    params: QueryParams,
+    by_day: bool,
+    cached: bool,
+}
+
+impl ChunkedResult {
+    fn new(params: QueryParams, by_day: bool, cached: bool) -> Self {
+        ChunkedResult {
+            data: FlightData::new(DataFrame::default()),
+            failed: Vec::new(),
+            failed_parts: Vec::new(),
+            params,
+            by_day,
+            cached,
+        }
+    }
+
+    /// Whether every chunk succeeded.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Query the failed chunks again, adding their rows to `data`.
+    ///
+    /// Chunks that fail again stay in `failed`.
+    pub async fn retry_failed(&mut self, trino: &mut Trino) -> Result<()> {
+        let failed = std::mem::take(&mut self.failed);
+        let parts = std::mem::take(&mut self.failed_parts);
+        if let Err(e) = self.run(trino, parts.clone(), false, &mut |_| {}).await {
+            self.failed = failed;
+            self.failed_parts = parts;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Run `parts` one after the other and merge their rows into `data`.
+    ///
+    /// With `fail_fast`, or for errors no retry will fix, the first failure
+    /// is returned and `data` is left as it was.
+    async fn run<F>(&mut self, trino: &mut Trino, parts: Vec<QueryParams>, fail_fast: bool, progress_callback: &mut F) -> Result<()>
+    where
+        F: FnMut(QueryStatus),
+    {
+        let mut truncated = self.data.is_truncated();
+        let mut mismatch = self.data.schema_mismatch().to_vec();
+        let mut cache = (self.data.dataframe().width() > 0).then(|| self.data.cache_status());
+        let mut cache_files = self.data.cache_files().to_vec();
+        let mut remaining = self.params.limit.map(|limit| limit.saturating_sub(self.data.len() as u32));
+        // `set_max_rows` caps the whole result, not each chunk
+        let max_rows = trino.max_rows;
+        let mut budget = max_rows.map(|max| max.saturating_sub(self.data.len()));
+        let mut frames = Vec::new();
+        let mut statements = Vec::new();
+        for mut part in parts {
+            if remaining == Some(0) || truncated {
+                break;
+            }
+            part.limit = remaining;
+            trino.max_rows = budget;
+            let result = trino.history_part(part.clone(), self.cached, progress_callback).await;
+            trino.max_rows = max_rows;
+            let result = match result {
+                // Days are disjoint in time, so duplicates only come from one
+                // state vector falling within two flights of the aircraft,
+                // e.g. one of them joined from the day before. Dropping them
+                // per day, before counting rows against the limit, is enough.
+                Ok(data) if self.by_day => dedup_states(data),
+                result => result,
+            };
+            match result {
+                Ok(data) => {
+                    statements.extend(trino.last_report.as_ref().map(|report| report.sql.clone()));
+                    remaining = remaining.map(|r| r.saturating_sub(data.len() as u32));
+                    budget = budget.map(|b| b.saturating_sub(data.len()));
+                    truncated = data.is_truncated();
+                    cache = Some(cache.map_or(data.cache_status(), |c| c.merge(data.cache_status())));
+                    cache_files.extend_from_slice(data.cache_files());
+                    for m in data.schema_mismatch() {
+                        if !mismatch.contains(m) {
+                            mismatch.push(m.clone());
+                        }
+                    }
+                    frames.push(data.into_dataframe());
+                }
+                Err(e) if fail_fast || !is_chunk_retryable(&e) => return Err(e),
+                Err(e) => {
+                    let range = (part.start.clone().unwrap_or_default(), part.stop.clone().unwrap_or_default());
+                    tracing::warn!("Chunk {} to {} failed: {}", range.0, range.1, e);
+                    self.failed.push((range, e));
+                    self.failed_parts.push(part);
+                }
+            }
+        }
+
+        // Rows of a retry are appended after later chunks, and need sorting
+        let retried = self.data.dataframe().width() > 0;
+        let mut df: Option<DataFrame> = retried.then(|| self.data.dataframe().clone());
+        for frame in frames {
+            match df.as_mut() {
+                Some(df) => {
+                    df.vstack_mut(&frame)
+                        .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+                }
+                None => df = Some(frame),
+            }
+        }
+        let mut df = df.unwrap_or_default();
+        // Cached chunks are served whole, whatever the budget left
+        if let Some(max) = max_rows.filter(|&max| df.height() > max) {
+            df = df.head(Some(max));
+            truncated = true;
+        }
+        let sort_by: &[&str] = match self.params.order {
+            Order::IcaoThenTime => &["icao24", "time"],
+            Order::Time if retried => &["time"],
+            _ => &[],
+        };
+        if !sort_by.is_empty() && df.height() > 0 {
+            df = df
+                .sort(sort_by.iter().copied(), SortMultipleOptions::default().with_maintain_order(true))
+                .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+        }
+        // The report of the last chunk speaks for all of them
+        let cache = cache.unwrap_or_default();
+        if let Some(report) = trino.last_report.as_mut() {
+            report.cache = cache;
+            report.cache_files = cache_files.clone();
+            if !statements.is_empty() {
+                report.sql = statements.join(";\n");
+            }
+        }
+        self.data = FlightData::new(df)
+            .with_truncated(truncated)
+            .with_schema_mismatch(mismatch)
+            .with_cache(cache, cache_files);
+        Ok(())
+    }
+}
+
+/// Whether `error` is the cluster running out of memory or time.
+fn is_resource_error(error: &OpenSkyError) -> bool {
+    match error {
+        OpenSkyError::Query(error) => error.is_resource_exhausted(),
+        OpenSkyError::InQuery { source, .. } => is_resource_error(source),
+        _ => false,
+    }
+}
+
+/// The two halves of the time range of `params`; `None` for ranges too
+/// short to split, and for offset queries, whose rows cannot be split.
+fn halve(params: &QueryParams) -> Option<Vec<QueryParams>> {
+    let span = params.time_span()?;
+    if span < NARROW_MIN_SPAN || params.offset.is_some() {
+        return None;
+    }
+    // Bounds are inclusive: a span of n seconds holds n + 1 of them
+    let half = chrono::Duration::seconds((span.num_seconds() + 2) / 2);
+    Some(params.split_time_range(half))
+}
+
+/// Sum of the `field` estimates of the tables read in an `EXPLAIN (TYPE IO,
+/// FORMAT JSON)` plan; `None` when a table has no statistics ("NaN").
+fn io_estimate(plan: &serde_json::Value, field: &str) -> Option<u64> {
+    let tables = plan.get("inputTableColumnInfos")?.as_array()?;
+    let mut total = 0.0;
+    for table in tables {
+        let value = table.get("estimate")?.get(field)?;
+        let value = value.as_f64().or_else(|| value.as_str()?.parse().ok())?;
+        if !value.is_finite() {
+            return None;
+        }
+        total += value;
+    }
+    (!tables.is_empty()).then_some(total as u64)
+}
+
+/// Whether a chunk failing with `error` may succeed when run again; errors
+/// in the query itself or the credentials stop a chunked run.
+fn is_chunk_retryable(error: &OpenSkyError) -> bool {
+    match error {
+        OpenSkyError::Config(_)
+        | OpenSkyError::Auth(_)
+        | OpenSkyError::InvalidParam(_)
+        | OpenSkyError::Cancelled
+        | OpenSkyError::CacheMiss { .. } => false,
+        OpenSkyError::HttpStatus { status, .. } => !matches!(status, 401 | 403),
+        OpenSkyError::Query(error) => !error.is_user_error(),
+        OpenSkyError::InQuery { source, .. } => is_chunk_retryable(source),
+        _ => true,
+    }
+}
+
+impl QueryStatus {
+    /// Build a status update from the stats block of a Trino response.
+    fn from_stats(
+        query_id: Option<String>,
+        stats: Option<&TrinoStats>,
+        row_count: usize,
+        started: std::time::Instant,
+    ) -> Self {
+        let elapsed = started.elapsed().as_secs_f64();
+        let progress = stats.and_then(|s| s.progress_percentage).unwrap_or(0.0);
+        let rate = |count: Option<u64>| count.filter(|_| elapsed > 0.0).map(|c| c as f64 / elapsed);
+        let eta = (progress > 0.0 && progress < 100.0).then(|| elapsed * (100.0 - progress) / progress);
+        Self {
+            query_id,
+            state: stats
+                .map(|s| s.state.clone())
+                .unwrap_or_else(|| "RUNNING".to_string()),
+            progress,
+            row_count,
+            elapsed,
+            rows_per_sec: rate(stats.and_then(|s| s.processed_rows)),
+            bytes_per_sec: rate(stats.and_then(|s| s.processed_bytes)),
+            eta,
+            cache_file: None,
+            aircraft: None,
+            first_time: None,
+            last_time: None,
+        }
+    }
+
+    /// Add the running aggregates of the rows received so far.
+    fn with_running(mut self, running: &RunningStats) -> Self {
+        self.aircraft = running.icao24.map(|_| running.aircraft.len());
+        self.first_time = running.first_time;
+        self.last_time = running.last_time;
+        self
+    }
+}
+
+/// Aggregates of the rows of a statement received so far, for progress
+/// updates: distinct aircraft and the time span covered.
+#[derive(Debug, Default)]
+struct RunningStats {
+    /// Index of the `icao24` column, once the columns are known
+    icao24: Option<usize>,
+    /// Index of the `time` column
+    time: Option<usize>,
+    aircraft: HashSet<String>,
+    first_time: Option<i64>,
+    last_time: Option<i64>,
+}
+
+impl RunningStats {
+    /// Take the column positions from `columns`, if not known yet.
+    fn set_columns(&mut self, columns: &[TrinoColumn]) {
+        if self.icao24.is_none() && self.time.is_none() {
+            self.icao24 = columns.iter().position(|c| c.name == "icao24");
+            self.time = columns.iter().position(|c| c.name == "time");
+        }
+    }
+
+    fn add(&mut self, rows: &[Vec<serde_json::Value>]) {
+        for row in rows {
+            if let Some(icao24) = self.icao24.and_then(|i| row.get(i)?.as_str()) {
+                if !self.aircraft.contains(icao24) {
+                    self.aircraft.insert(icao24.to_string());
+                }
+            }
+            if let Some(time) = self.time.and_then(|i| row.get(i)?.as_i64()) {
+                self.first_time = Some(self.first_time.map_or(time, |t| t.min(time)));
+                self.last_time = Some(self.last_time.map_or(time, |t| t.max(time)));
+            }
+        }
+    }
 }
 
+/// Receiving end of a progress channel, see [`Trino::history_with_progress_stream`].
+pub type ProgressReceiver = mpsc::UnboundedReceiver<QueryStatus>;
+
 impl Trino {
     /// Create a new Trino client, loading config from the default location.
     pub async fn new() -> Result<Self> {
@@ -92,6 +565,9 @@ impl Trino {
 
     /// Create a new Trino client with the given config.
     pub async fn with_config(config: Config) -> Result<Self> {
+        // Reject invalid cache settings up front rather than on the first save
+        config.cache_compression()?;
+
         let client = Client::builder()
             .timeout(Duration::from_secs(300))
             .user_agent("opensky-rs/0.2.0")
@@ -99,19 +575,162 @@ impl Trino {
 
         Ok(Self {
             client,
-            config,
             token: None,
-            source: "opensky-rs".to_string(),
+            source: config.source.clone().unwrap_or_else(|| "opensky-rs".to_string()),
+            client_tags: config.client_tags.clone(),
+            auth_urls: config.auth_urls(),
+            trino_url: config.trino_url.clone().unwrap_or_else(|| TRINO_URL.to_string()),
+            cassette: Cassette::from_env()?,
+            latest_available: None,
+            max_rows: None,
+            schema_check: SchemaCheck::default(),
+            cache_policy: match (config.cache_enabled, config.cache_read_only) {
+                (Some(false), _) => CachePolicy::Off,
+                (_, Some(true)) => CachePolicy::ReadOnly,
+                _ => CachePolicy::default(),
+            },
+            in_flight: HashMap::new(),
+            last_report: None,
+            pending: Arc::new(Mutex::new(None)),
+            config,
         })
     }
 
+    /// Point the client at different auth and statement endpoints.
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn set_endpoints(&mut self, auth_url: impl Into<String>, trino_url: impl Into<String>) {
+        self.auth_urls = vec![auth_url.into()];
+        self.trino_url = trino_url.into();
+    }
+
+    /// Directory of cached results: the configured one, or the platform
+    /// cache directory.
+    pub fn cache_dir(&self) -> Option<std::path::PathBuf> {
+        self.config.cache_dir.clone().or_else(cache::cache_dir)
+    }
+
+    /// Name of the cache file of a history query with `params`, as
+    /// [`cache::cache_key`] after the `[query]` defaults and `[tables]`
+    /// names of this client. Queries split into chunks are cached per chunk.
+    pub fn cache_key(&self, params: &QueryParams) -> String {
+        cache::cache_key(&self.config.apply_query_defaults(params.clone()))
+    }
+
+    /// Record or replay all HTTP exchanges through a cassette.
+    ///
+    /// Overrides a cassette configured through `OPENSKY_CASSETTE`.
+    pub fn set_cassette(&mut self, cassette: Cassette) {
+        self.cassette = Some(cassette);
+    }
+
+    /// Send a request, going through the cassette if one is set.
+    ///
+    /// `body` identifies the request in the cassette; pass `None` for
+    /// requests carrying credentials so they are never written to disk.
+    async fn send(
+        &mut self,
+        request: RequestBuilder,
+        method: &str,
+        url: &str,
+        body: Option<&str>,
+    ) -> Result<(u16, String)> {
+        if let Some(cassette) = self.cassette.as_mut().filter(|c| c.is_replay()) {
+            return cassette.replay(method, url, body);
+        }
+
+        let response = request.send().await?;
+        let status = response.status().as_u16();
+        let text = response.text().await?;
+
+        if let Some(cassette) = self.cassette.as_mut() {
+            cassette.record(method, url, body, status, &text)?;
+        }
+
+        Ok((status, text))
+    }
+
     /// Set the source identifier shown in Trino UI.
     pub fn set_source(&mut self, source: impl Into<String>) {
         self.source = source.into();
     }
 
+    /// Set the client tags sent with every query, so cluster usage can be
+    /// attributed to a project or team. Tags must not contain commas.
+    pub fn set_client_tags<I, S>(&mut self, tags: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.client_tags = tags.into_iter().map(Into::into).collect();
+    }
+
+    /// Cap the rows of every result of this client, independently of the
+    /// SQL `LIMIT`.
+    ///
+    /// Once a query has returned more than `max_rows` rows, polling stops,
+    /// the server query is cancelled and the result is flagged with
+    /// [`FlightData::is_truncated`]. Truncated results are not cached.
+    ///
+    /// Queries run in several statements (split time ranges, batches of
+    /// flights) share the cap: it applies to their combined rows.
+    pub fn set_max_rows(&mut self, max_rows: Option<usize>) {
+        self.max_rows = max_rows;
+    }
+
+    /// Choose how results whose columns differ from the expected schema
+    /// are handled: recorded in [`FlightData::schema_mismatch`] and logged
+    /// (the default), or failed.
+    pub fn set_schema_check(&mut self, check: SchemaCheck) {
+        self.schema_check = check;
+    }
+
+    /// Choose whether history results are served from and written to the
+    /// cache. [`CachePolicy::Off`] keeps every result off disk, e.g. for
+    /// data that must not be stored, and [`CachePolicy::ReadOnly`] never
+    /// queries Trino for history, e.g. to re-run an analysis on exactly the
+    /// same data; the default comes from `[cache] enabled` and `read_only`.
+    pub fn set_cache_policy(&mut self, policy: CachePolicy) {
+        self.cache_policy = policy;
+    }
+
+    /// The current cache policy.
+    pub fn cache_policy(&self) -> CachePolicy {
+        self.cache_policy
+    }
+
+    /// Use a bearer token obtained elsewhere, e.g. from an identity broker,
+    /// instead of signing in with the configured credentials.
+    ///
+    /// ```rust,no_run
+    /// # async fn example(token: String) -> opensky::Result<()> {
+    /// use opensky::{Config, Trino};
+    ///
+    /// let expires_at = chrono::Utc::now() + chrono::Duration::minutes(30);
+    /// let trino = Trino::with_config(Config::default()).await?.with_token(token, expires_at);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_token(mut self, token: impl Into<String>, expires_at: chrono::DateTime<chrono::Utc>) -> Self {
+        self.set_token(token, expires_at);
+        self
+    }
+
+    /// Replace the bearer token, see [`Trino::with_token`].
+    ///
+    /// Once it expires, queries fail with [`OpenSkyError::Auth`] until a
+    /// new token is set; the client does not sign in by itself.
+    pub fn set_token(&mut self, token: impl Into<String>, expires_at: chrono::DateTime<chrono::Utc>) {
+        self.token = Some(TokenInfo {
+            access_token: SecretString::from(token.into()),
+            expires_at,
+            refresh_token: None,
+            refresh_expires_at: None,
+            external: true,
+        });
+    }
+
     /// Get or refresh the authentication token.
-    async fn get_token(&mut self) -> Result<String> {
+    async fn get_token(&mut self) -> Result<SecretString> {
         // Check if we have a valid token
         if let Some(ref token) = self.token {
             let now = chrono::Utc::now();
@@ -119,11 +738,45 @@ impl Trino {
             if token.expires_at > now + chrono::Duration::minutes(1) {
                 return Ok(token.access_token.clone());
             }
+            if token.external {
+                return Err(OpenSkyError::Auth(format!(
+                    "The provided token expired at {}, set a new one with set_token",
+                    token.expires_at.format("%Y-%m-%d %H:%M:%S")
+                )));
+            }
+        }
+
+        // Renew the session with the refresh token while it lasts, rather
+        // than sending the password again
+        let refresh_token = self.token.as_ref().and_then(|token| {
+            let usable = token
+                .refresh_expires_at
+                .map_or(true, |at| at > chrono::Utc::now() + chrono::Duration::minutes(1));
+            token.refresh_token.clone().filter(|_| usable)
+        });
+        if let Some(refresh_token) = refresh_token {
+            let grant = [("grant_type", "refresh_token"), ("refresh_token", refresh_token.expose_secret())];
+            match self.request_token(&grant).await {
+                Ok(token) => return Ok(token),
+                Err(e) => tracing::debug!("Token refresh failed, signing in again: {}", e),
+            }
         }
 
-        // Request new token with retry
-        let username = self.config.require_username()?;
-        let password = self.config.require_password()?;
+        let username = self.config.require_username()?.to_string();
+        let password = Zeroizing::new(self.config.require_password()?.to_string());
+        let grant = [
+            ("grant_type", "password"),
+            ("username", username.as_str()),
+            ("password", password.as_str()),
+        ];
+        self.request_token(&grant).await
+    }
+
+    /// Request a token with the given grant, with retry, failing over to the
+    /// next endpoint when one is unreachable or broken.
+    async fn request_token(&mut self, grant: &[(&str, &str)]) -> Result<SecretString> {
+        let mut form = vec![("client_id", "trino-client")];
+        form.extend_from_slice(grant);
 
         let mut last_error = None;
         for attempt in 1..=3 {
@@ -132,47 +785,187 @@ impl Trino {
                 tokio::time::sleep(Duration::from_millis(500 * attempt as u64)).await;
             }
 
-            let result = self
-                .client
-                .post(AUTH_URL)
-                .form(&[
-                    ("client_id", "trino-client"),
-                    ("grant_type", "password"),
-                    ("username", username),
-                    ("password", password),
-                ])
-                .send()
-                .await;
+            for i in 0..self.auth_urls.len() {
+                let auth_url = self.auth_urls[i].clone();
+                let request = self.client.post(&auth_url).form(&form);
 
-            match result {
-                Ok(response) => {
-                    if response.status() == 401 || response.status() == 400 {
+                let body = match self.send(request, "POST", &auth_url, None).await {
+                    Ok((401 | 400, _)) => {
                         return Err(OpenSkyError::Auth(
                             "Authentication failed. Check your username and password.".into(),
                         ));
                     }
+                    Ok((status, body)) => match check_status(status, &auth_url) {
+                        Ok(()) => body,
+                        Err(e) => {
+                            tracing::warn!("Token endpoint failed: {}", e);
+                            last_error = Some(e);
+                            continue;
+                        }
+                    },
+                    Err(OpenSkyError::Http(e)) => {
+                        tracing::warn!("Token endpoint {} unreachable: {}", auth_url, e);
+                        last_error = Some(e.into());
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+
+                let token_response: TokenResponse = serde_json::from_str(&body)?;
+                let now = chrono::Utc::now();
+
+                self.token = Some(TokenInfo {
+                    access_token: token_response.access_token.clone(),
+                    expires_at: now + chrono::Duration::seconds(token_response.expires_in as i64),
+                    refresh_token: token_response.refresh_token,
+                    refresh_expires_at: token_response
+                        .refresh_expires_in
+                        .filter(|&secs| secs > 0)
+                        .map(|secs| now + chrono::Duration::seconds(secs as i64)),
+                    external: false,
+                });
+                // Start with the working endpoint next time
+                if i > 0 {
+                    let url = self.auth_urls.remove(i);
+                    self.auth_urls.insert(0, url);
+                }
 
-                    response.error_for_status_ref()?;
+                return Ok(token_response.access_token);
+            }
+        }
 
-                    let token_response: TokenResponse = response.json().await?;
-                    let expires_at = chrono::Utc::now() + chrono::Duration::seconds(token_response.expires_in as i64);
+        // All retries failed
+        Err(last_error.unwrap_or_else(|| OpenSkyError::Config("No token endpoint configured".into())))
+    }
 
-                    self.token = Some(TokenInfo {
-                        access_token: token_response.access_token.clone(),
-                        expires_at,
-                    });
+    /// Apply the configured query defaults and validate the parameters,
+    /// including the `[query] max_span` limit.
+    fn prepare(&self, params: QueryParams) -> Result<QueryParams> {
+        let params = self.config.apply_query_defaults(params);
+        params.validate()?;
+        self.config.split_query(&params, false)?;
+        log_warnings(&params);
+        Ok(params)
+    }
 
-                    return Ok(token_response.access_token);
-                }
-                Err(e) => {
-                    last_error = Some(e);
-                    // Continue to retry
-                }
+    /// Start of the most recent hour of data in the state vectors table.
+    ///
+    /// OpenSky ingests data with a delay, so this is usually some hours
+    /// behind the current time. The answer is reused for ten minutes.
+    pub async fn latest_available_time(&mut self) -> Result<chrono::DateTime<chrono::Utc>> {
+        let hour = match self.latest_available {
+            Some((checked, hour)) if checked.elapsed() < AVAILABILITY_TTL => hour,
+            _ => {
+                // Only the partitions of the last week are scanned
+                let since = (chrono::Utc::now().timestamp() / 3600 - 24 * 7) * 3600;
+                let state_vectors = self.config.tables.get(Table::StateVectors);
+                let sql = format!("SELECT max(hour) FROM {} WHERE hour >= {}", state_vectors, since);
+                let (_, rows, _) = self.run_query(&sql, |_| {}, |_| {}).await?;
+                let hour = rows
+                    .first()
+                    .and_then(|row| row.first())
+                    .and_then(|v| v.as_i64().or_else(|| v.as_str()?.parse().ok()))
+                    .ok_or_else(|| OpenSkyError::DataConversion("No state vectors in the last week".to_string()))?;
+                self.latest_available = Some((std::time::Instant::now(), hour));
+                hour
             }
+        };
+        chrono::DateTime::from_timestamp(hour, 0)
+            .ok_or_else(|| OpenSkyError::DataConversion(format!("Invalid hour {}", hour)))
+    }
+
+    /// Check a history query against the data ingested so far.
+    ///
+    /// Only ranges ending within the last day are checked. A range starting
+    /// after the available data is rejected, as it cannot return anything;
+    /// one ending after it gets a warning. Failing to look up the latest
+    /// hour does not stop the query.
+    async fn check_available(&mut self, params: &QueryParams) -> Result<()> {
+        let Some(stop) = params.stop.as_deref() else {
+            return Ok(());
+        };
+        let stop = parse_bound(stop, true)?.and_utc();
+        if stop < chrono::Utc::now() - chrono::Duration::days(1) {
+            return Ok(());
         }
+        let available = match self.latest_available_time().await {
+            // The latest partition holds up to an hour of data
+            Ok(latest) => latest + chrono::Duration::hours(1),
+            Err(e) => {
+                tracing::debug!("Could not check data availability: {}", e);
+                return Ok(());
+            }
+        };
 
-        // All retries failed
-        Err(last_error.unwrap().into())
+        let format = |time: chrono::DateTime<chrono::Utc>| time.format("%Y-%m-%d %H:%M:%S").to_string();
+        let start = params.start.as_deref().map(|start| parse_bound(start, false)).transpose()?.map(|start| start.and_utc());
+        if start.is_some_and(|start| start >= available) {
+            return Err(OpenSkyError::InvalidParam(format!(
+                "No data available yet for the requested range; data is available up to {} UTC",
+                format(available)
+            )));
+        }
+        if stop > available {
+            tracing::warn!(
+                "Data is only available up to {} UTC; later results will be missing",
+                format(available)
+            );
+        }
+        Ok(())
+    }
+
+    /// Check the configured credentials with a token request and `SELECT 1`.
+    ///
+    /// On failure the error is an [`OpenSkyError::Verify`] naming the step
+    /// that failed: "Configuration", "Token request" or "Test query".
+    pub async fn verify_credentials(&mut self) -> Result<()> {
+        let step = |step: &'static str| move |e: OpenSkyError| OpenSkyError::Verify { step, source: Box::new(e) };
+
+        self.config.require_username().map_err(step("Configuration"))?;
+        self.config.require_password().map_err(step("Configuration"))?;
+
+        self.token = None;
+        self.get_token().await.map_err(step("Token request"))?;
+
+        self.run_query("SELECT 1", |_| {}, |_| {})
+            .await
+            .map_err(step("Test query"))?;
+        Ok(())
+    }
+
+    /// Estimate the cost of a history query without running it.
+    ///
+    /// Counts the hour partitions the time range covers and asks the
+    /// cluster for the rows and bytes it would read, with
+    /// `EXPLAIN (TYPE IO)`. Without table statistics, or when the cluster
+    /// rejects the `EXPLAIN`, only the partition count is reported.
+    pub async fn estimate(&mut self, params: QueryParams) -> Result<QueryEstimate> {
+        let (params, parts, _) = self.plan_history(params)?;
+        let mut estimate = QueryEstimate {
+            hours: hour_partitions(&params),
+            queries: parts.len(),
+            ..Default::default()
+        };
+
+        let sql = format!("EXPLAIN (TYPE IO, FORMAT JSON) {}", build_history_query(&params)?);
+        let rows = match self.run_query(&sql, |_| {}, |_| {}).await {
+            Ok((_, rows, _)) => rows,
+            Err(e @ (OpenSkyError::Query(_) | OpenSkyError::InQuery { .. })) => {
+                tracing::debug!("Could not explain the query: {}", e);
+                return Ok(estimate);
+            }
+            Err(e) => return Err(e),
+        };
+        let plan: Option<serde_json::Value> = rows
+            .first()
+            .and_then(|row| row.first())
+            .and_then(|plan| plan.as_str())
+            .and_then(|plan| serde_json::from_str(plan).ok());
+        if let Some(plan) = plan {
+            estimate.rows = io_estimate(&plan, "outputRowCount");
+            estimate.bytes = io_estimate(&plan, "outputSizeInBytes");
+        }
+        Ok(estimate)
     }
 
     /// Execute the history query and return flight data.
@@ -185,35 +978,19 @@ impl Trino {
     /// - `cached=true`: Use cache if available, otherwise query and cache result
     /// - `cached=false`: Force fresh query, bypass and clear existing cache
     pub async fn history_cached(&mut self, params: QueryParams, cached: bool) -> Result<FlightData> {
-        // Check cache first
-        if cached {
-            if let Some(data) = cache::get_cached(&params, None) {
-                return Ok(data);
-            }
-        } else {
-            // Clear existing cache for this query
-            let _ = cache::remove_cached(&params);
-        }
-
-        // Execute query
-        let sql = build_history_query(&params);
-        let data = self.execute_query(&sql, FLIGHT_COLUMNS).await?;
-
-        // Cache the result if we got data
-        if !data.is_empty() {
-            let _ = cache::save_to_cache(&params, &data);
-        }
-
-        Ok(data)
+        self.history_with_progress_cached(params, cached, |_| {}).await
     }
 
     /// Query flight list data from flights_data4 table.
     ///
     /// Returns a list of flights with departure/arrival times and airports.
     /// This is useful for finding flights before querying their trajectories.
-    pub async fn flightlist(&mut self, params: QueryParams) -> Result<FlightData> {
-        let sql = build_flightlist_query(&params);
-        self.execute_query(&sql, FLIGHTLIST_COLUMNS).await
+    pub async fn flightlist(&mut self, params: QueryParams) -> Result<FlightList> {
+        let params = self.prepare(params)?;
+        params.validate_flightlist()?;
+        let sql = build_flightlist_query(&params)?;
+        let data = self.execute_query(&sql, FLIGHTLIST_COLUMNS).await?;
+        FlightList::new(data.into_dataframe())
     }
 
     /// Query flight list with progress callback.
@@ -221,12 +998,75 @@ impl Trino {
         &mut self,
         params: QueryParams,
         progress_callback: F,
-    ) -> Result<FlightData>
+    ) -> Result<FlightList>
     where
         F: FnMut(QueryStatus),
     {
-        let sql = build_flightlist_query(&params);
-        self.execute_query_with_progress(&sql, FLIGHTLIST_COLUMNS, progress_callback).await
+        let params = self.prepare(params)?;
+        params.validate_flightlist()?;
+        let sql = build_flightlist_query(&params)?;
+        let data = self.execute_query_with_progress(&sql, FLIGHTLIST_COLUMNS, progress_callback).await?;
+        FlightList::new(data.into_dataframe())
+    }
+
+    /// List the distinct aircraft matching `params`.
+    ///
+    /// Returns one row per aircraft (icao24, firstseen, lastseen), computed
+    /// on the server from the same filters as [`Trino::history`].
+    pub async fn distinct_icao24(&mut self, params: QueryParams) -> Result<FlightData> {
+        let params = self.prepare(params)?;
+        let sql = build_distinct_icao24_query(&params)?;
+        self.execute_query(&sql, AIRCRAFT_COLUMNS).await
+    }
+
+    /// Run a server-side aggregation over the rows matching `params`.
+    ///
+    /// Only the grouped counts are transferred, e.g. aircraft per hour over
+    /// a region, instead of every state vector.
+    ///
+    /// ```rust,no_run
+    /// # use opensky::{Aggregation, QueryParams, Trino};
+    /// # async fn example(trino: &mut Trino) -> opensky::Result<()> {
+    /// let params = QueryParams::new()
+    ///     .time_range("2025-01-01 00:00:00", "2025-01-01 23:59:59")
+    ///     .bounds(-10.0, 35.0, 30.0, 60.0);
+    /// let counts = trino.aggregate(params, Aggregation::AircraftPerHour).await?;
+    /// println!("{}", counts);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn aggregate(&mut self, params: QueryParams, aggregation: Aggregation) -> Result<DataFrame> {
+        let params = self.prepare(params)?;
+        let sql = build_aggregate_query(&params, aggregation)?;
+        let data = self.execute_query(&sql, aggregation.columns()).await?;
+        Ok(data.into_dataframe())
+    }
+
+    /// Count departures and arrivals at `airport` per hour or per day.
+    ///
+    /// Returns `period` (unix seconds at the start of the bucket),
+    /// `departures` and `arrivals`, computed on the server from flights_data4.
+    ///
+    /// ```rust,no_run
+    /// # use opensky::{StatsInterval, Trino};
+    /// # async fn example(trino: &mut Trino) -> opensky::Result<()> {
+    /// let stats = trino
+    ///     .airport_stats("EHAM", "2025-01-01 00:00:00", "2025-01-07 23:59:59", StatsInterval::Daily)
+    ///     .await?;
+    /// println!("{}", stats);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn airport_stats(
+        &mut self,
+        airport: &str,
+        start: &str,
+        stop: &str,
+        interval: StatsInterval,
+    ) -> Result<DataFrame> {
+        let sql = build_airport_stats_query(airport, start, stop, interval, &self.config.tables)?;
+        let data = self.execute_query(&sql, AIRPORT_STATS_COLUMNS).await?;
+        Ok(data.into_dataframe())
     }
 
     /// Query raw ADS-B messages from OpenSky.
@@ -255,193 +1095,483 @@ impl Trino {
     where
         F: FnMut(QueryStatus),
     {
-        let sql = build_rawdata_query(&params, RawTable::default());
-        self.execute_query_with_progress(&sql, RAWDATA_COLUMNS, progress_callback).await
+        let params = self.prepare(params)?;
+        params.validate_columns(RAWDATA_COLUMNS)?;
+        let sql = build_rawdata_query(&params, RawTable::default())?;
+        self.execute_query_with_progress(&sql, &rawdata_columns(&params), progress_callback).await
     }
 
     /// Query raw ADS-B messages from a specific table.
     pub async fn rawdata_table(&mut self, params: QueryParams, table: RawTable) -> Result<FlightData> {
-        let sql = build_rawdata_query(&params, table);
-        self.execute_query(&sql, RAWDATA_COLUMNS).await
+        let params = self.prepare(params)?;
+        params.validate_columns(RAWDATA_COLUMNS)?;
+        let sql = build_rawdata_query(&params, table)?;
+        self.execute_query(&sql, &rawdata_columns(&params)).await
     }
 
-    /// Execute a raw SQL query.
-    pub async fn execute_query(&mut self, sql: &str, default_columns: &[&str]) -> Result<FlightData> {
-        let token = self.get_token().await?;
-        let username = self.config.username.as_deref().unwrap_or("opensky");
+    /// Query decoded ADS-B positions from `position_data4`.
+    ///
+    /// Returns one row per position with `mintime`, `maxtime`, `msgcount`,
+    /// `icao24`, `lat`, `lon`, `alt` and `surface`, typed, instead of the
+    /// raw messages of [`Trino::rawdata_table`]. Narrow the columns with
+    /// [`QueryParams::columns`]; `mintime` and `icao24` are always included.
+    ///
+    /// ```rust,no_run
+    /// # use opensky::{QueryParams, Trino};
+    /// # async fn example(trino: &mut Trino) -> opensky::Result<()> {
+    /// let params = QueryParams::new()
+    ///     .icao24("485a32")
+    ///     .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00");
+    /// let positions = trino.adsb_positions(params).await?;
+    /// println!("{}", positions.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn adsb_positions(&mut self, params: QueryParams) -> Result<FlightData> {
+        self.adsb(params, RawTable::Position).await
+    }
 
-        // Initial query submission
-        let response = self
-            .client
-            .post(TRINO_URL)
-            .header("Authorization", format!("Bearer {}", token))
-            .header("X-Trino-User", username)
-            .header("X-Trino-Source", &self.source)
-            .header("X-Trino-Catalog", "minio")
-            .header("X-Trino-Schema", "osky")
-            .body(sql.to_string())
-            .send()
-            .await?;
+    /// Query decoded ADS-B velocities from `velocity_data4`.
+    ///
+    /// Returns `mintime`, `maxtime`, `msgcount`, `icao24`, `velocity`,
+    /// `heading`, `vertrate`, the `ewvelocity` and `nsvelocity` components
+    /// and `geominurbaro`, the difference between geometric and barometric
+    /// altitude.
+    pub async fn adsb_velocities(&mut self, params: QueryParams) -> Result<FlightData> {
+        self.adsb(params, RawTable::Velocity).await
+    }
 
-        response.error_for_status_ref()?;
+    async fn adsb(&mut self, params: QueryParams, table: RawTable) -> Result<FlightData> {
+        let params = self.prepare(params)?;
+        params.validate_columns(adsb_table_columns(table))?;
+        let sql = build_adsb_query(&params, table)?;
+        self.execute_query(&sql, &adsb_columns(&params, table)).await
+    }
 
-        let mut trino_response: TrinoResponse = response.json().await?;
+    /// Execute a raw SQL query.
+    pub async fn execute_query(&mut self, sql: &str, default_columns: &[&str]) -> Result<FlightData> {
+        self.execute_query_with_progress(sql, default_columns, |_| {}).await
+    }
 
-        // Check for immediate errors
-        if let Some(error) = &trino_response.error {
-            return Err(OpenSkyError::Query(error.message.clone()));
-        }
+    /// Execute a SQL query with progress callback.
+    ///
+    /// This is the generic version that all query types can use.
+    pub async fn execute_query_with_progress<F>(
+        &mut self,
+        sql: &str,
+        default_columns: &[&str],
+        progress_callback: F,
+    ) -> Result<FlightData>
+    where
+        F: FnMut(QueryStatus),
+    {
+        let (columns, all_rows, truncated) = self.run_query(sql, progress_callback, |_| {}).await?;
+        let mismatch = check_schema(self.schema_check, &columns, default_columns)?;
+        let df = Self::rows_to_dataframe(&columns, all_rows, default_columns)?;
+        Ok(FlightData::new(df).with_truncated(truncated).with_schema_mismatch(mismatch))
+    }
 
-        // Collect all data by polling nextUri
-        let mut all_rows: Vec<Vec<serde_json::Value>> = Vec::new();
+    /// Submit a statement and poll `nextUri` until the query finishes.
+    ///
+    /// Every response page is passed to `on_page` as raw JSON before it is
+    /// interpreted, and a status update is reported after each page. Also
+    /// returns whether the rows were cut at [`Trino::set_max_rows`].
+    pub(crate) async fn run_query<F, P>(
+        &mut self,
+        sql: &str,
+        mut progress_callback: F,
+        mut on_page: P,
+    ) -> Result<(Vec<TrinoColumn>, Vec<Vec<serde_json::Value>>, bool)>
+    where
+        F: FnMut(QueryStatus),
+        P: FnMut(&serde_json::Value),
+    {
+        let started = std::time::Instant::now();
+        self.last_report = None;
+        let page = self.submit_statement(sql).await?;
+        on_page(&page);
+        let mut trino_response: TrinoResponse = serde_json::from_value(page)?;
+        let query_id = trino_response.id.clone();
+        tracing::debug!("Query {} submitted", query_id.as_deref().unwrap_or("(no id)"));
+        // Errors from here on name the query, so it can be reported
+        let in_query = |e: OpenSkyError| e.in_query(query_id.as_deref());
+
+        if let Some(error) = trino_response.error.take() {
+            return Err(in_query(OpenSkyError::Query(Box::new(error))));
+        }
+
+        let mut warnings = Vec::new();
+        note_warnings(&mut warnings, std::mem::take(&mut trino_response.warnings), query_id.as_deref());
+        let mut all_rows: Vec<Vec<serde_json::Value>> = Vec::new();
         let mut columns: Option<Vec<TrinoColumn>> = trino_response.columns;
+        let mut running = RunningStats::default();
+        running.set_columns(columns.as_deref().unwrap_or_default());
 
-        // Collect data from first response
         if let Some(data) = trino_response.data {
+            running.add(&data);
             all_rows.extend(data);
         }
 
-        // Poll for more results
-        while let Some(next_uri) = trino_response.next_uri {
+        // Report initial status
+        progress_callback(
+            QueryStatus::from_stats(query_id.clone(), trino_response.stats.as_ref(), all_rows.len(), started)
+                .with_running(&running),
+        );
+
+        let max_rows = self.max_rows;
+        let over_limit = |rows: usize| max_rows.is_some_and(|max| rows > max);
+        while let Some(next_uri) = trino_response.next_uri.filter(|_| !over_limit(all_rows.len())) {
             tokio::time::sleep(Duration::from_millis(100)).await;
 
-            let response = self
-                .client
-                .get(&next_uri)
-                .header("Authorization", format!("Bearer {}", token))
-                .header("X-Trino-User", username)
-                .send()
-                .await?;
+            let page = self.fetch_page(&next_uri).await.map_err(in_query)?;
+            on_page(&page);
+            trino_response = serde_json::from_value(page).map_err(|e| in_query(e.into()))?;
 
-            response.error_for_status_ref()?;
-            trino_response = response.json().await?;
-
-            if let Some(error) = &trino_response.error {
-                return Err(OpenSkyError::Query(error.message.clone()));
+            if let Some(error) = trino_response.error.take() {
+                return Err(in_query(OpenSkyError::Query(Box::new(error))));
             }
+            note_warnings(&mut warnings, std::mem::take(&mut trino_response.warnings), query_id.as_deref());
 
-            // Update columns if we get them
             if columns.is_none() {
                 columns = trino_response.columns;
+                running.set_columns(columns.as_deref().unwrap_or_default());
             }
 
             if let Some(data) = trino_response.data {
+                running.add(&data);
                 all_rows.extend(data);
             }
+
+            // Report progress
+            let status = QueryStatus::from_stats(
+                query_id.clone(),
+                trino_response.stats.as_ref(),
+                all_rows.len(),
+                started,
+            )
+            .with_running(&running);
+            tracing::trace!("{} {:.1}%, {} rows", status.state, status.progress, status.row_count);
+            progress_callback(status);
+        }
+
+        let bytes = trino_response.stats.as_ref().and_then(|s| s.processed_bytes);
+        let truncated = over_limit(all_rows.len());
+        if let (true, Some(max)) = (truncated, max_rows) {
+            all_rows.truncate(max);
+            tracing::warn!("Result truncated to {} rows", max);
+            if let Some(id) = query_id.as_deref() {
+                // Best-effort: the query expires on the server anyway
+                if let Err(e) = self.cancel(id).await {
+                    tracing::debug!("Could not cancel query {}: {}", id, e);
+                }
+            }
         }
 
-        // Convert to DataFrame
-        let df = self.rows_to_dataframe(&columns.unwrap_or_default(), all_rows, default_columns)?;
-        Ok(FlightData::new(df))
+        tracing::debug!(
+            "Query {} finished: {} rows in {:.1}s",
+            query_id.as_deref().unwrap_or("(no id)"),
+            all_rows.len(),
+            started.elapsed().as_secs_f64()
+        );
+        self.last_report = Some(QueryReport {
+            query_id,
+            wall_time: started.elapsed().as_secs_f64(),
+            rows: all_rows.len(),
+            bytes,
+            cache: CacheStatus::Miss,
+            cache_files: Vec::new(),
+            sql_hash: format!("{:016x}", statement_hash(sql)),
+            sql: sql.to_string(),
+            truncated,
+            warnings,
+        });
+        Ok((columns.unwrap_or_default(), all_rows, truncated))
     }
 
-    /// Execute a SQL query with progress callback.
+    /// POST a statement and return the first response page.
     ///
-    /// This is the generic version that all query types can use.
-    pub async fn execute_query_with_progress<F>(
-        &mut self,
-        sql: &str,
-        default_columns: &[&str],
-        mut progress_callback: F,
-    ) -> Result<FlightData>
-    where
-        F: FnMut(QueryStatus),
-    {
-        let token = self.get_token().await?;
-        let username = self.config.username.as_deref().unwrap_or("opensky");
+    /// Refuses a statement identical to one this client submitted and has
+    /// not read to the end, unless that query has been left unpolled for
+    /// longer than Trino keeps it: a retry after an abandoned wait would
+    /// otherwise run it twice. Each statement is tagged with its hash, so
+    /// duplicates from several clients can be told apart on the server.
+    async fn submit_statement(&mut self, sql: &str) -> Result<serde_json::Value> {
+        let hash = statement_hash(sql);
+        self.in_flight.retain(|_, (_, polled)| polled.elapsed() < CLIENT_TIMEOUT);
+        if let Some((query_id, _)) = self.in_flight.get(&hash) {
+            return Err(OpenSkyError::DuplicateQuery {
+                query_id: query_id.clone(),
+            });
+        }
 
-        // Initial query submission
-        let response = self
+        let token = self.get_token().await?;
+        let username = self.config.username.clone().unwrap_or_else(|| "opensky".to_string());
+        let trino_url = self.trino_url.clone();
+        let mut tags = self.client_tags.clone();
+        tags.push(format!("sql-{:016x}", hash));
+
+        tracing::trace!("Submitting query:\n{}", sql);
+        let authorization = bearer(&token)?;
+        let request = self
             .client
-            .post(TRINO_URL)
-            .header("Authorization", format!("Bearer {}", token))
-            .header("X-Trino-User", username)
+            .post(&trino_url)
+            .header("Authorization", authorization.clone())
+            .header("X-Trino-User", &username)
             .header("X-Trino-Source", &self.source)
-            .header("X-Trino-Catalog", "minio")
-            .header("X-Trino-Schema", "osky")
-            .body(sql.to_string())
-            .send()
-            .await?;
-
-        response.error_for_status_ref()?;
+            .header("X-Trino-Catalog", tables::CATALOG)
+            .header("X-Trino-Schema", tables::SCHEMA)
+            .header("X-Trino-Client-Tags", tags.join(","))
+            .body(sql.to_string());
+
+        let (status, body) = self.send(request, "POST", &trino_url, Some(sql)).await?;
+        check_status(status, &trino_url)?;
+        let page = serde_json::from_str(&body)?;
+        self.track(Some(hash), &page);
+        self.set_pending(&page, authorization, username);
+        Ok(page)
+    }
 
-        let mut trino_response: TrinoResponse = response.json().await?;
-        let query_id = trino_response.id.clone();
+    /// GET the next response page of a running statement.
+    async fn fetch_page(&mut self, next_uri: &str) -> Result<serde_json::Value> {
+        let token = self.get_token().await?;
+        let username = self.config.username.clone().unwrap_or_else(|| "opensky".to_string());
+        let authorization = bearer(&token)?;
+        let request = self
+            .client
+            .get(next_uri)
+            .header("Authorization", authorization.clone())
+            .header("X-Trino-User", &username);
 
-        if let Some(error) = &trino_response.error {
-            return Err(OpenSkyError::Query(error.message.clone()));
+        let page = match self.send(request, "GET", next_uri, None).await {
+            Ok((status, body)) => check_status(status, next_uri).and_then(|_| Ok(serde_json::from_str(&body)?)),
+            Err(e) => Err(e),
+        };
+        match &page {
+            Ok(page) => {
+                self.track(None, page);
+                self.set_pending(page, authorization, username);
+            }
+            // The query is gone or unreachable; a new attempt is no duplicate
+            Err(_) => {
+                self.in_flight.retain(|_, (query_id, _)| !next_uri.contains(query_id.as_str()));
+                self.pending.lock().unwrap_or_else(|e| e.into_inner()).take();
+            }
         }
+        page
+    }
 
-        let mut all_rows: Vec<Vec<serde_json::Value>> = Vec::new();
-        let mut columns: Option<Vec<TrinoColumn>> = trino_response.columns;
-
-        if let Some(data) = trino_response.data {
-            all_rows.extend(data);
-        }
+    /// Remember the `nextUri` of a response page for [`QueryCanceller`],
+    /// or forget it once the statement is done.
+    fn set_pending(&self, page: &serde_json::Value, authorization: reqwest::header::HeaderValue, user: String) {
+        let next = page.get("nextUri").and_then(|uri| uri.as_str()).map(|next_uri| PendingPage {
+            next_uri: next_uri.to_string(),
+            authorization,
+            user,
+        });
+        *self.pending.lock().unwrap_or_else(|e| e.into_inner()) = next;
+    }
 
-        // Report initial status
-        let status = QueryStatus {
-            query_id: query_id.clone(),
-            state: trino_response
-                .stats
-                .as_ref()
-                .map(|s| s.state.clone())
-                .unwrap_or_else(|| "RUNNING".to_string()),
-            progress: trino_response
-                .stats
-                .as_ref()
-                .and_then(|s| s.progress_percentage)
-                .unwrap_or(0.0),
-            row_count: all_rows.len(),
+    /// Note a response page of a statement: with a `nextUri` the query is
+    /// still running, without it the query is done.
+    fn track(&mut self, hash: Option<u64>, page: &serde_json::Value) {
+        let Some(query_id) = page.get("id").and_then(|id| id.as_str()) else {
+            return;
         };
-        progress_callback(status);
-
-        while let Some(next_uri) = trino_response.next_uri {
-            tokio::time::sleep(Duration::from_millis(100)).await;
-
-            let response = self
-                .client
-                .get(&next_uri)
-                .header("Authorization", format!("Bearer {}", token))
-                .header("X-Trino-User", username)
-                .send()
-                .await?;
+        if page.get("nextUri").is_none() {
+            self.in_flight.retain(|_, (id, _)| id != query_id);
+            return;
+        }
+        let now = std::time::Instant::now();
+        match hash {
+            Some(hash) => {
+                self.in_flight.insert(hash, (query_id.to_string(), now));
+            }
+            None => {
+                for (id, polled) in self.in_flight.values_mut() {
+                    if id == query_id {
+                        *polled = now;
+                    }
+                }
+            }
+        }
+    }
 
-            response.error_for_status_ref()?;
-            trino_response = response.json().await?;
+    /// Execute a SQL statement and return its response pages as they arrive,
+    /// without converting them to a DataFrame.
+    ///
+    /// Each [`TrinoPage`] holds the rows of one response as JSON values,
+    /// for custom converters (e.g. straight to Arrow). Pages of a queued or
+    /// running query may hold no rows but still report progress. Dropping
+    /// the pager early leaves the query running until it expires; use
+    /// [`Trino::cancel`] with its `query_id` to stop it.
+    ///
+    /// ```rust,no_run
+    /// # async fn example(trino: &mut opensky::Trino) -> opensky::Result<()> {
+    /// let mut pages = trino.execute_raw("SELECT icao24, time FROM state_vectors_data4 LIMIT 10");
+    /// while let Some(page) = pages.next().await {
+    ///     let page = page?;
+    ///     println!("{} rows, {:.0}%", page.rows.len(), page.status.progress);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn execute_raw(&mut self, sql: impl Into<String>) -> RawPages<'_> {
+        RawPages {
+            trino: self,
+            sql: sql.into(),
+            started: None,
+            next_uri: None,
+            query_id: None,
+            columns: Vec::new(),
+            warnings: Vec::new(),
+            row_count: 0,
+            running: RunningStats::default(),
+            done: false,
+        }
+    }
 
-            if let Some(error) = &trino_response.error {
-                return Err(OpenSkyError::Query(error.message.clone()));
+    /// Execute a SQL statement, feeding the rows to `sink` page by page.
+    ///
+    /// Each response page is converted to a DataFrame as it arrives, so the
+    /// sink can write results out while the query runs. Without any column
+    /// information from the server, the schema of `default_columns` is used.
+    /// The row cap of [`Trino::set_max_rows`] does not apply, since rows are
+    /// not held here.
+    ///
+    /// ```rust,no_run
+    /// # async fn example(trino: &mut opensky::Trino) -> opensky::Result<()> {
+    /// use opensky::sink::ParquetSink;
+    ///
+    /// let sql = opensky::build_history_query(&opensky::QueryParams::new().icao24("485a32"))?;
+    /// trino.execute_into(&sql, opensky::FLIGHT_COLUMNS, ParquetSink::new("flight.parquet")).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn execute_into<S: ResultSink>(&mut self, sql: &str, default_columns: &[&str], mut sink: S) -> Result<S::Output> {
+        let schema_check = self.schema_check;
+        let mut pages = self.execute_raw(sql);
+        let mut has_schema = false;
+        while let Some(page) = pages.next().await {
+            let page = page?;
+            if !has_schema && !page.columns.is_empty() {
+                check_schema(schema_check, &page.columns, default_columns)?;
+                sink.on_schema(&trino_schema(&page.columns))?;
+                has_schema = true;
+            }
+            if !page.rows.is_empty() {
+                let batch = Self::rows_to_dataframe(&page.columns, page.rows, default_columns)?;
+                sink.ready().await?;
+                sink.on_batch(&batch)?;
             }
+        }
+        if !has_schema {
+            sink.on_schema(schema::empty_frame(default_columns)?.schema())?;
+        }
+        sink.finish()
+    }
 
-            if columns.is_none() {
-                columns = trino_response.columns;
+    /// Execute history query into a [`ResultSink`], page by page.
+    ///
+    /// Only one response page is held at a time, so results larger than
+    /// memory can be written to a file. Unlike [`Trino::history`], the
+    /// query is not split by `[query] max_span` and bypasses the cache,
+    /// except under [`CachePolicy::ReadOnly`]: then the result of
+    /// [`Trino::history`] is written, which comes from the cache or fails
+    /// with [`OpenSkyError::CacheMiss`].
+    ///
+    /// ```rust,no_run
+    /// # async fn example(trino: &mut opensky::Trino) -> opensky::Result<()> {
+    /// use opensky::sink::ParquetSink;
+    ///
+    /// let params = opensky::QueryParams::new()
+    ///     .departure("EHAM")
+    ///     .time_range("2025-01-01", "2025-01-02");
+    /// trino.history_into(params, ParquetSink::new("eham.parquet")).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn history_into<S: ResultSink>(&mut self, params: QueryParams, mut sink: S) -> Result<S::Output> {
+        if self.cache_policy == CachePolicy::ReadOnly {
+            let data = self.history(params).await?;
+            sink.on_schema(data.dataframe().schema())?;
+            if !data.is_empty() {
+                sink.ready().await?;
+                sink.on_batch(data.dataframe())?;
             }
+            return sink.finish();
+        }
+        let params = self.prepare(params)?;
+        params.validate_columns(FLIGHT_COLUMNS)?;
+        self.check_available(&params).await?;
+        let sql = build_history_query(&params)?;
+        self.execute_into(&sql, &history_columns(&params), sink).await
+    }
 
-            if let Some(data) = trino_response.data {
-                all_rows.extend(data);
+    /// Download the trajectories of the flights of a flight list.
+    ///
+    /// Instead of one query per flight, the state vectors are selected by
+    /// aircraft and time window, `firstseen - buffer` to `lastseen + buffer`,
+    /// up to 50 windows per query. Overlapping windows of the same aircraft
+    /// are merged. Rows are labeled as in [`FlightData::attach_flight_info`],
+    /// with the buffer counted as part of the flight. Results bypass the
+    /// cache, so under [`CachePolicy::ReadOnly`] this fails with
+    /// [`OpenSkyError::CacheMiss`].
+    ///
+    /// ```rust,no_run
+    /// # async fn example(trino: &mut opensky::Trino) -> opensky::Result<()> {
+    /// let params = opensky::QueryParams::new()
+    ///     .time_range("2025-01-01 00:00:00", "2025-01-01 23:59:59")
+    ///     .arrival("EHAM");
+    /// let flights = trino.flightlist(params).await?;
+    /// let data = trino.history_for_flights(&flights, chrono::Duration::minutes(10)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn history_for_flights(&mut self, flights: &FlightList, buffer: chrono::Duration) -> Result<FlightData> {
+        let mut windows: Vec<(String, i64, i64)> = flights
+            .iter()
+            .map(|f| (f.icao24, (f.firstseen - buffer).timestamp(), (f.lastseen + buffer).timestamp()))
+            .collect();
+        windows.sort();
+        let mut merged: Vec<(String, i64, i64)> = Vec::with_capacity(windows.len());
+        for window in windows {
+            match merged.last_mut() {
+                Some(last) if last.0 == window.0 && window.1 <= last.2 => last.2 = last.2.max(window.2),
+                _ => merged.push(window),
             }
+        }
 
-            // Report progress
-            let status = QueryStatus {
-                query_id: query_id.clone(),
-                state: trino_response
-                    .stats
-                    .as_ref()
-                    .map(|s| s.state.clone())
-                    .unwrap_or_else(|| "RUNNING".to_string()),
-                progress: trino_response
-                    .stats
-                    .as_ref()
-                    .and_then(|s| s.progress_percentage)
-                    .unwrap_or(0.0),
-                row_count: all_rows.len(),
-            };
-            progress_callback(status);
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+        if self.cache_policy == CachePolicy::ReadOnly {
+            if let Some(batch) = merged.chunks(WINDOWS_PER_QUERY).next() {
+                let sql = build_windows_history_query(batch, &self.config.tables);
+                return Err(OpenSkyError::CacheMiss { key: format!("{:016x}", statement_hash(&sql)) });
+            }
         }
 
-        let df = self.rows_to_dataframe(&columns.unwrap_or_default(), all_rows, default_columns)?;
-        Ok(FlightData::new(df))
+        let mut df = schema::empty_frame(FLIGHT_COLUMNS)?;
+        let mut truncated = false;
+        // `set_max_rows` caps the whole result, not each batch
+        let max_rows = self.max_rows;
+        for batch in merged.chunks(WINDOWS_PER_QUERY) {
+            let sql = build_windows_history_query(batch, &self.config.tables);
+            self.max_rows = max_rows.map(|max| max.saturating_sub(df.height()));
+            let result = self.execute_query(&sql, FLIGHT_COLUMNS).await;
+            self.max_rows = max_rows;
+            let data = result?;
+            truncated = data.is_truncated();
+            if df.height() == 0 {
+                df = data.into_dataframe();
+            } else {
+                df.vstack_mut(data.dataframe()).map_err(to_err)?;
+            }
+            if truncated {
+                break;
+            }
+        }
+        Ok(FlightData::new(df)
+            .attach_flight_info_within(flights, buffer)?
+            .with_truncated(truncated))
     }
 
     /// Execute query with progress callback.
@@ -463,174 +1593,386 @@ impl Trino {
         cached: bool,
         mut progress_callback: F,
     ) -> Result<FlightData>
+    where
+        F: FnMut(QueryStatus),
+    {
+        let (params, mut parts, by_day) = self.plan_history(params)?;
+        if parts.len() == 1 {
+            return self.history_part(parts.remove(0), cached, &mut progress_callback).await;
+        }
+        let mut result = ChunkedResult::new(params, by_day, cached);
+        result.run(self, parts, true, &mut progress_callback).await?;
+        Ok(result.data)
+    }
+
+    /// Execute history query, carrying on when a chunk fails.
+    ///
+    /// Like [`Trino::history`], but a query split by `[query] max_span` or
+    /// by day returns the rows of the chunks that succeeded together with
+    /// the ranges that failed, instead of the first error. Errors that would
+    /// fail every chunk (credentials, invalid SQL) are still returned.
+    ///
+    /// ```rust,no_run
+    /// # async fn example(trino: &mut opensky::Trino) -> opensky::Result<()> {
+    /// let params = opensky::QueryParams::new()
+    ///     .departure("EHAM")
+    ///     .time_range("2025-01-01", "2025-01-31");
+    /// let mut result = trino.history_chunked(params).await?;
+    /// if !result.is_complete() {
+    ///     result.retry_failed(trino).await?;
+    /// }
+    /// for ((start, stop), error) in &result.failed {
+    ///     eprintln!("{} to {}: {}", start, stop, error);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn history_chunked(&mut self, params: QueryParams) -> Result<ChunkedResult> {
+        self.history_chunked_with_progress(params, |_| {}).await
+    }
+
+    /// Execute chunked history query with progress callback.
+    pub async fn history_chunked_with_progress<F>(
+        &mut self,
+        params: QueryParams,
+        mut progress_callback: F,
+    ) -> Result<ChunkedResult>
+    where
+        F: FnMut(QueryStatus),
+    {
+        let (params, parts, by_day) = self.plan_history(params)?;
+        let mut result = ChunkedResult::new(params, by_day, true);
+        result.run(self, parts, false, &mut progress_callback).await?;
+        Ok(result)
+    }
+
+    /// Validate history parameters and split them into the queries to run.
+    ///
+    /// Also returns whether the parts are days of an airport join, whose
+    /// rows need deduplicating.
+    fn plan_history(&self, params: QueryParams) -> Result<(QueryParams, Vec<QueryParams>, bool)> {
+        let params = self.config.apply_query_defaults(params);
+        params.validate()?;
+        params.validate_columns(FLIGHT_COLUMNS)?;
+        let mut parts = self.config.split_query(&params, true)?;
+        log_warnings(&params);
+        // Joins with flights_data4 over weeks exceed the cluster limits
+        let by_day = params.has_airport_filter()
+            && params.offset.is_none()
+            && params.time_span().is_some_and(|span| span > AIRPORT_JOIN_MAX_SPAN);
+        if by_day {
+            parts = parts.iter().flat_map(split_by_day).collect();
+            tracing::debug!("Airport join split into {} daily queries", parts.len());
+        }
+
+        // Time range longer than `[query] max_span`, or a long airport join:
+        // one query (and cache entry) per chunk, concatenated in time order
+        if parts.len() > 1 && params.offset.is_some() {
+            return Err(OpenSkyError::InvalidParam(
+                "Offset cannot be combined with a time range split by max_span".to_string(),
+            ));
+        }
+        Ok((params, parts, by_day))
+    }
+
+    /// Run one history query, through the cache.
+    async fn history_part<F>(&mut self, params: QueryParams, cached: bool, progress_callback: &mut F) -> Result<FlightData>
     where
         F: FnMut(QueryStatus),
     {
         // Check cache first
-        if cached {
-            if let Some(data) = cache::get_cached(&params, None) {
+        if cached && self.cache_policy.reads() {
+            if let Some((data, path)) = self.cache_dir().and_then(|dir| {
+                let data = cache::get_cached_in(&dir, &params, None)?;
+                Some((data, dir.join(self.cache_key(&params))))
+            }) {
+                tracing::debug!("Served from cache: {}", path.display());
+                let data = data.with_cache(CacheStatus::Hit, vec![path.clone()]);
+                let metadata = data.metadata();
+                let sql = self
+                    .cache_dir()
+                    .and_then(|dir| cache::cached_manifest_in(&dir, &params))
+                    .map_or_else(|| build_history_query(&params), |manifest| Ok(manifest.sql))?;
+                self.last_report = Some(QueryReport {
+                    rows: data.len(),
+                    cache: CacheStatus::Hit,
+                    cache_files: vec![path.clone()],
+                    sql_hash: format!("{:016x}", statement_hash(&sql)),
+                    sql,
+                    ..Default::default()
+                });
                 // Report cached status
                 progress_callback(QueryStatus {
                     query_id: None,
                     state: "CACHED".to_string(),
                     progress: 100.0,
                     row_count: data.len(),
+                    elapsed: 0.0,
+                    rows_per_sec: None,
+                    bytes_per_sec: None,
+                    eta: None,
+                    cache_file: Some(path),
+                    aircraft: data.dataframe().column("icao24").and_then(|c| c.drop_nulls().n_unique()).ok(),
+                    first_time: metadata.start,
+                    last_time: metadata.stop,
                 });
                 return Ok(data);
             }
-        } else {
+        } else if !cached && self.cache_policy.writes() {
             // Clear existing cache for this query
-            let _ = cache::remove_cached(&params);
+            if let Some(dir) = self.cache_dir() {
+                let _ = cache::remove_cached_in(&dir, &params);
+            }
         }
-
-        let sql = build_history_query(&params);
-        let token = self.get_token().await?;
-        let username = self.config.username.as_deref().unwrap_or("opensky");
-
-        // Initial query submission
-        let response = self
-            .client
-            .post(TRINO_URL)
-            .header("Authorization", format!("Bearer {}", token))
-            .header("X-Trino-User", username)
-            .header("X-Trino-Source", &self.source)
-            .header("X-Trino-Catalog", "minio")
-            .header("X-Trino-Schema", "osky")
-            .body(sql.to_string())
-            .send()
-            .await?;
-
-        response.error_for_status_ref()?;
-
-        let mut trino_response: TrinoResponse = response.json().await?;
-        let query_id = trino_response.id.clone();
-
-        if let Some(error) = &trino_response.error {
-            return Err(OpenSkyError::Query(error.message.clone()));
+        if self.cache_policy == CachePolicy::ReadOnly {
+            return Err(OpenSkyError::CacheMiss { key: self.cache_key(&params) });
         }
 
-        let mut all_rows: Vec<Vec<serde_json::Value>> = Vec::new();
-        let mut columns: Option<Vec<TrinoColumn>> = trino_response.columns;
+        self.check_available(&params).await?;
+        let data = self.execute_history_narrowing(&params, &mut *progress_callback).await?;
 
-        if let Some(data) = trino_response.data {
-            all_rows.extend(data);
+        // Cache the result if we got data, and all of it
+        let mut files = Vec::new();
+        if !data.is_empty() && !data.is_truncated() && self.cache_policy.writes() {
+            let compression = self.config.cache_compression().unwrap_or_default();
+            let sql = match self.last_report.as_ref() {
+                Some(report) => report.sql.clone(),
+                None => build_history_query(&params)?,
+            };
+            if let Some(dir) = self.cache_dir() {
+                let saved = cache::save_to_cache_with_sql(&dir, &params, &data, compression, &sql);
+                files.extend(saved.ok());
+            }
+        }
+        if let Some(report) = self.last_report.as_mut() {
+            report.cache_files = files.clone();
         }
 
-        // Report initial status
-        let status = QueryStatus {
-            query_id: query_id.clone(),
-            state: trino_response
-                .stats
-                .as_ref()
-                .map(|s| s.state.clone())
-                .unwrap_or_else(|| "RUNNING".to_string()),
-            progress: trino_response
-                .stats
-                .as_ref()
-                .and_then(|s| s.progress_percentage)
-                .unwrap_or(0.0),
-            row_count: all_rows.len(),
-        };
-        progress_callback(status);
-
-        while let Some(next_uri) = trino_response.next_uri {
-            tokio::time::sleep(Duration::from_millis(100)).await;
-
-            let response = self
-                .client
-                .get(&next_uri)
-                .header("Authorization", format!("Bearer {}", token))
-                .header("X-Trino-User", username)
-                .send()
-                .await?;
-
-            response.error_for_status_ref()?;
-            trino_response = response.json().await?;
+        Ok(data.with_cache(CacheStatus::Miss, files))
+    }
 
-            if let Some(error) = &trino_response.error {
-                return Err(OpenSkyError::Query(error.message.clone()));
+    /// Run a history query, retrying it split in halves when the cluster
+    /// runs out of memory or time, up to [`NARROW_DEPTH`] times.
+    ///
+    /// Each split is reported with a `SPLIT` progress update. The parts run
+    /// in time order and their rows are concatenated.
+    async fn execute_history_narrowing<F>(&mut self, params: &QueryParams, progress_callback: &mut F) -> Result<FlightData>
+    where
+        F: FnMut(QueryStatus),
+    {
+        let mut pending = vec![(params.clone(), 0)];
+        let mut results: Vec<FlightData> = Vec::new();
+        let mut statements = Vec::new();
+        let max_rows = self.max_rows;
+        while let Some((part, depth)) = pending.pop() {
+            let sql = build_history_query(&part)?;
+            // The halves share the row cap of the whole query
+            let rows: usize = results.iter().map(FlightData::len).sum();
+            self.max_rows = max_rows.map(|max| max.saturating_sub(rows));
+            let result = self.execute_query_with_progress(&sql, &history_columns(&part), &mut *progress_callback).await;
+            self.max_rows = max_rows;
+            match result {
+                Ok(data) => {
+                    let truncated = data.is_truncated();
+                    results.push(data);
+                    statements.push(sql);
+                    if truncated {
+                        break;
+                    }
+                }
+                Err(e) if depth < NARROW_DEPTH && is_resource_error(&e) => {
+                    let Some(halves) = halve(&part) else {
+                        return Err(e);
+                    };
+                    tracing::warn!(
+                        "Query ran out of cluster resources ({}); retrying {} to {} in two parts",
+                        e,
+                        part.start.as_deref().unwrap_or_default(),
+                        part.stop.as_deref().unwrap_or_default()
+                    );
+                    progress_callback(QueryStatus {
+                        query_id: e.query_id().map(str::to_string),
+                        state: "SPLIT".to_string(),
+                        progress: 0.0,
+                        row_count: results.iter().map(FlightData::len).sum(),
+                        elapsed: 0.0,
+                        rows_per_sec: None,
+                        bytes_per_sec: None,
+                        eta: None,
+                        cache_file: None,
+                        aircraft: None,
+                        first_time: None,
+                        last_time: None,
+                    });
+                    // The stack runs the first half next
+                    pending.extend(halves.into_iter().rev().map(|half| (half, depth + 1)));
+                }
+                Err(e) => return Err(e),
             }
+        }
+        if results.len() == 1 {
+            return Ok(results.remove(0));
+        }
 
-            if columns.is_none() {
-                columns = trino_response.columns;
+        let truncated = results.iter().any(FlightData::is_truncated);
+        let mut mismatch: Vec<String> = Vec::new();
+        let mut df: Option<DataFrame> = None;
+        for data in results {
+            for m in data.schema_mismatch() {
+                if !mismatch.contains(m) {
+                    mismatch.push(m.clone());
+                }
             }
-
-            if let Some(data) = trino_response.data {
-                all_rows.extend(data);
+            match df.as_mut() {
+                // Parts without rows add nothing
+                Some(_) if data.is_empty() => {}
+                Some(df) => {
+                    df.vstack_mut(data.dataframe())
+                        .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+                }
+                None => df = Some(data.into_dataframe()),
             }
-
-            // Report progress
-            let status = QueryStatus {
-                query_id: query_id.clone(),
-                state: trino_response
-                    .stats
-                    .as_ref()
-                    .map(|s| s.state.clone())
-                    .unwrap_or_else(|| "RUNNING".to_string()),
-                progress: trino_response
-                    .stats
-                    .as_ref()
-                    .and_then(|s| s.progress_percentage)
-                    .unwrap_or(0.0),
-                row_count: all_rows.len(),
-            };
-            progress_callback(status);
         }
+        let mut df = df.unwrap_or_default();
+        if params.order == Order::IcaoThenTime && df.height() > 0 {
+            df = df
+                .sort(["icao24", "time"], SortMultipleOptions::default().with_maintain_order(true))
+                .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+        }
+        // Every part ran with the full limit
+        if let Some(limit) = params.limit {
+            df = df.head(Some(limit as usize));
+        }
+        if let Some(report) = self.last_report.as_mut() {
+            report.rows = df.height();
+            report.sql = statements.join(";\n");
+        }
+        Ok(FlightData::new(df).with_truncated(truncated).with_schema_mismatch(mismatch))
+    }
 
-        let df = self.rows_to_dataframe(&columns.unwrap_or_default(), all_rows, FLIGHT_COLUMNS)?;
-        let data = FlightData::new(df);
+    /// Execute history query, reporting progress through a channel.
+    ///
+    /// Returns a receiver of status updates and a future resolving to the
+    /// result. The receiver closes once the future completes, so callers can
+    /// `tokio::select!` on both instead of passing a callback:
+    ///
+    /// ```rust,no_run
+    /// # async fn example(trino: &mut opensky::Trino) -> opensky::Result<()> {
+    /// let params = opensky::QueryParams::new().icao24("485a32");
+    /// let (mut progress, result) = trino.history_with_progress_stream(params);
+    /// tokio::pin!(result);
+    ///
+    /// let data = loop {
+    ///     tokio::select! {
+    ///         Some(status) = progress.recv() => println!("{:.1}%", status.progress),
+    ///         data = &mut result => break data?,
+    ///     }
+    /// };
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn history_with_progress_stream(
+        &mut self,
+        params: QueryParams,
+    ) -> (ProgressReceiver, impl Future<Output = Result<FlightData>> + '_) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let future = async move {
+            self.history_with_progress(params, move |status| {
+                // The receiver may have been dropped; progress is best-effort
+                let _ = tx.send(status);
+            })
+            .await
+        };
+        (rx, future)
+    }
 
-        // Cache the result if we got data
-        if !data.is_empty() {
-            let _ = cache::save_to_cache(&params, &data);
+    /// Page through history results, `page_size` rows per query.
+    ///
+    /// Each call to [`HistoryPages::next`] issues one query with the next
+    /// OFFSET/LIMIT window, starting at `params.offset` and stopping after
+    /// `params.limit` rows in total if set. Pages bypass the cache, so under
+    /// [`CachePolicy::ReadOnly`] the first page fails with
+    /// [`OpenSkyError::CacheMiss`].
+    ///
+    /// Windows only line up over a stable order, so pages are always ordered
+    /// by time, then aircraft; [`Order::None`] is rejected.
+    ///
+    /// ```rust,no_run
+    /// # async fn example(trino: &mut opensky::Trino) -> opensky::Result<()> {
+    /// let params = opensky::QueryParams::new().icao24("485a32");
+    /// let mut pages = trino.history_pages(params, 10_000);
+    /// while let Some(page) = pages.next().await {
+    ///     println!("{} rows", page?.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn history_pages(&mut self, params: QueryParams, page_size: u32) -> HistoryPages<'_> {
+        let params = self.config.apply_query_defaults(params);
+        log_warnings(&params);
+        HistoryPages {
+            offset: params.offset.unwrap_or(0),
+            remaining: params.limit.map(u64::from),
+            trino: self,
+            params,
+            page_size: page_size.max(1),
+            done: false,
         }
+    }
 
-        Ok(data)
+    /// A handle to cancel the statement this client is polling from
+    /// another task, since polling holds the client borrowed.
+    ///
+    /// The handle stays valid for every later statement of the client.
+    /// Dropping a future that polls a statement does not stop the query on
+    /// the server; calling [`QueryCanceller::cancel`] does.
+    pub fn canceller(&self) -> QueryCanceller {
+        QueryCanceller {
+            client: self.client.clone(),
+            pending: Arc::clone(&self.pending),
+        }
     }
 
     /// Cancel a running query.
     pub async fn cancel(&mut self, query_id: &str) -> Result<()> {
         let token = self.get_token().await?;
-        let username = self.config.username.as_deref().unwrap_or("opensky");
+        let username = self.config.username.clone().unwrap_or_else(|| "opensky".to_string());
 
-        let url = format!("https://trino.opensky-network.org/v1/query/{}", query_id);
+        let base = self.trino_url.trim_end_matches("/v1/statement");
+        let url = format!("{}/v1/query/{}", base, query_id);
 
-        let response = self
+        let request = self
             .client
             .delete(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .header("X-Trino-User", username)
-            .send()
-            .await?;
+            .header("Authorization", bearer(&token)?)
+            .header("X-Trino-User", username);
 
-        if response.status().is_success() || response.status() == 204 {
+        let (status, _) = self.send(request, "DELETE", &url, None).await?;
+
+        if (200..300).contains(&status) {
+            self.in_flight.retain(|_, (id, _)| id != query_id);
             Ok(())
         } else {
-            Err(OpenSkyError::Query(format!(
-                "Failed to cancel query: {}",
-                response.status()
-            )))
+            Err(OpenSkyError::Query(Box::new(QueryError::new(format!(
+                "Failed to cancel query: HTTP {}",
+                status
+            )))))
         }
     }
 
     /// Convert Trino rows to a Polars DataFrame.
     fn rows_to_dataframe(
-        &self,
         columns: &[TrinoColumn],
         rows: Vec<Vec<serde_json::Value>>,
         default_columns: &[&str],
     ) -> Result<DataFrame> {
         if rows.is_empty() {
-            // Return empty DataFrame with correct columns
-            let series: Vec<Column> = default_columns
-                .iter()
-                .map(|name| Column::new((*name).into(), Vec::<String>::new()))
-                .collect();
-            return DataFrame::new(series)
-                .map_err(|e| OpenSkyError::DataConversion(e.to_string()));
+            // Return empty DataFrame with correctly typed columns
+            return schema::empty_frame(default_columns);
         }
 
-        // Build series for each column
+        // Build series for each column, with the type Trino declares, see
+        // result_type
         let mut series_vec: Vec<Column> = Vec::new();
 
         for (col_idx, col) in columns.iter().enumerate() {
@@ -639,30 +1981,35 @@ impl Trino {
                 .map(|row| row.get(col_idx))
                 .collect();
 
-            let series = match col.col_type.as_str() {
-                "double" | "real" => {
+            let series = match result_type(col) {
+                ColumnType::Float64 => {
                     let data: Vec<Option<f64>> = values
                         .iter()
-                        .map(|v| v.and_then(|x| x.as_f64()))
+                        .map(|v| v.and_then(|x| x.as_f64().or_else(|| x.as_str().and_then(|s| s.parse().ok()))))
                         .collect();
                     Column::new(col.name.clone().into(), data)
                 }
-                "bigint" | "integer" => {
+                ColumnType::Int64 => {
                     let data: Vec<Option<i64>> = values
                         .iter()
-                        .map(|v| v.and_then(|x| x.as_i64()))
+                        .map(|v| {
+                            v.and_then(|x| {
+                                x.as_i64()
+                                    .or_else(|| x.as_f64().map(|f| f as i64))
+                                    .or_else(|| x.as_str().and_then(|s| s.parse().ok()))
+                            })
+                        })
                         .collect();
                     Column::new(col.name.clone().into(), data)
                 }
-                "boolean" => {
+                ColumnType::Boolean => {
                     let data: Vec<Option<bool>> = values
                         .iter()
-                        .map(|v| v.and_then(|x| x.as_bool()))
+                        .map(|v| v.and_then(|x| x.as_bool().or_else(|| x.as_str().and_then(|s| s.parse().ok()))))
                         .collect();
                     Column::new(col.name.clone().into(), data)
                 }
-                _ => {
-                    // Default to string for varchar, timestamp, etc.
+                ColumnType::String => {
                     let data: Vec<Option<String>> = values
                         .iter()
                         .map(|v| {
@@ -687,6 +2034,13 @@ impl Trino {
         DataFrame::new(series_vec).map_err(|e| OpenSkyError::DataConversion(e.to_string()))
     }
 
+    /// Report of the last statement this client ran or read from the cache.
+    ///
+    /// A history query split by `[query] max_span` reports its last chunk.
+    pub fn last_report(&self) -> Option<&QueryReport> {
+        self.last_report.as_ref()
+    }
+
     /// Get the current query ID (if a query is running).
     pub fn current_query_id(&self) -> Option<&str> {
         // This would need state tracking for async queries
@@ -694,6 +2048,310 @@ impl Trino {
     }
 }
 
+/// Type a result column is read as: the one Trino declares, or the
+/// canonical type of a known column when Trino declares none, or declares
+/// varchar where the schema has numbers or booleans.
+fn result_type(column: &TrinoColumn) -> ColumnType {
+    let declared = column.col_type.trim();
+    match schema::column_type(&column.name) {
+        Some(canonical)
+            if declared.is_empty()
+                || declared == "unknown"
+                || (declared.starts_with("varchar") && canonical != ColumnType::String) =>
+        {
+            canonical
+        }
+        _ => ColumnType::from_trino(declared),
+    }
+}
+
+/// Polars schema of result columns, with the types `rows_to_dataframe` gives them.
+fn trino_schema(columns: &[TrinoColumn]) -> Schema {
+    columns
+        .iter()
+        .map(|c| Field::new(c.name.as_str().into(), result_type(c).to_polars()))
+        .collect()
+}
+
+/// Log parameter combinations that are accepted but probably not intended.
+fn log_warnings(params: &QueryParams) {
+    for warning in params.warnings() {
+        tracing::warn!("{}", warning);
+    }
+}
+
+/// Successive pages of a history query, see [`Trino::history_pages`].
+pub struct HistoryPages<'a> {
+    trino: &'a mut Trino,
+    params: QueryParams,
+    page_size: u32,
+    offset: u64,
+    remaining: Option<u64>,
+    done: bool,
+}
+
+impl HistoryPages<'_> {
+    /// Fetch the next page, or `None` once all rows have been returned.
+    pub async fn next(&mut self) -> Option<Result<FlightData>> {
+        let page_size = match self.remaining {
+            Some(remaining) => remaining.min(u64::from(self.page_size)) as u32,
+            None => self.page_size,
+        };
+        if self.done || page_size == 0 {
+            return None;
+        }
+
+        let mut params = self.params.clone();
+        params.offset = Some(self.offset);
+        params.limit = Some(page_size);
+        let checked = params
+            .validate()
+            .and_then(|_| match params.order {
+                Order::None => Err(OpenSkyError::InvalidParam(
+                    "Paged queries need an order, Order::None is not supported".to_string(),
+                )),
+                _ => Ok(()),
+            })
+            .and_then(|_| params.validate_columns(FLIGHT_COLUMNS))
+            .and_then(|_| match self.trino.cache_policy {
+                CachePolicy::ReadOnly => Err(OpenSkyError::CacheMiss { key: self.trino.cache_key(&params) }),
+                _ => Ok(()),
+            })
+            .and_then(|_| self.trino.config.split_query(&params, false));
+        if let Err(e) = checked {
+            self.done = true;
+            return Some(Err(e));
+        }
+
+        let sql = match build_history_page_query(&params) {
+            Ok(sql) => sql,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+        let data = match self.trino.execute_query(&sql, &history_columns(&params)).await {
+            Ok(data) => data,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+
+        let rows = data.len() as u64;
+        self.offset += rows;
+        self.remaining = self.remaining.map(|r| r - rows);
+        self.done = rows < u64::from(page_size);
+
+        if rows == 0 {
+            None
+        } else {
+            Some(Ok(data))
+        }
+    }
+
+    /// Offset of the next page.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+/// One response page of a statement, see [`Trino::execute_raw`].
+#[derive(Debug, Clone)]
+pub struct TrinoPage {
+    pub query_id: Option<String>,
+    /// Result columns; empty until the server has reported them
+    pub columns: Vec<TrinoColumn>,
+    /// Rows of this page, one JSON value per column
+    pub rows: Vec<Vec<serde_json::Value>>,
+    /// Progress after this page, with the rows received so far
+    pub status: QueryStatus,
+    /// Warnings Trino raised for the query so far
+    pub warnings: Vec<TrinoWarning>,
+}
+
+/// Response pages of a statement, see [`Trino::execute_raw`].
+pub struct RawPages<'a> {
+    trino: &'a mut Trino,
+    sql: String,
+    started: Option<std::time::Instant>,
+    next_uri: Option<String>,
+    query_id: Option<String>,
+    columns: Vec<TrinoColumn>,
+    warnings: Vec<TrinoWarning>,
+    row_count: usize,
+    running: RunningStats,
+    done: bool,
+}
+
+impl RawPages<'_> {
+    /// Fetch the next page, or `None` once the query has finished.
+    pub async fn next(&mut self) -> Option<Result<TrinoPage>> {
+        if self.done {
+            return None;
+        }
+        let page = match (self.started, self.next_uri.take()) {
+            (None, _) => {
+                self.started = Some(std::time::Instant::now());
+                self.trino.submit_statement(&self.sql).await
+            }
+            (Some(_), Some(next_uri)) => {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                self.trino.fetch_page(&next_uri).await
+            }
+            (Some(_), None) => return None,
+        };
+        let response = page.and_then(|page| Ok(serde_json::from_value::<TrinoResponse>(page)?));
+        let mut response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e.in_query(self.query_id.as_deref())));
+            }
+        };
+
+        if self.query_id.is_none() {
+            self.query_id = response.id.clone();
+        }
+        if let Some(error) = response.error.take() {
+            self.done = true;
+            return Some(Err(OpenSkyError::Query(Box::new(error)).in_query(self.query_id.as_deref())));
+        }
+        note_warnings(&mut self.warnings, std::mem::take(&mut response.warnings), self.query_id.as_deref());
+        if let (true, Some(columns)) = (self.columns.is_empty(), response.columns) {
+            self.columns = columns;
+        }
+        let rows = response.data.unwrap_or_default();
+        self.row_count += rows.len();
+        self.running.set_columns(&self.columns);
+        self.running.add(&rows);
+        self.next_uri = response.next_uri;
+        self.done = self.next_uri.is_none();
+
+        let status = QueryStatus::from_stats(
+            self.query_id.clone(),
+            response.stats.as_ref(),
+            self.row_count,
+            self.started.unwrap_or_else(std::time::Instant::now),
+        )
+        .with_running(&self.running);
+        Some(Ok(TrinoPage {
+            query_id: self.query_id.clone(),
+            columns: self.columns.clone(),
+            rows,
+            status,
+            warnings: self.warnings.clone(),
+        }))
+    }
+}
+
+/// Log the warnings of a response page not seen before, adding them to
+/// `seen`; Trino repeats them on every page.
+fn note_warnings(seen: &mut Vec<TrinoWarning>, page: Vec<TrinoWarning>, query_id: Option<&str>) {
+    for warning in page {
+        if !seen.contains(&warning) {
+            tracing::warn!("Query {}: {} ({})", query_id.unwrap_or("(no id)"), warning.message, warning.code);
+            seen.push(warning);
+        }
+    }
+}
+
+/// `Authorization` header value for a token, kept out of debug output.
+///
+/// Fails for a token that cannot be sent in a header, e.g. one holding a
+/// line break, rather than sending the request unauthenticated.
+fn bearer(token: &SecretString) -> Result<reqwest::header::HeaderValue> {
+    let value = Zeroizing::new(format!("Bearer {}", token.expose_secret()));
+    let mut header = reqwest::header::HeaderValue::from_str(&value)
+        .map_err(|_| OpenSkyError::Auth("Access token holds characters not allowed in a header".to_string()))?;
+    header.set_sensitive(true);
+    Ok(header)
+}
+
+/// Compare the columns of a result with the expected ones, returning the
+/// differences to record; with [`SchemaCheck::Strict`], differences fail.
+fn check_schema(check: SchemaCheck, columns: &[TrinoColumn], expected: &[&str]) -> Result<Vec<String>> {
+    // Nothing to compare without column information or expectations
+    if columns.is_empty() || expected.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mismatch = schema::schema_mismatches(expected, columns.iter().map(|c| (c.name.as_str(), c.col_type.as_str())));
+    if mismatch.is_empty() {
+        return Ok(mismatch);
+    }
+    match check {
+        SchemaCheck::Strict => Err(OpenSkyError::DataConversion(format!(
+            "Result columns do not match the expected schema: {}",
+            mismatch.join("; ")
+        ))),
+        SchemaCheck::Lenient => {
+            tracing::warn!("Result columns differ from the expected schema: {}", mismatch.join("; "));
+            Ok(mismatch)
+        }
+    }
+}
+
+/// Drop repeated (icao24, time) rows of a day of an airport join.
+fn dedup_states(data: FlightData) -> Result<FlightData> {
+    if data.is_empty() {
+        return Ok(data);
+    }
+    let subset = ["icao24".to_string(), "time".to_string()];
+    let df = data
+        .dataframe()
+        .unique_stable(Some(&subset), UniqueKeepStrategy::First, None)
+        .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+    Ok(data.with_dataframe(df))
+}
+
+/// Split the time range of `params` at UTC midnight.
+///
+/// Days after the first also join the flights of the day before, which the
+/// whole range would have matched: a `time_buffer` of at least a day.
+fn split_by_day(params: &QueryParams) -> Vec<QueryParams> {
+    let (Some(start), Some(stop)) = (&params.start, &params.stop) else {
+        return vec![params.clone()];
+    };
+    let format = |t: chrono::NaiveDateTime| t.format("%Y-%m-%d %H:%M:%S").to_string();
+    let (Ok(stop), Ok(mut from)) = (parse_bound(stop, true), parse_bound(start, false)) else {
+        return vec![params.clone()];
+    };
+    let short_buffer = time_buffer_seconds(params).is_ok_and(|buffer| buffer < 86_400);
+    let mut parts: Vec<QueryParams> = Vec::new();
+    while from <= stop {
+        let midnight = (from.date() + chrono::Duration::days(1)).and_hms_opt(0, 0, 0).unwrap_or(stop);
+        let to = (midnight - chrono::Duration::seconds(1)).min(stop);
+        let mut part = params.clone();
+        part.start = Some(format(from));
+        part.stop = Some(format(to));
+        if !parts.is_empty() && short_buffer {
+            part.time_buffer = Some("1d".to_string());
+        }
+        parts.push(part);
+        from = midnight;
+    }
+    parts
+}
+
+/// Hash identifying a statement, for [`Trino::submit_statement`].
+fn statement_hash(sql: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    sql.trim().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Turn a non-success HTTP status into an error.
+fn check_status(status: u16, url: &str) -> Result<()> {
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(OpenSkyError::HttpStatus {
+            status,
+            url: url.to_string(),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -701,9 +2359,1079 @@ mod tests {
     #[test]
     fn test_token_info() {
         let token = TokenInfo {
-            access_token: "test".to_string(),
+            access_token: "test".into(),
             expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+            refresh_token: None,
+            refresh_expires_at: None,
+            external: false,
+        };
+        assert!(!token.access_token.expose_secret().is_empty());
+        assert!(!format!("{:?}", token).contains("test"));
+    }
+
+    #[tokio::test]
+    async fn test_external_token() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let fixture = Fixture::from_rows(&[("icao24", "varchar")], vec![vec!["485a32".into()]]);
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = Trino::with_config(Config::default())
+            .await
+            .unwrap()
+            .with_token("brokered", chrono::Utc::now() + chrono::Duration::hours(1));
+        trino.set_endpoints(mock.auth_url(), mock.trino_url());
+
+        trino.execute_query("SELECT 1", FLIGHT_COLUMNS).await.unwrap();
+        assert!(mock.grants().is_empty());
+
+        trino.set_token("brokered", chrono::Utc::now());
+        let err = trino.execute_query("SELECT 1", FLIGHT_COLUMNS).await.unwrap_err();
+        assert!(matches!(err, OpenSkyError::Auth(_)), "{}", err);
+        assert!(mock.grants().is_empty());
+
+        // A token unfit for a header is not sent without it
+        trino.set_token("broken\ntoken", chrono::Utc::now() + chrono::Duration::hours(1));
+        let err = trino.execute_query("SELECT 2", FLIGHT_COLUMNS).await.unwrap_err();
+        assert!(matches!(err, OpenSkyError::Auth(_)), "{}", err);
+        assert_eq!(mock.statements().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_token_refresh() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let fixture = Fixture::from_rows(&[("icao24", "varchar")], vec![vec!["485a32".into()]]);
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        let expire = |trino: &mut Trino| {
+            let token = trino.token.as_mut().unwrap();
+            token.expires_at = chrono::Utc::now();
+        };
+
+        trino.execute_query("SELECT 1", FLIGHT_COLUMNS).await.unwrap();
+        assert_eq!(trino.token.as_ref().unwrap().refresh_token.as_ref().unwrap().expose_secret(), "mock-refresh");
+
+        expire(&mut trino);
+        trino.execute_query("SELECT 1", FLIGHT_COLUMNS).await.unwrap();
+        assert_eq!(mock.grants(), ["password", "refresh_token"]);
+
+        // A revoked refresh token falls back to the password
+        expire(&mut trino);
+        trino.token.as_mut().unwrap().refresh_token = Some("revoked".into());
+        trino.execute_query("SELECT 1", FLIGHT_COLUMNS).await.unwrap();
+        assert_eq!(mock.grants(), ["password", "refresh_token", "refresh_token", "password"]);
+    }
+
+    #[test]
+    fn test_status_rates_and_eta() {
+        let stats = TrinoStats {
+            state: "RUNNING".to_string(),
+            progress_percentage: Some(25.0),
+            processed_rows: Some(1_000_000),
+            processed_bytes: None,
+        };
+        let started = std::time::Instant::now() - Duration::from_secs(10);
+        let status = QueryStatus::from_stats(None, Some(&stats), 0, started);
+        assert!((status.eta.unwrap() - 30.0).abs() < 0.5);
+        assert!((status.rows_per_sec.unwrap() - 100_000.0).abs() < 5_000.0);
+        assert!(status.bytes_per_sec.is_none());
+
+        let status = QueryStatus::from_stats(None, None, 0, started);
+        assert!(status.eta.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cassette_replays_without_server() {
+        use crate::cassette::CassetteMode;
+        use crate::testing::{Fixture, MockTrino};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        let fixture = Fixture::from_rows(
+            &[("icao24", "varchar"), ("firstseen", "bigint"), ("lastseen", "bigint")],
+            vec![vec!["485a32".into(), 1.into(), 2.into()]],
+        );
+        let params = QueryParams::new().icao24("485a32");
+
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        trino.set_cassette(Cassette::open(&path, CassetteMode::Record).unwrap());
+        let recorded = trino.flightlist(params.clone()).await.unwrap();
+
+        // Same endpoints, but the server is gone
+        let mut trino = mock.client().await.unwrap();
+        drop(mock);
+        trino.set_cassette(Cassette::open(&path, CassetteMode::Replay).unwrap());
+        let replayed = trino.flightlist(params).await.unwrap();
+
+        assert_eq!(recorded.len(), 1);
+        assert!(recorded.dataframe().equals(replayed.dataframe()));
+
+        // Credentials never end up in the cassette
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("password"));
+        assert!(!content.contains("mock-token"));
+        assert!(!content.contains("mock-refresh"));
+    }
+
+    #[tokio::test]
+    async fn test_verify_credentials() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let mock = MockTrino::start(Fixture::from_rows(&[("_col0", "integer")], vec![vec![1.into()]]))
+            .await
+            .unwrap();
+        let mut trino = mock.client().await.unwrap();
+        trino.verify_credentials().await.unwrap();
+        assert_eq!(mock.statements(), ["SELECT 1"]);
+
+        let mock = MockTrino::start(Fixture::error("Access Denied", "PERMISSION_DENIED")).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        match trino.verify_credentials().await {
+            Err(OpenSkyError::Verify { step, .. }) => assert_eq!(step, "Test query"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        trino.config.password = None;
+        match trino.verify_credentials().await {
+            Err(OpenSkyError::Verify { step, .. }) => assert_eq!(step, "Configuration"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_configured_cache_dir() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = Fixture::from_rows(
+            &[("icao24", "varchar"), ("time", "bigint")],
+            vec![vec!["485a32".into(), 1.into()]],
+        );
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        trino.config.cache_dir = Some(dir.path().join("cache"));
+
+        let params = QueryParams::new().icao24("485a32");
+        trino.history(params.clone()).await.unwrap();
+        let cached = trino.history(params.clone()).await.unwrap();
+
+        assert_eq!(cached.len(), 1);
+        assert_eq!(mock.statements().len(), 1);
+        // Cache hits report the SQL stored with the entry
+        assert_eq!(trino.last_report().unwrap().sql, mock.statements()[0]);
+        assert!(dir.path().join("cache").join(cache::cache_key(&params)).exists());
+    }
+
+    #[tokio::test]
+    async fn test_estimate() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let plan = serde_json::json!({
+            "inputTableColumnInfos": [
+                { "estimate": { "outputRowCount": 2.5e6, "outputSizeInBytes": 3.0e8 } },
+                { "estimate": { "outputRowCount": 1000.0, "outputSizeInBytes": "NaN" } },
+            ],
+        });
+        let fixture = Fixture::from_rows(&[("Query Plan", "varchar")], vec![vec![plan.to_string().into()]]);
+        let mock = MockTrino::with_fixtures(vec![fixture, Fixture::error("Access denied", "PERMISSION_DENIED")])
+            .await
+            .unwrap();
+        let mut trino = mock.client().await.unwrap();
+
+        let params = QueryParams::new().icao24("485a32").time_range("2025-01-01", "2025-01-02");
+        let estimate = trino.estimate(params.clone()).await.unwrap();
+        assert_eq!(estimate.hours, Some(48));
+        assert_eq!(estimate.queries, 1);
+        assert_eq!(estimate.rows, Some(2_501_000));
+        assert_eq!(estimate.bytes, None);
+        assert!(mock.statements()[0].starts_with("EXPLAIN (TYPE IO, FORMAT JSON) SELECT"));
+        assert_eq!(estimate.to_string(), "48 hour partitions in 1 query, ~2501000 rows");
+
+        // Without EXPLAIN, only the partitions are counted
+        let estimate = trino.estimate(params).await.unwrap();
+        assert_eq!((estimate.hours, estimate.rows), (Some(48), None));
+    }
+
+    #[tokio::test]
+    async fn test_client_table_names() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = Fixture::from_rows(
+            &[("icao24", "varchar"), ("time", "bigint")],
+            vec![vec!["485a32".into(), 1.into()]],
+        );
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut renamed = mock.client().await.unwrap();
+        renamed.config.tables = crate::TableNames::default().version("data5");
+        renamed.config.cache_dir = Some(dir.path().to_path_buf());
+        let mut current = mock.client().await.unwrap();
+        current.config.cache_dir = Some(dir.path().to_path_buf());
+
+        // Each client queries its own tables, with its own cache entries
+        let params = QueryParams::new().icao24("485a32").time_range("2025-01-01 10:00:00", "2025-01-01 11:00:00");
+        renamed.history(params.clone()).await.unwrap();
+        current.history(params.clone()).await.unwrap();
+        let statements = mock.statements();
+        assert_eq!(statements.len(), 2);
+        assert!(statements[0].contains("FROM minio.osky.state_vectors_data5"));
+        assert!(statements[1].contains("FROM minio.osky.state_vectors_data4"));
+        assert_ne!(renamed.cache_key(&params), current.cache_key(&params));
+        assert_eq!(current.cache_key(&params), cache::cache_key(&params));
+        assert!(dir.path().join(renamed.cache_key(&params)).exists());
+
+        // Tables named in the parameters take precedence
+        let params = params.tables(crate::TableNames::default().version("data6"));
+        renamed.history(params.clone()).await.unwrap();
+        assert!(mock.statements()[2].contains("FROM minio.osky.state_vectors_data6"));
+        assert_eq!(renamed.cache_key(&params), current.cache_key(&params));
+    }
+
+    #[tokio::test]
+    async fn test_cache_policy() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = Fixture::from_rows(
+            &[("icao24", "varchar"), ("time", "bigint")],
+            vec![vec!["485a32".into(), 1.into()]],
+        );
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        trino.config.cache_dir = Some(dir.path().join("cache"));
+        let params = QueryParams::new().icao24("485a32");
+        let key = dir.path().join("cache").join(cache::cache_key(&params));
+
+        trino.set_cache_policy(CachePolicy::Off);
+        trino.history(params.clone()).await.unwrap();
+        trino.history(params.clone()).await.unwrap();
+        assert_eq!(mock.statements().len(), 2);
+        assert!(!key.exists());
+
+        // Misses fail without querying Trino
+        trino.set_cache_policy(CachePolicy::ReadOnly);
+        let err = trino.history(params.clone()).await.unwrap_err();
+        assert!(matches!(err, OpenSkyError::CacheMiss { .. }));
+        assert_eq!(mock.statements().len(), 2);
+
+        trino.set_cache_policy(CachePolicy::ReadWrite);
+        trino.history(params.clone()).await.unwrap();
+        assert!(key.exists());
+        trino.set_cache_policy(CachePolicy::ReadOnly);
+        assert_eq!(trino.history(params.clone()).await.unwrap().cache_status(), CacheStatus::Hit);
+        assert!(trino.history_cached(params.clone(), false).await.is_err());
+        assert!(key.exists());
+        assert_eq!(mock.statements().len(), 3);
+
+        // Paths that bypass the cache serve it or fail instead of querying
+        let data = trino.history_into(params.clone(), crate::sink::DataFrameSink::new()).await.unwrap();
+        assert_eq!(data.len(), 1);
+        let (_, result) = trino.history_with_progress_stream(params.clone());
+        assert_eq!(result.await.unwrap().cache_status(), CacheStatus::Hit);
+        let missing = QueryParams::new().icao24("3c6444");
+        let err = trino.history_into(missing.clone(), crate::sink::DataFrameSink::new()).await.unwrap_err();
+        assert!(matches!(err, OpenSkyError::CacheMiss { .. }));
+        let err = trino.history_pages(params.clone(), 10).next().await.unwrap().unwrap_err();
+        assert!(matches!(err, OpenSkyError::CacheMiss { .. }));
+        let flights = FlightList::new(
+            df!(
+                "icao24" => ["485a32"],
+                "firstseen" => [1735725600i64],
+                "lastseen" => [1735729200i64],
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let err = trino.history_for_flights(&flights, chrono::Duration::zero()).await.unwrap_err();
+        assert!(matches!(err, OpenSkyError::CacheMiss { .. }));
+        assert_eq!(mock.statements().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_split_history() {
+        use crate::config::SpanPolicy;
+        use crate::testing::{Fixture, MockTrino};
+
+        let dir = tempfile::tempdir().unwrap();
+        let row = |time: i64| {
+            Fixture::from_rows(&[("icao24", "varchar"), ("time", "bigint")], vec![vec!["485a32".into(), time.into()]])
+        };
+        let mock = MockTrino::with_fixtures(vec![row(1), row(2), row(3)]).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        trino.config.cache_dir = Some(dir.path().to_path_buf());
+        trino.config.query_max_span = Some(chrono::Duration::days(1));
+
+        let params = QueryParams::new().icao24("485a32").time_range("2025-01-01", "2025-01-03");
+        assert!(trino.history(params.clone()).await.is_err());
+        assert!(mock.statements().is_empty());
+
+        trino.config.query_span_policy = Some(SpanPolicy::Split);
+        let data = trino.history(params.clone()).await.unwrap();
+        assert_eq!(data.len(), 3);
+        assert_eq!(mock.statements().len(), 3);
+        assert!(mock.statements()[1].contains("AND time >= 1735776000\n  AND time <= 1735862399"));
+        assert!(trino.flightlist(params).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_airport_join_by_day() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let rows = |times: &[i64]| {
+            Fixture::from_rows(
+                &[("icao24", "varchar"), ("time", "bigint")],
+                times.iter().map(|&t| vec!["485a32".into(), t.into()]).collect(),
+            )
+        };
+        // A state vector of the second day falls within two flights
+        let fixtures = || vec![rows(&[1735740000]), rows(&[1735830000, 1735830000, 1735850000]), rows(&[1735870000])];
+        let mock = MockTrino::with_fixtures(fixtures()).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+
+        let range = QueryParams::new()
+            .departure("EHAM")
+            .time_range("2025-01-01 12:00:00", "2025-01-03 18:00:00");
+        let data = trino.history_cached(range.clone(), false).await.unwrap();
+        let statements = mock.statements();
+        assert_eq!(statements.len(), 3);
+        assert!(statements[0].contains("sv.time >= 1735732800\n  AND sv.time <= 1735775999"));
+        assert!(statements[2].contains("sv.time >= 1735862400\n  AND sv.time <= 1735927200"));
+        // Later days join the flights of the day before, the first does not
+        assert!(statements[0].contains("day >= 1735689600"));
+        assert!(statements[1].contains("day >= 1735689600"));
+        assert!(statements[2].contains("day >= 1735776000"));
+        let times: Vec<_> = data.dataframe().column("time").unwrap().i64().unwrap().into_no_null_iter().collect();
+        assert_eq!(times, [1735740000, 1735830000, 1735850000, 1735870000]);
+
+        // Short ranges stay one query, without looking back
+        let params = QueryParams::new().departure("EHAM").time_range("2025-01-02", "2025-01-03");
+        trino.history_cached(params, false).await.unwrap();
+        assert_eq!(mock.statements().len(), 4);
+        assert!(mock.statements()[3].contains("day >= 1735776000"));
+
+        // The limit counts rows without duplicates
+        let mock = MockTrino::with_fixtures(fixtures()).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        let data = trino.history_cached(range.limit(4), false).await.unwrap();
+        assert_eq!(mock.statements().len(), 3);
+        assert_eq!(data.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_history_chunked() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let dir = tempfile::tempdir().unwrap();
+        let rows = |times: &[i64]| {
+            Fixture::from_rows(
+                &[("icao24", "varchar"), ("time", "bigint")],
+                times.iter().map(|&t| vec!["485a32".into(), t.into()]).collect(),
+            )
+        };
+        // The second day fails once, then succeeds on retry
+        let fixtures = vec![
+            rows(&[1735740000]),
+            Fixture::error("Worker node went away", "REMOTE_HOST_GONE"),
+            rows(&[1735870000]),
+            rows(&[1735830000]),
+        ];
+        let mock = MockTrino::with_fixtures(fixtures).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        trino.config.cache_dir = Some(dir.path().to_path_buf());
+
+        let params = QueryParams::new()
+            .departure("EHAM")
+            .time_range("2025-01-01 12:00:00", "2025-01-03 18:00:00");
+        let mut result = trino.history_chunked(params.clone()).await.unwrap();
+        assert!(!result.is_complete());
+        assert_eq!(result.data.len(), 2);
+        let ((start, stop), error) = &result.failed[0];
+        assert_eq!((start.as_str(), stop.as_str()), ("2025-01-02 00:00:00", "2025-01-02 23:59:59"));
+        assert!(matches!(error, OpenSkyError::Query(_)));
+
+        result.retry_failed(&mut trino).await.unwrap();
+        assert!(result.is_complete());
+        assert_eq!(mock.statements().len(), 4);
+        let times: Vec<_> = result.data.dataframe().column("time").unwrap().i64().unwrap().into_no_null_iter().collect();
+        assert_eq!(times, [1735740000, 1735830000, 1735870000]);
+        assert_eq!(result.data.cache_status(), CacheStatus::Miss);
+        assert_eq!(result.data.cache_files().len(), 3);
+
+        // Chunks cached by the first run are served from the cache
+        std::fs::remove_file(&result.data.cache_files()[0]).unwrap();
+        let again = trino.history_chunked(params.clone()).await.unwrap();
+        assert_eq!(again.data.cache_status(), CacheStatus::Partial);
+        assert_eq!(again.data.metadata().cache, CacheStatus::Partial);
+        let report = trino.last_report().unwrap();
+        assert_eq!((report.cache, report.cache_files.as_slice()), (CacheStatus::Partial, again.data.cache_files()));
+        let again = trino.history_chunked(params.clone()).await.unwrap();
+        assert_eq!(again.data.cache_status(), CacheStatus::Hit);
+        assert_eq!(mock.statements().len(), 5);
+
+        // Without chunked handling the failure fails the query
+        let mock = MockTrino::with_fixtures(vec![rows(&[1735740000]), Fixture::error("boom", "REMOTE_HOST_GONE")])
+            .await
+            .unwrap();
+        let mut trino = mock.client().await.unwrap();
+        assert!(trino.history_cached(params, false).await.is_err());
+        assert_eq!(mock.statements().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_progress_running_stats() {
+        use crate::testing::{Fixture, MockTrino};
+        use serde_json::json;
+
+        let columns = json!([{ "name": "time", "type": "bigint" }, { "name": "icao24", "type": "varchar" }]);
+        let fixture = Fixture {
+            pages: vec![
+                json!({ "id": "q1", "columns": columns, "data": [[20, "485a32"], [10, "3c6444"]], "stats": { "state": "RUNNING" } }),
+                json!({ "id": "q1", "data": [[30, "485a32"]], "stats": { "state": "FINISHED", "progressPercentage": 100.0 } }),
+            ],
+        };
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+
+        let mut statuses = Vec::new();
+        trino
+            .execute_query_with_progress("SELECT time, icao24 FROM t", FLIGHT_COLUMNS, |status| statuses.push(status))
+            .await
+            .unwrap();
+        let running: Vec<_> = statuses.iter().map(|s| (s.aircraft, s.first_time, s.last_time)).collect();
+        assert_eq!(running, [(Some(2), Some(10), Some(20)), (Some(2), Some(10), Some(30))]);
+
+        // Raw pages carry the same
+        let mut pages = trino.execute_raw("SELECT time, icao24 FROM t");
+        let mut last = None;
+        while let Some(page) = pages.next().await {
+            last = Some(page.unwrap().status);
+        }
+        let last = last.unwrap();
+        assert_eq!((last.aircraft, last.first_time, last.last_time), (Some(2), Some(10), Some(30)));
+    }
+
+    #[tokio::test]
+    async fn test_narrow_on_resource_error() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let rows = |times: &[i64]| {
+            Fixture::from_rows(
+                &[("icao24", "varchar"), ("time", "bigint")],
+                times.iter().map(|&t| vec!["485a32".into(), t.into()]).collect(),
+            )
+        };
+        let memory = || Fixture::error("Query exceeded per-node memory limit", "EXCEEDED_LOCAL_MEMORY_LIMIT");
+        // The whole day and its first half run out of memory
+        let fixtures = vec![memory(), memory(), rows(&[1735700000]), rows(&[1735720000]), rows(&[1735760000])];
+        let mock = MockTrino::with_fixtures(fixtures).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        trino.config.cache_dir = Some(dir.path().to_path_buf());
+
+        let mut splits = 0;
+        let params = QueryParams::new().icao24("485a32").time_range("2025-01-01", "2025-01-01");
+        let data = trino
+            .history_with_progress_cached(params.clone(), false, |status| splits += (status.state == "SPLIT") as u32)
+            .await
+            .unwrap();
+        assert_eq!(splits, 2);
+        let statements = mock.statements();
+        assert_eq!(statements.len(), 5);
+        assert!(statements[1].contains("AND time >= 1735689600\n  AND time <= 1735732799"));
+        assert!(statements[2].contains("AND time >= 1735689600\n  AND time <= 1735711199"));
+        assert!(statements[4].contains("AND time >= 1735732800\n  AND time <= 1735775999"));
+        let times: Vec<_> = data.dataframe().column("time").unwrap().i64().unwrap().into_no_null_iter().collect();
+        assert_eq!(times, [1735700000, 1735720000, 1735760000]);
+        let report = trino.last_report().unwrap();
+        assert_eq!(report.rows, 3);
+        assert_eq!(report.sql, [&statements[2], &statements[3], &statements[4]].map(String::as_str).join(";\n"));
+        // The manifest records the statements that ran, not the original query
+        let manifest = cache::cached_manifest_in(dir.path(), &params).unwrap();
+        assert_eq!(manifest.sql, report.sql);
+
+        // Bounded: a part still failing after NARROW_DEPTH splits fails the query
+        let mock = MockTrino::start(memory()).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        trino.config.cache_dir = Some(dir.path().to_path_buf());
+        let err = trino.history_cached(params.clone(), false).await.unwrap_err();
+        assert!(is_resource_error(&err));
+        assert_eq!(mock.statements().len(), 1 + NARROW_DEPTH as usize);
+
+        // The halves share the row cap
+        let fixtures = vec![memory(), memory(), rows(&[1735700000]), rows(&[1735720000]), rows(&[1735760000])];
+        let mock = MockTrino::with_fixtures(fixtures).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        trino.config.cache_dir = Some(dir.path().to_path_buf());
+        trino.set_max_rows(Some(1));
+        let data = trino.history_cached(params, false).await.unwrap();
+        assert_eq!(data.len(), 1);
+        assert!(data.is_truncated());
+        assert_eq!(mock.statements().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_latest_available_time() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let dir = tempfile::tempdir().unwrap();
+        let hour = (chrono::Utc::now().timestamp() / 3600 - 3) * 3600;
+        let latest = Fixture::from_rows(&[("_col0", "bigint")], vec![vec![hour.into()]]);
+        let mock = MockTrino::with_fixtures(vec![latest]).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        trino.config.cache_dir = Some(dir.path().to_path_buf());
+
+        assert_eq!(trino.latest_available_time().await.unwrap().timestamp(), hour);
+        trino.latest_available_time().await.unwrap();
+        assert_eq!(mock.statements().len(), 1);
+        assert!(mock.statements()[0].contains("SELECT max(hour) FROM minio.osky.state_vectors_data4"));
+
+        // Starts after the latest hour: rejected without running the query
+        let format = |t: i64| chrono::DateTime::from_timestamp(t, 0).unwrap().format("%Y-%m-%d %H:%M:%S").to_string();
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range(format(hour + 7200), format(hour + 9000));
+        assert!(matches!(trino.history(params).await, Err(OpenSkyError::InvalidParam(_))));
+        assert_eq!(mock.statements().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_history_for_flights() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let rows = Fixture::from_rows(
+            &[("icao24", "varchar"), ("time", "bigint"), ("callsign", "varchar")],
+            vec![
+                vec!["485a32".into(), 1735725300.into(), "KLM1234".into()],
+                vec!["485a32".into(), 1735729200.into(), "KLM1234".into()],
+                vec!["3c6444".into(), 1735812100.into(), "DLH5".into()],
+            ],
+        );
+        let mock = MockTrino::with_fixtures(vec![rows]).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+
+        let df = df!(
+            "icao24" => ["485a32", "485a32", "3c6444"],
+            "callsign" => [Some("KLM1234"), Some("KLM1234"), Some("DLH5")],
+            "firstseen" => [1735725600i64, 1735729000, 1735812600],
+            "lastseen" => [1735728000i64, 1735731000, 1735816200],
+        )
+        .unwrap();
+        let flights = FlightList::new(df).unwrap();
+        let data = trino.history_for_flights(&flights, chrono::Duration::minutes(10)).await.unwrap();
+
+        // One query; the two overlapping windows of 485a32 are merged
+        let statements = mock.statements();
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].matches("(icao24 = ").count(), 2);
+        assert!(statements[0].contains("(icao24 = '485a32' AND time >= 1735725000 AND time <= 1735731600"));
+
+        // The first row lies in the buffer before takeoff
+        let flight_id = data.dataframe().column("flight_id").unwrap().str().unwrap().clone();
+        assert_eq!(flight_id.get(0), Some("485a32_1735725600"));
+        assert_eq!(flight_id.get(2), Some("3c6444_1735812600"));
+    }
+
+    #[tokio::test]
+    async fn test_max_rows() {
+        use crate::testing::{Fixture, MockTrino};
+        use serde_json::json;
+
+        let columns = json!([{ "name": "icao24", "type": "varchar" }, { "name": "time", "type": "bigint" }]);
+        let page = |first: i64| {
+            json!({
+                "id": "q1",
+                "columns": columns,
+                "data": [["485a32", first], ["485a32", first + 1]],
+                "stats": { "state": "RUNNING" },
+            })
+        };
+        let fixture = Fixture {
+            pages: vec![page(0), page(2), page(4)],
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        trino.config.cache_dir = Some(dir.path().to_path_buf());
+
+        // Exactly the cap is not truncated
+        trino.set_max_rows(Some(6));
+        let data = trino.execute_query("SELECT 1", FLIGHT_COLUMNS).await.unwrap();
+        assert_eq!(data.len(), 6);
+        assert!(!data.is_truncated());
+
+        trino.set_max_rows(Some(3));
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01 10:00:00", "2025-01-01 11:00:00");
+        let data = trino.history(params.clone()).await.unwrap();
+        assert_eq!(data.len(), 3);
+        assert!(data.is_truncated());
+        assert!(data.metadata().truncated);
+        assert!(!dir.path().join("cache").join(cache::cache_key(&params)).exists());
+    }
+
+    #[tokio::test]
+    async fn test_max_rows_across_chunks() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let dir = tempfile::tempdir().unwrap();
+        let rows = |time: i64| {
+            Fixture::from_rows(
+                &[("icao24", "varchar"), ("time", "bigint")],
+                vec![vec!["485a32".into(), time.into()], vec!["485a32".into(), (time + 1).into()]],
+            )
         };
-        assert!(!token.access_token.is_empty());
+        let mock = MockTrino::with_fixtures(vec![rows(1735740000), rows(1735830000), rows(1735920000)]).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        trino.config.cache_dir = Some(dir.path().to_path_buf());
+        trino.set_max_rows(Some(3));
+
+        // Split by day, two rows a day: the second day uses up the cap
+        let params = QueryParams::new()
+            .departure("EHAM")
+            .time_range("2025-01-01 12:00:00", "2025-01-03 18:00:00");
+        let result = trino.history_chunked(params).await.unwrap();
+        assert_eq!(result.data.len(), 3);
+        assert!(result.data.is_truncated());
+        assert_eq!(mock.statements().len(), 2);
+        assert_eq!(trino.max_rows, Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_schema_check() {
+        use crate::sink::DataFrameSink;
+        use crate::testing::{Fixture, MockTrino};
+        use serde_json::json;
+
+        let fixture = Fixture::from_rows(
+            &[("icao24", "varchar"), ("time", "varchar"), ("extra", "double")],
+            vec![vec![json!("485a32"), json!("1735725600"), json!(1.0)]],
+        );
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        let expected = ["icao24", "time", "lat"];
+
+        let data = trino.execute_query("SELECT 1", &expected).await.unwrap();
+        assert_eq!(
+            data.schema_mismatch(),
+            ["missing column 'lat'", "column 'time' is varchar, expected Int64", "unexpected column 'extra' (double)"]
+        );
+        assert_eq!(data.metadata().schema_mismatch.len(), 3);
+
+        trino.set_schema_check(SchemaCheck::Strict);
+        let err = trino.execute_query("SELECT 1", &expected).await.unwrap_err();
+        assert!(matches!(err, OpenSkyError::DataConversion(ref m) if m.contains("missing column 'lat'")));
+        let err = trino.execute_into("SELECT 1", &expected, DataFrameSink::new()).await.unwrap_err();
+        assert!(matches!(err, OpenSkyError::DataConversion(_)));
+
+        // Without expected columns there is nothing to check
+        let data = trino.execute_query("SELECT 1", &[]).await.unwrap();
+        assert!(data.schema_mismatch().is_empty());
+    }
+
+    #[test]
+    fn test_declared_types() {
+        use serde_json::json;
+
+        let column = |name: &str, col_type: &str| TrinoColumn { name: name.into(), col_type: col_type.into() };
+        let columns = [
+            column("lat", "bigint"),
+            column("time", "varchar"),
+            column("hour", ""),
+            column("velocity", "varchar(8)"),
+            column("day", "double"),
+        ];
+        let df = Trino::rows_to_dataframe(
+            &columns,
+            vec![vec![json!(52), json!("1735725600"), json!(1735725600), json!("fast"), json!(1.5)]],
+            &[],
+        )
+        .unwrap();
+
+        // Declared types win, canonical ones fill in for missing or varchar
+        // declarations of typed columns
+        assert_eq!(df.column("lat").unwrap().dtype(), &DataType::Int64);
+        assert_eq!(df.column("time").unwrap().i64().unwrap().get(0), Some(1735725600));
+        assert_eq!(df.column("hour").unwrap().dtype(), &DataType::Int64);
+        assert_eq!(df.column("velocity").unwrap().f64().unwrap().get(0), None);
+        assert_eq!(df.column("day").unwrap().f64().unwrap().get(0), Some(1.5));
+        assert_eq!(trino_schema(&columns).get("lat"), Some(&DataType::Int64));
+    }
+
+    #[tokio::test]
+    async fn test_history_columns() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = Fixture::from_rows(
+            &[("time", "bigint"), ("icao24", "varchar"), ("lat", "double")],
+            vec![vec![1.into(), "485a32".into(), 52.3.into()]],
+        );
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        trino.config.cache_dir = Some(dir.path().to_path_buf());
+        trino.set_schema_check(SchemaCheck::Strict);
+
+        let params = QueryParams::new().icao24("485a32").columns(["lat"]);
+        let data = trino.history(params.clone()).await.unwrap();
+        assert_eq!(data.columns(), ["time", "icao24", "lat"]);
+        assert!(mock.statements()[0].starts_with("SELECT time, icao24, lat\n"));
+
+        // Unknown names fail before anything is sent
+        let err = trino.history(params.columns(["lat", "alt"])).await.unwrap_err();
+        assert!(matches!(err, OpenSkyError::InvalidParam(ref m) if m.contains("valid columns are: time, icao24")));
+        assert_eq!(mock.statements().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_adsb_positions() {
+        use crate::testing::{Fixture, MockTrino};
+
+        // Trino declares some columns loosely: the canonical types win
+        let fixture = Fixture::from_rows(
+            &[("mintime", "double"), ("icao24", "varchar"), ("alt", "varchar"), ("surface", "boolean")],
+            vec![vec![1735725600.5.into(), "485a32".into(), 36000.0.into(), false.into()]],
+        );
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00")
+            .columns(["alt", "surface"]);
+        let data = trino.adsb_positions(params.clone()).await.unwrap();
+        let df = data.dataframe();
+        assert_eq!(df.column("alt").unwrap().dtype(), &DataType::Float64);
+        assert_eq!(df.column("surface").unwrap().dtype(), &DataType::Boolean);
+        assert!(mock.statements()[0].starts_with("SELECT mintime, icao24, alt, surface\nFROM minio.osky.position_data4"));
+
+        // Velocity columns are not position columns
+        let err = trino.adsb_positions(params.columns(["heading"])).await.unwrap_err();
+        assert!(matches!(err, OpenSkyError::InvalidParam(ref m) if m.contains("valid columns are: mintime")));
+        assert_eq!(mock.statements().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_raw() {
+        use crate::testing::{Fixture, MockTrino};
+        use serde_json::json;
+
+        let columns = json!([{ "name": "icao24", "type": "varchar" }, { "name": "time", "type": "bigint" }]);
+        let fixture = Fixture {
+            pages: vec![
+                json!({ "id": "q1", "stats": { "state": "QUEUED" } }),
+                json!({ "id": "q1", "columns": columns, "data": [["485a32", 1], ["485a32", 2]], "stats": { "state": "RUNNING", "progressPercentage": 50.0 } }),
+                json!({ "id": "q1", "data": [["485a32", 3]], "stats": { "state": "FINISHED", "progressPercentage": 100.0 } }),
+            ],
+        };
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+
+        let mut pages = trino.execute_raw("SELECT icao24, time FROM state_vectors_data4");
+        let mut received = Vec::new();
+        while let Some(page) = pages.next().await {
+            received.push(page.unwrap());
+        }
+        assert_eq!(received.len(), 3);
+        assert!(received[0].rows.is_empty() && received[0].columns.is_empty());
+        assert_eq!(received[2].columns[1].col_type, "bigint");
+        assert_eq!(received[2].rows, [[json!("485a32"), json!(3)]]);
+        assert_eq!(received[2].status.row_count, 3);
+        assert_eq!(received[2].status.state, "FINISHED");
+        assert_eq!(received[1].query_id.as_deref(), Some("q1"));
+
+        let mock = MockTrino::start(Fixture::error("Table not found", "TABLE_NOT_FOUND")).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        let mut pages = trino.execute_raw("SELECT 1");
+        assert!(pages.next().await.unwrap().is_err());
+        assert!(pages.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_auth_failover() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let fixture = Fixture::from_rows(&[("icao24", "varchar")], vec![vec!["485a32".into()]]);
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        // Nothing listens on port 1
+        trino.auth_urls = vec!["http://127.0.0.1:1/token".to_string(), mock.auth_url()];
+
+        assert_eq!(trino.execute_query("SELECT 1", FLIGHT_COLUMNS).await.unwrap().len(), 1);
+        assert_eq!(trino.auth_urls[0], mock.auth_url());
+    }
+
+    #[tokio::test]
+    async fn test_last_report() {
+        use crate::testing::{Fixture, MockTrino};
+        use serde_json::json;
+
+        let columns = json!([{ "name": "icao24", "type": "varchar" }]);
+        let warning = json!({
+            "warningCode": { "code": 1, "name": "DEPRECATED_FUNCTION" },
+            "message": "Function approx_set is deprecated",
+        });
+        let fixture = Fixture {
+            pages: vec![
+                json!({ "id": "q1", "columns": columns, "data": [["485a32"]], "stats": { "state": "RUNNING" } }),
+                json!({ "id": "q1", "data": [["3c6444"]], "stats": { "state": "FINISHED", "processedBytes": 2048 }, "warnings": [warning, warning] }),
+            ],
+        };
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        assert!(trino.last_report().is_none());
+
+        trino.execute_query("SELECT 1", FLIGHT_COLUMNS).await.unwrap();
+        let report = trino.last_report().unwrap();
+        assert_eq!(report.query_id.as_deref(), Some("q1"));
+        assert_eq!((report.rows, report.bytes, report.cache), (2, Some(2048), CacheStatus::Miss));
+        assert_eq!(report.sql_hash, format!("{:016x}", statement_hash("SELECT 1")));
+        assert_eq!(report.sql, "SELECT 1");
+        assert_eq!(
+            report.warnings,
+            [TrinoWarning {
+                code: "DEPRECATED_FUNCTION".to_string(),
+                message: "Function approx_set is deprecated".to_string(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_statement() {
+        use crate::testing::{Fixture, MockTrino};
+        use serde_json::json;
+
+        let columns = json!([{ "name": "icao24", "type": "varchar" }, { "name": "time", "type": "bigint" }]);
+        let fixture = Fixture {
+            pages: vec![
+                json!({ "id": "q1", "columns": columns, "data": [["485a32", 1], ["485a32", 2]], "stats": { "state": "RUNNING" } }),
+                json!({ "id": "q1", "data": [["485a32", 3]], "stats": { "state": "FINISHED" } }),
+            ],
+        };
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+
+        // Read to the end, the statement can run again
+        assert_eq!(trino.execute_query("SELECT 1", FLIGHT_COLUMNS).await.unwrap().len(), 3);
+
+        // Stop reading while the query runs, as a dropped future would
+        let mut pages = trino.execute_raw("SELECT 1");
+        assert!(pages.next().await.unwrap().is_ok());
+        drop(pages);
+        let err = trino.execute_query(" SELECT 1\n", FLIGHT_COLUMNS).await.unwrap_err();
+        assert!(matches!(err, OpenSkyError::DuplicateQuery { .. }));
+        assert_eq!(err.query_id(), Some("q1"));
+        assert_eq!(mock.statements().len(), 2);
+
+        trino.cancel("q1").await.unwrap();
+        assert_eq!(mock.deletes(), ["/v1/query/q1"]);
+        assert_eq!(trino.execute_query("SELECT 1", FLIGHT_COLUMNS).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_canceller() {
+        use crate::testing::{Fixture, MockTrino};
+        use serde_json::json;
+
+        let columns = json!([{ "name": "icao24", "type": "varchar" }, { "name": "time", "type": "bigint" }]);
+        let fixture = Fixture {
+            pages: vec![
+                json!({ "id": "q1", "stats": { "state": "QUEUED" } }),
+                json!({ "id": "q1", "columns": columns, "data": [["485a32", 1]], "stats": { "state": "RUNNING" } }),
+                json!({ "id": "q1", "data": [["485a32", 2]], "stats": { "state": "FINISHED" } }),
+            ],
+        };
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        let canceller = trino.canceller();
+
+        // Nothing running yet
+        assert!(!canceller.cancel().await.unwrap());
+
+        // A statement abandoned while running is cancelled at its nextUri
+        let mut pages = trino.execute_raw("SELECT 1");
+        assert!(pages.next().await.unwrap().is_ok());
+        drop(pages);
+        assert!(canceller.cancel().await.unwrap());
+        assert_eq!(mock.deletes(), ["/v1/statement/mock/0/1"]);
+
+        // A statement read to the end leaves nothing to cancel
+        trino.cancel("q1").await.unwrap();
+        assert_eq!(trino.execute_query("SELECT 1", FLIGHT_COLUMNS).await.unwrap().len(), 2);
+        assert!(!canceller.cancel().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_execute_into_sinks() {
+        use crate::sink::{CsvSink, DataFrameSink, ParquetSink};
+        use crate::testing::{Fixture, MockTrino};
+        use serde_json::json;
+
+        let columns = json!([{ "name": "icao24", "type": "varchar" }, { "name": "time", "type": "bigint" }]);
+        let fixture = Fixture {
+            pages: vec![
+                json!({ "id": "q1", "columns": columns, "data": [["485a32", 1], ["485a32", 2]], "stats": { "state": "RUNNING" } }),
+                json!({ "id": "q1", "data": [["485a32", 3]], "stats": { "state": "FINISHED" } }),
+            ],
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+
+        let data = trino.execute_into("SELECT 1", FLIGHT_COLUMNS, DataFrameSink::new()).await.unwrap();
+        assert_eq!(data.columns(), ["icao24", "time"]);
+        assert_eq!(data.len(), 3);
+
+        let path = dir.path().join("out.parquet");
+        trino.execute_into("SELECT 1", FLIGHT_COLUMNS, ParquetSink::new(&path)).await.unwrap();
+        assert!(FlightData::from_parquet(&path).unwrap().dataframe().equals(data.dataframe()));
+
+        let path = dir.path().join("out.csv.gz");
+        let sink = CsvSink::new(&path, crate::CsvOptions::new().gzip(true));
+        trino.execute_into("SELECT 1", FLIGHT_COLUMNS, sink).await.unwrap();
+        assert_eq!(FlightData::from_csv(&path).unwrap().len(), 3);
+
+        // History queries stream the same way
+        let path = dir.path().join("history.parquet");
+        let params = QueryParams::new().icao24("485a32").time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00");
+        trino.history_into(params, ParquetSink::new(&path)).await.unwrap();
+        assert_eq!(FlightData::from_parquet(&path).unwrap().len(), 3);
+        assert!(mock.statements().last().unwrap().contains("485a32"));
+
+        // An empty result still gets the schema
+        let empty = Fixture::from_rows(&[("icao24", "varchar")], vec![]);
+        let mock = MockTrino::start(empty).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+        let path = dir.path().join("empty.csv");
+        trino.execute_into("SELECT 1", FLIGHT_COLUMNS, CsvSink::new(&path, crate::CsvOptions::new())).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "icao24\n");
+    }
+
+    #[tokio::test]
+    async fn test_execute_into_forward() {
+        use crate::sink::{BatchFormat, ForwardSink, Publisher};
+        use crate::testing::{Fixture, MockTrino};
+        use async_trait::async_trait;
+        use polars::prelude::{IpcReader, SerReader};
+        use serde_json::json;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<Mutex<Vec<Vec<u8>>>>);
+
+        #[async_trait]
+        impl Publisher for Recorder {
+            async fn publish(&mut self, payload: Vec<u8>) -> Result<()> {
+                self.0.lock().unwrap().push(payload);
+                Ok(())
+            }
+        }
+
+        let columns = json!([{ "name": "icao24", "type": "varchar" }, { "name": "time", "type": "bigint" }]);
+        let fixture = Fixture {
+            pages: vec![
+                json!({ "id": "q1", "columns": columns, "data": [["485a32", 1], ["485a32", 2]], "stats": { "state": "RUNNING" } }),
+                json!({ "id": "q1", "data": [["485a32", 3]], "stats": { "state": "FINISHED" } }),
+            ],
+        };
+        let mock = MockTrino::start(fixture).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+
+        let recorder = Recorder::default();
+        let sink = ForwardSink::new(recorder.clone(), BatchFormat::Ndjson);
+        let sent = trino.execute_into("SELECT 1", FLIGHT_COLUMNS, sink).await.unwrap().await.unwrap();
+        assert_eq!(sent, 2);
+        let messages = recorder.0.lock().unwrap().clone();
+        assert_eq!(
+            String::from_utf8(messages[0].clone()).unwrap(),
+            "{\"icao24\":\"485a32\",\"time\":1}\n{\"icao24\":\"485a32\",\"time\":2}\n"
+        );
+
+        let recorder = Recorder::default();
+        let sink = ForwardSink::new(recorder.clone(), BatchFormat::ArrowIpc);
+        trino.execute_into("SELECT 1", FLIGHT_COLUMNS, sink).await.unwrap().await.unwrap();
+        let messages = recorder.0.lock().unwrap().clone();
+        let batch = IpcReader::new(std::io::Cursor::new(messages[1].clone())).finish().unwrap();
+        assert_eq!(batch.height(), 1);
+        assert_eq!(batch.get_column_names(), ["icao24", "time"]);
+    }
+
+    #[tokio::test]
+    async fn test_forward_sink_backpressure() {
+        use crate::sink::{BatchFormat, ForwardSink, Publisher, ResultSink};
+        use async_trait::async_trait;
+        use polars::prelude::*;
+        use std::sync::Arc;
+        use std::time::Duration;
+        use tokio::sync::Semaphore;
+
+        // Publishes one message per permit added to the gate
+        struct Gated(Arc<Semaphore>);
+
+        #[async_trait]
+        impl Publisher for Gated {
+            async fn publish(&mut self, _payload: Vec<u8>) -> Result<()> {
+                self.0.acquire().await.unwrap().forget();
+                Ok(())
+            }
+        }
+
+        let gate = Arc::new(Semaphore::new(0));
+        let mut sink = ForwardSink::new(Gated(gate.clone()), BatchFormat::Ndjson);
+        let batch = df!("icao24" => ["485a32"], "time" => [1i64]).unwrap();
+        let mut accepted = 0;
+        while tokio::time::timeout(Duration::from_millis(200), sink.ready()).await.is_ok() {
+            sink.on_batch(&batch).unwrap();
+            accepted += 1;
+            assert!(accepted <= 20, "queue is unbounded");
+        }
+        // Eight queued and one held by the stalled publisher
+        assert_eq!(accepted, 9);
+        assert!(sink.on_batch(&batch).is_err());
+
+        gate.add_permits(accepted);
+        let sent = sink.finish().unwrap().await.unwrap();
+        assert_eq!(sent, accepted);
+    }
+
+    #[tokio::test]
+    async fn test_history_pages() {
+        use crate::testing::{Fixture, MockTrino};
+
+        let page = |n: usize| {
+            Fixture::from_rows(
+                &[("icao24", "varchar"), ("time", "bigint")],
+                (0..n).map(|i| vec!["485a32".into(), (i as i64).into()]).collect(),
+            )
+        };
+        let mock = MockTrino::with_fixtures(vec![page(2), page(2), page(1)]).await.unwrap();
+        let mut trino = mock.client().await.unwrap();
+
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00");
+        let mut pages = trino.history_pages(params, 2);
+        let mut rows = Vec::new();
+        while let Some(page) = pages.next().await {
+            rows.push(page.unwrap().len());
+        }
+        assert_eq!(rows, [2, 2, 1]);
+
+        let statements = mock.statements();
+        assert_eq!(statements.len(), 3);
+        assert!(statements[0].ends_with("ORDER BY time, icao24\nLIMIT 2"));
+        assert!(statements[2].ends_with("OFFSET 4\nLIMIT 2"));
+
+        // The order is forced, and cannot be left out
+        let params = QueryParams::new()
+            .icao24("485a32")
+            .time_range("2025-01-01 10:00:00", "2025-01-01 12:00:00");
+        let mut pages = trino.history_pages(params.clone().order(Order::IcaoThenTime), 2);
+        pages.next().await.unwrap().unwrap();
+        assert!(mock.statements()[3].contains("ORDER BY time, icao24\n"));
+        let mut pages = trino.history_pages(params.order(Order::None), 2);
+        assert!(matches!(pages.next().await, Some(Err(OpenSkyError::InvalidParam(_)))));
+        assert!(pages.next().await.is_none());
     }
 }
@@ -1,6 +1,8 @@
 //! Core types for OpenSky queries and results.
 
+#[cfg(feature = "client")]
 use polars::prelude::*;
+use crate::tables::{Table, TableNames};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -13,15 +15,40 @@ pub enum OpenSkyError {
     #[error("Authentication failed: {0}")]
     Auth(String),
 
+    #[cfg(feature = "client")]
     #[error("HTTP request failed: {0}")]
     Http(#[from] reqwest::Error),
 
+    #[error("HTTP status {status} from {url}")]
+    HttpStatus { status: u16, url: String },
+
     #[error("Query execution failed: {0}")]
-    Query(String),
+    Query(Box<QueryError>),
 
     #[error("Query was cancelled")]
     Cancelled,
 
+    /// A result is not in the cache and the cache policy is read-only.
+    #[error("No cached result for {key} and the cache is read-only")]
+    CacheMiss { key: String },
+
+    /// The same statement is still running from this client.
+    #[error("Identical statement is already running as query {query_id}")]
+    DuplicateQuery { query_id: String },
+
+    /// An error raised while a Trino query was running.
+    #[error("{source} (query {query_id})")]
+    InQuery {
+        query_id: String,
+        source: Box<OpenSkyError>,
+    },
+
+    #[error("{step} failed: {source}")]
+    Verify {
+        step: &'static str,
+        source: Box<OpenSkyError>,
+    },
+
     #[error("Invalid parameter: {0}")]
     InvalidParam(String),
 
@@ -35,6 +62,108 @@ pub enum OpenSkyError {
     Json(#[from] serde_json::Error),
 }
 
+impl OpenSkyError {
+    /// Trino query id of the query during which the error occurred, if any.
+    ///
+    /// Include it when reporting failures to the OpenSky operators.
+    pub fn query_id(&self) -> Option<&str> {
+        match self {
+            OpenSkyError::Query(error) => error.query_id.as_deref(),
+            OpenSkyError::InQuery { query_id, .. } | OpenSkyError::DuplicateQuery { query_id } => Some(query_id),
+            OpenSkyError::Verify { source, .. } => source.query_id(),
+            _ => None,
+        }
+    }
+
+    /// Attach the id of the running query; query errors store it directly,
+    /// other errors are wrapped in [`OpenSkyError::InQuery`].
+    #[cfg(feature = "client")]
+    pub(crate) fn in_query(self, query_id: Option<&str>) -> Self {
+        let Some(query_id) = query_id else {
+            return self;
+        };
+        match self {
+            OpenSkyError::Query(mut error) => {
+                error.query_id.get_or_insert_with(|| query_id.to_string());
+                OpenSkyError::Query(error)
+            }
+            OpenSkyError::InQuery { .. } => self,
+            other => OpenSkyError::InQuery {
+                query_id: query_id.to_string(),
+                source: Box::new(other),
+            },
+        }
+    }
+}
+
+/// Error reported by Trino for a failed query.
+///
+/// Besides the message, Trino classifies errors by `error_name` (e.g.
+/// `SYNTAX_ERROR`, `EXCEEDED_TIME_LIMIT`) and `error_type` (`USER_ERROR`,
+/// `INTERNAL_ERROR`, `INSUFFICIENT_RESOURCES`, `EXTERNAL`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryError {
+    pub message: String,
+    pub error_name: Option<String>,
+    pub error_code: Option<i64>,
+    pub error_type: Option<String>,
+    pub failure_info: Option<FailureInfo>,
+    /// Id of the failed query, set by the client
+    #[serde(default)]
+    pub query_id: Option<String>,
+}
+
+/// Server-side failure details of a [`QueryError`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailureInfo {
+    /// Java exception class
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub message: Option<String>,
+    #[serde(default)]
+    pub stack: Vec<String>,
+    pub cause: Option<Box<FailureInfo>>,
+}
+
+impl QueryError {
+    /// An error with only a message, for failures detected by the client.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Whether the query itself is at fault (syntax, missing column, ...).
+    pub fn is_user_error(&self) -> bool {
+        self.error_type.as_deref() == Some("USER_ERROR")
+    }
+
+    /// Whether the cluster ran out of memory or time for the query.
+    pub fn is_resource_exhausted(&self) -> bool {
+        self.error_type.as_deref() == Some("INSUFFICIENT_RESOURCES")
+            || self.error_name.as_deref().is_some_and(|name| name.starts_with("EXCEEDED_"))
+    }
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let details: Vec<String> = self
+            .error_name
+            .iter()
+            .cloned()
+            .chain(self.query_id.iter().map(|id| format!("query {}", id)))
+            .collect();
+        if details.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{} ({})", self.message, details.join(", "))
+        }
+    }
+}
+
 /// Result type alias for OpenSky operations.
 pub type Result<T> = std::result::Result<T, OpenSkyError>;
 
@@ -51,6 +180,34 @@ impl Bounds {
     pub fn new(west: f64, south: f64, east: f64, north: f64) -> Self {
         Self { west, south, east, north }
     }
+
+    /// Smallest box containing the circle of `radius_km` around a point.
+    ///
+    /// The longitude span widens with latitude. Boxes reaching a pole, or
+    /// crossing the antimeridian (which bounds cannot express), cover all
+    /// longitudes; latitudes are clamped to [-90, 90].
+    pub fn from_center(lat: f64, lon: f64, radius_km: f64) -> Self {
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+
+        let angle = radius_km.max(0.0) / EARTH_RADIUS_KM;
+        let dlat = angle.to_degrees();
+        let south = (lat - dlat).max(-90.0);
+        let north = (lat + dlat).min(90.0);
+
+        // Widest longitude offset of the circle, at the tangent meridians
+        let ratio = angle.sin() / lat.to_radians().cos();
+        let (west, east) = if south <= -90.0 || north >= 90.0 || ratio >= 1.0 {
+            (-180.0, 180.0)
+        } else {
+            let dlon = ratio.asin().to_degrees();
+            if lon - dlon < -180.0 || lon + dlon > 180.0 {
+                (-180.0, 180.0)
+            } else {
+                (lon - dlon, lon + dlon)
+            }
+        };
+        Self { west, south, east, north }
+    }
 }
 
 /// Parameters for querying flight history.
@@ -71,20 +228,69 @@ pub struct QueryParams {
     /// Geographic bounding box
     pub bounds: Option<Bounds>,
 
-    /// Departure airport ICAO code (e.g., "EHAM")
-    pub departure_airport: Option<String>,
+    /// Departure airport ICAO codes (e.g., "EHAM"), any of which matches
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub departure_airport: Vec<String>,
+
+    /// Arrival airport ICAO codes (e.g., "EGLL"), any of which matches
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub arrival_airport: Vec<String>,
 
-    /// Arrival airport ICAO code (e.g., "EGLL")
-    pub arrival_airport: Option<String>,
+    /// Airports matching either departure or arrival
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub airport: Vec<String>,
 
-    /// Airport (either departure or arrival)
-    pub airport: Option<String>,
+    /// Departure airports whose flights are excluded
+    #[serde(default)]
+    pub exclude_departure: Vec<String>,
 
-    /// Time buffer around flight (e.g., "1h", "30m")
+    /// Arrival airports whose flights are excluded
+    #[serde(default)]
+    pub exclude_arrival: Vec<String>,
+
+    /// How long before `start` flights matched by an airport filter may
+    /// have taken off (e.g., "6h", "1d"); by default only flights of the
+    /// days of the time range are joined
     pub time_buffer: Option<String>,
 
     /// Maximum number of records to return
     pub limit: Option<u32>,
+
+    /// Number of records to skip before returning results
+    pub offset: Option<u64>,
+
+    /// Keep one state vector per aircraft every N seconds
+    pub resolution: Option<u32>,
+
+    /// Exclude state vectors without a position
+    #[serde(default)]
+    pub require_position: bool,
+
+    /// Exclude state vectors without a valid position
+    #[serde(default)]
+    pub valid_positions: bool,
+
+    /// Server-side sort order of the results
+    #[serde(default)]
+    pub order: Order,
+
+    /// Columns selected by history and raw data queries, all when empty
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub columns: Vec<String>,
+
+    /// Downlink formats of the messages returned by raw data queries, all
+    /// when empty
+    #[serde(default)]
+    pub downlink_formats: Vec<u8>,
+
+    /// Flight times used by flight list queries
+    #[serde(default)]
+    pub flight_time: FlightTime,
+
+    /// Tables to query instead of the current version; a `Trino` client
+    /// fills them from the `[tables]` section of its config when unset
+    #[serde(skip)]
+    pub tables: TableNames,
 }
 
 impl QueryParams {
@@ -94,27 +300,98 @@ impl QueryParams {
     }
 
     /// Set ICAO24 filter.
+    ///
+    /// The address is normalized with [`normalize_icao24`], so "0x485A32"
+    /// and "485a32" are the same query (and cache entry).
     pub fn icao24(mut self, icao24: impl Into<String>) -> Self {
-        self.icao24 = Some(icao24.into());
+        self.icao24 = Some(normalize_icao24(&icao24.into()));
         self
     }
 
     /// Set time range.
+    ///
+    /// Accepts "YYYY-MM-DD HH:MM:SS" and "YYYY-MM-DD" in UTC, as well as
+    /// RFC 3339 / ISO 8601 times such as "2025-01-01T10:00:00+01:00" or
+    /// "2025-01-01T09:00:00Z", which are normalized to UTC.
     pub fn time_range(mut self, start: impl Into<String>, stop: impl Into<String>) -> Self {
-        self.start = Some(start.into());
-        self.stop = Some(stop.into());
+        self.start = Some(normalize_time(start.into()));
+        self.stop = Some(normalize_time(stop.into()));
+        self
+    }
+
+    /// Set a time range given in local time of `tz` (IANA name, e.g.
+    /// "Europe/Amsterdam").
+    ///
+    /// Times are converted to UTC. Times repeated when clocks go back resolve
+    /// to the earlier instant; times skipped when clocks go forward are an error.
+    /// As in UTC, a date-only stop covers the whole local day.
+    pub fn time_range_tz(self, start: &str, stop: &str, tz: &str) -> Result<Self> {
+        let tz = parse_tz(tz)?;
+        Ok(self.time_range(local_to_utc(start, tz, false)?, local_to_utc(stop, tz, true)?))
+    }
+
+    /// Set departure airport(s).
+    ///
+    /// Accepts a single ICAO code, a list, or an [`AirportGroup`](crate::AirportGroup)
+    /// such as `AirportGroup::LondonArea`. Codes containing `%` are matched
+    /// with `LIKE`.
+    pub fn departure(mut self, airports: impl IntoAirports) -> Self {
+        self.departure_airport = airports.into_airports();
+        self
+    }
+
+    /// Set arrival airport(s).
+    pub fn arrival(mut self, airports: impl IntoAirports) -> Self {
+        self.arrival_airport = airports.into_airports();
+        self
+    }
+
+    /// Set airport(s) matching either departure or arrival.
+    pub fn airport(mut self, airports: impl IntoAirports) -> Self {
+        self.airport = airports.into_airports();
+        self
+    }
+
+    /// Add all airports of a country (ISO 3166 code, e.g. "NL") to the
+    /// departure filter.
+    ///
+    /// Fails for countries not covered by [`crate::airports::country_prefixes`].
+    pub fn departure_country(mut self, country: &str) -> Result<Self> {
+        self.departure_airport.extend(crate::airports::country_patterns(country)?);
+        Ok(self)
+    }
+
+    /// Add all airports of a country to the arrival filter.
+    pub fn arrival_country(mut self, country: &str) -> Result<Self> {
+        self.arrival_airport.extend(crate::airports::country_patterns(country)?);
+        Ok(self)
+    }
+
+    /// Add all airports of a country to the either-direction airport filter.
+    pub fn airport_country(mut self, country: &str) -> Result<Self> {
+        self.airport.extend(crate::airports::country_patterns(country)?);
+        Ok(self)
+    }
+
+    /// Exclude flights departing from `airport`.
+    ///
+    /// Can be called repeatedly. Combined with positive airport filters this
+    /// expresses e.g. "arrivals at EHAM that did not depart from EGLL";
+    /// otherwise it becomes an anti-join against the flights table.
+    pub fn exclude_departure(mut self, airport: impl Into<String>) -> Self {
+        self.exclude_departure.push(airport.into());
         self
     }
 
-    /// Set departure airport.
-    pub fn departure(mut self, airport: impl Into<String>) -> Self {
-        self.departure_airport = Some(airport.into());
+    /// Exclude flights arriving at `airport`. Can be called repeatedly.
+    pub fn exclude_arrival(mut self, airport: impl Into<String>) -> Self {
+        self.exclude_arrival.push(airport.into());
         self
     }
 
-    /// Set arrival airport.
-    pub fn arrival(mut self, airport: impl Into<String>) -> Self {
-        self.arrival_airport = Some(airport.into());
+    /// Query the tables named in `tables`, e.g. a new version.
+    pub fn tables(mut self, tables: TableNames) -> Self {
+        self.tables = tables;
         self
     }
 
@@ -124,12 +401,309 @@ impl QueryParams {
         self
     }
 
+    /// Skip the first `offset` records.
+    ///
+    /// Combine with [`QueryParams::limit`] to fetch one page of results; the
+    /// default time ordering keeps pages stable between queries.
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Downsample state vectors to one every `seconds` seconds, server-side.
+    ///
+    /// OpenSky state vectors are sampled at 1 Hz, so `resolution(10)` cuts
+    /// the result roughly tenfold. Values of 0 or 1 keep every row.
+    pub fn resolution(mut self, seconds: u32) -> Self {
+        self.resolution = Some(seconds);
+        self
+    }
+
+    /// Exclude state vectors with a null `lat` or `lon`, server-side, so
+    /// they are never transferred.
+    pub fn require_position(mut self, require: bool) -> Self {
+        self.require_position = require;
+        self
+    }
+
+    /// Exclude state vectors with a null position, a (0, 0) position, or
+    /// coordinates outside the valid latitude/longitude ranges, server-side.
+    ///
+    /// See [`FlightData::drop_invalid_positions`] for results already downloaded.
+    pub fn valid_positions(mut self, valid: bool) -> Self {
+        self.valid_positions = valid;
+        self
+    }
+
+    /// Set the server-side sort order.
+    ///
+    /// `Order::None` skips the sort on the cluster, which is much cheaper for
+    /// large results that are sorted locally or streamed to a file anyway.
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Select only these columns in history and raw data queries.
+    ///
+    /// Names are checked against the queried table before the query runs.
+    /// Columns come back in table order, and history queries always include
+    /// `time` and `icao24`, which results are sorted and grouped by.
+    pub fn columns<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.columns = columns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only return raw messages of these downlink formats, e.g. `[20, 21]`
+    /// for Comm-B replies; 24 stands for all Comm-D formats. See
+    /// [`crate::modes`] to classify the results.
+    pub fn downlink_formats(mut self, formats: impl IntoIterator<Item = u8>) -> Self {
+        self.downlink_formats = formats.into_iter().collect();
+        self
+    }
+
+    /// Set which flight times flight list queries filter on.
+    ///
+    /// Only used by flight list queries; state vector queries filter on `time`.
+    pub fn flight_time(mut self, flight_time: FlightTime) -> Self {
+        self.flight_time = flight_time;
+        self
+    }
+
     /// Set geographic bounds.
     pub fn bounds(mut self, west: f64, south: f64, east: f64, north: f64) -> Self {
         self.bounds = Some(Bounds::new(west, south, east, north));
         self
     }
 
+    /// Set the bounding box around a point, see [`Bounds::from_center`].
+    ///
+    /// The filter is the box, not the circle: corners are up to ~1.4 times
+    /// `radius_km` away.
+    pub fn around(mut self, lat: f64, lon: f64, radius_km: f64) -> Self {
+        self.bounds = Some(Bounds::from_center(lat, lon, radius_km));
+        self
+    }
+
+    /// Whether a positive departure/arrival/airport filter is set.
+    pub fn has_airport_filter(&self) -> bool {
+        !self.departure_airport.is_empty() || !self.arrival_airport.is_empty() || !self.airport.is_empty()
+    }
+
+    /// The same query written one way: times as "YYYY-MM-DD HH:MM:SS" (a
+    /// date-only stop is the end of that day), ICAO24 addresses lowercase,
+    /// airport lists sorted without duplicates, and a resolution keeping
+    /// every row unset.
+    ///
+    /// Only spellings the query builder reads the same way are merged, so
+    /// the canonical form selects the same rows, and logically identical
+    /// parameters share one cache entry. Callsigns and airport codes are
+    /// compared as written, and kept as they are. Unparseable times are kept
+    /// for [`QueryParams::validate`] to report.
+    pub fn canonical(&self) -> QueryParams {
+        let time = |value: &Option<String>, end_of_day: bool| {
+            value.as_ref().map(|value| match crate::query::parse_bound(value, end_of_day) {
+                Ok(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                Err(_) => value.clone(),
+            })
+        };
+        let airports = |codes: &[String]| {
+            let mut codes = codes.to_vec();
+            codes.sort();
+            codes.dedup();
+            codes
+        };
+        QueryParams {
+            icao24: self.icao24.as_deref().map(normalize_icao24),
+            start: time(&self.start, false),
+            stop: time(&self.stop, true),
+            departure_airport: airports(&self.departure_airport),
+            arrival_airport: airports(&self.arrival_airport),
+            airport: airports(&self.airport),
+            exclude_departure: airports(&self.exclude_departure),
+            exclude_arrival: airports(&self.exclude_arrival),
+            resolution: self.resolution.filter(|&seconds| seconds > 1),
+            columns: self.columns.iter().map(|column| column.trim().to_lowercase()).collect(),
+            downlink_formats: {
+                let mut formats = self.downlink_formats.clone();
+                formats.sort();
+                formats.dedup();
+                formats
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Check that the parameters can be turned into a query.
+    ///
+    /// Times must parse (see [`QueryParams::time_range`]) and the time range
+    /// must not be reversed; a date-only stop is the end of that day, so a
+    /// range within a single date is valid.
+    pub fn validate(&self) -> Result<()> {
+        let parse = |value: &Option<String>, end_of_day: bool| {
+            value
+                .as_deref()
+                .map(|v| crate::query::parse_bound(v, end_of_day))
+                .transpose()
+        };
+        if let Some(icao24) = &self.icao24 {
+            let icao24 = normalize_icao24(icao24);
+            let wildcard = icao24.contains(['%', '_']);
+            let valid = icao24.chars().all(|c| c.is_ascii_hexdigit() || c == '%' || c == '_')
+                && (wildcard || icao24.len() == 6);
+            if !valid {
+                return Err(OpenSkyError::InvalidParam(format!(
+                    "Invalid icao24 '{}', expected 6 hex digits",
+                    icao24
+                )));
+            }
+        }
+        for (kind, include, exclude) in [
+            ("departure", &self.departure_airport, &self.exclude_departure),
+            ("arrival", &self.arrival_airport, &self.exclude_arrival),
+        ] {
+            if let Some(airport) = include.iter().find(|a| exclude.contains(a)) {
+                return Err(OpenSkyError::InvalidParam(format!(
+                    "Airport {} is both a {} airport and excluded",
+                    airport, kind
+                )));
+            }
+        }
+        crate::query::time_buffer_seconds(self)?;
+        if let Some(format) = self.downlink_formats.iter().find(|&&format| format > 24) {
+            return Err(OpenSkyError::InvalidParam(format!(
+                "Invalid downlink format {}, expected 0 to 24",
+                format
+            )));
+        }
+        if let (Some(start), Some(stop)) = (parse(&self.start, false)?, parse(&self.stop, true)?) {
+            if start > stop {
+                return Err(OpenSkyError::InvalidParam(format!(
+                    "Start time {} is after stop time {}",
+                    start, stop
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Length of the time range, if both bounds are set and valid.
+    ///
+    /// A date-only stop counts up to the end of that day.
+    pub fn time_span(&self) -> Option<chrono::Duration> {
+        let start = crate::query::parse_bound(self.start.as_deref()?, false).ok()?;
+        let stop = crate::query::parse_bound(self.stop.as_deref()?, true).ok()?;
+        Some(stop - start)
+    }
+
+    /// Split the time range into consecutive ranges of at most `span`.
+    ///
+    /// Bounds are inclusive, so each part starts one second after the
+    /// previous one stops. Parameters without a valid time range are
+    /// returned unchanged.
+    pub fn split_time_range(&self, span: chrono::Duration) -> Vec<QueryParams> {
+        let (Some(start), Some(stop)) = (&self.start, &self.stop) else {
+            return vec![self.clone()];
+        };
+        let one = chrono::Duration::seconds(1);
+        if span < one {
+            return vec![self.clone()];
+        }
+        let (Ok(stop), Ok(mut from)) = (crate::query::parse_bound(stop, true), crate::query::parse_bound(start, false)) else {
+            return vec![self.clone()];
+        };
+        let mut parts = Vec::new();
+        while from <= stop {
+            let to = (from + span - one).min(stop);
+            let mut part = self.clone();
+            part.start = Some(from.format("%Y-%m-%d %H:%M:%S").to_string());
+            part.stop = Some(to.format("%Y-%m-%d %H:%M:%S").to_string());
+            parts.push(part);
+            from = to + one;
+        }
+        parts
+    }
+
+    /// Parameter combinations that are accepted but probably not intended.
+    ///
+    /// The client logs these with `tracing` before running a query; the CLI
+    /// prints them.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let directional = !self.departure_airport.is_empty() || !self.arrival_airport.is_empty();
+        if !self.airport.is_empty() && directional {
+            warnings.push(
+                "airport is combined with departure/arrival airports: flights must match both filters".to_string(),
+            );
+        }
+        if self.time_buffer.is_some() && !self.has_airport_filter() {
+            warnings.push("time_buffer only applies to airport filters and is ignored".to_string());
+        }
+        if self.offset.is_some() && self.order == Order::None {
+            warnings.push("offset without an order returns arbitrary, possibly overlapping pages".to_string());
+        }
+        warnings
+    }
+
+    /// Check that the parameters make sense for a flight list query.
+    ///
+    /// In addition to [`QueryParams::validate`], bounds, resolution and
+    /// position filtering are rejected since they only apply to state vectors.
+    pub fn validate_flightlist(&self) -> Result<()> {
+        self.validate()?;
+        if self.bounds.is_some() {
+            return Err(OpenSkyError::InvalidParam(
+                "Bounds are not supported by flight list queries".to_string(),
+            ));
+        }
+        if self.resolution.is_some() {
+            return Err(OpenSkyError::InvalidParam(
+                "Resolution is not supported by flight list queries".to_string(),
+            ));
+        }
+        if self.require_position || self.valid_positions {
+            return Err(OpenSkyError::InvalidParam(
+                "Position filtering is not supported by flight list queries".to_string(),
+            ));
+        }
+        if !self.columns.is_empty() {
+            return Err(OpenSkyError::InvalidParam(
+                "Column selection is not supported by flight list queries".to_string(),
+            ));
+        }
+        if !self.downlink_formats.is_empty() {
+            return Err(OpenSkyError::InvalidParam(
+                "Downlink format filtering is only supported by raw data queries".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check the selected [`QueryParams::columns`] against `available`, the
+    /// columns of the queried table, listing them when a name is unknown.
+    pub fn validate_columns(&self, available: &[&str]) -> Result<()> {
+        let unknown: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| column.trim())
+            .filter(|column| !available.iter().any(|name| name.eq_ignore_ascii_case(column)))
+            .map(|column| format!("'{}'", column))
+            .collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        Err(OpenSkyError::InvalidParam(format!(
+            "Unknown column{} {}; valid columns are: {}",
+            if unknown.len() == 1 { "" } else { "s" },
+            unknown.join(", "),
+            available.join(", ")
+        )))
+    }
+
     /// Check if any query parameters are set.
     pub fn is_empty(&self) -> bool {
         self.icao24.is_none()
@@ -137,46 +711,196 @@ impl QueryParams {
             && self.stop.is_none()
             && self.callsign.is_none()
             && self.bounds.is_none()
-            && self.departure_airport.is_none()
-            && self.arrival_airport.is_none()
-            && self.airport.is_none()
-    }
-}
-
-/// Flight data columns returned by history queries (state vectors).
-pub const FLIGHT_COLUMNS: &[&str] = &[
-    "time",
-    "icao24",
-    "lat",
-    "lon",
-    "velocity",
-    "heading",
-    "vertrate",
-    "callsign",
-    "onground",
-    "squawk",
-    "baroaltitude",
-    "geoaltitude",
-    "hour",
-];
-
-/// Flight list columns returned by flightlist queries.
-pub const FLIGHTLIST_COLUMNS: &[&str] = &[
-    "icao24",
-    "callsign",
-    "firstseen",
-    "lastseen",
-    "estdepartureairport",
-    "estarrivalairport",
-    "day",
-];
-
-/// Default columns for raw data queries.
-pub const RAWDATA_COLUMNS: &[&str] = &[
-    "mintime",
-    "rawmsg",
-    "icao24",
-];
+            && !self.has_airport_filter()
+            && self.exclude_departure.is_empty()
+            && self.exclude_arrival.is_empty()
+    }
+}
+
+/// Normalize an ICAO24 address: trim, drop a `0x` prefix and lowercase.
+///
+/// " 0x485A32" becomes "485a32". Wildcards (`%`, `_`) are kept; non-hex
+/// values are rejected by [`QueryParams::validate`].
+pub fn normalize_icao24(icao24: &str) -> String {
+    let icao24 = icao24.trim();
+    let icao24 = icao24
+        .strip_prefix("0x")
+        .or_else(|| icao24.strip_prefix("0X"))
+        .unwrap_or(icao24);
+    icao24.to_lowercase()
+}
+
+/// Parse a query time to UTC.
+///
+/// Accepts "YYYY-MM-DD HH:MM:SS", "YYYY-MM-DD" (midnight), ISO 8601 with a
+/// `T` separator, and RFC 3339 with `Z` or an offset. Times without an
+/// offset are UTC.
+pub fn parse_time(value: &str) -> Option<chrono::NaiveDateTime> {
+    use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+    let value = value.trim();
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .or_else(|| DateTime::parse_from_rfc3339(value).ok().map(|dt| dt.naive_utc()))
+        .or_else(|| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f").ok())
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok().and_then(|d| d.and_hms_opt(0, 0, 0)))
+}
+
+/// Rewrite ISO 8601 / RFC 3339 times as "YYYY-MM-DD HH:MM:SS" in UTC.
+///
+/// Dates without a time are kept as is, since the query builder reads a
+/// date-only stop as the end of that day. Unparseable values are kept for
+/// [`QueryParams::validate`] to report.
+fn normalize_time(value: String) -> String {
+    let is_date = chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").is_ok();
+    match parse_time(&value) {
+        Some(time) if !is_date => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        _ => value,
+    }
+}
+
+/// Parse an IANA time zone name.
+pub(crate) fn parse_tz(tz: &str) -> Result<chrono_tz::Tz> {
+    tz.parse()
+        .map_err(|_| OpenSkyError::InvalidParam(format!("Unknown time zone '{}'", tz)))
+}
+
+/// Convert "YYYY-MM-DD HH:MM:SS" (or "YYYY-MM-DD") in `tz` to the same format in UTC.
+fn local_to_utc(value: &str, tz: chrono_tz::Tz, end_of_day: bool) -> Result<String> {
+    use chrono::{DateTime, TimeZone};
+
+    // Times with an explicit offset are already unambiguous
+    if let Ok(time) = DateTime::parse_from_rfc3339(value.trim()) {
+        return Ok(time.naive_utc().format("%Y-%m-%d %H:%M:%S").to_string());
+    }
+    let naive = crate::query::parse_bound(value, end_of_day)?;
+    let local = tz.from_local_datetime(&naive).earliest().ok_or_else(|| {
+        OpenSkyError::InvalidParam(format!("Time '{}' does not exist in {}", value, tz))
+    })?;
+    Ok(local.naive_utc().format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+pub use crate::schema::{ADSB_POSITION_COLUMNS, ADSB_VELOCITY_COLUMNS, AIRCRAFT_COLUMNS, AIRPORT_STATS_COLUMNS, FLIGHT_COLUMNS, FLIGHTLIST_COLUMNS, RAWDATA_COLUMNS};
+
+/// Conversion into a list of airport codes, see [`QueryParams::departure`].
+pub trait IntoAirports {
+    fn into_airports(self) -> Vec<String>;
+}
+
+impl IntoAirports for &str {
+    fn into_airports(self) -> Vec<String> {
+        vec![self.to_string()]
+    }
+}
+
+impl IntoAirports for String {
+    fn into_airports(self) -> Vec<String> {
+        vec![self]
+    }
+}
+
+impl<S: Into<String>> IntoAirports for Vec<S> {
+    fn into_airports(self) -> Vec<String> {
+        self.into_iter().map(Into::into).collect()
+    }
+}
+
+impl<S: Into<String> + Clone> IntoAirports for &[S] {
+    fn into_airports(self) -> Vec<String> {
+        self.iter().cloned().map(Into::into).collect()
+    }
+}
+
+impl<S: Into<String>, const N: usize> IntoAirports for [S; N] {
+    fn into_airports(self) -> Vec<String> {
+        self.into_iter().map(Into::into).collect()
+    }
+}
+
+/// Deserialize either a single string or a list of strings.
+fn one_or_many<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Option::<OneOrMany>::deserialize(deserializer)? {
+        None => Vec::new(),
+        Some(OneOrMany::One(s)) => vec![s],
+        Some(OneOrMany::Many(v)) => v,
+    })
+}
+
+/// Sort order of query results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Order {
+    /// By time (default)
+    #[default]
+    Time,
+    /// By aircraft, then time within each aircraft
+    IcaoThenTime,
+    /// Unordered, skipping the sort on the server
+    None,
+}
+
+impl std::str::FromStr for Order {
+    type Err = OpenSkyError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "time" => Ok(Order::Time),
+            "icao24" | "icao-then-time" => Ok(Order::IcaoThenTime),
+            "none" => Ok(Order::None),
+            _ => Err(OpenSkyError::InvalidParam(format!(
+                "Unknown order '{}', expected time, icao24 or none",
+                s
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Order {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Order::Time => "time",
+            Order::IcaoThenTime => "icao24",
+            Order::None => "none",
+        })
+    }
+}
+
+/// Which flight times a flight list query filters on.
+///
+/// Flight lists have no `time` column: a flight spans `firstseen..lastseen`
+/// and is partitioned by `day`, the UTC day of `lastseen`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum FlightTime {
+    /// `firstseen` with a departure filter, `lastseen` otherwise (default)
+    #[default]
+    Auto,
+    /// Flights first seen within the time range
+    FirstSeen,
+    /// Flights last seen within the time range
+    LastSeen,
+    /// Flights airborne at any point of the time range
+    Overlap,
+}
+
+impl FlightTime {
+    /// Resolve `Auto` against the airport filters of `params`.
+    pub fn resolve(self, params: &QueryParams) -> FlightTime {
+        match self {
+            FlightTime::Auto if !params.departure_airport.is_empty() => FlightTime::FirstSeen,
+            FlightTime::Auto => FlightTime::LastSeen,
+            other => other,
+        }
+    }
+}
 
 /// Raw data table types available in OpenSky.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
@@ -199,89 +923,1853 @@ pub enum RawTable {
 }
 
 impl RawTable {
-    /// Get the SQL table name.
-    pub fn table_name(&self) -> &'static str {
+    /// The database table holding these messages.
+    pub fn table(&self) -> Table {
         match self {
-            RawTable::RollcallReplies => "minio.osky.rollcall_replies_data4",
-            RawTable::Acas => "minio.osky.acas_data4",
-            RawTable::AllcallReplies => "minio.osky.allcall_replies_data4",
-            RawTable::Identification => "minio.osky.identification_data4",
-            RawTable::OperationalStatus => "minio.osky.operational_status_data4",
-            RawTable::Position => "minio.osky.position_data4",
-            RawTable::Velocity => "minio.osky.velocity_data4",
+            RawTable::RollcallReplies => Table::RollcallReplies,
+            RawTable::Acas => Table::Acas,
+            RawTable::AllcallReplies => Table::AllcallReplies,
+            RawTable::Identification => Table::Identification,
+            RawTable::OperationalStatus => Table::OperationalStatus,
+            RawTable::Position => Table::Position,
+            RawTable::Velocity => Table::Velocity,
         }
     }
+
+    /// Get the SQL table name, as named in `tables`.
+    pub fn table_name(&self, tables: &TableNames) -> String {
+        tables.get(self.table())
+    }
 }
 
-/// Wrapper around Polars DataFrame for flight data.
-#[derive(Debug, Clone)]
-pub struct FlightData {
-    df: DataFrame,
+/// Server-side aggregations available through `Trino::aggregate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Aggregation {
+    /// State vectors per hour (`hour`, `messages`)
+    MessagesPerHour,
+    /// Distinct aircraft per hour (`hour`, `aircraft`)
+    AircraftPerHour,
+    /// Distinct aircraft per day (`day`, `aircraft`)
+    AircraftPerDay,
+    /// Flights per day (`day`, `flights`)
+    FlightsPerDay,
+    /// Flights per departure airport (`airport`, `flights`)
+    FlightsPerDeparture,
+    /// Flights per arrival airport (`airport`, `flights`)
+    FlightsPerArrival,
 }
 
-impl FlightData {
-    /// Create FlightData from a Polars DataFrame.
-    pub fn new(df: DataFrame) -> Self {
-        Self { df }
+impl Aggregation {
+    /// Columns of the aggregated result.
+    pub fn columns(&self) -> &'static [&'static str] {
+        match self {
+            Aggregation::MessagesPerHour => &["hour", "messages"],
+            Aggregation::AircraftPerHour => &["hour", "aircraft"],
+            Aggregation::AircraftPerDay => &["day", "aircraft"],
+            Aggregation::FlightsPerDay => &["day", "flights"],
+            Aggregation::FlightsPerDeparture | Aggregation::FlightsPerArrival => &["airport", "flights"],
+        }
     }
+}
 
-    /// Get the underlying DataFrame.
-    pub fn dataframe(&self) -> &DataFrame {
-        &self.df
+/// Bucket size of airport statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum StatsInterval {
+    Hourly,
+    #[default]
+    Daily,
+}
+
+impl StatsInterval {
+    /// Bucket size in seconds.
+    pub fn seconds(&self) -> u32 {
+        match self {
+            StatsInterval::Hourly => 3600,
+            StatsInterval::Daily => 86400,
+        }
     }
+}
 
-    /// Get mutable reference to the underlying DataFrame.
-    pub fn dataframe_mut(&mut self) -> &mut DataFrame {
-        &mut self.df
+impl std::str::FromStr for StatsInterval {
+    type Err = OpenSkyError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "hourly" | "hour" => Ok(StatsInterval::Hourly),
+            "daily" | "day" => Ok(StatsInterval::Daily),
+            _ => Err(OpenSkyError::InvalidParam(format!(
+                "Unknown interval '{}', expected hourly or daily",
+                s
+            ))),
+        }
     }
+}
 
-    /// Consume and return the underlying DataFrame.
-    pub fn into_dataframe(self) -> DataFrame {
-        self.df
+/// When CSV fields are quoted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CsvQuoting {
+    /// Quote only fields containing the separator, quotes or newlines (default)
+    #[default]
+    Necessary,
+    /// Quote every field
+    Always,
+    /// Quote every non-numeric field
+    NonNumeric,
+    /// Never quote
+    Never,
+}
+
+impl std::str::FromStr for CsvQuoting {
+    type Err = OpenSkyError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "necessary" => Ok(CsvQuoting::Necessary),
+            "always" => Ok(CsvQuoting::Always),
+            "non-numeric" | "non_numeric" => Ok(CsvQuoting::NonNumeric),
+            "never" => Ok(CsvQuoting::Never),
+            other => Err(OpenSkyError::InvalidParam(format!(
+                "Unknown quoting '{}'. Use necessary, always, non-numeric or never",
+                other
+            ))),
+        }
     }
+}
 
-    /// Get the number of rows.
-    pub fn len(&self) -> usize {
-        self.df.height()
+/// Options for CSV export.
+///
+/// # Example
+///
+/// ```rust
+/// use opensky::{CsvOptions, CsvQuoting};
+///
+/// let options = CsvOptions::new()
+///     .separator(b';')
+///     .quoting(CsvQuoting::Always)
+///     .float_precision(3)
+///     .gzip(true);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub separator: u8,
+    pub quote_char: u8,
+    pub quoting: CsvQuoting,
+    pub include_header: bool,
+    /// Number of decimals for floats, `None` for full precision.
+    pub float_precision: Option<usize>,
+    /// chrono format string for datetime columns.
+    pub datetime_format: Option<String>,
+    /// Compress the output with gzip.
+    pub gzip: bool,
+    /// Append to an existing file instead of overwriting it.
+    pub append: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            separator: b',',
+            quote_char: b'"',
+            quoting: CsvQuoting::default(),
+            include_header: true,
+            float_precision: None,
+            datetime_format: None,
+            gzip: false,
+            append: false,
+        }
     }
+}
 
-    /// Check if empty.
-    pub fn is_empty(&self) -> bool {
-        self.df.height() == 0
+impl CsvOptions {
+    /// Create options matching the default CSV output.
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    /// Get column names.
-    pub fn columns(&self) -> Vec<String> {
-        self.df.get_column_names().iter().map(|s| s.to_string()).collect()
+    /// Set the field separator.
+    pub fn separator(mut self, separator: u8) -> Self {
+        self.separator = separator;
+        self
     }
 
-    /// Export to CSV file.
-    pub fn to_csv(&self, path: &str) -> Result<()> {
-        let mut file = std::fs::File::create(path)?;
-        CsvWriter::new(&mut file)
-            .finish(&mut self.df.clone())
-            .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
-        Ok(())
+    /// Set the quote character.
+    pub fn quote_char(mut self, quote_char: u8) -> Self {
+        self.quote_char = quote_char;
+        self
     }
 
-    /// Export to Parquet file.
-    pub fn to_parquet(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
-        let mut file = std::fs::File::create(path)?;
-        ParquetWriter::new(&mut file)
-            .finish(&mut self.df.clone())
-            .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
-        Ok(())
+    /// Set when fields are quoted.
+    pub fn quoting(mut self, quoting: CsvQuoting) -> Self {
+        self.quoting = quoting;
+        self
     }
 
-    /// Load from Parquet file.
-    pub fn from_parquet(path: impl AsRef<std::path::Path>) -> Result<Self> {
-        let file = std::fs::File::open(path)?;
-        let df = ParquetReader::new(file)
-            .finish()
-            .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
-        Ok(Self { df })
+    /// Write or omit the header row.
+    pub fn include_header(mut self, include_header: bool) -> Self {
+        self.include_header = include_header;
+        self
     }
-}
+
+    /// Round floats to a fixed number of decimals.
+    pub fn float_precision(mut self, precision: usize) -> Self {
+        self.float_precision = Some(precision);
+        self
+    }
+
+    /// Format datetime columns with a chrono format string.
+    pub fn datetime_format(mut self, format: &str) -> Self {
+        self.datetime_format = Some(format.to_string());
+        self
+    }
+
+    /// Compress the output with gzip.
+    pub fn gzip(mut self, gzip: bool) -> Self {
+        self.gzip = gzip;
+        self
+    }
+
+    /// Append to the file if it exists.
+    ///
+    /// The header is only written to a new or empty file. Gzip output is
+    /// appended as a new gzip member, which readers decode as one stream.
+    pub fn append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+}
+
+/// Where the rows of a result came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheStatus {
+    /// Fresh from Trino
+    #[default]
+    Miss,
+    /// Served from the local cache
+    Hit,
+    /// Some chunks of a split query from the cache, the others from Trino
+    Partial,
+}
+
+impl CacheStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Miss => "miss",
+            CacheStatus::Hit => "hit",
+            CacheStatus::Partial => "partial",
+        }
+    }
+
+    pub fn is_miss(&self) -> bool {
+        *self == CacheStatus::Miss
+    }
+
+    /// Status of rows merged from results with statuses `self` and `other`.
+    pub fn merge(self, other: CacheStatus) -> CacheStatus {
+        if self == other {
+            self
+        } else {
+            CacheStatus::Partial
+        }
+    }
+}
+
+/// Compact summary of a query result: its columns, size and time span.
+///
+/// See [`FlightData::metadata`]. Used by cache manifests, the CLI's JSON
+/// output and prefetch reports.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResultMetadata {
+    pub columns: Vec<ColumnMetadata>,
+    pub rows: usize,
+    /// Earliest time in the result (Unix seconds)
+    pub start: Option<i64>,
+    /// Latest time in the result (Unix seconds)
+    pub stop: Option<i64>,
+    /// Rows were dropped by the client-side row cap, see [`crate::Trino::set_max_rows`]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// How the columns sent by the server differed from the expected ones,
+    /// see [`crate::SchemaCheck`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schema_mismatch: Vec<String>,
+    /// Whether the rows were served from the local cache
+    #[serde(default, skip_serializing_if = "CacheStatus::is_miss")]
+    pub cache: CacheStatus,
+    /// Cache entries the rows were read from, or written to when fresh
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cache_files: Vec<std::path::PathBuf>,
+}
+
+/// Name and Polars dtype of a result column.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnMetadata {
+    pub name: String,
+    pub dtype: String,
+}
+
+/// Wrapper around Polars DataFrame for flight data.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct FlightData {
+    df: DataFrame,
+    truncated: bool,
+    schema_mismatch: Vec<String>,
+    cache: CacheStatus,
+    cache_files: Vec<std::path::PathBuf>,
+}
+
+#[cfg(feature = "client")]
+impl FlightData {
+    /// Create FlightData from a Polars DataFrame.
+    pub fn new(df: DataFrame) -> Self {
+        Self {
+            df,
+            truncated: false,
+            schema_mismatch: Vec::new(),
+            cache: CacheStatus::Miss,
+            cache_files: Vec::new(),
+        }
+    }
+
+    /// Mark the data as cut short by the client-side row cap.
+    pub(crate) fn with_truncated(mut self, truncated: bool) -> Self {
+        self.truncated = truncated;
+        self
+    }
+
+    /// Record how the server's columns differed from the expected ones.
+    pub(crate) fn with_schema_mismatch(mut self, mismatch: Vec<String>) -> Self {
+        self.schema_mismatch = mismatch;
+        self
+    }
+
+    /// Replace the rows, keeping what is recorded about them.
+    pub(crate) fn with_dataframe(mut self, df: DataFrame) -> Self {
+        self.df = df;
+        self
+    }
+
+    /// Record whether the rows came from the cache, and which entries.
+    pub(crate) fn with_cache(mut self, cache: CacheStatus, files: Vec<std::path::PathBuf>) -> Self {
+        self.cache = cache;
+        self.cache_files = files;
+        self
+    }
+
+    /// Get the underlying DataFrame.
+    pub fn dataframe(&self) -> &DataFrame {
+        &self.df
+    }
+
+    /// Get mutable reference to the underlying DataFrame.
+    pub fn dataframe_mut(&mut self) -> &mut DataFrame {
+        &mut self.df
+    }
+
+    /// Consume and return the underlying DataFrame.
+    pub fn into_dataframe(self) -> DataFrame {
+        self.df
+    }
+
+    /// Get the number of rows.
+    pub fn len(&self) -> usize {
+        self.df.height()
+    }
+
+    /// Whether the query returned more rows than [`crate::Trino::set_max_rows`]
+    /// allowed, so only the first ones are here.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// How the columns sent by the server differed from the expected ones;
+    /// empty if they matched. See [`crate::SchemaCheck`].
+    pub fn schema_mismatch(&self) -> &[String] {
+        &self.schema_mismatch
+    }
+
+    /// Whether the rows were served from the local cache, fully or for
+    /// some chunks of a split query.
+    pub fn cache_status(&self) -> CacheStatus {
+        self.cache
+    }
+
+    /// Cache entries the rows were read from, or written to when fresh from
+    /// Trino; compare them to find why two runs differ.
+    pub fn cache_files(&self) -> &[std::path::PathBuf] {
+        &self.cache_files
+    }
+
+    /// Check if empty.
+    pub fn is_empty(&self) -> bool {
+        self.df.height() == 0
+    }
+
+    /// Get column names.
+    pub fn columns(&self) -> Vec<String> {
+        self.df.get_column_names().iter().map(|s| s.to_string()).collect()
+    }
+
+    /// Statistics of each numeric column: one row per column with
+    /// `column`, `count` (non-null values), `null_count`, `mean`, `std`,
+    /// `min`, `median` and `max`.
+    pub fn describe(&self) -> Result<DataFrame> {
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+        let mut names = Vec::new();
+        let mut counts = Vec::new();
+        let mut nulls = Vec::new();
+        let mut stats: [Vec<Option<f64>>; 5] = Default::default();
+        for column in self.df.get_columns() {
+            if !column.dtype().is_primitive_numeric() {
+                continue;
+            }
+            let values = column.cast(&DataType::Float64).map_err(to_err)?;
+            let values = values.f64().map_err(to_err)?;
+            names.push(column.name().to_string());
+            counts.push((values.len() - values.null_count()) as u64);
+            nulls.push(values.null_count() as u64);
+            for (stat, value) in stats.iter_mut().zip([
+                values.mean(),
+                values.std(1),
+                values.min(),
+                values.median(),
+                values.max(),
+            ]) {
+                stat.push(value);
+            }
+        }
+        let [mean, std, min, median, max] = stats;
+        df!(
+            "column" => names,
+            "count" => counts,
+            "null_count" => nulls,
+            "mean" => mean,
+            "std" => std,
+            "min" => min,
+            "median" => median,
+            "max" => max,
+        )
+        .map_err(to_err)
+    }
+
+    /// Summarize the result: column names and dtypes, row count and the
+    /// time span covered by `time` (`mintime` for raw data, or
+    /// `firstseen`/`lastseen` for flight lists).
+    pub fn metadata(&self) -> ResultMetadata {
+        let bound = |name: &str, max: bool| -> Option<i64> {
+            let column = self.df.column(name).ok()?.cast(&DataType::Float64).ok()?;
+            let values = column.f64().ok()?;
+            let value = if max { values.max() } else { values.min() };
+            value.map(|v| v as i64)
+        };
+        let span = |start: &str, stop: &str| Some((bound(start, false)?, bound(stop, true)?));
+        let (start, stop) = span("time", "time")
+            .or_else(|| span("mintime", "mintime"))
+            .or_else(|| span("firstseen", "lastseen"))
+            .unzip();
+
+        ResultMetadata {
+            columns: self
+                .df
+                .get_columns()
+                .iter()
+                .map(|c| ColumnMetadata {
+                    name: c.name().to_string(),
+                    dtype: c.dtype().to_string(),
+                })
+                .collect(),
+            rows: self.df.height(),
+            start,
+            stop,
+            truncated: self.truncated,
+            schema_mismatch: self.schema_mismatch.clone(),
+            cache: self.cache,
+            cache_files: self.cache_files.clone(),
+        }
+    }
+
+    /// Export to CSV file.
+    pub fn to_csv(&self, path: &str) -> Result<()> {
+        crate::write_csv(&self.df, path)
+    }
+
+    /// Export to CSV file with custom options.
+    pub fn to_csv_with(&self, path: impl AsRef<std::path::Path>, options: &CsvOptions) -> Result<()> {
+        crate::write_csv_with(&self.df, path, options)
+    }
+
+    /// Export to Parquet file.
+    pub fn to_parquet(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        crate::write_parquet(&self.df, path)
+    }
+
+    /// Export to newline-delimited JSON file.
+    pub fn to_ndjson(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        crate::write_ndjson(&self.df, path, false)
+    }
+
+    /// Export to a Hive-partitioned Parquet dataset, e.g. by `["date",
+    /// "icao24"]`; see [`crate::write_parquet_dataset`].
+    pub fn to_parquet_dataset(&self, dir: impl AsRef<std::path::Path>, partition_by: &[&str]) -> Result<()> {
+        crate::write_parquet_dataset(&self.df, dir, partition_by)
+    }
+
+    /// Load from Parquet file.
+    ///
+    /// Known columns are cast to their canonical types.
+    pub fn from_parquet(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let df = ParquetReader::new(file)
+            .finish()
+            .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+        Ok(Self::new(crate::schema::coerce(df)?))
+    }
+
+    /// Load from CSV file, plain or gzip-compressed.
+    ///
+    /// Known columns are parsed with their canonical types, so
+    /// `icao24` and `squawk` stay strings even when they look numeric.
+    pub fn from_csv(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        use std::io::{Cursor, Read};
+
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+
+        let mut bytes = std::fs::read(path)?;
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            let mut decoded = Vec::new();
+            flate2::read::MultiGzDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+            bytes = decoded;
+        }
+
+        let header = CsvReadOptions::default()
+            .with_n_rows(Some(0))
+            .into_reader_with_file_handle(Cursor::new(bytes.as_slice()))
+            .finish()
+            .map_err(to_err)?;
+        let overwrite: Schema = header
+            .get_column_names()
+            .into_iter()
+            .filter_map(|name| {
+                let dtype = crate::schema::column_type(name)?;
+                Some(Field::new(name.clone(), dtype.to_polars()))
+            })
+            .collect();
+
+        let df = CsvReadOptions::default()
+            .with_schema_overwrite(Some(std::sync::Arc::new(overwrite)))
+            .into_reader_with_file_handle(Cursor::new(bytes))
+            .finish()
+            .map_err(to_err)?;
+        Ok(Self::new(df))
+    }
+
+    /// Load from Arrow IPC (Feather v2) file.
+    ///
+    /// Known columns are cast to their canonical types.
+    pub fn from_ipc(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let df = IpcReader::new(file)
+            .finish()
+            .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+        Ok(Self::new(crate::schema::coerce(df)?))
+    }
+
+    /// Add a `time_local` column with `time` formatted in time zone `tz`
+    /// (IANA name), e.g. "2025-01-01 11:00:00+01:00".
+    pub fn with_local_time(&self, tz: &str) -> Result<FlightData> {
+        let tz = parse_tz(tz)?;
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+
+        let time = self.df.column("time").map_err(to_err)?.cast(&DataType::Int64).map_err(to_err)?;
+        let local: Vec<Option<String>> = time
+            .i64()
+            .map_err(to_err)?
+            .into_iter()
+            .map(|t| {
+                let utc = chrono::DateTime::from_timestamp(t?, 0)?;
+                Some(utc.with_timezone(&tz).format("%Y-%m-%d %H:%M:%S%:z").to_string())
+            })
+            .collect();
+
+        let mut df = self.df.clone();
+        df.with_column(Column::new("time_local".into(), local)).map_err(to_err)?;
+        Ok(FlightData::new(df))
+    }
+
+    /// Classify the raw messages of `rawmsg`: adds `df`, their downlink
+    /// format, and `bds`, the candidate Comm-B registers of DF 20 and 21
+    /// replies (e.g. "4,0" or "5,0 6,0"), null when none fits.
+    ///
+    /// See [`crate::modes`] for how registers are inferred.
+    pub fn classify_bds(&self) -> Result<FlightData> {
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+
+        let rawmsg = self.df.column("rawmsg").map_err(to_err)?.str().map_err(to_err)?;
+        let df: Vec<Option<i64>> = rawmsg
+            .into_iter()
+            .map(|msg| crate::modes::downlink_format(msg?).map(i64::from))
+            .collect();
+        let bds: Vec<Option<String>> = rawmsg
+            .into_iter()
+            .map(|msg| {
+                let candidates = crate::modes::infer_bds(msg?);
+                let names: Vec<String> = candidates.iter().map(|bds| bds.to_string()).collect();
+                (!names.is_empty()).then(|| names.join(" "))
+            })
+            .collect();
+
+        let mut frame = self.df.clone();
+        frame.with_column(Column::new("df".into(), df)).map_err(to_err)?;
+        frame.with_column(Column::new("bds".into(), bds)).map_err(to_err)?;
+        Ok(FlightData::new(frame))
+    }
+
+    /// Drop rows with a null or (0, 0) position, or `lat`/`lon` outside
+    /// [-90, 90] and [-180, 180].
+    ///
+    /// The client-side counterpart of [`QueryParams::valid_positions`].
+    pub fn drop_invalid_positions(&self) -> Result<FlightData> {
+        let lat = col("lat");
+        let lon = col("lon");
+        let valid = lat
+            .clone()
+            .is_not_null()
+            .and(lon.clone().is_not_null())
+            .and(lat.clone().neq(lit(0.0)).or(lon.clone().neq(lit(0.0))))
+            .and(lat.clone().gt_eq(lit(-90.0)).and(lat.lt_eq(lit(90.0))))
+            .and(lon.clone().gt_eq(lit(-180.0)).and(lon.lt_eq(lit(180.0))));
+        let df = self
+            .df
+            .clone()
+            .lazy()
+            .filter(valid)
+            .collect()
+            .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+        Ok(FlightData::new(df))
+    }
+
+    /// Drop rows whose time falls within `start..=stop` (Unix seconds).
+    ///
+    /// Uses the same time column as [`FlightData::metadata`]; flights are
+    /// dropped if their `firstseen..=lastseen` overlaps the range. Data
+    /// without a time column is returned unchanged.
+    pub fn exclude_time_range(&self, start: i64, stop: i64) -> Result<FlightData> {
+        let Some((first, last)) = self.time_bounds_columns() else {
+            return Ok(self.clone());
+        };
+        let keep = col(last).lt(lit(start)).or(col(first).gt(lit(stop)));
+        let df = self
+            .df
+            .clone()
+            .lazy()
+            .filter(keep)
+            .collect()
+            .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+        Ok(FlightData::new(df))
+    }
+
+    /// Time span (Unix seconds) of the rows of each aircraft.
+    ///
+    /// Read from the same columns as [`FlightData::exclude_time_range`];
+    /// empty without an `icao24` or a time column.
+    pub fn time_spans(&self) -> Result<std::collections::HashMap<String, (i64, i64)>> {
+        let mut spans = std::collections::HashMap::new();
+        let Some((icao24, first, last)) = self.aircraft_times()? else {
+            return Ok(spans);
+        };
+        for ((icao24, first), last) in icao24.iter().zip(first.iter()).zip(last.iter()) {
+            if let (Some(icao24), Some(first), Some(last)) = (icao24, first, last) {
+                let span = spans.entry(icao24.to_string()).or_insert((first, last));
+                *span = (span.0.min(first), span.1.max(last));
+            }
+        }
+        Ok(spans)
+    }
+
+    /// Drop rows within the span of their own aircraft in `spans`, as given
+    /// by [`FlightData::time_spans`].
+    ///
+    /// Flights are dropped if they overlap the span. Rows of aircraft not in
+    /// `spans`, and data without an `icao24` or a time column, are kept.
+    pub fn exclude_time_spans(&self, spans: &std::collections::HashMap<String, (i64, i64)>) -> Result<FlightData> {
+        let Some((icao24, first, last)) = self.aircraft_times()? else {
+            return Ok(self.clone());
+        };
+        let keep: BooleanChunked = icao24
+            .iter()
+            .zip(first.iter())
+            .zip(last.iter())
+            .map(|((icao24, first), last)| match (icao24.and_then(|icao24| spans.get(icao24)), first, last) {
+                (Some(&(start, stop)), Some(first), Some(last)) => last < start || first > stop,
+                _ => true,
+            })
+            .collect();
+        let df = self
+            .df
+            .filter(&keep)
+            .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+        Ok(FlightData::new(df))
+    }
+
+    /// Columns holding the first and last time of each row: `time`,
+    /// `mintime`, or `firstseen` and `lastseen` for flights.
+    fn time_bounds_columns(&self) -> Option<(&'static str, &'static str)> {
+        let has = |name: &str| self.df.column(name).is_ok();
+        if has("time") {
+            Some(("time", "time"))
+        } else if has("mintime") {
+            Some(("mintime", "mintime"))
+        } else if has("firstseen") && has("lastseen") {
+            Some(("firstseen", "lastseen"))
+        } else {
+            None
+        }
+    }
+
+    /// `icao24` and the first and last time of each row, in whole seconds.
+    fn aircraft_times(&self) -> Result<Option<(StringChunked, Int64Chunked, Int64Chunked)>> {
+        let (Some((first, last)), Ok(icao24)) = (self.time_bounds_columns(), self.df.column("icao24")) else {
+            return Ok(None);
+        };
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+        let seconds = |name: &str| -> Result<Int64Chunked> {
+            let column = self.df.column(name).map_err(to_err)?.cast(&DataType::Int64).map_err(to_err)?;
+            Ok(column.i64().map_err(to_err)?.clone())
+        };
+        let icao24 = icao24.cast(&DataType::String).map_err(to_err)?;
+        Ok(Some((icao24.str().map_err(to_err)?.clone(), seconds(first)?, seconds(last)?)))
+    }
+
+    /// Keep only the given columns, in that order.
+    ///
+    /// ```rust,no_run
+    /// # fn example(data: &opensky::FlightData) -> opensky::Result<()> {
+    /// let track = data.select(&["time", "lat", "lon"])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn select(&self, columns: &[&str]) -> Result<FlightData> {
+        let df = self
+            .df
+            .select(columns.iter().copied())
+            .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+        Ok(FlightData::new(df))
+    }
+
+    /// Keep rows with `time` within `start..=stop`.
+    ///
+    /// Times are read as in [`QueryParams::time_range`]; a date-only stop
+    /// counts up to the end of that day.
+    pub fn filter_time(&self, start: &str, stop: &str) -> Result<FlightData> {
+        let bound = |value: &str, end_of_day: bool| {
+            crate::query::parse_bound(value, end_of_day).map(|time| time.and_utc().timestamp())
+        };
+        let (start, stop) = (bound(start, false)?, bound(stop, true)?);
+        self.filtered(col("time").gt_eq(lit(start)).and(col("time").lt_eq(lit(stop))))
+    }
+
+    /// Keep rows of the given aircraft; addresses are normalized as in
+    /// [`QueryParams::icao24`].
+    pub fn filter_icao24(&self, icao24: &[&str]) -> Result<FlightData> {
+        let matches = icao24
+            .iter()
+            .map(|address| col("icao24").eq(lit(normalize_icao24(address))))
+            .reduce(|any, matches| any.or(matches))
+            .unwrap_or(lit(false));
+        self.filtered(matches)
+    }
+
+    /// Keep rows with a position inside `bounds` (edges included).
+    pub fn filter_bbox(&self, bounds: &Bounds) -> Result<FlightData> {
+        let (lat, lon) = (col("lat"), col("lon"));
+        let inside = lat
+            .clone()
+            .gt_eq(lit(bounds.south))
+            .and(lat.lt_eq(lit(bounds.north)))
+            .and(lon.clone().gt_eq(lit(bounds.west)))
+            .and(lon.lt_eq(lit(bounds.east)));
+        self.filtered(inside)
+    }
+
+    /// Keep the rows matching `predicate`.
+    fn filtered(&self, predicate: Expr) -> Result<FlightData> {
+        let df = self
+            .df
+            .clone()
+            .lazy()
+            .filter(predicate)
+            .collect()
+            .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+        Ok(FlightData::new(df))
+    }
+
+    /// Split the rows per aircraft, keyed by `icao24`.
+    ///
+    /// Each part is in time order, whatever the order of the query.
+    ///
+    /// ```rust,no_run
+    /// # fn example(data: &opensky::FlightData) -> opensky::Result<()> {
+    /// for (icao24, track) in data.by_aircraft()? {
+    ///     println!("{}: {} positions", icao24, track.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn by_aircraft(&self) -> Result<std::collections::HashMap<String, FlightData>> {
+        self.partition("icao24")
+    }
+
+    /// Split the rows per flight, keyed by the `flight_id` added by
+    /// [`FlightData::attach_flight_info`].
+    ///
+    /// Each part is in time order; rows matched to no flight are left out.
+    pub fn by_flight(&self) -> Result<std::collections::HashMap<String, FlightData>> {
+        if self.df.column("flight_id").is_err() {
+            return Err(OpenSkyError::InvalidParam(
+                "No flight_id column: match the rows to flights with attach_flight_info first".to_string(),
+            ));
+        }
+        self.partition("flight_id")
+    }
+
+    /// Split the rows by the values of a string column, skipping nulls.
+    fn partition(&self, key: &str) -> Result<std::collections::HashMap<String, FlightData>> {
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+        let keys = self.df.column(key).map_err(to_err)?.str().map_err(to_err)?.clone();
+        let mut rows: std::collections::HashMap<String, Vec<IdxSize>> = std::collections::HashMap::new();
+        for (i, value) in keys.into_iter().enumerate() {
+            if let Some(value) = value {
+                rows.entry(value.to_string()).or_default().push(i as IdxSize);
+            }
+        }
+
+        let sorted = self.df.column("time").is_ok();
+        rows.into_iter()
+            .map(|(value, rows)| {
+                let mut df = self.df.take(&IdxCa::from_vec("rows".into(), rows)).map_err(to_err)?;
+                if sorted {
+                    df = df
+                        .sort(["time"], SortMultipleOptions::default().with_maintain_order(true))
+                        .map_err(to_err)?;
+                }
+                Ok((value, FlightData::new(df)))
+            })
+            .collect()
+    }
+
+    /// Add `flight_id`, `estdepartureairport` and `estarrivalairport` columns
+    /// by matching each state vector to a flight of `flights`.
+    ///
+    /// A row matches a flight with the same `icao24` whose
+    /// `firstseen..=lastseen` window contains its `time`; callsigns must also
+    /// agree when both are known. The flight id is `{icao24}_{firstseen}`.
+    /// Rows without a matching flight get nulls. This avoids repeating the
+    /// server-side airport join when both datasets are already downloaded.
+    pub fn attach_flight_info(&self, flights: &FlightList) -> Result<FlightData> {
+        self.attach_flight_info_within(flights, chrono::Duration::zero())
+    }
+
+    /// [`FlightData::attach_flight_info`], widening each flight window by
+    /// `margin` on both sides.
+    pub(crate) fn attach_flight_info_within(&self, flights: &FlightList, margin: chrono::Duration) -> Result<FlightData> {
+        let margin = margin.num_seconds();
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+
+        let mut by_icao24: std::collections::HashMap<String, Vec<FlightListEntry>> =
+            std::collections::HashMap::new();
+        for flight in flights.iter() {
+            by_icao24.entry(flight.icao24.clone()).or_default().push(flight);
+        }
+
+        let icao24 = self.df.column("icao24").map_err(to_err)?;
+        let icao24 = icao24.str().map_err(to_err)?;
+        let time = self.df.column("time").map_err(to_err)?.cast(&DataType::Int64).map_err(to_err)?;
+        let time = time.i64().map_err(to_err)?;
+        let callsign = match self.df.column("callsign") {
+            Ok(column) => Some(column.str().map_err(to_err)?.clone()),
+            Err(_) => None,
+        };
+
+        let height = self.df.height();
+        let mut flight_id = Vec::with_capacity(height);
+        let mut departure = Vec::with_capacity(height);
+        let mut arrival = Vec::with_capacity(height);
+
+        for i in 0..height {
+            let row_callsign = callsign.as_ref().and_then(|c| c.get(i)).map(str::trim);
+            let matched = match (icao24.get(i), time.get(i)) {
+                (Some(icao24), Some(t)) => by_icao24.get(icao24).and_then(|candidates| {
+                    candidates.iter().find(|f| {
+                        f.firstseen.timestamp() - margin <= t
+                            && t <= f.lastseen.timestamp() + margin
+                            && match (row_callsign, f.callsign.as_deref()) {
+                                (Some(a), Some(b)) if !a.is_empty() => a == b,
+                                _ => true,
+                            }
+                    })
+                }),
+                _ => None,
+            };
+            flight_id.push(matched.map(|f| format!("{}_{}", f.icao24, f.firstseen.timestamp())));
+            departure.push(matched.and_then(|f| f.estdepartureairport.clone()));
+            arrival.push(matched.and_then(|f| f.estarrivalairport.clone()));
+        }
+
+        let mut df = self.df.clone();
+        for (name, values) in [
+            ("flight_id", flight_id),
+            ("estdepartureairport", departure),
+            ("estarrivalairport", arrival),
+        ] {
+            df.with_column(Column::new(name.into(), values)).map_err(to_err)?;
+        }
+        Ok(FlightData::new(df))
+    }
+
+    /// Vertical profile of each flight, resampled every `interval` seconds.
+    ///
+    /// Flights are told apart by `flight_id` when present (see
+    /// [`FlightData::attach_flight_info`]), otherwise by `icao24`. The altitude
+    /// is `baroaltitude`, or `geoaltitude` where it is null, linearly
+    /// interpolated and smoothed with a centered moving average over 5
+    /// samples; the vertical rate (m/s) is derived from the smoothed altitude.
+    pub fn vertical_profile(&self, interval: u32) -> Result<Vec<VerticalProfile>> {
+        if interval == 0 {
+            return Err(OpenSkyError::InvalidParam("Profile interval must be positive".to_string()));
+        }
+        let key = self.flight_key()?;
+        let time = self.time_column()?;
+        let altitude = self.altitude()?;
+
+        let mut tracks: std::collections::BTreeMap<&str, Vec<(i64, f64)>> = std::collections::BTreeMap::new();
+        for i in 0..self.df.height() {
+            if let (Some(key), Some(t), Some(altitude)) = (key.get(i), time.get(i), altitude.get(i)) {
+                tracks.entry(key).or_default().push((t, altitude));
+            }
+        }
+
+        Ok(tracks
+            .into_iter()
+            .map(|(flight, mut track)| {
+                track.sort_by_key(|&(t, _)| t);
+                track.dedup_by_key(|&mut (t, _)| t);
+                VerticalProfile::from_track(flight.to_string(), &track, interval as i64)
+            })
+            .collect())
+    }
+
+    /// Most likely runway of each takeoff and landing.
+    ///
+    /// Samples within 10 km of a threshold of `runways` and less than 1000 m
+    /// above the lowest point of the flight there vote for the runway whose
+    /// centerline is within 500 m and whose bearing is within 20° of their
+    /// `heading`. The operation is an arrival when the altitude decreases
+    /// over the votes, or without altitudes when they fall in the second
+    /// half of the flight.
+    ///
+    /// Returns one row per flight with a match: `flight` (`flight_id`, or
+    /// `icao24` without flight ids), `operation` ("arrival" or "departure"),
+    /// `runway` and `time`, the last aligned sample of an arrival or the first
+    /// of a departure.
+    pub fn infer_runway(&self, runways: &[crate::airports::Runway]) -> Result<DataFrame> {
+        const RADIUS: f64 = 10_000.0;
+        const MAX_HEIGHT: f64 = 1000.0;
+        const MAX_CROSS_TRACK: f64 = 500.0;
+        const MAX_ANGLE: f64 = 20.0;
+
+        let key = self.flight_key()?;
+        let time = self.time_column()?;
+        let missing = |name: &str| OpenSkyError::DataConversion(format!("Column '{}' not found", name));
+        let lat = self.float_column("lat")?.ok_or_else(|| missing("lat"))?;
+        let lon = self.float_column("lon")?.ok_or_else(|| missing("lon"))?;
+        let heading = self.float_column("heading")?.ok_or_else(|| missing("heading"))?;
+        let altitude = self.altitude()?;
+
+        // Per flight: time, position, heading and altitude of every sample
+        type Sample = (i64, f64, f64, Option<f64>, Option<f64>);
+        let mut flights: std::collections::BTreeMap<&str, Vec<Sample>> = std::collections::BTreeMap::new();
+        for i in 0..self.df.height() {
+            if let (Some(key), Some(t), Some(lat), Some(lon)) = (key.get(i), time.get(i), lat.get(i), lon.get(i)) {
+                flights.entry(key).or_default().push((t, lat, lon, heading.get(i), altitude.get(i)));
+            }
+        }
+
+        let mut columns: (Vec<String>, Vec<&str>, Vec<String>, Vec<i64>) = Default::default();
+        for (flight, mut samples) in flights {
+            samples.sort_by_key(|s| s.0);
+            let middle = (samples[0].0 + samples[samples.len() - 1].0) as f64 / 2.0;
+            let near: Vec<&Sample> = samples
+                .iter()
+                .filter(|s| {
+                    runways.iter().any(|r| {
+                        let (x, y) = r.offset(s.1, s.2);
+                        x.hypot(y) < RADIUS
+                    })
+                })
+                .collect();
+            let floor = near.iter().filter_map(|s| s.4).fold(f64::INFINITY, f64::min);
+
+            let mut votes: Vec<Vec<(i64, Option<f64>)>> = vec![Vec::new(); runways.len()];
+            for &&(t, lat, lon, heading, altitude) in &near {
+                let Some(heading) = heading else { continue };
+                if altitude.is_some_and(|a| a > floor + MAX_HEIGHT) {
+                    continue;
+                }
+                let aligned = runways
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, r)| {
+                        let angle = (heading - r.bearing).rem_euclid(360.0);
+                        let (x, y) = r.offset(lat, lon);
+                        let bearing = r.bearing.to_radians();
+                        let cross_track = (x * bearing.cos() - y * bearing.sin()).abs();
+                        (angle.min(360.0 - angle) <= MAX_ANGLE && cross_track <= MAX_CROSS_TRACK)
+                            .then_some((index, cross_track))
+                    })
+                    .min_by(|a, b| a.1.total_cmp(&b.1));
+                if let Some((index, _)) = aligned {
+                    votes[index].push((t, altitude));
+                }
+            }
+
+            let Some((index, times)) = votes
+                .iter()
+                .enumerate()
+                .filter(|(_, times)| !times.is_empty())
+                .max_by_key(|(index, times)| (times.len(), std::cmp::Reverse(*index)))
+            else {
+                continue;
+            };
+            let (first, last) = (times[0], times[times.len() - 1]);
+            let arrival = match (first.1, last.1) {
+                (Some(a), Some(b)) if a != b => b < a,
+                _ => times.iter().map(|v| v.0).sum::<i64>() as f64 / times.len() as f64 > middle,
+            };
+            columns.0.push(flight.to_string());
+            columns.1.push(if arrival { "arrival" } else { "departure" });
+            columns.2.push(runways[index].name.clone());
+            columns.3.push(if arrival { last.0 } else { first.0 });
+        }
+
+        df!(
+            "flight" => columns.0,
+            "operation" => columns.1,
+            "runway" => columns.2,
+            "time" => columns.3,
+        )
+        .map_err(|e| OpenSkyError::DataConversion(e.to_string()))
+    }
+
+    /// Add derived kinematics columns, computed per flight in time order.
+    ///
+    /// - `track`: `heading`, or the bearing between neighboring positions where it is null (degrees)
+    /// - `turn_rate`: change of `track` (degrees/s, positive to the right)
+    /// - `acceleration`: change of `velocity` (m/s²)
+    /// - `vertical_speed`: `vertrate` averaged over 5 samples, or derived from
+    ///   the altitude without a `vertrate` column (m/s)
+    ///
+    /// Rates are central differences between the neighboring samples and null
+    /// where an input is missing. Flights are told apart as in
+    /// [`FlightData::vertical_profile`].
+    pub fn with_kinematics(&self) -> Result<FlightData> {
+        let key = self.flight_key()?;
+        let time = self.time_column()?;
+        let heading = self.float_column("heading")?;
+        let lat = self.float_column("lat")?;
+        let lon = self.float_column("lon")?;
+        let velocity = self.float_column("velocity")?;
+        let vertrate = self.float_column("vertrate")?;
+        let altitude = self.altitude()?;
+        let get = |column: &Option<Float64Chunked>, i: usize| column.as_ref().and_then(|c| c.get(i));
+
+        let mut flights: std::collections::BTreeMap<&str, Vec<usize>> = std::collections::BTreeMap::new();
+        for i in 0..self.df.height() {
+            if let (Some(key), Some(_)) = (key.get(i), time.get(i)) {
+                flights.entry(key).or_default().push(i);
+            }
+        }
+
+        let height = self.df.height();
+        let mut track = vec![None; height];
+        let mut turn_rate = vec![None; height];
+        let mut acceleration = vec![None; height];
+        let mut vertical_speed = vec![None; height];
+        for mut rows in flights.into_values() {
+            rows.sort_by_key(|&i| time.get(i));
+            let n = rows.len();
+            let times: Vec<i64> = rows.iter().filter_map(|&i| time.get(i)).collect();
+            let position = |j: usize| get(&lat, rows[j]).zip(get(&lon, rows[j]));
+
+            let flight_track: Vec<Option<f64>> = (0..n)
+                .map(|j| {
+                    get(&heading, rows[j]).or_else(|| {
+                        let (from, to) = (position(j.saturating_sub(1))?, position((j + 1).min(n - 1))?);
+                        (from != to).then(|| bearing(from, to))
+                    })
+                })
+                .collect();
+            let flight_turn_rate = central_difference(&flight_track, &times, true);
+            let speed: Vec<Option<f64>> = rows.iter().map(|&i| get(&velocity, i)).collect();
+            let flight_acceleration = central_difference(&speed, &times, false);
+            let climb: Vec<Option<f64>> = match vertrate {
+                Some(_) => rows.iter().map(|&i| get(&vertrate, i)).collect(),
+                None => central_difference(&rows.iter().map(|&i| altitude.get(i)).collect::<Vec<_>>(), &times, false),
+            };
+            let flight_vertical_speed = (0..n).map(|j| {
+                let values: Vec<f64> = climb[j.saturating_sub(2)..=(j + 2).min(n - 1)].iter().flatten().copied().collect();
+                (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+            });
+
+            for (j, vs) in flight_vertical_speed.enumerate() {
+                let i = rows[j];
+                track[i] = flight_track[j];
+                turn_rate[i] = flight_turn_rate[j];
+                acceleration[i] = flight_acceleration[j];
+                vertical_speed[i] = vs;
+            }
+        }
+
+        let mut df = self.df.clone();
+        for (name, values) in [
+            ("track", track),
+            ("turn_rate", turn_rate),
+            ("acceleration", acceleration),
+            ("vertical_speed", vertical_speed),
+        ] {
+            df.with_column(Column::new(name.into(), values))
+                .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+        }
+        Ok(FlightData::new(df))
+    }
+
+    /// Add `fuel_flow` (kg/s), cumulative `fuel` (kg) and `co2` (kg) columns.
+    ///
+    /// The fuel flow of each sample comes from `model`, given the aircraft
+    /// `typecode` column, the altitude, `velocity` and `vertrate`; the crate
+    /// ships no performance tables, so `model` typically wraps
+    /// OpenAP-style data. Fuel is integrated per flight in time order with
+    /// the trapezoidal rule, and CO2 is 3.16 kg per kg of fuel. Samples the
+    /// model has no answer for get a null fuel flow and add nothing.
+    pub fn estimate_fuel(&self, model: &impl FuelModel) -> Result<FlightData> {
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+        let key = self.flight_key()?;
+        let time = self.time_column()?;
+        let typecode = self.df.column("typecode").map_err(to_err)?.str().map_err(to_err)?.clone();
+        let altitude = self.altitude()?;
+        let velocity = self.float_column("velocity")?;
+        let vertrate = self.float_column("vertrate")?;
+        let get = |column: &Option<Float64Chunked>, i: usize| column.as_ref().and_then(|c| c.get(i));
+
+        let height = self.df.height();
+        let fuel_flow: Vec<Option<f64>> = (0..height)
+            .map(|i| {
+                model.fuel_flow(
+                    typecode.get(i)?.trim(),
+                    altitude.get(i)?,
+                    get(&velocity, i)?,
+                    get(&vertrate, i).unwrap_or(0.0),
+                )
+            })
+            .collect();
+
+        let mut flights: std::collections::BTreeMap<&str, Vec<usize>> = std::collections::BTreeMap::new();
+        for i in 0..height {
+            if let (Some(key), Some(_)) = (key.get(i), time.get(i)) {
+                flights.entry(key).or_default().push(i);
+            }
+        }
+        let mut fuel = vec![None; height];
+        for mut rows in flights.into_values() {
+            rows.sort_by_key(|&i| time.get(i));
+            let mut total = 0.0;
+            for (j, &i) in rows.iter().enumerate() {
+                if j > 0 {
+                    let previous = rows[j - 1];
+                    let dt = (time.get(i).unwrap_or(0) - time.get(previous).unwrap_or(0)) as f64;
+                    total += dt * (fuel_flow[previous].unwrap_or(0.0) + fuel_flow[i].unwrap_or(0.0)) / 2.0;
+                }
+                fuel[i] = Some(total);
+            }
+        }
+        let co2: Vec<Option<f64>> = fuel.iter().map(|f| f.map(|f| f * CO2_PER_FUEL)).collect();
+
+        let mut df = self.df.clone();
+        for (name, values) in [("fuel_flow", fuel_flow), ("fuel", fuel), ("co2", co2)] {
+            df.with_column(Column::new(name.into(), values)).map_err(to_err)?;
+        }
+        Ok(FlightData::new(df))
+    }
+
+    /// Deviation of this trajectory from `other`, e.g. flown against planned.
+    ///
+    /// Both are aligned on the time elapsed since their first position, so
+    /// the same flight on different days compares directly. Each position of
+    /// this trajectory within the span of `other` is measured against the
+    /// position of `other` interpolated at the same elapsed time, along and
+    /// across its direction of travel (positive ahead and to the right).
+    pub fn compare(&self, other: &FlightData) -> Result<TrajectoryComparison> {
+        let this = self.positions()?;
+        let reference = other.positions()?;
+        if this.is_empty() || reference.len() < 2 {
+            return Err(OpenSkyError::InvalidParam(
+                "Comparing trajectories needs positions in both, and two in the reference".to_string(),
+            ));
+        }
+        let (start, reference_start) = (this[0].0, reference[0].0);
+        let reference_span = reference[reference.len() - 1].0 - reference_start;
+
+        let mut columns: (Vec<i64>, Vec<f64>, Vec<f64>, Vec<f64>) = Default::default();
+        let mut j = 0;
+        for &(t, lat, lon) in &this {
+            let elapsed = t - start;
+            if elapsed > reference_span {
+                break;
+            }
+            while j + 2 < reference.len() && reference[j + 1].0 - reference_start <= elapsed {
+                j += 1;
+            }
+            let (t0, lat0, lon0) = reference[j];
+            let (t1, lat1, lon1) = reference[j + 1];
+            let f = (elapsed - (t0 - reference_start)) as f64 / (t1 - t0) as f64;
+            let (ref_lat, ref_lon) = (lat0 + (lat1 - lat0) * f, lon0 + (lon1 - lon0) * f);
+
+            let direction = bearing((lat0, lon0), (lat1, lon1)).to_radians();
+            let east = (lon - ref_lon).to_radians() * ref_lat.to_radians().cos() * EARTH_RADIUS;
+            let north = (lat - ref_lat).to_radians() * EARTH_RADIUS;
+            columns.0.push(elapsed);
+            columns.1.push(east * direction.sin() + north * direction.cos());
+            columns.2.push(east * direction.cos() - north * direction.sin());
+            columns.3.push(east.hypot(north));
+        }
+
+        let n = columns.0.len();
+        let mean = |values: &[f64]| if values.is_empty() { 0.0 } else { values.iter().sum::<f64>() / values.len() as f64 };
+        let max_abs = |values: &[f64]| values.iter().fold(0.0_f64, |m, v| m.max(v.abs()));
+        let summary = DeviationSummary {
+            samples: n,
+            mean_along_track: mean(&columns.1),
+            max_along_track: max_abs(&columns.1),
+            mean_cross_track: mean(&columns.2.iter().map(|v| v.abs()).collect::<Vec<_>>()),
+            max_cross_track: max_abs(&columns.2),
+            rms_cross_track: mean(&columns.2.iter().map(|v| v * v).collect::<Vec<_>>()).sqrt(),
+        };
+        let deviations = df!(
+            "elapsed" => columns.0,
+            "along_track" => columns.1,
+            "cross_track" => columns.2,
+            "distance" => columns.3,
+        )
+        .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?;
+        Ok(TrajectoryComparison { deviations, summary })
+    }
+
+    /// Coverage holes of at least `min_gap` between consecutive positions.
+    ///
+    /// Returns one row per gap: `flight` (`flight_id`, or `icao24` without
+    /// flight ids), `start` and `stop` (Unix seconds of the positions around
+    /// the gap), `duration` (s), and the positions before (`lat_before`,
+    /// `lon_before`) and after (`lat_after`, `lon_after`). Rows without a
+    /// position do not count as coverage.
+    pub fn gaps(&self, min_gap: chrono::Duration) -> Result<DataFrame> {
+        let key = self.flight_key()?;
+        let time = self.time_column()?;
+        let missing = |name: &str| OpenSkyError::DataConversion(format!("Column '{}' not found", name));
+        let lat = self.float_column("lat")?.ok_or_else(|| missing("lat"))?;
+        let lon = self.float_column("lon")?.ok_or_else(|| missing("lon"))?;
+
+        let mut flights: std::collections::BTreeMap<&str, Vec<(i64, f64, f64)>> = std::collections::BTreeMap::new();
+        for i in 0..self.df.height() {
+            if let (Some(key), Some(t), Some(lat), Some(lon)) = (key.get(i), time.get(i), lat.get(i), lon.get(i)) {
+                flights.entry(key).or_default().push((t, lat, lon));
+            }
+        }
+
+        let min_gap = min_gap.num_seconds();
+        let mut flight = Vec::new();
+        let mut bounds: (Vec<i64>, Vec<i64>, Vec<i64>) = Default::default();
+        let mut positions: (Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>) = Default::default();
+        for (key, mut samples) in flights {
+            samples.sort_by_key(|s| s.0);
+            for pair in samples.windows(2) {
+                let (before, after) = (pair[0], pair[1]);
+                if after.0 - before.0 < min_gap {
+                    continue;
+                }
+                flight.push(key.to_string());
+                bounds.0.push(before.0);
+                bounds.1.push(after.0);
+                bounds.2.push(after.0 - before.0);
+                positions.0.push(before.1);
+                positions.1.push(before.2);
+                positions.2.push(after.1);
+                positions.3.push(after.2);
+            }
+        }
+
+        df!(
+            "flight" => flight,
+            "start" => bounds.0,
+            "stop" => bounds.1,
+            "duration" => bounds.2,
+            "lat_before" => positions.0,
+            "lon_before" => positions.1,
+            "lat_after" => positions.2,
+            "lon_after" => positions.3,
+        )
+        .map_err(|e| OpenSkyError::DataConversion(e.to_string()))
+    }
+
+    /// Iterate over the rows as [`StateVector`]s, skipping rows without a time.
+    pub fn state_vectors(&self) -> Result<impl Iterator<Item = StateVector> + '_> {
+        let rows = StateVectorRows::new(self)?;
+        Ok((0..self.df.height()).filter_map(move |i| rows.get(i)))
+    }
+
+    /// Replay the rows in simulated real time.
+    ///
+    /// [`Replay::next`] returns the state vectors in time order, each once
+    /// its time has come: the first straight away, the next ones after the
+    /// time elapsed between them divided by `speed`. A `speed` of 60 plays
+    /// an hour of traffic in a minute.
+    ///
+    /// ```rust,no_run
+    /// # async fn example(data: &opensky::FlightData) -> opensky::Result<()> {
+    /// let mut replay = data.replay(10.0)?;
+    /// while let Some(sv) = replay.next().await {
+    ///     println!("{} {} {:?} {:?}", sv.time, sv.icao24, sv.lat, sv.lon);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn replay(&self, speed: f64) -> Result<Replay> {
+        if !(speed.is_finite() && speed > 0.0) {
+            return Err(OpenSkyError::InvalidParam(format!(
+                "Replay speed must be a positive number, got {}",
+                speed
+            )));
+        }
+        let rows = StateVectorRows::new(self)?;
+        let mut order: Vec<(i64, usize)> = (0..self.df.height())
+            .filter_map(|i| Some((rows.time.get(i)?, i)))
+            .collect();
+        order.sort_by_key(|o| o.0);
+        Ok(Replay {
+            rows,
+            order,
+            position: 0,
+            start: None,
+            speed,
+        })
+    }
+
+    /// Non-null `(time, lat, lon)` samples in time order.
+    fn positions(&self) -> Result<Vec<(i64, f64, f64)>> {
+        let time = self.time_column()?;
+        let missing = |name: &str| OpenSkyError::DataConversion(format!("Column '{}' not found", name));
+        let lat = self.float_column("lat")?.ok_or_else(|| missing("lat"))?;
+        let lon = self.float_column("lon")?.ok_or_else(|| missing("lon"))?;
+        let mut positions: Vec<(i64, f64, f64)> = (0..self.df.height())
+            .filter_map(|i| Some((time.get(i)?, lat.get(i)?, lon.get(i)?)))
+            .collect();
+        positions.sort_by_key(|p| p.0);
+        positions.dedup_by_key(|p| p.0);
+        Ok(positions)
+    }
+
+    /// `flight_id` when present, otherwise `icao24`: what tells flights apart.
+    fn flight_key(&self) -> Result<StringChunked> {
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+        let key = if self.df.column("flight_id").is_ok() { "flight_id" } else { "icao24" };
+        Ok(self.df.column(key).map_err(to_err)?.str().map_err(to_err)?.clone())
+    }
+
+    pub(crate) fn time_column(&self) -> Result<Int64Chunked> {
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+        let time = self.df.column("time").map_err(to_err)?.cast(&DataType::Int64).map_err(to_err)?;
+        Ok(time.i64().map_err(to_err)?.clone())
+    }
+
+    /// A column cast to Float64, or `None` if the data has no such column.
+    pub(crate) fn float_column(&self, name: &str) -> Result<Option<Float64Chunked>> {
+        let to_err = |e: PolarsError| OpenSkyError::DataConversion(e.to_string());
+        match self.df.column(name) {
+            Ok(column) => {
+                let column = column.cast(&DataType::Float64).map_err(to_err)?;
+                Ok(Some(column.f64().map_err(to_err)?.clone()))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// `baroaltitude`, or `geoaltitude` where it is null.
+    pub(crate) fn altitude(&self) -> Result<Float64Chunked> {
+        let baro = self.float_column("baroaltitude")?;
+        let geo = self.float_column("geoaltitude")?;
+        Ok(match (baro, geo) {
+            (Some(baro), Some(geo)) => baro
+                .zip_with(&baro.is_not_null(), &geo)
+                .map_err(|e| OpenSkyError::DataConversion(e.to_string()))?,
+            (Some(altitude), None) | (None, Some(altitude)) => altitude,
+            (None, None) => Float64Chunked::full_null("altitude".into(), self.df.height()),
+        })
+    }
+}
+
+/// Mean Earth radius (m)
+#[cfg(feature = "client")]
+pub(crate) const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// Result of [`FlightData::compare`].
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct TrajectoryComparison {
+    /// `elapsed` (s), `along_track`, `cross_track` and `distance` (m) per position
+    pub deviations: DataFrame,
+    pub summary: DeviationSummary,
+}
+
+/// Summary statistics of a [`TrajectoryComparison`], in meters.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct DeviationSummary {
+    /// Positions compared
+    pub samples: usize,
+    /// Mean along-track deviation; positive when ahead of the reference
+    pub mean_along_track: f64,
+    pub max_along_track: f64,
+    /// Mean absolute cross-track deviation
+    pub mean_cross_track: f64,
+    pub max_cross_track: f64,
+    pub rms_cross_track: f64,
+}
+
+/// CO2 emitted per kg of jet fuel burnt (kg)
+#[cfg(feature = "client")]
+const CO2_PER_FUEL: f64 = 3.16;
+
+/// Aircraft performance model for [`FlightData::estimate_fuel`].
+#[cfg(feature = "client")]
+pub trait FuelModel {
+    /// Fuel flow (kg/s) of an aircraft type at an altitude (m), ground speed
+    /// (m/s) and vertical rate (m/s), or `None` for unknown types.
+    fn fuel_flow(&self, typecode: &str, altitude: f64, velocity: f64, vertical_rate: f64) -> Option<f64>;
+}
+
+#[cfg(feature = "client")]
+impl<F: Fn(&str, f64, f64, f64) -> Option<f64>> FuelModel for F {
+    fn fuel_flow(&self, typecode: &str, altitude: f64, velocity: f64, vertical_rate: f64) -> Option<f64> {
+        self(typecode, altitude, velocity, vertical_rate)
+    }
+}
+
+/// Initial great-circle bearing between two `(lat, lon)` positions (degrees).
+#[cfg(feature = "client")]
+fn bearing(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+    let dlon = (to.1 - from.1).to_radians();
+    let y = dlon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Rate of change per second between the neighbors of each sample.
+///
+/// With `angular`, differences are wrapped to [-180, 180) degrees.
+#[cfg(feature = "client")]
+fn central_difference(values: &[Option<f64>], times: &[i64], angular: bool) -> Vec<Option<f64>> {
+    let n = values.len();
+    (0..n)
+        .map(|i| {
+            let (lo, hi) = (i.saturating_sub(1), (i + 1).min(n - 1));
+            let dt = (times[hi] - times[lo]) as f64;
+            let (a, b) = (values[lo]?, values[hi]?);
+            let delta = if angular { (b - a + 180.0).rem_euclid(360.0) - 180.0 } else { b - a };
+            (dt > 0.0).then(|| delta / dt)
+        })
+        .collect()
+}
+
+/// Altitude below the highest point of a flight still counted as cruise (m)
+#[cfg(feature = "client")]
+pub(crate) const CRUISE_TOLERANCE: f64 = 300.0;
+
+/// Resampled vertical profile of one flight, see [`FlightData::vertical_profile`].
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerticalProfile {
+    /// `flight_id`, or `icao24` when the data has no flight ids
+    pub flight: String,
+    /// Unix seconds
+    pub time: Vec<i64>,
+    /// Smoothed altitude (m)
+    pub altitude: Vec<f64>,
+    /// Vertical rate (m/s)
+    pub vertical_rate: Vec<f64>,
+    /// First point within 300 m of the highest altitude, if the climb was observed
+    pub top_of_climb: Option<ProfilePoint>,
+    /// Last point within 300 m of the highest altitude, if the descent was observed
+    pub top_of_descent: Option<ProfilePoint>,
+}
+
+/// A point of a [`VerticalProfile`].
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProfilePoint {
+    /// Unix seconds
+    pub time: i64,
+    /// Altitude (m)
+    pub altitude: f64,
+}
+
+#[cfg(feature = "client")]
+impl VerticalProfile {
+    /// Resample, smooth and differentiate a time-sorted, non-empty track.
+    fn from_track(flight: String, track: &[(i64, f64)], interval: i64) -> Self {
+        let (start, stop) = (track[0].0, track[track.len() - 1].0);
+        let time: Vec<i64> = (start..=stop).step_by(interval as usize).collect();
+
+        let mut j = 0;
+        let raw: Vec<f64> = time
+            .iter()
+            .map(|&t| {
+                while j + 1 < track.len() && track[j + 1].0 <= t {
+                    j += 1;
+                }
+                match track.get(j + 1) {
+                    Some(&(t1, a1)) => {
+                        let (t0, a0) = track[j];
+                        a0 + (a1 - a0) * (t - t0) as f64 / (t1 - t0) as f64
+                    }
+                    None => track[j].1,
+                }
+            })
+            .collect();
+
+        let n = raw.len();
+        let window = |i: usize, half: usize| (i.saturating_sub(half), (i + half).min(n - 1));
+        let altitude: Vec<f64> = (0..n)
+            .map(|i| {
+                let (lo, hi) = window(i, 2);
+                raw[lo..=hi].iter().sum::<f64>() / (hi - lo + 1) as f64
+            })
+            .collect();
+        let vertical_rate: Vec<f64> = (0..n)
+            .map(|i| {
+                let (lo, hi) = window(i, 1);
+                if hi == lo {
+                    0.0
+                } else {
+                    (altitude[hi] - altitude[lo]) / (time[hi] - time[lo]) as f64
+                }
+            })
+            .collect();
+
+        let top = altitude.iter().cloned().fold(f64::NEG_INFINITY, f64::max) - CRUISE_TOLERANCE;
+        let point = |i: usize| ProfilePoint {
+            time: time[i],
+            altitude: altitude[i],
+        };
+        let top_of_climb = (altitude[0] < top)
+            .then(|| altitude.iter().position(|&a| a >= top))
+            .flatten()
+            .map(point);
+        let top_of_descent = (altitude[n - 1] < top)
+            .then(|| altitude.iter().rposition(|&a| a >= top))
+            .flatten()
+            .map(point);
+
+        VerticalProfile {
+            flight,
+            time,
+            altitude,
+            vertical_rate,
+            top_of_climb,
+            top_of_descent,
+        }
+    }
+}
+
+/// One row of a history result, see [`FlightData::state_vectors`].
+///
+/// Fields of columns missing from the result are `None`.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateVector {
+    pub time: chrono::DateTime<chrono::Utc>,
+    pub icao24: String,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub velocity: Option<f64>,
+    pub heading: Option<f64>,
+    pub vertrate: Option<f64>,
+    pub callsign: Option<String>,
+    pub onground: Option<bool>,
+    pub squawk: Option<String>,
+    pub baroaltitude: Option<f64>,
+    pub geoaltitude: Option<f64>,
+}
+
+/// The state vector columns of a result, cast once for row access.
+#[cfg(feature = "client")]
+struct StateVectorRows {
+    time: Int64Chunked,
+    strings: [Option<StringChunked>; 3],
+    floats: [Option<Float64Chunked>; 7],
+    onground: Option<BooleanChunked>,
+}
+
+#[cfg(feature = "client")]
+impl StateVectorRows {
+    fn new(data: &FlightData) -> Result<Self> {
+        let string = |name: &str| data.df.column(name).ok().and_then(|c| c.str().ok().cloned());
+        let float = |name: &str| data.float_column(name);
+        Ok(StateVectorRows {
+            time: data.time_column()?,
+            strings: [string("icao24"), string("callsign"), string("squawk")],
+            floats: [
+                float("lat")?,
+                float("lon")?,
+                float("velocity")?,
+                float("heading")?,
+                float("vertrate")?,
+                float("baroaltitude")?,
+                float("geoaltitude")?,
+            ],
+            onground: data.df.column("onground").ok().and_then(|c| c.bool().ok().cloned()),
+        })
+    }
+
+    fn get(&self, i: usize) -> Option<StateVector> {
+        let time = chrono::DateTime::from_timestamp(self.time.get(i)?, 0)?;
+        let string = |k: usize| self.strings[k].as_ref()?.get(i).map(|v| v.trim().to_string());
+        let float = |k: usize| self.floats[k].as_ref()?.get(i);
+        Some(StateVector {
+            time,
+            icao24: string(0).unwrap_or_default(),
+            lat: float(0),
+            lon: float(1),
+            velocity: float(2),
+            heading: float(3),
+            vertrate: float(4),
+            callsign: string(1).filter(|v| !v.is_empty()),
+            onground: self.onground.as_ref().and_then(|c| c.get(i)),
+            squawk: string(2).filter(|v| !v.is_empty()),
+            baroaltitude: float(5),
+            geoaltitude: float(6),
+        })
+    }
+}
+
+/// State vectors paced by their timestamps, from [`FlightData::replay`].
+#[cfg(feature = "client")]
+pub struct Replay {
+    rows: StateVectorRows,
+    /// `(time, row)` in time order
+    order: Vec<(i64, usize)>,
+    position: usize,
+    /// Clock and data time of the first state vector
+    start: Option<(tokio::time::Instant, i64)>,
+    speed: f64,
+}
+
+#[cfg(feature = "client")]
+impl Replay {
+    /// Wait for the next state vector, or `None` after the last one.
+    pub async fn next(&mut self) -> Option<StateVector> {
+        while let Some(&(time, i)) = self.order.get(self.position) {
+            self.position += 1;
+            let (clock, start) = *self.start.get_or_insert_with(|| (tokio::time::Instant::now(), time));
+            let offset = (time - start) as f64 / self.speed;
+            tokio::time::sleep_until(clock + std::time::Duration::from_secs_f64(offset)).await;
+            if let Some(sv) = self.rows.get(i) {
+                return Some(sv);
+            }
+        }
+        None
+    }
+
+    /// Number of state vectors not yet returned.
+    pub fn remaining(&self) -> usize {
+        self.order.len() - self.position
+    }
+}
+
+/// One flight from a [`FlightList`].
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlightListEntry {
+    pub icao24: String,
+    pub callsign: Option<String>,
+    pub firstseen: chrono::DateTime<chrono::Utc>,
+    pub lastseen: chrono::DateTime<chrono::Utc>,
+    pub estdepartureairport: Option<String>,
+    pub estarrivalairport: Option<String>,
+    pub day: Option<chrono::NaiveDate>,
+}
+
+#[cfg(feature = "client")]
+impl FlightListEntry {
+    /// History query for the state vectors of this flight.
+    ///
+    /// Covers `firstseen - buffer` to `lastseen + buffer` for the aircraft, so
+    /// the takeoff and landing just outside the detected window are included.
+    pub fn history_params(&self, buffer: chrono::Duration) -> QueryParams {
+        let format = |t: chrono::DateTime<chrono::Utc>| t.format("%Y-%m-%d %H:%M:%S").to_string();
+        QueryParams::new()
+            .icao24(self.icao24.as_str())
+            .time_range(format(self.firstseen - buffer), format(self.lastseen + buffer))
+    }
+}
+
+/// Flight list from `flights_data4`, as returned by [`crate::Trino::flightlist`].
+///
+/// `icao24`, `firstseen` and `lastseen` are always present and non-null;
+/// the other [`FLIGHTLIST_COLUMNS`] are optional.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct FlightList {
+    df: DataFrame,
+}
+
+#[cfg(feature = "client")]
+impl FlightList {
+    /// Wrap a flight list DataFrame, casting columns to their canonical types.
+    pub fn new(df: DataFrame) -> Result<Self> {
+        let mut df = crate::schema::coerce(df)?;
+        for name in ["icao24", "firstseen", "lastseen"] {
+            let column = df.column(name).map_err(|_| {
+                OpenSkyError::DataConversion(format!("Flight list is missing column '{}'", name))
+            })?;
+            if column.null_count() > 0 {
+                return Err(OpenSkyError::DataConversion(format!(
+                    "Flight list has null values in column '{}'",
+                    name
+                )));
+            }
+        }
+        df.as_single_chunk();
+        Ok(Self { df })
+    }
+
+    /// Get the underlying DataFrame.
+    pub fn dataframe(&self) -> &DataFrame {
+        &self.df
+    }
+
+    /// Consume and return the underlying DataFrame.
+    pub fn into_dataframe(self) -> DataFrame {
+        self.df
+    }
+
+    /// Get the number of flights.
+    pub fn len(&self) -> usize {
+        self.df.height()
+    }
+
+    /// Check if empty.
+    pub fn is_empty(&self) -> bool {
+        self.df.height() == 0
+    }
+
+    /// Summarize the flight list, see [`FlightData::metadata`].
+    pub fn metadata(&self) -> ResultMetadata {
+        FlightData::new(self.df.clone()).metadata()
+    }
+
+    /// Get the flight at `index`.
+    pub fn get(&self, index: usize) -> Option<FlightListEntry> {
+        (index < self.len()).then(|| self.entry(index))
+    }
+
+    /// Iterate over the flights.
+    pub fn iter(&self) -> impl Iterator<Item = FlightListEntry> + '_ {
+        (0..self.len()).map(|i| self.entry(i))
+    }
+
+    fn entry(&self, i: usize) -> FlightListEntry {
+        let string = |name: &str| -> Option<String> {
+            let value = self.df.column(name).ok()?.str().ok()?.get(i)?.trim();
+            (!value.is_empty()).then(|| value.to_string())
+        };
+        let int = |name: &str| self.df.column(name).ok()?.i64().ok()?.get(i);
+        let time = |name: &str| {
+            int(name)
+                .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                .unwrap_or_default()
+        };
+
+        FlightListEntry {
+            icao24: string("icao24").unwrap_or_default(),
+            callsign: string("callsign"),
+            firstseen: time("firstseen"),
+            lastseen: time("lastseen"),
+            estdepartureairport: string("estdepartureairport"),
+            estarrivalairport: string("estarrivalairport"),
+            day: int("day")
+                .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                .map(|t| t.date_naive()),
+        }
+    }
+
+    /// Export to CSV file.
+    pub fn to_csv(&self, path: &str) -> Result<()> {
+        crate::write_csv(&self.df, path)
+    }
+
+    /// Export to CSV file with custom options.
+    pub fn to_csv_with(&self, path: impl AsRef<std::path::Path>, options: &CsvOptions) -> Result<()> {
+        crate::write_csv_with(&self.df, path, options)
+    }
+
+    /// Export to Parquet file.
+    pub fn to_parquet(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        crate::write_parquet(&self.df, path)
+    }
+
+    /// Load from Parquet file.
+    pub fn from_parquet(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Self::new(FlightData::from_parquet(path)?.into_dataframe())
+    }
+
+    /// Load from CSV file, plain or gzip-compressed.
+    pub fn from_csv(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Self::new(FlightData::from_csv(path)?.into_dataframe())
+    }
+
+    /// Load from Arrow IPC (Feather v2) file.
+    pub fn from_ipc(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Self::new(FlightData::from_ipc(path)?.into_dataframe())
+    }
+}
+
+/// A one-line summary (rows, columns, time span, aircraft) and the first
+/// rows.
+#[cfg(feature = "client")]
+impl std::fmt::Display for FlightData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let metadata = self.metadata();
+        write!(f, "FlightData: {} rows x {} columns", metadata.rows, metadata.columns.len())?;
+        let format = |t: i64| chrono::DateTime::from_timestamp(t, 0).map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string());
+        if let (Some(start), Some(stop)) = (metadata.start.and_then(format), metadata.stop.and_then(format)) {
+            write!(f, ", {} to {} UTC", start, stop)?;
+        }
+        if let Ok(aircraft) = self.df.column("icao24").and_then(|c| c.drop_nulls().n_unique()) {
+            write!(f, ", {} aircraft", aircraft)?;
+        }
+        if self.truncated {
+            write!(f, " (truncated)")?;
+        }
+        write!(f, "\n{}", self.df.head(Some(5)))
+    }
+}
+
+#[cfg(feature = "client")]
+impl From<FlightList> for FlightData {
+    fn from(flights: FlightList) -> Self {
+        FlightData::new(flights.df)
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -296,13 +2784,786 @@ mod tests {
             .arrival("EGLL");
 
         assert_eq!(params.icao24, Some("485a32".to_string()));
-        assert_eq!(params.departure_airport, Some("EHAM".to_string()));
+        assert_eq!(params.departure_airport, ["EHAM"]);
         assert!(!params.is_empty());
     }
 
+    #[test]
+    fn test_airports_one_or_many() {
+        let params: QueryParams =
+            serde_json::from_str(r#"{"departure_airport": "EHAM", "arrival_airport": ["EGLL", "EGKK"]}"#).unwrap();
+        assert_eq!(params.departure_airport, ["EHAM"]);
+        assert_eq!(params.arrival_airport, ["EGLL", "EGKK"]);
+        assert!(params.airport.is_empty());
+        assert!(params.has_airport_filter());
+    }
+
     #[test]
     fn test_query_params_empty() {
         let params = QueryParams::new();
         assert!(params.is_empty());
     }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_export_multi_chunk() {
+        let a = df!("icao24" => ["485a32"], "time" => [1i64]).unwrap();
+        let b = df!("icao24" => ["485a33"], "time" => [2i64]).unwrap();
+        let data = FlightData::new(a.vstack(&b).unwrap());
+        assert_eq!(data.dataframe().first_col_n_chunks(), 2);
+
+        let dir = tempfile::tempdir().unwrap();
+        let parquet = dir.path().join("out.parquet");
+        data.to_parquet(&parquet).unwrap();
+        assert!(FlightData::from_parquet(&parquet).unwrap().dataframe().equals(data.dataframe()));
+
+        let csv = dir.path().join("out.csv");
+        data.to_csv(csv.to_str().unwrap()).unwrap();
+        let content = std::fs::read_to_string(&csv).unwrap();
+        assert_eq!(content, "icao24,time\n485a32,1\n485a33,2\n");
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_csv_options() {
+        use std::io::Read;
+
+        let df = df!("icao24" => ["485a32"], "lat" => [52.123456f64]).unwrap();
+        let data = FlightData::new(df);
+        let dir = tempfile::tempdir().unwrap();
+
+        let path = dir.path().join("out.csv");
+        let options = CsvOptions::new()
+            .separator(b';')
+            .quoting(CsvQuoting::NonNumeric)
+            .include_header(false)
+            .float_precision(2);
+        data.to_csv_with(&path, &options).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "\"485a32\";52.12\n");
+
+        let path = dir.path().join("out.csv.gz");
+        data.to_csv_with(&path, &CsvOptions::new().gzip(true)).unwrap();
+        let mut content = String::new();
+        flate2::read::GzDecoder::new(std::fs::File::open(&path).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+        assert!(content.starts_with("icao24,lat\n485a32,52.123456"));
+
+        assert_eq!("non-numeric".parse::<CsvQuoting>().unwrap(), CsvQuoting::NonNumeric);
+        assert!("sometimes".parse::<CsvQuoting>().is_err());
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_csv_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = FlightData::new(df!("time" => [1i64], "icao24" => ["485a32"]).unwrap());
+        let second = FlightData::new(df!("time" => [2i64], "icao24" => ["485a33"]).unwrap());
+
+        for name in ["out.csv", "out.csv.gz"] {
+            let path = dir.path().join(name);
+            let options = CsvOptions::new().gzip(name.ends_with(".gz")).append(true);
+            first.to_csv_with(&path, &options).unwrap();
+            second.to_csv_with(&path, &options).unwrap();
+            let loaded = FlightData::from_csv(&path).unwrap();
+            assert_eq!(loaded.len(), 2, "{}", name);
+            assert_eq!(loaded.dataframe().column("icao24").unwrap().str().unwrap().get(1), Some("485a33"));
+        }
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_append_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = FlightData::new(df!("time" => [10i64, 20], "icao24" => ["485a32", "485a32"]).unwrap());
+        let second = FlightData::new(df!("time" => [20i64, 30], "icao24" => ["485a32", "485a32"]).unwrap());
+
+        let path = dir.path().join("out.ndjson");
+        first.to_ndjson(&path).unwrap();
+        crate::write_ndjson(second.dataframe(), &path, true).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().next(), Some(r#"{"time":10,"icao24":"485a32"}"#));
+        assert_eq!(crate::output_time_range(&path).unwrap(), Some((10, 30)));
+
+        let path = dir.path().join("out.parquet");
+        crate::append_parquet(first.dataframe(), &path).unwrap();
+        crate::append_parquet(second.dataframe(), &path).unwrap();
+        assert_eq!(FlightData::from_parquet(&path).unwrap().len(), 4);
+        let other = df!("time" => [1.5f64]).unwrap();
+        assert!(crate::append_parquet(&other, &path).is_err());
+
+        let dataset = dir.path().join("dataset");
+        crate::append_parquet(first.dataframe(), &dataset).unwrap();
+        crate::append_parquet(second.dataframe(), &dataset).unwrap();
+        assert!(dataset.join("part-00001.parquet").exists());
+        assert_eq!(crate::output_time_range(&dataset).unwrap(), Some((10, 30)));
+        assert_eq!(crate::output_time_range(dir.path().join("missing.csv")).unwrap(), None);
+
+        let (start, stop) = crate::output_time_range(&dataset).unwrap().unwrap();
+        let fresh = FlightData::new(df!("time" => [25i64, 40]).unwrap());
+        assert_eq!(fresh.exclude_time_range(start, stop).unwrap().len(), 1);
+
+        // Appends skip rows per aircraft: other aircraft within the span stay
+        let spans = crate::output_time_spans(&dataset).unwrap();
+        assert_eq!(spans.get("485a32"), Some(&(10, 30)));
+        assert_eq!(crate::output_time_spans(dir.path().join("out.ndjson")).unwrap(), spans);
+        let fresh = FlightData::new(
+            df!("time" => [25i64, 40, 25], "icao24" => ["485a32", "485a32", "3c6444"]).unwrap(),
+        );
+        let fresh = fresh.exclude_time_spans(&spans).unwrap();
+        let times: Vec<Option<i64>> = fresh.dataframe().column("time").unwrap().i64().unwrap().into_iter().collect();
+        assert_eq!(times, [Some(40), Some(25)]);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_parquet_dataset() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = FlightData::new(
+            df!(
+                "time" => [1735732800i64, 1735740000, 1735819200],
+                "icao24" => [Some("485a32"), Some("3c6444"), None],
+                "lat" => [52.3, 48.1, 52.4]
+            )
+            .unwrap(),
+        );
+        data.to_parquet_dataset(dir.path(), &["date", "icao24"]).unwrap();
+        data.to_parquet_dataset(dir.path(), &["date", "icao24"]).unwrap();
+
+        let part = dir.path().join("date=2025-01-01").join("icao24=485a32");
+        assert!(part.join("part-00001.parquet").exists());
+        let rows = FlightData::from_parquet(part.join("part-00000.parquet")).unwrap();
+        assert_eq!(rows.dataframe().get_column_names_str(), ["time", "lat"]);
+        assert!(dir.path().join("date=2025-01-02").join("icao24=__HIVE_DEFAULT_PARTITION__").is_dir());
+
+        // Scanned back with the partition columns restored from the paths
+        let args = ScanArgsParquet {
+            hive_options: polars::io::HiveOptions {
+                enabled: Some(true),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let pattern = format!("{}/**/*.parquet", dir.path().display());
+        let scanned = LazyFrame::scan_parquet(pattern, args).unwrap().collect().unwrap();
+        assert_eq!(scanned.height(), 6);
+        assert!(scanned.column("icao24").is_ok() && scanned.column("date").is_ok());
+
+        assert!(data.to_parquet_dataset(dir.path(), &["callsign"]).is_err());
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_read_csv_and_ipc() {
+        let df = df!(
+            "time" => [1735725600i64],
+            "icao24" => ["400000"],
+            "onground" => [false],
+            "squawk" => ["0123"],
+            "extra" => [1.5f64]
+        )
+        .unwrap();
+        let data = FlightData::new(df);
+        let dir = tempfile::tempdir().unwrap();
+
+        for name in ["out.csv", "out.csv.gz"] {
+            let path = dir.path().join(name);
+            data.to_csv_with(&path, &CsvOptions::new().gzip(name.ends_with(".gz"))).unwrap();
+            let loaded = FlightData::from_csv(&path).unwrap();
+            assert!(loaded.dataframe().equals(data.dataframe()), "{}", name);
+        }
+
+        let path = dir.path().join("out.arrow");
+        let mut df = df!("time" => [1735725600i32], "icao24" => ["485a32"]).unwrap();
+        IpcWriter::new(std::fs::File::create(&path).unwrap())
+            .finish(&mut df)
+            .unwrap();
+        let loaded = FlightData::from_ipc(&path).unwrap();
+        assert_eq!(loaded.dataframe().column("time").unwrap().dtype(), &DataType::Int64);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_flightlist_entries() {
+        let df = df!(
+            "icao24" => ["485a32", "3c6444"],
+            "callsign" => [Some("KLM1234 "), None],
+            "firstseen" => [1735725600i64, 1735729200],
+            "lastseen" => [1735731000i64, 1735734600],
+            "estdepartureairport" => [Some("EHAM"), None],
+            "estarrivalairport" => [Some("EGLL"), Some("EDDF")],
+            "day" => [1735689600i64, 1735689600],
+        )
+        .unwrap();
+        let flights = FlightList::new(df).unwrap();
+
+        let first = flights.get(0).unwrap();
+        assert_eq!(first.callsign.as_deref(), Some("KLM1234"));
+        assert_eq!(first.firstseen.to_rfc3339(), "2025-01-01T10:00:00+00:00");
+        assert_eq!(first.day, chrono::NaiveDate::from_ymd_opt(2025, 1, 1));
+
+        let params = first.history_params(chrono::Duration::minutes(10));
+        assert_eq!(params.icao24.as_deref(), Some("485a32"));
+        assert_eq!(params.start.as_deref(), Some("2025-01-01 09:50:00"));
+        assert_eq!(params.stop.as_deref(), Some("2025-01-01 11:40:00"));
+
+        let second = flights.iter().nth(1).unwrap();
+        assert_eq!(second.estdepartureairport, None);
+        assert_eq!(second.estarrivalairport.as_deref(), Some("EDDF"));
+        assert!(flights.get(2).is_none());
+
+        let partial = df!("icao24" => ["485a32"], "firstseen" => [1i64]).unwrap();
+        assert!(FlightList::new(partial).is_err());
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_attach_flight_info() {
+        let flights = FlightList::new(
+            df!(
+                "icao24" => ["485a32", "485a32"],
+                "callsign" => ["KLM1", "KLM2"],
+                "firstseen" => [100i64, 1000],
+                "lastseen" => [200i64, 2000],
+                "estdepartureairport" => ["EHAM", "EGLL"],
+                "estarrivalairport" => ["EGLL", "EHAM"],
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let data = FlightData::new(
+            df!(
+                "time" => [150i64, 1500, 1500, 500],
+                "icao24" => ["485a32", "485a32", "485a32", "485a32"],
+                "callsign" => ["KLM1    ", "KLM2", "KLM9", "KLM1"],
+            )
+            .unwrap(),
+        );
+
+        let joined = data.attach_flight_info(&flights).unwrap();
+        let df = joined.dataframe();
+        let ids: Vec<_> = df.column("flight_id").unwrap().str().unwrap().into_iter().collect();
+        assert_eq!(ids, [Some("485a32_100"), Some("485a32_1000"), None, None]);
+        let departures: Vec<_> =
+            df.column("estdepartureairport").unwrap().str().unwrap().into_iter().collect();
+        assert_eq!(departures, [Some("EHAM"), Some("EGLL"), None, None]);
+    }
+
+    #[test]
+    fn test_time_range_tz() {
+        let params = QueryParams::new()
+            .time_range_tz("2025-07-01 10:00:00", "2025-07-01", "Europe/Amsterdam")
+            .unwrap();
+        assert_eq!(params.start.as_deref(), Some("2025-07-01 08:00:00"));
+        assert_eq!(params.stop.as_deref(), Some("2025-07-01 21:59:59"));
+
+        // A date-only range is the whole local day
+        let params = QueryParams::new().time_range_tz("2025-01-01", "2025-01-01", "Europe/Amsterdam").unwrap();
+        assert_eq!(params.start.as_deref(), Some("2024-12-31 23:00:00"));
+        assert_eq!(params.stop.as_deref(), Some("2025-01-01 22:59:59"));
+
+        // An explicit offset wins over the zone
+        let params = QueryParams::new()
+            .time_range_tz("2025-07-01T10:00:00Z", "2025-07-01T12:00:00", "Europe/Amsterdam")
+            .unwrap();
+        assert_eq!(params.start.as_deref(), Some("2025-07-01 10:00:00"));
+        assert_eq!(params.stop.as_deref(), Some("2025-07-01 10:00:00"));
+
+        // Clocks go forward at 02:00 on 2025-03-30
+        assert!(QueryParams::new()
+            .time_range_tz("2025-03-30 02:30:00", "2025-03-30 04:00:00", "Europe/Amsterdam")
+            .is_err());
+        assert!(QueryParams::new().time_range_tz("2025-01-01", "2025-01-02", "Mars/Olympus").is_err());
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_with_local_time() {
+        let data = FlightData::new(df!("time" => [1735729200i64]).unwrap());
+        let local = data.with_local_time("Europe/Amsterdam").unwrap();
+        let value = local.dataframe().column("time_local").unwrap().str().unwrap().get(0).map(str::to_string);
+        assert_eq!(value.as_deref(), Some("2025-01-01 12:00:00+01:00"));
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_classify_bds() {
+        let data = FlightData::new(
+            df!("rawmsg" => [Some("a0001839ca3800315800007448d9"), Some("8d406b902015a678d4d220aa4bda"), None]).unwrap(),
+        );
+        let classified = data.classify_bds().unwrap();
+        let df = classified.dataframe();
+        let formats: Vec<Option<i64>> = df.column("df").unwrap().i64().unwrap().into_iter().collect();
+        assert_eq!(formats, [Some(20), Some(17), None]);
+        let bds: Vec<Option<&str>> = df.column("bds").unwrap().str().unwrap().into_iter().collect();
+        assert_eq!(bds, [Some("4,0"), None, None]);
+
+        assert!(FlightData::new(df!("time" => [1i64]).unwrap()).classify_bds().is_err());
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_vertical_profile() {
+        // Climb at 5 m/s to 3000 m, cruise, then descend at 5 m/s, one sample per 30s
+        let times: Vec<i64> = (0..=60).map(|i| i * 30).collect();
+        let altitude: Vec<Option<f64>> = times
+            .iter()
+            .map(|&t| Some((t.min(600) as f64 * 5.0).min(3000.0) - ((t - 1200).max(0) as f64 * 5.0)))
+            .collect();
+        let n = times.len();
+        let mut icao24 = vec!["485a32"; n];
+        icao24.push("3c6444");
+        let mut time = times.clone();
+        time.push(0);
+        let mut baro = altitude.clone();
+        baro.push(None);
+        let df = df!(
+            "icao24" => icao24,
+            "time" => time,
+            "baroaltitude" => baro,
+            "geoaltitude" => vec![Some(1000.0); n + 1],
+        )
+        .unwrap();
+
+        let profiles = FlightData::new(df).vertical_profile(10).unwrap();
+        assert_eq!(profiles.len(), 2);
+
+        // Null baroaltitude falls back to geoaltitude
+        let other = &profiles[0];
+        assert_eq!(other.flight, "3c6444");
+        assert_eq!(other.altitude, [1000.0]);
+        assert_eq!(other.top_of_climb, None);
+
+        let profile = &profiles[1];
+        assert_eq!(profile.time.len(), 181);
+        assert_eq!(profile.time[1] - profile.time[0], 10);
+        assert!((profile.vertical_rate[30] - 5.0).abs() < 1e-6);
+        assert!((profile.vertical_rate[150] + 5.0).abs() < 1e-6);
+        let climb = profile.top_of_climb.unwrap();
+        let descent = profile.top_of_descent.unwrap();
+        assert!((540..=600).contains(&climb.time), "{}", climb.time);
+        assert!((1200..=1260).contains(&descent.time), "{}", descent.time);
+
+        assert!(FlightData::new(DataFrame::default()).vertical_profile(0).is_err());
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_infer_runway() {
+        use crate::airports::Runway;
+
+        let runways = [Runway::new("09", 52.0, 3.95, 90.0), Runway::new("27", 52.0, 4.0, 270.0)];
+        // Lands on 27 from the east, then takes off on 09 towards the east
+        let n = 20;
+        let arrival_lon: Vec<f64> = (0..n).map(|i| 4.1 - i as f64 * 0.1 / n as f64).collect();
+        let departure_lon: Vec<f64> = (0..n).map(|i| 3.95 + i as f64 * 0.15 / n as f64).collect();
+        let arrival_alt: Vec<f64> = (0..n).map(|i| 600.0 - i as f64 * 30.0).collect();
+        let departure_alt: Vec<f64> = (0..n).map(|i| i as f64 * 50.0).collect();
+        // A third aircraft crosses the runways at right angles
+        let crossing_lat: Vec<f64> = (0..n).map(|i| 51.95 + i as f64 * 0.005).collect();
+
+        let mut df = DataFrame::default();
+        for (icao24, lat, lon, heading, altitude) in [
+            ("485a32", vec![52.0; n], arrival_lon, 270.0, arrival_alt),
+            ("3c6444", vec![52.0; n], departure_lon, 90.0, departure_alt),
+            ("4ca123", crossing_lat, vec![3.97; n], 0.0, vec![300.0; n]),
+        ] {
+            let flight = df!(
+                "icao24" => vec![icao24; n],
+                "time" => (0..n as i64).map(|i| 1735725600 + i * 10).collect::<Vec<_>>(),
+                "lat" => lat,
+                "lon" => lon,
+                "heading" => vec![heading; n],
+                "baroaltitude" => altitude,
+            )
+            .unwrap();
+            df.vstack_mut(&flight).unwrap();
+        }
+
+        let runways_used = FlightData::new(df).infer_runway(&runways).unwrap();
+        assert_eq!(runways_used.height(), 2);
+        let column = |name: &str| runways_used.column(name).unwrap().str().unwrap().clone();
+        assert_eq!(column("flight").get(0), Some("3c6444"));
+        assert_eq!(column("operation").get(0), Some("departure"));
+        assert_eq!(column("runway").get(0), Some("09"));
+        assert_eq!(column("operation").get(1), Some("arrival"));
+        assert_eq!(column("runway").get(1), Some("27"));
+        assert_eq!(runways_used.column("time").unwrap().i64().unwrap().get(1), Some(1735725790));
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_with_kinematics() {
+        // Turning right at 3°/s through north, accelerating at 1 m/s², climbing at 10 m/s
+        let df = df!(
+            "icao24" => ["485a32"; 5],
+            "time" => [40i64, 0, 10, 20, 30],
+            "heading" => [Some(30.0), Some(350.0), None, Some(10.0), Some(20.0)],
+            "lat" => [52.0; 5],
+            "lon" => [4.0, 4.0, 4.0, 4.0, 4.1],
+            "velocity" => [140.0, 100.0, 110.0, 120.0, 130.0],
+            "vertrate" => [10.0; 5],
+        )
+        .unwrap();
+        let data = FlightData::new(df).with_kinematics().unwrap();
+        let column = |name: &str| data.dataframe().column(name).unwrap().f64().unwrap().clone();
+
+        let track = column("track");
+        // The missing heading has no position change to fall back on
+        assert_eq!(track.get(2), None);
+        assert_eq!(track.get(1), Some(350.0));
+        assert!((track.get(4).unwrap() - 20.0).abs() < 1e-9);
+        assert!((column("turn_rate").get(4).unwrap() - 1.0).abs() < 1e-9);
+        assert_eq!(column("turn_rate").get(3), None);
+        assert!((column("turn_rate").get(0).unwrap() - 1.0).abs() < 1e-9);
+        assert!((column("acceleration").get(2).unwrap() - 1.0).abs() < 1e-9);
+        assert_eq!(column("vertical_speed").get(0), Some(10.0));
+
+        let moving = df!("icao24" => ["485a32"; 2], "time" => [0i64, 10], "lat" => [52.0, 52.1], "lon" => [4.0, 4.0]).unwrap();
+        let data = FlightData::new(moving).with_kinematics().unwrap();
+        assert!(data.dataframe().column("track").unwrap().f64().unwrap().get(0).unwrap().abs() < 1e-9);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_estimate_fuel() {
+        let df = df!(
+            "icao24" => ["485a32", "485a32", "485a32", "3c6444"],
+            "typecode" => [Some("A320"), Some("A320"), Some("A320"), Some("ZZZZ")],
+            "time" => [20i64, 0, 10, 0],
+            "baroaltitude" => [11000.0, 10000.0, 10500.0, 3000.0],
+            "velocity" => [230.0, 230.0, 230.0, 150.0],
+            "vertrate" => [5.0, 5.0, 5.0, 0.0],
+        )
+        .unwrap();
+        // 1 kg/s, doubled while climbing
+        let model = |typecode: &str, _altitude: f64, _velocity: f64, vertical_rate: f64| {
+            (typecode == "A320").then_some(if vertical_rate > 1.0 { 2.0 } else { 1.0 })
+        };
+        let data = FlightData::new(df).estimate_fuel(&model).unwrap();
+        let column = |name: &str| data.dataframe().column(name).unwrap().f64().unwrap().clone();
+
+        assert_eq!(column("fuel_flow").get(0), Some(2.0));
+        assert_eq!(column("fuel_flow").get(3), None);
+        assert_eq!(column("fuel").get(1), Some(0.0));
+        assert_eq!(column("fuel").get(0), Some(40.0));
+        assert!((column("co2").get(0).unwrap() - 126.4).abs() < 1e-9);
+        assert_eq!(column("fuel").get(3), Some(0.0));
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_compare() {
+        // Reference flies north along 4°E; the other flies 0.01° further east
+        // a day later, and is not recorded past the reference's last position
+        let reference = df!(
+            "time" => [0i64, 100, 200],
+            "lat" => [52.0, 52.1, 52.2],
+            "lon" => [4.0, 4.0, 4.0],
+        )
+        .unwrap();
+        let flown = df!(
+            "time" => [86400i64, 86450, 86500, 86700],
+            "lat" => [Some(52.0), Some(52.05), None, Some(52.3)],
+            "lon" => [4.01, 4.01, 4.01, 4.01],
+        )
+        .unwrap();
+
+        let comparison = FlightData::new(flown).compare(&FlightData::new(reference.clone())).unwrap();
+        let deviations = &comparison.deviations;
+        assert_eq!(deviations.height(), 2);
+        assert_eq!(deviations.column("elapsed").unwrap().i64().unwrap().get(1), Some(50));
+        let cross_track = deviations.column("cross_track").unwrap().f64().unwrap().get(1).unwrap();
+        // 0.01° of longitude at 52°N
+        assert!((cross_track - 684.0).abs() < 5.0, "{}", cross_track);
+        assert!(comparison.summary.mean_along_track.abs() < 1.0);
+        assert_eq!(comparison.summary.samples, 2);
+        assert!((comparison.summary.rms_cross_track - comparison.summary.max_cross_track).abs() < 1.0);
+
+        let single = reference.head(Some(1));
+        assert!(FlightData::new(reference).compare(&FlightData::new(single)).is_err());
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_gaps() {
+        let df = df!(
+            "icao24" => ["485a32", "485a32", "485a32", "485a32", "3c6444", "3c6444"],
+            "time" => [0i64, 10, 400, 20, 0, 30],
+            "lat" => [Some(52.0), Some(52.1), Some(52.5), None, Some(50.0), Some(50.1)],
+            "lon" => [4.0, 4.1, 4.5, 4.2, 8.0, 8.1],
+        )
+        .unwrap();
+        let data = FlightData::new(df);
+
+        let gaps = data.gaps(chrono::Duration::minutes(5)).unwrap();
+        assert_eq!(gaps.height(), 1);
+        assert_eq!(gaps.column("flight").unwrap().str().unwrap().get(0), Some("485a32"));
+        // The row at 20s has no position, so the gap starts at 10s
+        assert_eq!(gaps.column("start").unwrap().i64().unwrap().get(0), Some(10));
+        assert_eq!(gaps.column("duration").unwrap().i64().unwrap().get(0), Some(390));
+        assert_eq!(gaps.column("lat_after").unwrap().f64().unwrap().get(0), Some(52.5));
+
+        assert_eq!(data.gaps(chrono::Duration::seconds(30)).unwrap().height(), 2);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_replay() {
+        let df = df!(
+            "time" => [1_700_000_000i64, 1_700_000_020, 1_700_000_010],
+            "icao24" => ["485a32", "485a32", "485a32"],
+            "lat" => [Some(52.0), None, Some(52.1)],
+            "callsign" => ["KLM1234 ", "KLM1234 ", ""],
+        )
+        .unwrap();
+        let data = FlightData::new(df);
+
+        let first = data.state_vectors().unwrap().next().unwrap();
+        assert_eq!(first.time.timestamp(), 1_700_000_000);
+        assert_eq!(first.callsign.as_deref(), Some("KLM1234"));
+        assert_eq!(first.velocity, None);
+
+        let mut replay = data.replay(100.0).unwrap();
+        let started = tokio::time::Instant::now();
+        let mut times = Vec::new();
+        while let Some(sv) = replay.next().await {
+            times.push(sv.time.timestamp() - 1_700_000_000);
+        }
+        assert_eq!(times, [0, 10, 20]);
+        // 20 s of data at 100x
+        assert!(started.elapsed() >= std::time::Duration::from_millis(200));
+        assert_eq!(replay.remaining(), 0);
+
+        assert!(data.replay(0.0).is_err());
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_drop_invalid_positions() {
+        let df = df!(
+            "time" => [1i64, 2, 3, 4, 5],
+            "lat" => [Some(52.3), None, Some(0.0), Some(95.0), Some(0.0)],
+            "lon" => [Some(4.8), Some(4.8), Some(0.0), Some(4.8), Some(4.8)]
+        )
+        .unwrap();
+        let data = FlightData::new(df).drop_invalid_positions().unwrap();
+        let times: Vec<_> = data.dataframe().column("time").unwrap().i64().unwrap().into_no_null_iter().collect();
+        assert_eq!(times, [1, 5]);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_select_and_filter() {
+        let df = df!(
+            "time" => [1735732800i64, 1735740000, 1735819200, 1735880000],
+            "icao24" => ["485a32", "3c6444", "485a32", "4b1805"],
+            "lat" => [Some(52.3), Some(48.1), None, Some(47.4)],
+            "lon" => [Some(4.8), Some(11.6), Some(4.8), Some(8.5)]
+        )
+        .unwrap();
+        let data = FlightData::new(df);
+        let times = |data: FlightData| -> Vec<i64> {
+            data.dataframe().column("time").unwrap().i64().unwrap().into_no_null_iter().collect()
+        };
+
+        let track = data.select(&["lon", "time"]).unwrap();
+        assert_eq!(track.dataframe().get_column_names_str(), ["lon", "time"]);
+        assert!(data.select(&["altitude"]).is_err());
+
+        assert_eq!(times(data.filter_time("2025-01-01 14:00:00", "2025-01-02").unwrap()), [1735740000, 1735819200]);
+        assert!(data.filter_time("yesterday", "2025-01-02").is_err());
+        assert_eq!(times(data.filter_icao24(&["0x485A32", "4b1805"]).unwrap()), [1735732800, 1735819200, 1735880000]);
+        // The row without a position is dropped
+        let europe = Bounds::new(0.0, 45.0, 10.0, 55.0);
+        assert_eq!(times(data.filter_bbox(&europe).unwrap()), [1735732800, 1735880000]);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_by_aircraft() {
+        let df = df!(
+            "time" => [3i64, 1, 2, 4, 5],
+            "icao24" => [Some("485a32"), Some("485a32"), Some("3c6444"), None, Some("3c6444")],
+            "flight_id" => [Some("485a32_1"), Some("485a32_1"), None, None, Some("3c6444_5")]
+        )
+        .unwrap();
+        let data = FlightData::new(df);
+        let times = |data: &FlightData| -> Vec<i64> {
+            data.dataframe().column("time").unwrap().i64().unwrap().into_no_null_iter().collect()
+        };
+
+        let aircraft = data.by_aircraft().unwrap();
+        assert_eq!(aircraft.len(), 2);
+        assert_eq!(times(&aircraft["485a32"]), [1, 3]);
+        assert_eq!(times(&aircraft["3c6444"]), [2, 5]);
+
+        let flights = data.by_flight().unwrap();
+        assert_eq!(flights.len(), 2);
+        assert_eq!(times(&flights["3c6444_5"]), [5]);
+        assert!(data.select(&["time", "icao24"]).unwrap().by_flight().is_err());
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_display_and_describe() {
+        let df = df!(
+            "time" => [1735732800i64, 1735736400, 1735740000],
+            "icao24" => [Some("485a32"), None, Some("3c6444")],
+            "callsign" => ["KLM1234", "KLM1234", "DLH5"],
+            "baroaltitude" => [Some(1000.0), None, Some(3000.0)]
+        )
+        .unwrap();
+        let data = FlightData::new(df);
+        let text = data.to_string();
+        assert!(text.starts_with("FlightData: 3 rows x 4 columns, 2025-01-01 12:00:00 to 2025-01-01 14:00:00 UTC, 2 aircraft\n"));
+        assert!(text.contains("KLM1234"));
+
+        let summary = data.describe().unwrap();
+        let names: Vec<_> = summary.column("column").unwrap().str().unwrap().into_no_null_iter().collect();
+        assert_eq!(names, ["time", "baroaltitude"]);
+        let row = |name: &str| summary.column(name).unwrap().f64().unwrap().get(1);
+        assert_eq!(summary.column("null_count").unwrap().u64().unwrap().get(1), Some(1));
+        assert_eq!((row("mean"), row("min"), row("max")), (Some(2000.0), Some(1000.0), Some(3000.0)));
+    }
+
+    #[test]
+    fn test_icao24_normalization() {
+        assert_eq!(QueryParams::new().icao24(" 0x485A32 ").icao24.as_deref(), Some("485a32"));
+        assert!(QueryParams::new().icao24("485A32").validate().is_ok());
+        assert!(QueryParams::new().icao24("485%").validate().is_ok());
+        assert!(QueryParams::new().icao24("klm123").validate().is_err());
+        assert!(QueryParams::new().icao24("485a3").validate().is_err());
+
+        // Set directly, bypassing the setter
+        let mut params = QueryParams::new();
+        params.icao24 = Some("0x485A32".to_string());
+        assert!(params.validate().is_ok());
+        #[cfg(feature = "client")]
+        assert_eq!(
+            crate::cache::cache_key(&params),
+            crate::cache::cache_key(&QueryParams::new().icao24("485a32"))
+        );
+    }
+
+    #[test]
+    fn test_query_error_details() {
+        let error: QueryError = serde_json::from_value(serde_json::json!({
+            "message": "Query exceeded per-node memory limit of 10GB",
+            "errorCode": 131079,
+            "errorName": "EXCEEDED_LOCAL_MEMORY_LIMIT",
+            "errorType": "INSUFFICIENT_RESOURCES",
+            "failureInfo": {
+                "type": "io.trino.ExceededMemoryLimitException",
+                "message": "Query exceeded per-node memory limit of 10GB",
+                "stack": ["io.trino.memory.QueryContext.enforceUserMemoryLimit(QueryContext.java:310)"]
+            }
+        }))
+        .unwrap();
+        assert!(error.is_resource_exhausted() && !error.is_user_error());
+        assert_eq!(error.error_code, Some(131079));
+        assert_eq!(error.failure_info.unwrap().stack.len(), 1);
+
+        let err = OpenSkyError::Query(Box::new(QueryError::new("boom")));
+        assert_eq!(err.to_string(), "Query execution failed: boom");
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_errors_name_query_id() {
+        let error = QueryError {
+            error_name: Some("SYNTAX_ERROR".to_string()),
+            ..QueryError::new("boom")
+        };
+        let err = OpenSkyError::Query(Box::new(error)).in_query(Some("q1"));
+        assert_eq!(err.query_id(), Some("q1"));
+        assert_eq!(err.to_string(), "Query execution failed: boom (SYNTAX_ERROR, query q1)");
+
+        let url = "https://trino/v1/statement/q1".to_string();
+        let err = OpenSkyError::HttpStatus { status: 502, url }.in_query(Some("q1"));
+        assert_eq!(err.query_id(), Some("q1"));
+        assert!(err.to_string().ends_with("(query q1)"));
+
+        assert!(OpenSkyError::Cancelled.in_query(None).query_id().is_none());
+    }
+
+    #[test]
+    fn test_parameter_combinations() {
+        let params = QueryParams::new().airport("EHAM").departure("EGLL");
+        assert_eq!(params.warnings().len(), 1);
+        assert!(params.validate().is_ok());
+
+        let params = QueryParams {
+            time_buffer: Some("1h".to_string()),
+            ..QueryParams::new().offset(10).order(Order::None)
+        };
+        assert_eq!(params.warnings().len(), 2);
+        assert!(QueryParams::new().departure("EHAM").warnings().is_empty());
+        let params = QueryParams {
+            time_buffer: Some("6h".to_string()),
+            ..QueryParams::new().departure("EHAM")
+        };
+        assert!(params.warnings().is_empty());
+        assert!(params.validate().is_ok());
+        let params = QueryParams {
+            time_buffer: Some("6 hours".to_string()),
+            ..QueryParams::new().departure("EHAM")
+        };
+        assert!(params.validate().is_err());
+
+        let params = QueryParams::new().departure(["EHAM", "EGLL"]).exclude_departure("EGLL");
+        assert!(params.validate().is_err());
+    }
+
+    #[test]
+    fn test_bounds_from_center() {
+        // 111.195 km is one degree of latitude
+        let b = Bounds::from_center(0.0, 10.0, 111.195);
+        assert!((b.north - 1.0).abs() < 1e-3 && (b.south + 1.0).abs() < 1e-3);
+        assert!((b.east - 11.0).abs() < 1e-3);
+
+        // Twice as wide in longitude at 60 degrees
+        let b = Bounds::from_center(60.0, 10.0, 111.195);
+        assert!((b.east - b.west - 4.0).abs() < 0.01, "{:?}", b);
+
+        let b = Bounds::from_center(89.5, 10.0, 200.0);
+        assert_eq!((b.west, b.east, b.north), (-180.0, 180.0, 90.0));
+        let b = Bounds::from_center(0.0, 179.5, 200.0);
+        assert_eq!((b.west, b.east), (-180.0, 180.0));
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_result_metadata() {
+        let data = FlightData::new(df!("time" => [30i64, 10, 20], "icao24" => ["a", "b", "c"]).unwrap());
+        let metadata = data.metadata();
+        assert_eq!(metadata.rows, 3);
+        assert_eq!((metadata.start, metadata.stop), (Some(10), Some(30)));
+        assert_eq!(metadata.columns[1], ColumnMetadata { name: "icao24".into(), dtype: "str".into() });
+
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert_eq!(serde_json::from_str::<ResultMetadata>(&json).unwrap(), metadata);
+
+        let empty = FlightData::new(df!("x" => [1i64]).unwrap()).metadata();
+        assert_eq!((empty.start, empty.stop), (None, None));
+    }
+
+    #[test]
+    fn test_time_formats() {
+        let params = QueryParams::new().time_range("2025-01-01T10:00:00+01:00", "2025-01-01T11:30:00Z");
+        assert_eq!(params.start.as_deref(), Some("2025-01-01 09:00:00"));
+        assert_eq!(params.stop.as_deref(), Some("2025-01-01 11:30:00"));
+
+        let params = QueryParams::new().time_range("2025-01-01T10:00:00", "2025-01-02");
+        assert_eq!(params.start.as_deref(), Some("2025-01-01 10:00:00"));
+        assert_eq!(params.stop.as_deref(), Some("2025-01-02"));
+        assert!(params.validate().is_ok());
+
+        assert!(QueryParams::new().time_range("yesterday", "today").validate().is_err());
+        assert!(QueryParams::new().time_range("2025-01-02", "2025-01-01").validate().is_err());
+
+        // A date-only stop is the end of that day
+        assert!(QueryParams::new().time_range("2025-01-01", "2025-01-01").validate().is_ok());
+        assert!(QueryParams::new().time_range("2025-01-01 12:00:00", "2025-01-01").validate().is_ok());
+    }
 }